@@ -0,0 +1,85 @@
+//! Renders intersecting meshes with MSAA and a depth prepass, to check that
+//! the depth buffers which Bevy allocates for the camera match the window's
+//! render target, including after it's resized.
+//!
+//! The cubes should cut cleanly into each other and into the ground, with no
+//! flickering or misaligned edges at any window size.
+
+use bevy::{
+    core_pipeline::prepass::{DepthPrepass, NormalPrepass},
+    prelude::*,
+    render::settings::WgpuSettings,
+};
+use bevy_mod_adwaita::AdwaitaWindowPlugin;
+
+fn main() -> AppExit {
+    App::new()
+        .add_plugins((
+            DefaultPlugins
+                .set(AdwaitaWindowPlugin::window_plugin())
+                .set(AdwaitaWindowPlugin::render_plugin(WgpuSettings::default())),
+            AdwaitaWindowPlugin::default(),
+        ))
+        .insert_resource(Msaa::Sample4)
+        .add_systems(PreStartup, setup_scene)
+        .add_systems(Update, rotate_cubes)
+        .run()
+}
+
+#[derive(Debug, Component)]
+struct Rotated(f32);
+
+fn setup_scene(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    commands.spawn(PbrBundle {
+        mesh: meshes.add(Plane3d::default().mesh().size(8.0, 8.0)),
+        material: materials.add(Color::WHITE),
+        ..default()
+    });
+    let cube = meshes.add(Cuboid::new(1.5, 1.5, 1.5));
+    for (i, color) in [
+        Color::srgb(0.9, 0.3, 0.3),
+        Color::srgb(0.3, 0.9, 0.3),
+        Color::srgb(0.3, 0.3, 0.9),
+    ]
+    .into_iter()
+    .enumerate()
+    {
+        let i = i as f32;
+        commands.spawn((
+            PbrBundle {
+                mesh: cube.clone(),
+                material: materials.add(color),
+                // sunk into the ground and overlapping each other
+                transform: Transform::from_xyz(i * 0.8 - 0.8, 0.4, 0.0),
+                ..default()
+            },
+            Rotated(0.5 + i * 0.3),
+        ));
+    }
+    commands.spawn(PointLightBundle {
+        point_light: PointLight {
+            shadows_enabled: true,
+            ..default()
+        },
+        transform: Transform::from_xyz(4.0, 8.0, 4.0),
+        ..default()
+    });
+    commands.spawn((
+        Camera3dBundle {
+            transform: Transform::from_xyz(-2.5, 4.5, 9.0).looking_at(Vec3::ZERO, Vec3::Y),
+            ..default()
+        },
+        DepthPrepass,
+        NormalPrepass,
+    ));
+}
+
+fn rotate_cubes(time: Res<Time>, mut cubes: Query<(&mut Transform, &Rotated)>) {
+    for (mut transform, Rotated(speed)) in &mut cubes {
+        transform.rotate_y(time.delta_seconds() * speed);
+    }
+}
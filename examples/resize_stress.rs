@@ -0,0 +1,107 @@
+//! Resizes the window every frame, with a camera that only renders into part
+//! of it, to check that frames never come out garbled or clipped.
+//!
+//! Any mismatch between the camera's target size and the render target is
+//! logged as an error.
+
+use std::time::Duration;
+
+use bevy::{
+    prelude::*,
+    render::{
+        camera::{CameraUpdateSystem, Viewport},
+        settings::WgpuSettings,
+    },
+};
+use bevy_mod_adwaita::{
+    AdwaitaRenderTargetSize, AdwaitaWindow, AdwaitaWindowConfig, AdwaitaWindowPlugin,
+    PrimaryAdwaitaWindow,
+};
+fn main() -> AppExit {
+    App::new()
+        .add_plugins((
+            DefaultPlugins
+                .set(AdwaitaWindowPlugin::window_plugin())
+                .set(AdwaitaWindowPlugin::render_plugin(WgpuSettings::default())),
+            AdwaitaWindowPlugin {
+                primary_window_config: Some(AdwaitaWindowConfig {
+                    // reallocate on every single size change
+                    resize_debounce: Duration::ZERO,
+                    ..default()
+                }),
+                ..default()
+            },
+        ))
+        .add_systems(PreStartup, setup_scene)
+        .add_systems(Update, resize_window)
+        // once cameras have picked up this frame's render target
+        .add_systems(PostUpdate, check_camera_sizes.after(CameraUpdateSystem))
+        .run()
+}
+
+fn setup_scene(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    commands.spawn(PbrBundle {
+        mesh: meshes.add(Cuboid::new(1.0, 1.0, 1.0)),
+        material: materials.add(Color::srgb_u8(124, 144, 255)),
+        ..default()
+    });
+    commands.spawn(PointLightBundle {
+        transform: Transform::from_xyz(4.0, 8.0, 4.0),
+        ..default()
+    });
+    commands.spawn(Camera3dBundle {
+        camera: Camera {
+            // deliberately larger than the window gets at its smallest
+            viewport: Some(Viewport {
+                physical_position: UVec2::new(100, 100),
+                physical_size: UVec2::new(800, 600),
+                ..default()
+            }),
+            ..default()
+        },
+        transform: Transform::from_xyz(-2.5, 4.5, 9.0).looking_at(Vec3::ZERO, Vec3::Y),
+        ..default()
+    });
+}
+
+fn resize_window(time: Res<Time>, windows: Query<&AdwaitaWindow, With<PrimaryAdwaitaWindow>>) {
+    let Ok(window) = windows.get_single() else {
+        return;
+    };
+    let t = time.elapsed_seconds();
+    let size = Vec2::new(
+        640.0 + 320.0 * (t * 3.1).sin(),
+        480.0 + 240.0 * (t * 2.3).cos(),
+    );
+    _ = window.set_size(size.as_uvec2());
+}
+
+fn check_camera_sizes(
+    windows: Query<&AdwaitaRenderTargetSize, With<PrimaryAdwaitaWindow>>,
+    cameras: Query<&Camera>,
+) {
+    let Ok(target_size) = windows.get_single() else {
+        return;
+    };
+    for camera in &cameras {
+        let Some(camera_target_size) = camera.physical_target_size() else {
+            continue;
+        };
+        if target_size.physical != UVec2::ZERO && camera_target_size != target_size.physical {
+            error!(
+                "Camera target size {camera_target_size} doesn't match render target size {}",
+                target_size.physical
+            );
+        }
+        if let Some(viewport) = &camera.viewport {
+            let end = viewport.physical_position + viewport.physical_size;
+            if end.x > camera_target_size.x || end.y > camera_target_size.y {
+                error!("Camera viewport ends at {end}, outside of target {camera_target_size}");
+            }
+        }
+    }
+}
@@ -0,0 +1,80 @@
+//! Opens two windows and moves [`PrimaryAdwaitaWindow`] between them every
+//! few seconds, to check that cameras targeting the primary window follow it.
+//!
+//! The camera should always render into whichever window is titled
+//! "Primary".
+
+use std::time::Duration;
+
+use bevy::{prelude::*, render::settings::WgpuSettings, time::common_conditions::on_timer};
+use bevy_mod_adwaita::{
+    AdwaitaWindow, AdwaitaWindowConfig, AdwaitaWindowPlugin, PrimaryAdwaitaWindow,
+};
+
+fn main() -> AppExit {
+    App::new()
+        .add_plugins((
+            DefaultPlugins
+                .set(AdwaitaWindowPlugin::window_plugin())
+                .set(AdwaitaWindowPlugin::render_plugin(WgpuSettings::default())),
+            AdwaitaWindowPlugin {
+                primary_window_config: None,
+                ..default()
+            },
+        ))
+        .add_systems(Startup, setup)
+        .add_systems(
+            Update,
+            swap_primary.run_if(on_timer(Duration::from_secs(3))),
+        )
+        .run()
+}
+
+fn setup(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    commands
+        .spawn(PrimaryAdwaitaWindow)
+        .add(AdwaitaWindow::open(AdwaitaWindowConfig {
+            title: "Primary".into(),
+            ..default()
+        }));
+    commands
+        .spawn_empty()
+        .add(AdwaitaWindow::open(AdwaitaWindowConfig {
+            title: "Secondary".into(),
+            ..default()
+        }));
+
+    commands.spawn(PbrBundle {
+        mesh: meshes.add(Cuboid::new(1.0, 1.0, 1.0)),
+        material: materials.add(Color::srgb_u8(124, 144, 255)),
+        ..default()
+    });
+    commands.spawn(PointLightBundle {
+        transform: Transform::from_xyz(4.0, 8.0, 4.0),
+        ..default()
+    });
+    // targets `WindowRef::Primary` by default
+    commands.spawn(Camera3dBundle {
+        transform: Transform::from_xyz(-2.5, 4.5, 9.0).looking_at(Vec3::ZERO, Vec3::Y),
+        ..default()
+    });
+}
+
+fn swap_primary(
+    mut commands: Commands,
+    windows: Query<(Entity, &AdwaitaWindow, Has<PrimaryAdwaitaWindow>)>,
+) {
+    for (entity, window, is_primary) in &windows {
+        if is_primary {
+            commands.entity(entity).remove::<PrimaryAdwaitaWindow>();
+            _ = window.set_title("Secondary");
+        } else {
+            commands.entity(entity).insert(PrimaryAdwaitaWindow);
+            _ = window.set_title("Primary");
+        }
+    }
+}
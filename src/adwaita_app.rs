@@ -1,48 +1,548 @@
-use std::sync::atomic::{AtomicBool, AtomicI32, Ordering};
-use std::sync::Arc;
+use std::cell::{Cell, RefCell};
+use std::collections::VecDeque;
+use std::fmt;
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, AtomicI32, AtomicU32, AtomicU64, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
 
+use adw::glib::translate::IntoGlib;
 use adw::prelude::*;
-use adw::{glib, gtk};
+use adw::{gdk, gio, glib, gtk};
 use atomicbox::AtomicOptionBox;
+use bevy::color::{Color, ColorToComponents};
+use bevy::ecs::entity::Entity;
+use bevy::math::{UVec2, Vec2};
 
-use crate::render::{self, FrameInfo};
-use crate::{AdwaitaHeaderBar, AdwaitaWindowConfig};
+use crate::paintable::FramePaintable;
+use crate::render::{self, FrameInfo, SoftwareFrame};
+use crate::{
+    AdwaitaAboutConfig, AdwaitaAction, AdwaitaActionBar, AdwaitaColorSchemePreference,
+    AdwaitaCursorIcon, AdwaitaDecorationLayout, AdwaitaDisplayBackend, AdwaitaDisplayCaps,
+    AdwaitaDragContent, AdwaitaFileDialog, AdwaitaFileFilter, AdwaitaFramePresentation,
+    AdwaitaHeaderBar, AdwaitaHeaderButton, AdwaitaHeaderButtonSide, AdwaitaHeaderTitle,
+    AdwaitaKeyFilter, AdwaitaLogCapture, AdwaitaMenuItem, AdwaitaMenuItemKind, AdwaitaMonitor,
+    AdwaitaPointerGestureKind, AdwaitaRenderArea, AdwaitaRequest, AdwaitaResponsePayload,
+    AdwaitaSafeArea, AdwaitaSplash, AdwaitaStylusPhase, AdwaitaSystemSettings, AdwaitaTitleEmitter,
+    AdwaitaToolbarStyle, AdwaitaView, AdwaitaWindowConfig, AdwaitaWindowHint, AdwaitaWindowState,
+    FramesInFlight,
+};
 
 #[derive(Debug)]
 pub struct WindowOpen {
+    /// Entity of the window on the Bevy side, used for diagnostics.
+    pub entity: Entity,
     pub config: AdwaitaWindowConfig,
-    pub recv_command: flume::Receiver<WindowCommand>,
+    pub builders: WindowBuilders,
+    pub recv_command: flume::Receiver<WindowMessage>,
     pub render_target_width: Arc<AtomicI32>,
     pub render_target_height: Arc<AtomicI32>,
-    pub scale_factor: Arc<AtomicI32>,
+    /// Bits of the fractional scale factor of the window's surface as an
+    /// [`f32`].
+    pub scale_factor: Arc<AtomicU32>,
     pub shared_next_frame: Arc<AtomicOptionBox<FrameInfo>>,
     pub closed: Arc<AtomicBool>,
+    pub focused: Arc<AtomicBool>,
+    pub fullscreen: Arc<AtomicBool>,
+    pub maximized: Arc<AtomicBool>,
+    /// Latest title set with `AdwaitaWindow::set_title` which hasn't been
+    /// applied yet.
+    pub pending_title: Arc<AtomicOptionBox<String>>,
+    pub send_event: flume::Sender<WindowEvent>,
+    pub frames_in_flight: Arc<FramesInFlight>,
+    pub presented_frame_id: Arc<AtomicU64>,
+    /// Latest frame read back into CPU memory, if this window uses software
+    /// present instead of dmabufs.
+    pub shared_software_frame: Option<Arc<AtomicOptionBox<SoftwareFrame>>>,
+    /// Notified every time the window's frame clock ticks, if this window
+    /// paces Bevy's main loop.
+    pub send_frame_tick: Option<flume::Sender<()>>,
+    /// Entity of the window whose frames this window presents, if it's a
+    /// mirror.
+    pub mirror_of: Option<Entity>,
 }
 
+/// User-provided callbacks which customize the window's widget tree.
+#[derive(Debug, Default)]
+pub struct WindowBuilders {
+    pub window: Option<BuildWindow>,
+    pub overlay: Option<BuildOverlay>,
+    pub sidebar: Option<BuildSidebar>,
+    pub title: Option<BuildTitle>,
+    pub views: Option<BuildViews>,
+    pub bottom_bars: Option<BuildBottomBars>,
+}
+
+/// Builds a custom window around the render widget, instead of the crate
+/// creating its own window.
+pub struct BuildWindow(pub Box<dyn FnOnce(AdwaitaRenderArea) -> gtk::Window + Send>);
+
+impl fmt::Debug for BuildWindow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BuildWindow").finish_non_exhaustive()
+    }
+}
+
+/// Adds widgets on top of the render widget, in the crate's own window.
+pub struct BuildOverlay(pub Box<dyn FnOnce(&gtk::Overlay) + Send>);
+
+impl fmt::Debug for BuildOverlay {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BuildOverlay").finish_non_exhaustive()
+    }
+}
+
+/// Builds the sidebar of the crate's own window.
+pub struct BuildSidebar(pub Box<dyn FnOnce() -> gtk::Widget + Send>);
+
+impl fmt::Debug for BuildSidebar {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BuildSidebar").finish_non_exhaustive()
+    }
+}
+
+/// Builds a custom title widget for the header bar of the crate's own window.
+pub struct BuildTitle(pub Box<dyn FnOnce(AdwaitaTitleEmitter) -> gtk::Widget + Send>);
+
+impl fmt::Debug for BuildTitle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BuildTitle").finish_non_exhaustive()
+    }
+}
+
+/// Builds the bars along the bottom of the crate's own window.
+pub struct BuildBottomBars(pub Box<dyn FnOnce() -> Vec<gtk::Widget> + Send>);
+
+impl fmt::Debug for BuildBottomBars {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BuildBottomBars").finish_non_exhaustive()
+    }
+}
+
+/// Builds the pages of a view stack in the crate's own window, besides the
+/// render area's own page.
+pub struct BuildViews {
+    pub render_view: AdwaitaView,
+    pub build_pages: Box<dyn FnOnce() -> Vec<(AdwaitaView, gtk::Widget)> + Send>,
+}
+
+impl fmt::Debug for BuildViews {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BuildViews")
+            .field("render_view", &self.render_view)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Closure to run on the GTK main thread, from
+/// [`AdwaitaWindow::run_on_main_thread`](crate::AdwaitaWindow::run_on_main_thread).
+pub struct RunOnMainThread(pub Box<dyn FnOnce(&gtk::Window) + Send>);
+
+impl fmt::Debug for RunOnMainThread {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RunOnMainThread").finish_non_exhaustive()
+    }
+}
+
+/// Sent from the Bevy app to the GTK side of a window, and handled in the
+/// order it was sent.
 #[derive(Debug)]
+pub enum WindowMessage {
+    Command(WindowCommand),
+    Run(RunOnMainThread),
+    /// Stops presenting the current frame, and shows the backdrop instead.
+    ClearFrame,
+    /// Presents frames which Bevy reads back to the CPU from now on, after
+    /// GTK couldn't import our dmabufs.
+    UseSoftwarePresent(Arc<AtomicOptionBox<SoftwareFrame>>),
+    /// Shows this text in the diagnostics overlay, or hides it if [`None`].
+    Diagnostics(Option<String>),
+}
+
+/// Command which changes an open [`AdwaitaWindow`](crate::AdwaitaWindow) on
+/// the GTK side.
+///
+/// Each command has a matching setter on `AdwaitaWindow`, but these can
+/// be sent directly with [`AdwaitaWindow::send_command`](crate::AdwaitaWindow::send_command).
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
 pub enum WindowCommand {
     SetMaximized(bool),
     SetFullscreen(bool),
     SetTitle(String),
+    /// Sets the name of the window's icon, or falls back to the
+    /// application's if [`None`].
+    SetIconName(Option<String>),
+    /// Sets the default size of the window, in logical pixels.
+    /// This applies even if the user can't resize the window.
+    SetSize(UVec2),
+    /// Sets whether the user can resize the window.
+    SetUserResizable(bool),
+    SetBackdropColor(Option<Color>),
+    SetHideOnClose(bool),
+    /// Shows the window if it was hidden, and brings it to the front.
+    Present,
+    SetSidebarShown(bool),
+    SetSkipTaskbar(bool),
+    SetSkipPager(bool),
+    /// Makes the window transient for the window of this entity, once it's
+    /// open, or for no window if [`None`].
+    SetTransientFor(Option<Entity>),
+    /// Switches to the view with this ID, in a window opened with
+    /// [`AdwaitaWindow::open_with_views`](crate::AdwaitaWindow::open_with_views).
+    SetView(String),
+    /// Shows progress between `0.0` and `1.0` in the window and the taskbar,
+    /// or hides it if [`None`].
+    SetProgress(Option<f32>),
+    /// Shows or hides the search bar of a window with
+    /// [`AdwaitaWindowConfig::header_search`](crate::AdwaitaWindowConfig::header_search).
+    SetSearchShown(bool),
+    /// Shows or hides the action bar of a window with
+    /// [`AdwaitaWindowConfig::action_bar`](crate::AdwaitaWindowConfig::action_bar).
+    SetActionBarShown(bool),
+    /// Sets the cursor theme and size for the window's whole display, or
+    /// follows the system setting for either if [`None`].
+    SetCursorTheme {
+        name: Option<String>,
+        size: Option<u32>,
+    },
+    /// Sets the cursor shown over the render area.
+    SetCursorIcon(AdwaitaCursorIcon),
+    /// Shows or hides the cursor over the render area.
+    SetCursorVisible(bool),
+    /// Shows the emoji chooser of a window with
+    /// [`AdwaitaWindowConfig::text_input`](crate::AdwaitaWindowConfig::text_input),
+    /// and sends the chosen emoji as text input.
+    ShowEmojiChooser,
+    /// Starts dragging this content out of the window, from the pointer's
+    /// current position.
+    StartDrag(AdwaitaDragContent),
+    /// Moves the window to this workspace, on X11.
+    MoveToWorkspace(u32),
+    /// Moves keyboard focus back to the render area, away from any native
+    /// widget.
+    FocusRenderArea,
+    /// Shows a toast over the render area for `timeout_secs` seconds, or
+    /// until it's dismissed if `0`.
+    ShowToast {
+        title: String,
+        timeout_secs: u32,
+    },
+    /// Shows an about window for the app, on top of this window.
+    ShowAbout(AdwaitaAboutConfig),
+    /// Puts text on the clipboard of the window's display.
+    SetClipboardText(String),
+    /// Closes the window, or hides it with
+    /// [`AdwaitaWindowConfig::hide_on_close`](crate::AdwaitaWindowConfig::hide_on_close),
+    /// even with
+    /// [`AdwaitaWindowConfig::intercept_close`](crate::AdwaitaWindowConfig::intercept_close).
+    Close,
+}
+
+/// Command sent from the Bevy app to the GTK side, not tied to any specific
+/// window.
+#[derive(Debug)]
+pub enum AppCommand {
+    /// Replaces all installed accelerators with these `(accelerator, action)`
+    /// pairs.
+    SetKeymap(Vec<(String, String)>),
+    SetColorScheme(AdwaitaColorSchemePreference),
+    OpenInspector,
+    /// Starts a request, whose result is sent back as an
+    /// [`AppEvent::Response`] with the same ID.
+    Request {
+        id: AdwaitaRequest,
+        request: AppRequest,
+    },
+    /// Destroys every window and stops the GTK thread, once the app exits.
+    Shutdown,
+}
+
+/// Operation started by [`AppCommand::Request`].
+#[derive(Debug)]
+pub enum AppRequest {
+    ReadClipboardText,
+    /// Finds the workspace of the window of this entity.
+    Workspace(Entity),
+    /// Asks the user to pick an existing file, with a dialog over the window
+    /// of this entity.
+    OpenFile {
+        window: Entity,
+        dialog: AdwaitaFileDialog,
+    },
+    /// Asks the user where to save a file, with a dialog over the window of
+    /// this entity.
+    SaveFile {
+        window: Entity,
+        dialog: AdwaitaFileDialog,
+    },
+}
+
+/// Event sent from the GTK side back to the Bevy app, not tied to any
+/// specific window.
+#[derive(Debug)]
+pub enum AppEvent {
+    DisplayCaps(AdwaitaDisplayCaps),
+    DisplayLost,
+    ActionActivated(String),
+    ColorScheme {
+        dark: bool,
+        system_supported: bool,
+    },
+    SystemSettings(AdwaitaSystemSettings),
+    ReducedMotion(bool),
+    Response {
+        id: AdwaitaRequest,
+        payload: Result<AdwaitaResponsePayload, String>,
+    },
+}
+
+/// Event sent from the GTK side of a window back to the Bevy app.
+#[derive(Debug)]
+pub enum WindowEvent {
+    Error {
+        operation: &'static str,
+        message: String,
+    },
+    /// GTK couldn't import a frame laid out with this DRM format modifier,
+    /// e.g. because the display is driven by another GPU.
+    DmabufImportFailed {
+        modifier: u64,
+    },
+    /// Positions are in logical pixels, relative to the render widget.
+    CursorMoved {
+        position: Vec2,
+    },
+    CursorEntered {
+        position: Vec2,
+    },
+    CursorLeft,
+    MouseButton {
+        button: u32,
+        pressed: bool,
+    },
+    /// Deltas point right and down, in lines if `lines` is set, or else in
+    /// logical pixels.
+    Scroll {
+        delta: Vec2,
+        lines: bool,
+    },
+    StateChanged(AdwaitaWindowState),
+    FramePresented(AdwaitaFramePresentation),
+    SafeAreaChanged(AdwaitaSafeArea),
+    MonitorChanged(AdwaitaMonitor),
+    SidebarShown(bool),
+    /// The user asked to close a window with
+    /// [`AdwaitaWindowConfig::intercept_close`], which was kept open.
+    CloseRequested,
+    TitleWidget {
+        key: String,
+        value: String,
+    },
+    SearchChanged(String),
+    SearchActivated(String),
+    SearchShown(bool),
+    ViewChanged(String),
+    ActionActivated(String),
+    HeaderButtonPressed(String),
+    MenuActivated {
+        id: String,
+        checked: Option<bool>,
+    },
+    TextInput(String),
+    /// Position is in logical pixels, relative to the render widget.
+    PointerGesture {
+        position: Vec2,
+        kind: AdwaitaPointerGestureKind,
+    },
+    DragEnded {
+        accepted: bool,
+    },
+    /// Position is in logical pixels, relative to the render widget.
+    FilesDropped {
+        position: Vec2,
+        paths: Vec<PathBuf>,
+    },
+    /// Position is in logical pixels, relative to the render widget.
+    Stylus {
+        position: Vec2,
+        phase: AdwaitaStylusPhase,
+        pressure: f32,
+        tilt: Vec2,
+        button: Option<u32>,
+        eraser: bool,
+    },
+    /// Position is in logical pixels, relative to the render widget.
+    PrimaryPaste {
+        position: Vec2,
+        text: String,
+    },
+    RawKey {
+        pressed: bool,
+        keyval: u32,
+        keycode: u32,
+        group: u32,
+        modifiers: u32,
+    },
+}
+
+/// Configuration for the GTK side of the app as a whole.
+#[derive(Debug, Clone)]
+pub struct AppConfig {
+    pub application_id: Option<String>,
+    pub flags: gio::ApplicationFlags,
+    pub log_capture: Option<AdwaitaLogCapture>,
+    pub open_inspector: bool,
+    pub resources: Vec<PathBuf>,
+    pub icon_search_paths: Vec<PathBuf>,
+    pub icon_resource_paths: Vec<String>,
+    pub color_scheme_fallback: AdwaitaColorSchemePreference,
+    pub splash: Option<AdwaitaSplash>,
 }
 
-pub fn main_thread_loop(recv_window_open: flume::Receiver<WindowOpen>) {
+/// How long to block waiting for a window to be opened while there are no
+/// windows, before iterating the main context again.
+const IDLE_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+pub fn main_thread_loop(
+    config: AppConfig,
+    recv_window_open: flume::Receiver<WindowOpen>,
+    recv_app_command: flume::Receiver<AppCommand>,
+    send_app_event: flume::Sender<AppEvent>,
+) {
+    if let Some(log_capture) = config.log_capture.clone() {
+        // before `init`, so that we capture anything logged while initializing
+        install_log_writer(log_capture);
+    }
+
     // when we `init`, this thread is marked as the main thread
-    adw::init().expect("failed to initialize Adwaita");
+    if let Err(err) = adw::init() {
+        // dropping `recv_window_open` here lets the Bevy side notice that we're gone
+        tracing::error!("Failed to initialize Adwaita: {err}");
+        return;
+    }
     let main_context = glib::MainContext::default();
-    let mut windows = Vec::new();
+    let mut windows = Vec::<WindowState>::new();
+    if config.open_inspector {
+        gtk::Window::set_interactive_debugging(true);
+    }
+    let application = make_application(&config);
+    let mut keymap_actions = Vec::<String>::new();
+
+    watch_color_scheme(&send_app_event, config.color_scheme_fallback);
+    watch_system_settings(&send_app_event);
+
+    let display_lost = Rc::new(Cell::new(false));
+    if let Some(display) = gdk::Display::default() {
+        load_resources(&config, &display);
+        let caps = probe_display_caps(&display);
+        tracing::info!("Display capabilities: {caps:?}");
+        _ = send_app_event.send(AppEvent::DisplayCaps(caps));
+
+        display.connect_closed({
+            let display_lost = display_lost.clone();
+            move |_, is_error| {
+                tracing::warn!("Display connection closed (is_error = {is_error})");
+                display_lost.set(true);
+            }
+        });
+    } else {
+        tracing::error!("No default display available");
+        display_lost.set(true);
+    }
+    let mut splash = config
+        .splash
+        .as_ref()
+        .filter(|_| !display_lost.get())
+        .map(make_splash);
 
     loop {
-        match recv_window_open.try_recv() {
-            Ok(request) => {
-                let window_state = WindowState::new(request);
-                windows.push(window_state);
+        if display_lost.get() {
+            // we can't do anything useful without a display, so close all
+            // windows and shut down this thread, leaving the app to decide
+            // what to do next
+            for window in &windows {
+                window.closed.store(true, Ordering::SeqCst);
+            }
+            _ = send_app_event.send(AppEvent::DisplayLost);
+            return;
+        }
+
+        let request = if windows.is_empty() {
+            // there's nothing to poll, so don't spin while we wait for a window
+            match recv_window_open.recv_timeout(IDLE_POLL_INTERVAL) {
+                Ok(request) => Some(request),
+                Err(flume::RecvTimeoutError::Disconnected) => return,
+                Err(flume::RecvTimeoutError::Timeout) => None,
+            }
+        } else {
+            match recv_window_open.try_recv() {
+                Ok(request) => Some(request),
+                Err(flume::TryRecvError::Disconnected) => return,
+                Err(flume::TryRecvError::Empty) => None,
+            }
+        };
+        if let Some(request) = request {
+            let transient_for = request.config.transient_for.and_then(|parent| {
+                windows
+                    .iter()
+                    .find(|window| window.entity == parent)
+                    .map(|window| window.window.clone())
+            });
+            let window_state =
+                WindowState::new(request, application.as_ref(), transient_for.as_ref());
+            windows.push(window_state);
+        }
+
+        for command in recv_app_command.try_iter() {
+            match command {
+                AppCommand::SetKeymap(bindings) => {
+                    let Some(application) = &application else {
+                        tracing::warn!("Cannot install keymap without a registered application");
+                        continue;
+                    };
+                    install_keymap(application, &mut keymap_actions, bindings, &send_app_event);
+                }
+                AppCommand::SetColorScheme(preference) => {
+                    let style_manager = adw::StyleManager::default();
+                    style_manager.set_color_scheme(adw_color_scheme(
+                        &style_manager,
+                        preference,
+                        config.color_scheme_fallback,
+                    ));
+                }
+                AppCommand::OpenInspector => {
+                    gtk::Window::set_interactive_debugging(true);
+                }
+                AppCommand::Request { id, request } => {
+                    start_request(id, request, &windows, &send_app_event);
+                }
+                AppCommand::Shutdown => {
+                    tracing::info!("Shutting down GTK thread");
+                    if let Some(splash) = splash.take() {
+                        splash.destroy();
+                    }
+                    for window in windows.drain(..) {
+                        window.window.destroy();
+                    }
+                    return;
+                }
             }
-            Err(flume::TryRecvError::Disconnected) => return,
-            Err(flume::TryRecvError::Empty) => {}
         }
 
         windows.retain_mut(|window| window.poll().is_ok());
+        sync_mirrors(&windows);
+        sync_transient_parents(&windows);
+        if splash.is_some()
+            && windows
+                .iter()
+                .any(|window| window.shown_frame.get().is_some())
+        {
+            if let Some(splash) = splash.take() {
+                splash.destroy();
+            }
+        }
 
         if main_context.pending() {
             main_context.iteration(true);
@@ -50,153 +550,539 @@ pub fn main_thread_loop(recv_window_open: flume::Receiver<WindowOpen>) {
     }
 }
 
+/// Shows the splash window, which stays open until the first frame is shown.
+fn make_splash(splash: &AdwaitaSplash) -> gtk::Window {
+    let content = gtk::Box::builder()
+        .orientation(gtk::Orientation::Vertical)
+        .spacing(24)
+        .valign(gtk::Align::Center)
+        .build();
+    if let Some(image) = &splash.image {
+        if !image.is_file() {
+            tracing::warn!("Splash image {} doesn't exist", image.display());
+        }
+        let picture = gtk::Picture::for_filename(image);
+        picture.set_content_fit(gtk::ContentFit::ScaleDown);
+        content.append(&picture);
+    }
+    let spinner = gtk::Spinner::builder()
+        .spinning(true)
+        .width_request(32)
+        .height_request(32)
+        .build();
+    content.append(&spinner);
+
+    // not part of the application, so that it isn't mistaken for the window
+    // which new windows are opened over
+    let window = gtk::Window::builder()
+        .decorated(false)
+        .resizable(false)
+        .default_width(i32::try_from(splash.size.x).unwrap_or(i32::MAX))
+        .default_height(i32::try_from(splash.size.y).unwrap_or(i32::MAX))
+        .child(&content)
+        .build();
+    window.present();
+    window
+}
+
+fn start_request(
+    id: AdwaitaRequest,
+    request: AppRequest,
+    windows: &[WindowState],
+    send_app_event: &flume::Sender<AppEvent>,
+) {
+    let respond = {
+        let send_app_event = send_app_event.clone();
+        move |payload: Result<AdwaitaResponsePayload, String>| {
+            _ = send_app_event.send(AppEvent::Response { id, payload });
+        }
+    };
+    let Some(display) = gdk::Display::default() else {
+        respond(Err("no display available".into()));
+        return;
+    };
+
+    match request {
+        AppRequest::ReadClipboardText => {
+            display
+                .clipboard()
+                .read_text_async(None::<&gio::Cancellable>, move |result| {
+                    respond(
+                        result
+                            .map(|text| {
+                                AdwaitaResponsePayload::ClipboardText(text.map(String::from))
+                            })
+                            .map_err(|err| err.to_string()),
+                    );
+                });
+        }
+        AppRequest::Workspace(entity) => {
+            let Some(window) = windows.iter().find(|window| window.entity == entity) else {
+                respond(Err(format!("{entity} isn't an open window")));
+                return;
+            };
+            respond(workspace(&window.window).map(AdwaitaResponsePayload::Workspace));
+        }
+        AppRequest::OpenFile { window, dialog } => {
+            show_file_dialog(windows, window, &dialog, false, respond);
+        }
+        AppRequest::SaveFile { window, dialog } => {
+            show_file_dialog(windows, window, &dialog, true, respond);
+        }
+    }
+}
+
+fn show_file_dialog(
+    windows: &[WindowState],
+    window: Entity,
+    dialog: &AdwaitaFileDialog,
+    save: bool,
+    respond: impl FnOnce(Result<AdwaitaResponsePayload, String>) + 'static,
+) {
+    let Some(parent) = windows.iter().find(|state| state.entity == window) else {
+        respond(Err(format!("{window} isn't an open window")));
+        return;
+    };
+    let on_chosen = move |result: Result<gio::File, glib::Error>| {
+        respond(match result {
+            Ok(file) => Ok(AdwaitaResponsePayload::File(file.path())),
+            // the user closed the dialog without picking anything
+            Err(err) if err.matches(gtk::DialogError::Dismissed) => {
+                Ok(AdwaitaResponsePayload::File(None))
+            }
+            Err(err) => Err(err.to_string()),
+        });
+    };
+    let file_dialog = make_file_dialog(dialog);
+    if save {
+        file_dialog.save(Some(&parent.window), None::<&gio::Cancellable>, on_chosen);
+    } else {
+        file_dialog.open(Some(&parent.window), None::<&gio::Cancellable>, on_chosen);
+    }
+}
+
+fn make_file_dialog(dialog: &AdwaitaFileDialog) -> gtk::FileDialog {
+    let file_dialog = gtk::FileDialog::builder().modal(true).build();
+    if !dialog.title.is_empty() {
+        file_dialog.set_title(&dialog.title);
+    }
+    if let Some(suggested_name) = &dialog.suggested_name {
+        file_dialog.set_initial_name(Some(suggested_name));
+    }
+    if !dialog.filters.is_empty() {
+        let filters = gio::ListStore::new::<gtk::FileFilter>();
+        for AdwaitaFileFilter {
+            name,
+            patterns,
+            mime_types,
+        } in &dialog.filters
+        {
+            let filter = gtk::FileFilter::new();
+            filter.set_name(Some(name));
+            for pattern in patterns {
+                filter.add_pattern(pattern);
+            }
+            for mime_type in mime_types {
+                filter.add_mime_type(mime_type);
+            }
+            filters.append(&filter);
+        }
+        file_dialog.set_filters(Some(&filters));
+    }
+    file_dialog
+}
+
+fn workspace(window: &gtk::Window) -> Result<u32, String> {
+    let Some(surface) = window.surface() else {
+        return Err("window isn't shown yet".into());
+    };
+    #[cfg(feature = "x11")]
+    if let Some(surface) = surface.downcast_ref::<gdk_x11::X11Surface>() {
+        return Ok(surface.desktop());
+    }
+
+    #[cfg(not(feature = "x11"))]
+    let _ = surface;
+    Err("workspaces are only supported on X11 with the `x11` feature enabled".into())
+}
+
+fn move_to_workspace(window: &gtk::Window, workspace: u32) {
+    #[cfg(feature = "x11")]
+    if let Some(surface) = window.surface().and_downcast::<gdk_x11::X11Surface>() {
+        surface.move_to_desktop(workspace);
+        return;
+    }
+
+    #[cfg(not(feature = "x11"))]
+    let _ = window;
+    tracing::warn!(
+        "Moving windows between workspaces is only supported on X11 with the `x11` feature \
+         enabled, ignoring move to workspace {workspace}"
+    );
+}
+
 #[derive(Debug)]
 struct WindowState {
-    window: adw::Window,
+    entity: Entity,
+    window: gtk::Window,
     render_target: gtk::Picture,
+    paintable: FramePaintable,
     shared_next_frame: Arc<AtomicOptionBox<FrameInfo>>,
-    recv_command: flume::Receiver<WindowCommand>,
+    recv_command: flume::Receiver<WindowMessage>,
     closed: Arc<AtomicBool>,
+    pending_title: Arc<AtomicOptionBox<String>>,
+    send_event: flume::Sender<WindowEvent>,
+    frames_in_flight: Arc<FramesInFlight>,
+    presented_frame_id: Arc<AtomicU64>,
+    shared_software_frame: Option<Arc<AtomicOptionBox<SoftwareFrame>>>,
     should_poll: Arc<AtomicBool>,
     current_frame: Option<FrameInfo>,
+    /// Frame which the paintable currently draws, for reporting when it's
+    /// presented.
+    shown_frame: Rc<Cell<Option<ShownFrame>>>,
+    /// Frame waiting to be shown on the next frame clock tick, if the window
+    /// uses [`AdwaitaWindowConfig::present_smoothing`].
+    queued_frame: Option<Rc<RefCell<Option<QueuedFrame>>>>,
+    /// Widgets which may be drawn over the render widget, which the safe area
+    /// avoids.
+    chrome: Vec<gtk::Widget>,
+    safe_area: AdwaitaSafeArea,
+    /// Name appended to the window title, with
+    /// [`AdwaitaWindowConfig::title_with_app_name`].
+    title_app_name: Option<String>,
+    header_title_label: Option<gtk::Label>,
+    /// Size request of the window while it's resizable.
+    min_size: Option<(i32, i32)>,
+    backdrop: Backdrop,
+    split_view: Option<adw::OverlaySplitView>,
+    skip_hints: Rc<Cell<SkipHints>>,
+    mirror_of: Option<Entity>,
+    /// Entity of the window which this one should be transient for.
+    transient_for: Option<Entity>,
+    fade: Option<Fade>,
+    /// Lets the next close request through, with
+    /// [`AdwaitaWindowConfig::intercept_close`].
+    close_confirmed: Rc<Cell<bool>>,
+    search_bar: Option<gtk::SearchBar>,
+    progress_bar: Option<gtk::ProgressBar>,
+    diagnostics_label: Option<gtk::Label>,
+    toast_overlay: Option<adw::ToastOverlay>,
+    view_stack: Option<adw::ViewStack>,
+    action_bar: Option<gtk::ActionBar>,
+    emoji_chooser: Option<gtk::EmojiChooser>,
+    /// Drag started with [`WindowCommand::StartDrag`], kept alive until the
+    /// next one starts.
+    drag: Option<gdk::Drag>,
+    cursor_icon: AdwaitaCursorIcon,
+    cursor_visible: bool,
 }
 
 impl WindowState {
-    fn new(request: WindowOpen) -> Self {
+    fn new(
+        request: WindowOpen,
+        application: Option<&adw::Application>,
+        transient_for: Option<&gtk::Window>,
+    ) -> Self {
         let WindowOpen {
+            entity,
             config,
+            builders,
             recv_command,
             render_target_width,
             render_target_height,
             scale_factor,
             shared_next_frame,
             closed,
+            focused,
+            fullscreen,
+            maximized,
+            pending_title,
+            send_event,
+            frames_in_flight,
+            presented_frame_id,
+            shared_software_frame,
+            send_frame_tick,
+            mirror_of,
         } = request;
 
-        let render_target = gtk::Picture::new();
-        let render_target_container = {
-            let graphics_offload = gtk::GraphicsOffload::builder()
-                .black_background(true)
-                .child(&render_target)
-                .hexpand(true)
-                .vexpand(true)
-                .build();
-
-            // Use a trick to detect when the actual render target
-            // is resized, and send this new frame size to the app.
-            // https://stackoverflow.com/questions/70488187/get-calculated-size-of-widget-in-gtk-4-0
-            // +-----------------------+
-            // |          WL           |  WL: width_listener  (height 0)
-            // |-----------------------|  HL: height_listener (width 0)
-            // |   |                   |
-            // | H |     graphics      |
-            // | L |     offload       |
-            // |   |                   |
-            // +-----------------------+
-
-            let width_listener = gtk::DrawingArea::builder().hexpand(true).build();
-            width_listener.set_draw_func({
-                let render_target_width = render_target_width.clone();
-                move |_, _, width, _| {
-                    render_target_width.store(width, Ordering::SeqCst);
-                }
-            });
-
-            let height_listener = gtk::DrawingArea::builder().vexpand(true).build();
-            height_listener.set_draw_func({
-                let render_target_height = render_target_height.clone();
-                move |_, _, _, height| {
-                    render_target_height.store(height, Ordering::SeqCst);
-                }
-            });
-
-            let frame_content_h = gtk::Box::new(gtk::Orientation::Horizontal, 0);
-            frame_content_h.append(&height_listener);
-            frame_content_h.append(&graphics_offload);
-
-            let frame_content_v = gtk::Box::new(gtk::Orientation::Vertical, 0);
-            frame_content_v.append(&width_listener);
-            frame_content_v.append(&frame_content_h);
-
-            frame_content_v
+        let paintable = FramePaintable::default();
+        let render_target = gtk::Picture::for_paintable(&paintable);
+        let (render_widget, graphics_offload) =
+            make_render_widget(&render_target, &render_target_width, &render_target_height);
+        let clamp = config
+            .max_content_width
+            .map(|max_width| make_content_clamp(&render_widget, max_width));
+        let backdrop = Backdrop::new(&render_widget, clamp.as_ref(), graphics_offload);
+        let render_widget: gtk::Widget = match clamp {
+            Some(clamp) => clamp.upcast(),
+            None => render_widget.upcast(),
         };
+        backdrop.set_color(
+            config
+                .backdrop_color
+                .or(config.transparent.then_some(Color::NONE)),
+        );
 
-        let content: gtk::Widget = match config.header_bar {
-            AdwaitaHeaderBar::Full => {
-                let header_bar = adw::HeaderBar::new();
-
-                let content = gtk::Box::new(gtk::Orientation::Vertical, 0);
-                content.append(&header_bar);
-                content.append(&render_target_container);
-                content.upcast()
-            }
-            AdwaitaHeaderBar::OverContent => {
-                // this margin makes the window controls looks exactly like in an `adw::HeaderBar`
-                const MARGIN: i32 = 6;
-
-                let window_controls = gtk::WindowControls::builder()
-                    .side(gtk::PackType::End)
-                    .halign(gtk::Align::End)
-                    .valign(gtk::Align::Start)
-                    .margin_start(MARGIN)
-                    .margin_end(MARGIN)
-                    .margin_top(MARGIN)
-                    .margin_bottom(MARGIN)
-                    .build();
-
-                let content = gtk::Overlay::new();
-                content.set_child(Some(&render_target_container));
-                content.add_overlay(&window_controls);
-                content.upcast()
-            }
-            AdwaitaHeaderBar::None => render_target_container.upcast(),
-        };
+        // so that native widgets such as the search entry don't keep focus
+        // after the user goes back to the content
+        render_target.set_focusable(true);
+        render_target.add_controller(make_motion_controller(&send_event));
+        if config.focus_render_area_on_click {
+            render_target.add_controller(make_focus_on_click_controller());
+        }
+        if config.pointer_input {
+            render_target.add_controller(make_button_controller(&send_event));
+            render_target.add_controller(make_scroll_controller(&send_event));
+        }
+        if config.primary_paste {
+            render_target.add_controller(make_primary_paste_controller(&send_event));
+        }
+        if config.file_drop {
+            render_target.add_controller(make_file_drop_target(&send_event));
+        }
+        if config.pointer_gestures {
+            render_target.add_controller(make_secondary_click_controller(&send_event));
+            render_target.add_controller(make_long_press_controller(&send_event));
+        }
+        if config.stylus_input {
+            render_target.add_controller(make_stylus_controller(&send_event));
+        }
 
-        let window = adw::Window::builder()
-            .handle_menubar_accel(false)
-            .default_width(assert_i32(config.width, "window request width"))
-            .default_height(assert_i32(config.height, "window request height"))
-            .title(config.title)
-            .resizable(config.resizable)
-            .maximized(config.maximized)
-            .fullscreened(config.fullscreen)
-            .content(&content)
-            .build();
+        let mut split_view = None;
+        let mut search_bar = None;
+        let mut progress_bar = None;
+        let mut diagnostics_label = None;
+        let mut toast_overlay = None;
+        let mut view_stack = None;
+        let mut action_bar = None;
+        let mut chrome = Vec::new();
+        let mut header_title_label = None;
+        let mut layout_min_size = None;
+        let window = match builders.window {
+            Some(BuildWindow(build_window)) => build_window(AdwaitaRenderArea {
+                widget: render_widget,
+                paintable: paintable.clone().upcast(),
+            }),
+            None => {
+                let default_window =
+                    make_default_window(&config, &render_widget, builders, &send_event);
+                split_view = default_window.split_view;
+                search_bar = default_window.search_bar;
+                progress_bar = Some(default_window.progress_bar);
+                diagnostics_label = Some(default_window.diagnostics_label);
+                toast_overlay = Some(default_window.toast_overlay);
+                view_stack = default_window.view_stack;
+                action_bar = default_window.action_bar;
+                chrome = default_window.chrome;
+                header_title_label = default_window.header_title_label;
+                layout_min_size = default_window.layout_min_size;
+                default_window.window.upcast()
+            }
+        };
+        let min_size = window_min_size(entity, config.min_size, layout_min_size);
+        if let (Some((width, height)), true) = (min_size, window.is_resizable()) {
+            window.set_size_request(width, height);
+        }
+        set_application_name(config.app_name.as_deref().unwrap_or(&config.title));
+        let title_app_name = config
+            .title_with_app_name
+            .then(glib::application_name)
+            .flatten()
+            .map(String::from);
+        if let Some(app_name) = &title_app_name {
+            window.set_title(Some(&window_title(&config.title, Some(app_name))));
+        }
+        // look this up before we become part of the application ourselves
+        let parent = application.and_then(|application| application.active_window());
+        window.set_application(application);
+        apply_window_hint(&window, config.window_hint, parent.as_ref());
+        let skip_hints = Rc::new(Cell::new(SkipHints {
+            taskbar: config.skip_taskbar,
+            pager: config.skip_pager,
+            utility: config.window_hint == AdwaitaWindowHint::Utility,
+        }));
+        watch_skip_hints(&window, &skip_hints);
 
+        if config.hide_on_close {
+            window.set_hide_on_close(true);
+        }
+        let fade = config
+            .fade_transition
+            .map(|duration| Fade::new(&window, duration));
+        let close_confirmed = Rc::new(Cell::new(false));
         window.connect_close_request({
             let closed = closed.clone();
-            move |_| {
-                closed.store(true, Ordering::SeqCst);
+            let fade = fade.clone();
+            let close_confirmed = close_confirmed.clone();
+            let send_event = send_event.clone();
+            let intercept = config.intercept_close;
+            move |window| {
+                if intercept && !close_confirmed.replace(false) {
+                    _ = send_event.send(WindowEvent::CloseRequested);
+                    return glib::Propagation::Stop;
+                }
+                // GTK will just hide the window, which we track through its state
+                if !window.hides_on_close() {
+                    closed.store(true, Ordering::SeqCst);
+                } else if let Some(fade) = &fade {
+                    // we hide the window ourselves once it's faded out
+                    fade.hide(window);
+                    return glib::Propagation::Stop;
+                }
                 glib::Propagation::Proceed
             }
         });
 
-        window.connect_scale_factor_notify({
+        window.connect_is_active_notify({
+            let focused = focused.clone();
+            move |window| {
+                focused.store(window.is_active(), Ordering::SeqCst);
+            }
+        });
+        window.connect_fullscreened_notify(move |window| {
+            fullscreen.store(window.is_fullscreen(), Ordering::SeqCst);
+        });
+        window.connect_maximized_notify(move |window| {
+            maximized.store(window.is_maximized(), Ordering::SeqCst);
+        });
+
+        watch_window_state(&window, &send_event);
+        watch_monitor(&window, &send_event);
+        if let Some(icon_name) = &config.icon_name {
+            warn_on_missing_icon(icon_name);
+            window.set_icon_name(Some(icon_name));
+        }
+        set_cursor(&render_target, config.cursor_icon, config.cursor_visible);
+        if config.cursor_theme_name.is_some() || config.cursor_theme_size.is_some() {
+            set_cursor_theme(
+                &window,
+                config.cursor_theme_name.as_deref(),
+                config.cursor_theme_size,
+            );
+        }
+        let shown_frame = Rc::new(Cell::new(None));
+        watch_presentation(&window, &shown_frame, &send_event);
+        if config.emit_raw_keys || config.keyboard_input {
+            window.add_controller(make_raw_key_controller(config.raw_key_filter, &send_event));
+        }
+        // otherwise GTK focuses the first native widget it finds
+        render_target.grab_focus();
+        let mut emoji_chooser = None;
+        if config.text_input {
+            window.add_controller(make_text_input_controller(&window, &send_event));
+            emoji_chooser = Some(make_emoji_chooser(&render_target, &send_event));
+        }
+
+        // `scale_factor` on the window is only ever an integer, so use the
+        // surface's fractional scale, which only exists once realized
+        window.connect_realize({
             let scale_factor = scale_factor.clone();
             move |window| {
-                scale_factor.store(window.scale_factor(), Ordering::SeqCst);
+                let Some(surface) = window.surface() else {
+                    return;
+                };
+                scale_factor.store((surface.scale() as f32).to_bits(), Ordering::SeqCst);
+                surface.connect_scale_notify({
+                    let scale_factor = scale_factor.clone();
+                    move |surface| {
+                        scale_factor.store((surface.scale() as f32).to_bits(), Ordering::SeqCst);
+                    }
+                });
             }
         });
 
+        let queued_frame = config
+            .present_smoothing
+            .then(|| Rc::new(RefCell::new(None::<QueuedFrame>)));
         let should_poll = Arc::new(AtomicBool::new(false));
         window.add_tick_callback({
             let should_poll = should_poll.clone();
+            let queued_frame = queued_frame.clone();
+            let paintable = paintable.clone();
+            let shown_frame = shown_frame.clone();
+            let presented_frame_id = presented_frame_id.clone();
             move |_, _| {
+                // swap in the frame which arrived since the last tick, so
+                // it's drawn in this frame clock cycle no matter when it
+                // arrived, and otherwise keep drawing the frame we have
+                let queued = queued_frame.as_ref().and_then(|queued| queued.take());
+                if let Some(QueuedFrame { texture, shown }) = queued {
+                    show_frame(
+                        &paintable,
+                        &shown_frame,
+                        &presented_frame_id,
+                        texture,
+                        shown,
+                    );
+                }
                 should_poll.store(true, Ordering::SeqCst);
+                if let Some(send_frame_tick) = &send_frame_tick {
+                    _ = send_frame_tick.try_send(());
+                }
                 glib::ControlFlow::Continue
             }
         });
 
-        window.present();
+        // before showing, so that the window manager can place it over its parent
+        window.set_transient_for(transient_for);
+        apply_wm_class(
+            &window,
+            config.wm_instance.as_deref(),
+            config.wm_class.as_deref(),
+        );
+        let show = |window: &gtk::Window| {
+            if config.focus_on_open {
+                window.present();
+            } else {
+                // just mapping the window doesn't ask the compositor to activate it,
+                // although it may still decide to focus it anyway
+                window.set_visible(true);
+            }
+        };
+        match &fade {
+            Some(fade) => fade.show(&window, show),
+            None => show(&window),
+        }
 
         Self {
+            entity,
             window,
             render_target,
+            paintable,
             shared_next_frame,
             recv_command,
             closed,
+            pending_title,
+            send_event,
+            frames_in_flight,
+            presented_frame_id,
+            shared_software_frame,
             should_poll,
             current_frame: None,
+            shown_frame,
+            queued_frame,
+            chrome,
+            safe_area: AdwaitaSafeArea::default(),
+            title_app_name,
+            header_title_label,
+            min_size,
+            backdrop,
+            split_view,
+            skip_hints,
+            mirror_of,
+            transient_for: config.transient_for,
+            fade,
+            close_confirmed,
+            search_bar,
+            progress_bar,
+            diagnostics_label,
+            toast_overlay,
+            view_stack,
+            action_bar,
+            emoji_chooser,
+            drag: None,
+            cursor_icon: config.cursor_icon,
+            cursor_visible: config.cursor_visible,
         }
     }
 
@@ -212,21 +1098,150 @@ impl WindowState {
             return Err(());
         }
 
+        let safe_area = safe_area(self.render_target.upcast_ref(), &self.chrome);
+        if safe_area != self.safe_area {
+            self.safe_area = safe_area;
+            _ = self
+                .send_event
+                .send(WindowEvent::SafeAreaChanged(safe_area));
+        }
+
+        if let Some(shared_software_frame) = &self.shared_software_frame {
+            self.frames_in_flight.take();
+            if let Some(frame) = shared_software_frame.take(Ordering::SeqCst) {
+                let _span = tracing::info_span!(
+                    "present_software_frame",
+                    window = %self.entity,
+                    size = %frame.size,
+                    frame_id = frame.frame_id,
+                )
+                .entered();
+                let shown = ShownFrame {
+                    frame_id: frame.frame_id,
+                    handed_off_at: frame.handed_off_at,
+                };
+                self.present(render::create_memory_texture(*frame), shown);
+            }
+            return self.poll_commands();
+        }
+
+        let mut has_new_frame = self.frames_in_flight.take() > 0;
         if let Some(frame_info) = self.shared_next_frame.take(Ordering::SeqCst) {
             self.current_frame = Some(*frame_info);
+            has_new_frame = true;
         }
 
-        if let Some(frame_info) = self.current_frame.as_ref() {
-            let frame = render::create_dmabuf_texture(&frame_info.dmabuf);
-            self.render_target.set_paintable(Some(&frame));
-            self.render_target.queue_draw();
-        } else {
-            tracing::info!("Don't have a frame yet...");
+        // if Bevy hasn't rendered anything since we last presented,
+        // there's nothing new to show
+        if has_new_frame {
+            if let Some(frame_info) = self.current_frame.as_ref() {
+                // every frame is rendered into its own swapchain buffer and
+                // sent with its own info, so this is exactly the frame we show
+                let frame_id = frame_info.frame_id;
+                let _span = tracing::info_span!(
+                    "present_frame",
+                    window = %self.entity,
+                    size = %frame_info.dmabuf.size,
+                    frame_id,
+                )
+                .entered();
+                let texture = {
+                    let _span = tracing::info_span!("import_dmabuf").entered();
+                    render::create_dmabuf_texture(frame_info)
+                };
+                let expected_size = self.device_size();
+                if frame_info.dmabuf.size != expected_size {
+                    // normal while resizing or with a render scale, but
+                    // otherwise the frame is being scaled and will look blurry
+                    tracing::debug!(
+                        "Frame size {} doesn't match widget size {expected_size} in device pixels",
+                        frame_info.dmabuf.size,
+                    );
+                }
+                match texture {
+                    Ok(frame) => {
+                        let shown = ShownFrame {
+                            frame_id,
+                            handed_off_at: frame_info.handed_off_at,
+                        };
+                        self.present(frame, shown);
+                    }
+                    Err(err) => {
+                        // don't keep retrying the same broken import every tick;
+                        // wait until Bevy gives us a new frame instead
+                        self.current_frame = None;
+                        self.report_error("import dmabuf texture", &err);
+                        _ = self.send_event.send(WindowEvent::DmabufImportFailed {
+                            modifier: frame_info.dmabuf.modifier,
+                        });
+                    }
+                }
+            } else {
+                tracing::debug!("Don't have a frame yet...");
+            }
+        }
+
+        self.poll_commands()
+    }
+
+    /// Shows a frame from Bevy, or queues it until the next frame clock tick
+    /// with [`AdwaitaWindowConfig::present_smoothing`].
+    fn present(&self, texture: gdk::Texture, shown: ShownFrame) {
+        match &self.queued_frame {
+            // a newer frame replaces one which hasn't been shown yet
+            Some(queued_frame) => {
+                queued_frame.replace(Some(QueuedFrame { texture, shown }));
+            }
+            None => show_frame(
+                &self.paintable,
+                &self.shown_frame,
+                &self.presented_frame_id,
+                texture,
+                shown,
+            ),
+        }
+    }
+
+    fn set_title(&self, title: &str) {
+        if let Some(label) = &self.header_title_label {
+            label.set_label(title);
         }
+        self.window
+            .set_title(Some(&window_title(title, self.title_app_name.as_deref())));
+    }
 
+    fn poll_commands(&mut self) -> Result<(), ()> {
+        if let Some(title) = self.pending_title.take(Ordering::SeqCst) {
+            self.set_title(&title);
+        }
         loop {
             let command = match self.recv_command.try_recv() {
-                Ok(command) => command,
+                Ok(WindowMessage::Command(command)) => command,
+                Ok(WindowMessage::Run(RunOnMainThread(run))) => {
+                    run(&self.window);
+                    continue;
+                }
+                Ok(WindowMessage::ClearFrame) => {
+                    if let Some(queued_frame) = &self.queued_frame {
+                        queued_frame.take();
+                    }
+                    self.current_frame = None;
+                    self.shown_frame.set(None);
+                    self.paintable.set_texture(None);
+                    continue;
+                }
+                Ok(WindowMessage::Diagnostics(text)) => {
+                    if let Some(label) = &self.diagnostics_label {
+                        label.set_visible(text.is_some());
+                        label.set_label(text.as_deref().unwrap_or_default());
+                    }
+                    continue;
+                }
+                Ok(WindowMessage::UseSoftwarePresent(shared_software_frame)) => {
+                    self.current_frame = None;
+                    self.shared_software_frame = Some(shared_software_frame);
+                    continue;
+                }
                 Err(flume::TryRecvError::Disconnected) => return Err(()),
                 Err(flume::TryRecvError::Empty) => break,
             };
@@ -245,15 +1260,2348 @@ impl WindowState {
                     self.window.unfullscreen();
                 }
                 WindowCommand::SetTitle(title) => {
-                    self.window.set_title(Some(&title));
+                    self.set_title(&title);
+                }
+                WindowCommand::SetIconName(icon_name) => {
+                    if let Some(icon_name) = &icon_name {
+                        warn_on_missing_icon(icon_name);
+                    }
+                    self.window.set_icon_name(icon_name.as_deref());
+                }
+                WindowCommand::SetSize(size) => {
+                    // `AdwaitaWindow::send_command` rejects sizes which don't fit
+                    self.window.set_default_size(
+                        assert_i32(size.x, "window width"),
+                        assert_i32(size.y, "window height"),
+                    );
+                    if !self.window.is_resizable() {
+                        pin_window_size(&self.window);
+                    }
+                }
+                WindowCommand::SetUserResizable(true) => {
+                    self.window.set_resizable(true);
+                    let (width, height) = self.min_size.unwrap_or((-1, -1));
+                    self.window.set_size_request(width, height);
+                }
+                WindowCommand::SetUserResizable(false) => {
+                    self.window.set_resizable(false);
+                    pin_window_size(&self.window);
+                }
+                WindowCommand::SetBackdropColor(color) => {
+                    self.backdrop.set_color(color);
+                }
+                WindowCommand::SetHideOnClose(hide_on_close) => {
+                    self.window.set_hide_on_close(hide_on_close);
+                }
+                WindowCommand::Present => match &self.fade {
+                    Some(fade) => fade.show(&self.window, gtk::Window::present),
+                    None => self.window.present(),
+                },
+                WindowCommand::SetSkipTaskbar(skip) => {
+                    self.update_skip_hints(|hints| hints.taskbar = skip);
+                }
+                WindowCommand::SetTransientFor(parent) => {
+                    self.transient_for = parent;
+                }
+                WindowCommand::SetSkipPager(skip) => {
+                    self.update_skip_hints(|hints| hints.pager = skip);
+                }
+                WindowCommand::SetView(view_id) => {
+                    if let Some(view_stack) = &self.view_stack {
+                        view_stack.set_visible_child_name(&view_id);
+                    }
+                }
+                WindowCommand::SetProgress(progress) => {
+                    self.set_progress(progress);
+                }
+                WindowCommand::SetCursorTheme { name, size } => {
+                    set_cursor_theme(&self.window, name.as_deref(), size);
+                }
+                WindowCommand::SetCursorIcon(icon) => {
+                    self.cursor_icon = icon;
+                    set_cursor(&self.render_target, self.cursor_icon, self.cursor_visible);
+                }
+                WindowCommand::SetCursorVisible(visible) => {
+                    self.cursor_visible = visible;
+                    set_cursor(&self.render_target, self.cursor_icon, self.cursor_visible);
+                }
+                WindowCommand::ShowEmojiChooser => match &self.emoji_chooser {
+                    Some(emoji_chooser) => emoji_chooser.popup(),
+                    None => {
+                        tracing::warn!(
+                            "Adwaita window {} has no emoji chooser without text input",
+                            self.entity
+                        );
+                    }
+                },
+                WindowCommand::MoveToWorkspace(workspace) => {
+                    move_to_workspace(&self.window, workspace);
+                }
+                WindowCommand::FocusRenderArea => {
+                    self.render_target.grab_focus();
+                }
+                WindowCommand::ShowAbout(about) => {
+                    show_about(&self.window, &about, self.title_app_name.as_deref());
+                }
+                WindowCommand::SetClipboardText(text) => {
+                    self.window.clipboard().set_text(&text);
+                }
+                WindowCommand::ShowToast {
+                    title,
+                    timeout_secs,
+                } => match &self.toast_overlay {
+                    Some(toast_overlay) => {
+                        let toast = adw::Toast::builder()
+                            .title(title)
+                            .timeout(timeout_secs)
+                            .build();
+                        toast_overlay.add_toast(toast);
+                    }
+                    None => {
+                        tracing::warn!(
+                            "Adwaita window {} has no toast overlay in a custom window",
+                            self.entity
+                        );
+                    }
+                },
+                WindowCommand::Close => {
+                    self.close_confirmed.set(true);
+                    // goes through the close request handler, so this fades
+                    // out and hides the window just like a normal close
+                    self.window.close();
+                    self.close_confirmed.set(false);
+                }
+                WindowCommand::StartDrag(content) => {
+                    self.drag = start_drag(&self.window, &content, &self.send_event);
+                }
+                WindowCommand::SetActionBarShown(shown) => {
+                    if let Some(action_bar) = &self.action_bar {
+                        action_bar.set_revealed(shown);
+                    }
+                }
+                WindowCommand::SetSearchShown(shown) => {
+                    if let Some(search_bar) = &self.search_bar {
+                        search_bar.set_search_mode(shown);
+                    }
+                }
+                WindowCommand::SetSidebarShown(shown) => {
+                    if let Some(split_view) = &self.split_view {
+                        split_view.set_show_sidebar(shown);
+                    }
                 }
             }
         }
 
         Ok(())
     }
+
+    fn set_progress(&self, progress: Option<f32>) {
+        if let Some(progress_bar) = &self.progress_bar {
+            progress_bar.set_visible(progress.is_some());
+            progress_bar.set_fraction(f64::from(progress.unwrap_or(0.0)));
+        }
+        if let Some(application) = self.window.application() {
+            update_launcher_progress(&application, progress);
+        }
+    }
+
+    fn update_skip_hints(&self, f: impl FnOnce(&mut SkipHints)) {
+        let mut hints = self.skip_hints.get();
+        f(&mut hints);
+        self.skip_hints.set(hints);
+        if self.window.is_realized() {
+            set_skip_hints(&self.window, hints);
+        }
+    }
+
+    /// Size of the render target widget in device pixels.
+    fn device_size(&self) -> UVec2 {
+        let scale_factor = self
+            .render_target
+            .native()
+            .and_then(|native| native.surface())
+            .map_or(1.0, |surface| surface.scale() as f32);
+        let logical = UVec2::new(
+            u32::try_from(self.render_target.width()).unwrap_or(0),
+            u32::try_from(self.render_target.height()).unwrap_or(0),
+        );
+        render::physical_size(logical.max(UVec2::ONE), scale_factor)
+    }
+
+    fn report_error(&self, operation: &'static str, err: &glib::Error) {
+        tracing::error!("Failed to {operation}: {err}");
+        _ = self.send_event.send(WindowEvent::Error {
+            operation,
+            message: err.to_string(),
+        });
+    }
+}
+
+/// Presents the latest frame of each mirrored window in its mirrors.
+/// Finds how far `chrome` covers `render_widget` from each of its edges.
+///
+/// Each widget which overlaps the render widget counts towards whichever edge
+/// it covers the least from, e.g. window controls in the top-right corner only
+/// count towards the top edge.
+fn safe_area(render_widget: &gtk::Widget, chrome: &[gtk::Widget]) -> AdwaitaSafeArea {
+    let width = render_widget.width() as f32;
+    let height = render_widget.height() as f32;
+    let mut safe_area = AdwaitaSafeArea::default();
+    for widget in chrome {
+        if !widget.is_mapped() {
+            continue;
+        }
+        let Some(bounds) = widget.compute_bounds(render_widget) else {
+            continue;
+        };
+        let left = bounds.x().max(0.0);
+        let top = bounds.y().max(0.0);
+        let right = (bounds.x() + bounds.width()).min(width);
+        let bottom = (bounds.y() + bounds.height()).min(height);
+        if right <= left || bottom <= top {
+            continue;
+        }
+
+        let from_top = bottom;
+        let from_bottom = height - top;
+        let from_left = right;
+        let from_right = width - left;
+        let least = from_top.min(from_bottom).min(from_left).min(from_right);
+        if least == from_top {
+            safe_area.top = safe_area.top.max(from_top);
+        } else if least == from_bottom {
+            safe_area.bottom = safe_area.bottom.max(from_bottom);
+        } else if least == from_left {
+            safe_area.left = safe_area.left.max(from_left);
+        } else {
+            safe_area.right = safe_area.right.max(from_right);
+        }
+    }
+    safe_area
+}
+
+/// Sets the cursor shown while the pointer is over `render_target`, which GTK
+/// applies whenever the pointer enters it.
+fn set_cursor(render_target: &gtk::Picture, icon: AdwaitaCursorIcon, visible: bool) {
+    // a blank cursor, rather than moving the cursor out of the way
+    let name = if visible { icon.name() } else { "none" };
+    render_target.set_cursor_from_name(Some(name));
+}
+
+fn set_cursor_theme(window: &gtk::Window, name: Option<&str>, size: Option<u32>) {
+    let settings = gtk::Settings::for_display(&window.display());
+    match name {
+        Some(name) => settings.set_gtk_cursor_theme_name(Some(name)),
+        None => settings.reset_property("gtk-cursor-theme-name"),
+    }
+    match size.map(i32::try_from) {
+        Some(Ok(size)) => settings.set_gtk_cursor_theme_size(size),
+        Some(Err(err)) => {
+            tracing::warn!("Invalid cursor size, following the system setting: {err}");
+            settings.reset_property("gtk-cursor-theme-size");
+        }
+        None => settings.reset_property("gtk-cursor-theme-size"),
+    }
+}
+
+/// Runs `f` with the window's surface now if it's realized, or otherwise
+/// once it's first realized.
+pub fn run_with_surface(
+    window: &gtk::Window,
+    f: impl FnOnce(&gtk::Window, &gdk::Surface) + 'static,
+) {
+    if let Some(surface) = window.surface() {
+        f(window, &surface);
+        return;
+    }
+
+    let f = RefCell::new(Some(f));
+    let handler_id = Rc::new(Cell::new(None::<glib::SignalHandlerId>));
+    let id = window.connect_realize({
+        let handler_id = handler_id.clone();
+        move |window| {
+            if let (Some(f), Some(surface)) = (f.borrow_mut().take(), window.surface()) {
+                f(window, &surface);
+            }
+            if let Some(handler_id) = handler_id.take() {
+                window.disconnect(handler_id);
+            }
+        }
+    });
+    handler_id.set(Some(id));
+}
+
+fn sync_mirrors(windows: &[WindowState]) {
+    for mirror in windows {
+        let Some(source) = mirror.mirror_of else {
+            continue;
+        };
+        let texture = windows
+            .iter()
+            .find(|window| window.entity == source)
+            .and_then(|window| window.paintable.texture());
+        if mirror.paintable.texture() != texture {
+            mirror.paintable.set_texture(texture);
+            let shown_frame = windows
+                .iter()
+                .find(|window| window.entity == source)
+                .and_then(|window| window.shown_frame.get());
+            mirror.shown_frame.set(shown_frame);
+        }
+    }
+}
+
+fn sync_transient_parents(windows: &[WindowState]) {
+    for window in windows {
+        // resolved every time, since the parent may open or close later
+        let parent = window.transient_for.and_then(|parent| {
+            windows
+                .iter()
+                .find(|other| other.entity == parent && other.entity != window.entity)
+                .map(|other| &other.window)
+        });
+        if window.window.transient_for().as_ref() != parent {
+            window.window.set_transient_for(parent);
+        }
+    }
+}
+
+/// Sets the application name the first time this is called, since GLib only
+/// allows setting it once.
+fn set_application_name(name: &str) {
+    static APPLICATION_NAME: OnceLock<String> = OnceLock::new();
+
+    let current = APPLICATION_NAME.get_or_init(|| {
+        glib::set_application_name(name);
+        name.to_owned()
+    });
+    if current != name {
+        tracing::debug!(
+            "Application name is already set to {current:?}, ignoring {name:?} from a later window"
+        );
+    }
+}
+
+fn watch_color_scheme(
+    send_app_event: &flume::Sender<AppEvent>,
+    fallback: AdwaitaColorSchemePreference,
+) {
+    let style_manager = adw::StyleManager::default();
+    if !style_manager.system_supports_color_schemes() {
+        tracing::info!(
+            "System doesn't report a preferred color scheme, e.g. because the settings portal \
+             isn't running, so following the system falls back to {fallback:?}"
+        );
+        style_manager.set_color_scheme(adw_color_scheme(
+            &style_manager,
+            AdwaitaColorSchemePreference::FollowSystem,
+            fallback,
+        ));
+    }
+    _ = send_app_event.send(AppEvent::ColorScheme {
+        dark: style_manager.is_dark(),
+        system_supported: style_manager.system_supports_color_schemes(),
+    });
+    style_manager.connect_dark_notify({
+        let send_app_event = send_app_event.clone();
+        move |style_manager| {
+            _ = send_app_event.send(AppEvent::ColorScheme {
+                dark: style_manager.is_dark(),
+                system_supported: style_manager.system_supports_color_schemes(),
+            });
+        }
+    });
+}
+
+fn adw_color_scheme(
+    style_manager: &adw::StyleManager,
+    preference: AdwaitaColorSchemePreference,
+    fallback: AdwaitaColorSchemePreference,
+) -> adw::ColorScheme {
+    let preference = match preference {
+        AdwaitaColorSchemePreference::FollowSystem
+            if !style_manager.system_supports_color_schemes() =>
+        {
+            fallback
+        }
+        preference => preference,
+    };
+    match preference {
+        AdwaitaColorSchemePreference::FollowSystem => adw::ColorScheme::Default,
+        AdwaitaColorSchemePreference::ForceLight => adw::ColorScheme::ForceLight,
+        AdwaitaColorSchemePreference::ForceDark => adw::ColorScheme::ForceDark,
+    }
+}
+
+fn watch_system_settings(send_app_event: &flume::Sender<AppEvent>) {
+    let Some(settings) = gtk::Settings::default() else {
+        return;
+    };
+    _ = send_app_event.send(AppEvent::SystemSettings(system_settings(&settings)));
+    _ = send_app_event.send(AppEvent::ReducedMotion(
+        !settings.is_gtk_enable_animations(),
+    ));
+    // duplicates are filtered out on the Bevy side
+    settings.connect_notify_local(None, {
+        let send_app_event = send_app_event.clone();
+        move |settings, _| {
+            _ = send_app_event.send(AppEvent::SystemSettings(system_settings(settings)));
+        }
+    });
+    settings.connect_gtk_enable_animations_notify({
+        let send_app_event = send_app_event.clone();
+        move |settings| {
+            _ = send_app_event.send(AppEvent::ReducedMotion(
+                !settings.is_gtk_enable_animations(),
+            ));
+        }
+    });
+}
+
+fn system_settings(settings: &gtk::Settings) -> AdwaitaSystemSettings {
+    let millis = |millis: i32| Duration::from_millis(u64::try_from(millis).unwrap_or(0));
+    // in 1024ths of a DPI, or -1 for the default of 96
+    let xft_dpi = settings.gtk_xft_dpi();
+    let text_scale = if xft_dpi > 0 {
+        xft_dpi as f32 / 1024.0 / 96.0
+    } else {
+        1.0
+    };
+
+    AdwaitaSystemSettings {
+        double_click_time: millis(settings.gtk_double_click_time()),
+        double_click_distance: u32::try_from(settings.gtk_double_click_distance()).unwrap_or(0),
+        drag_threshold: u32::try_from(settings.gtk_dnd_drag_threshold()).unwrap_or(0),
+        cursor_blink: settings.is_gtk_cursor_blink(),
+        cursor_blink_time: millis(settings.gtk_cursor_blink_time()),
+        cursor_blink_timeout: Duration::from_secs(
+            u64::try_from(settings.gtk_cursor_blink_timeout()).unwrap_or(0),
+        ),
+        text_scale,
+    }
+}
+
+fn probe_display_caps(display: &gdk::Display) -> AdwaitaDisplayCaps {
+    let backend = match display.type_().name() {
+        "GdkWaylandDisplay" => AdwaitaDisplayBackend::Wayland,
+        "GdkX11Display" => AdwaitaDisplayBackend::X11,
+        _ => AdwaitaDisplayBackend::Other,
+    };
+    let dmabuf_formats = display.dmabuf_formats();
+
+    AdwaitaDisplayCaps {
+        backend,
+        dmabuf: dmabuf_formats.n_formats() > 0,
+        dmabuf_format_supported: render::is_dmabuf_format_supported(&dmabuf_formats),
+        dmabuf_modifiers: render::dmabuf_modifiers(&dmabuf_formats),
+        composited: display.is_composited(),
+        rgba: display.is_rgba(),
+    }
+}
+
+/// Routes GLib's structured log messages, which GTK, GDK and Adwaita all log
+/// through, into `tracing`.
+fn install_log_writer(capture: AdwaitaLogCapture) {
+    glib::log_set_writer_func(move |level, fields| {
+        let field = |key: &str| {
+            fields
+                .iter()
+                .find(|field| field.key() == key)
+                .and_then(|field| field.value_str())
+        };
+        let domain = field("GLIB_DOMAIN").unwrap_or("");
+        if !capture.domains.is_empty() && !capture.domains.iter().any(|d| d == domain) {
+            return glib::log_writer_default(level, fields);
+        }
+
+        let tracing_level = match level {
+            glib::LogLevel::Error | glib::LogLevel::Critical => tracing::Level::ERROR,
+            glib::LogLevel::Warning => tracing::Level::WARN,
+            glib::LogLevel::Message | glib::LogLevel::Info => tracing::Level::INFO,
+            glib::LogLevel::Debug => tracing::Level::DEBUG,
+        };
+        if tracing_level > capture.min_level {
+            return glib::LogWriterOutput::Handled;
+        }
+
+        let message = field("MESSAGE").unwrap_or("");
+        // `tracing` needs the level to be known at compile time
+        match tracing_level {
+            tracing::Level::ERROR => tracing::error!(target: "glib", domain, "{message}"),
+            tracing::Level::WARN => tracing::warn!(target: "glib", domain, "{message}"),
+            tracing::Level::INFO => tracing::info!(target: "glib", domain, "{message}"),
+            _ => tracing::debug!(target: "glib", domain, "{message}"),
+        }
+        glib::LogWriterOutput::Handled
+    });
+}
+
+/// Registers the app's own resource bundles and icon paths, so that icons
+/// bundled with the app resolve by name.
+fn load_resources(config: &AppConfig, display: &gdk::Display) {
+    for path in &config.resources {
+        match gio::Resource::load(path) {
+            Ok(resource) => gio::resources_register(&resource),
+            Err(err) => {
+                tracing::warn!("Failed to load resource bundle {}: {err}", path.display());
+            }
+        }
+    }
+
+    let icon_theme = gtk::IconTheme::for_display(display);
+    for path in &config.icon_search_paths {
+        if !path.is_dir() {
+            tracing::warn!("Icon search path {} isn't a directory", path.display());
+        }
+        icon_theme.add_search_path(path);
+    }
+    for path in &config.icon_resource_paths {
+        if gio::resources_enumerate_children(path, gio::ResourceLookupFlags::NONE).is_err() {
+            tracing::warn!("Icon resource path {path} isn't in any registered resource bundle");
+        }
+        icon_theme.add_resource_path(path);
+    }
+}
+
+/// Warns if `icon_name` isn't in the icon theme, since GTK just shows a
+/// broken image instead.
+fn warn_on_missing_icon(icon_name: &str) {
+    let Some(display) = gdk::Display::default() else {
+        return;
+    };
+    if !gtk::IconTheme::for_display(&display).has_icon(icon_name) {
+        tracing::warn!(
+            "Icon {icon_name:?} isn't in the icon theme, or any of the app's icon search paths"
+        );
+    }
+}
+
+fn make_application(config: &AppConfig) -> Option<adw::Application> {
+    validate_application_flags(config.flags);
+
+    let application = adw::Application::builder().flags(config.flags).build();
+    if let Some(application_id) = &config.application_id {
+        application.set_application_id(Some(application_id.as_str()));
+    }
+
+    // we drive the main context ourselves instead of calling `run`,
+    // so we have to register manually
+    if let Err(err) = application.register(None::<&gio::Cancellable>) {
+        tracing::error!("Failed to register application: {err}");
+        return None;
+    }
+    if application.is_remote() {
+        tracing::info!(
+            "Another instance of {:?} is already running, and is the primary instance",
+            config.application_id
+        );
+    }
+    Some(application)
+}
+
+fn install_keymap(
+    application: &adw::Application,
+    installed_actions: &mut Vec<String>,
+    bindings: Vec<(String, String)>,
+    send_app_event: &flume::Sender<AppEvent>,
+) {
+    for action in installed_actions.drain(..) {
+        application.set_accels_for_action(&format!("app.{action}"), &[]);
+        application.remove_action(&action);
+    }
+
+    let mut accels_by_action = std::collections::BTreeMap::<String, Vec<String>>::new();
+    for (accel, action) in bindings {
+        if gtk::accelerator_parse(&accel).is_none() {
+            tracing::warn!("Ignoring invalid accelerator {accel:?} for action {action:?}");
+            continue;
+        }
+        if !gio::Action::name_is_valid(&action) {
+            tracing::warn!("Ignoring invalid action name {action:?} for accelerator {accel:?}");
+            continue;
+        }
+        accels_by_action.entry(action).or_default().push(accel);
+    }
+
+    for (action, accels) in accels_by_action {
+        let simple_action = gio::SimpleAction::new(&action, None);
+        simple_action.connect_activate({
+            let send_app_event = send_app_event.clone();
+            let action = action.clone();
+            move |_, _| {
+                _ = send_app_event.send(AppEvent::ActionActivated(action.clone()));
+            }
+        });
+        application.add_action(&simple_action);
+        let accels = accels.iter().map(String::as_str).collect::<Vec<_>>();
+        application.set_accels_for_action(&format!("app.{action}"), &accels);
+        installed_actions.push(action);
+    }
+    tracing::debug!("Installed keymap with actions {installed_actions:?}");
+}
+
+fn validate_application_flags(flags: gio::ApplicationFlags) {
+    use gio::ApplicationFlags as Flags;
+
+    if flags.contains(Flags::IS_SERVICE | Flags::IS_LAUNCHER) {
+        tracing::warn!(
+            "Application flags {flags:?} contain both `IS_SERVICE` and `IS_LAUNCHER`, \
+             which are mutually exclusive"
+        );
+    }
+    if flags.contains(Flags::NON_UNIQUE)
+        && flags.intersects(Flags::ALLOW_REPLACEMENT | Flags::REPLACE)
+    {
+        tracing::warn!(
+            "Application flags {flags:?} contain `NON_UNIQUE` with `ALLOW_REPLACEMENT` or \
+             `REPLACE`, but a non-unique application can never be replaced"
+        );
+    }
+    if flags.intersects(Flags::HANDLES_OPEN | Flags::HANDLES_COMMAND_LINE) {
+        tracing::info!(
+            "Application flags {flags:?} handle `open` or `command-line`, but these are only \
+             emitted when another instance forwards them to this one"
+        );
+    }
+}
+
+impl Drop for WindowState {
+    fn drop(&mut self) {
+        // the texture references a dmabuf which is about to be freed,
+        // so make sure nobody holding on to the paintable can still draw it
+        self.paintable.set_texture(None);
+    }
+}
+
+fn apply_window_hint(window: &gtk::Window, hint: AdwaitaWindowHint, parent: Option<&gtk::Window>) {
+    if hint == AdwaitaWindowHint::Normal {
+        return;
+    }
+
+    if let Some(parent) = parent {
+        window.set_transient_for(Some(parent));
+    }
+
+    #[cfg(feature = "x11")]
+    window.connect_realize(move |window| {
+        let Some(surface) = window.surface().and_downcast::<gdk_x11::X11Surface>() else {
+            return;
+        };
+        if hint == AdwaitaWindowHint::Utility {
+            surface.set_utility_hint(true);
+        }
+    });
+}
+
+/// Shows progress on the application's launcher icon, e.g. in the GNOME dash
+/// with the Dash to Dock extension, or in KDE's task manager.
+///
+/// This uses Unity's `LauncherEntry` D-Bus API, which docks and taskbars
+/// which support it listen for. If nothing is listening, or the application
+/// has no ID to identify its desktop file by, this does nothing.
+fn update_launcher_progress(application: &gtk::Application, progress: Option<f32>) {
+    let (Some(application_id), Some(connection)) =
+        (application.application_id(), application.dbus_connection())
+    else {
+        return;
+    };
+
+    let properties = glib::VariantDict::new(None);
+    properties.insert("progress", f64::from(progress.unwrap_or(0.0)));
+    properties.insert("progress-visible", progress.is_some());
+    let parameters = glib::Variant::tuple_from_iter([
+        format!("application://{application_id}.desktop").to_variant(),
+        properties.end(),
+    ]);
+    let object_path = format!("/{}", application_id.replace(['.', '-'], "/"));
+    if let Err(err) = connection.emit_signal(
+        None,
+        &object_path,
+        "com.canonical.Unity.LauncherEntry",
+        "Update",
+        Some(&parameters),
+    ) {
+        tracing::debug!("Failed to update launcher entry progress: {err}");
+    }
+}
+
+/// Fades a window's opacity in when it's shown, and out before it's hidden.
+#[derive(Debug, Clone)]
+struct Fade {
+    animation: adw::TimedAnimation,
+    /// Whether to hide the window once the animation is done, i.e. whether
+    /// we're fading out rather than in.
+    hiding: Rc<Cell<bool>>,
+}
+
+impl Fade {
+    fn new(window: &gtk::Window, duration: Duration) -> Self {
+        let target = adw::CallbackAnimationTarget::new({
+            let window = window.downgrade();
+            move |opacity| {
+                if let Some(window) = window.upgrade() {
+                    window.set_opacity(opacity);
+                }
+            }
+        });
+        let duration = u32::try_from(duration.as_millis()).unwrap_or(u32::MAX);
+        let animation = adw::TimedAnimation::new(window, 0.0, 1.0, duration, target);
+        animation.set_easing(adw::Easing::EaseOutCubic);
+        // with reduced motion, the animation finishes as soon as it's played,
+        // so the window is shown and hidden instantly
+        animation.set_follow_enable_animations_setting(true);
+
+        let hiding = Rc::new(Cell::new(false));
+        animation.connect_done({
+            let window = window.downgrade();
+            let hiding = hiding.clone();
+            move |_| {
+                if hiding.replace(false) {
+                    if let Some(window) = window.upgrade() {
+                        window.set_visible(false);
+                    }
+                }
+            }
+        });
+        Self { animation, hiding }
+    }
+
+    fn show(&self, window: &gtk::Window, show: impl FnOnce(&gtk::Window)) {
+        // if we're in the middle of fading out, this stops the window from
+        // being hidden at the end, and fades back in from where we are
+        self.hiding.set(false);
+        if !window.is_visible() {
+            window.set_opacity(0.0);
+        }
+        show(window);
+        self.animate_to(window, 1.0);
+    }
+
+    fn hide(&self, window: &gtk::Window) {
+        self.hiding.set(true);
+        self.animate_to(window, 0.0);
+    }
+
+    fn animate_to(&self, window: &gtk::Window, opacity: f64) {
+        self.animation.set_value_from(window.opacity());
+        self.animation.set_value_to(opacity);
+        // unlike `skip`, this doesn't emit `done` for the interrupted animation
+        self.animation.reset();
+        self.animation.play();
+    }
+}
+
+/// Whether the window should be hidden from the taskbar and pager.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct SkipHints {
+    taskbar: bool,
+    pager: bool,
+    /// Whether this is an [`AdwaitaWindowHint::Utility`] window, which is
+    /// hidden from both even if `taskbar` and `pager` aren't set.
+    utility: bool,
+}
+
+/// Applies `hints` to the window now if it's realized, and whenever it is
+/// realized in the future.
+fn watch_skip_hints(window: &gtk::Window, hints: &Rc<Cell<SkipHints>>) {
+    window.connect_realize({
+        let hints = hints.clone();
+        move |window| set_skip_hints(window, hints.get())
+    });
+    if window.is_realized() {
+        set_skip_hints(window, hints.get());
+    }
+}
+
+/// Starts a drag of `content` from the pointer's position over `window`,
+/// reporting how it ends as [`WindowEvent::DragEnded`].
+fn start_drag(
+    window: &gtk::Window,
+    content: &AdwaitaDragContent,
+    send_event: &flume::Sender<WindowEvent>,
+) -> Option<gdk::Drag> {
+    let pointer = window
+        .display()
+        .default_seat()
+        .and_then(|seat| seat.pointer());
+    let drag = window
+        .surface()
+        .zip(pointer)
+        .and_then(|(surface, pointer)| {
+            let (x, y) = surface
+                .device_position(&pointer)
+                .map_or((0.0, 0.0), |(x, y, _)| (x, y));
+            gdk::Drag::begin(
+                &surface,
+                &pointer,
+                &drag_content_provider(content),
+                gdk::DragAction::COPY,
+                x,
+                y,
+            )
+        });
+    let Some(drag) = drag else {
+        tracing::warn!("Failed to start drag");
+        _ = send_event.send(WindowEvent::DragEnded { accepted: false });
+        return None;
+    };
+
+    // like `GtkDragSource`, tell GDK once we're done with the drag so it can
+    // clean up after it
+    drag.connect_dnd_finished({
+        let send_event = send_event.clone();
+        move |drag| {
+            drag.drop_done(true);
+            _ = send_event.send(WindowEvent::DragEnded { accepted: true });
+        }
+    });
+    drag.connect_cancel({
+        let send_event = send_event.clone();
+        move |drag, reason| {
+            tracing::debug!("Drag cancelled: {reason:?}");
+            drag.drop_done(false);
+            _ = send_event.send(WindowEvent::DragEnded { accepted: false });
+        }
+    });
+    Some(drag)
+}
+
+fn drag_content_provider(content: &AdwaitaDragContent) -> gdk::ContentProvider {
+    match content {
+        AdwaitaDragContent::Text(text) => gdk::ContentProvider::for_value(&text.to_value()),
+        AdwaitaDragContent::Uris(uris) => {
+            let files = uris.iter().map(gio::File::for_uri).collect::<Vec<_>>();
+            let uri_list = uris
+                .iter()
+                .fold(String::new(), |list, uri| list + uri + "\r\n");
+            // apps which don't take GDK's file list still take a URI list
+            gdk::ContentProvider::new_union(&[
+                gdk::ContentProvider::for_value(&gdk::FileList::from_array(&files).to_value()),
+                gdk::ContentProvider::for_bytes(
+                    "text/uri-list",
+                    &glib::Bytes::from_owned(uri_list),
+                ),
+            ])
+        }
+        AdwaitaDragContent::Bytes { mime_type, data } => {
+            gdk::ContentProvider::for_bytes(mime_type, &glib::Bytes::from(data.as_slice()))
+        }
+    }
+}
+
+/// Sets the instance and class names of the window's `WM_CLASS`, by
+/// realizing it early.
+fn apply_wm_class(window: &gtk::Window, instance: Option<&str>, class: Option<&str>) {
+    if instance.is_none() && class.is_none() {
+        return;
+    }
+
+    #[cfg(feature = "x11")]
+    if let Some(display) = window.display().downcast_ref::<gdk_x11::X11Display>() {
+        // GDK only reads these process-wide names when it creates the X11
+        // surface, so swap ours in just while realizing this window
+        let prgname = glib::prgname();
+        if let Some(instance) = instance {
+            glib::set_prgname(Some(instance));
+        }
+        if let Some(class) = class {
+            display.set_program_class(class);
+        }
+        window.realize();
+        if instance.is_some() {
+            glib::set_prgname(prgname.as_deref());
+        }
+        if class.is_some() {
+            display.set_program_class(&default_program_class(prgname.as_deref()));
+        }
+        return;
+    }
+
+    #[cfg(not(feature = "x11"))]
+    let _ = window;
+    tracing::warn!(
+        "WM class is only supported on X11 with the `x11` feature enabled, ignoring {:?} {:?}",
+        instance,
+        class,
+    );
+}
+
+/// Class name which GDK gives windows by default, i.e. the program name with
+/// its first letter capitalized.
+#[cfg(feature = "x11")]
+fn default_program_class(prgname: Option<&str>) -> String {
+    let mut chars = prgname.unwrap_or_default().chars();
+    chars.next().map_or_else(String::new, |first| {
+        first.to_ascii_uppercase().to_string() + chars.as_str()
+    })
+}
+
+fn set_skip_hints(window: &gtk::Window, hints: SkipHints) {
+    #[cfg(feature = "x11")]
+    if let Some(surface) = window.surface().and_downcast::<gdk_x11::X11Surface>() {
+        surface.set_skip_taskbar_hint(hints.taskbar || hints.utility);
+        surface.set_skip_pager_hint(hints.pager || hints.utility);
+        return;
+    }
+
+    #[cfg(not(feature = "x11"))]
+    let _ = window;
+    // utility windows are hidden from the taskbar where we can, but that's
+    // best effort, so only warn if the user asked for it
+    if hints.taskbar || hints.pager {
+        tracing::warn!(
+            "Skip taskbar and skip pager hints are only supported on X11 with the `x11` \
+             feature enabled, ignoring them"
+        );
+    }
+}
+
+fn window_state(window: &gtk::Window) -> AdwaitaWindowState {
+    let toplevel_state = window
+        .surface()
+        .and_downcast::<gdk::Toplevel>()
+        .map(|toplevel| toplevel.state())
+        .unwrap_or_else(gdk::ToplevelState::empty);
+
+    AdwaitaWindowState {
+        mapped: window.is_mapped(),
+        minimized: toplevel_state.contains(gdk::ToplevelState::MINIMIZED),
+        suspended: toplevel_state.contains(gdk::ToplevelState::SUSPENDED),
+        focused: window.is_active(),
+        fullscreen: window.is_fullscreen(),
+        maximized: window.is_maximized(),
+    }
+}
+
+/// Sends a [`WindowEvent::StateChanged`] whenever any part of the window's
+/// [`AdwaitaWindowState`] changes.
+#[derive(Debug, Clone, Copy)]
+struct ShownFrame {
+    frame_id: u64,
+    handed_off_at: Instant,
+}
+
+#[derive(Debug)]
+struct QueuedFrame {
+    texture: gdk::Texture,
+    shown: ShownFrame,
+}
+
+fn show_frame(
+    paintable: &FramePaintable,
+    shown_frame: &Cell<Option<ShownFrame>>,
+    presented_frame_id: &AtomicU64,
+    texture: gdk::Texture,
+    shown: ShownFrame,
+) {
+    paintable.set_texture(Some(texture));
+    presented_frame_id.store(shown.frame_id, Ordering::SeqCst);
+    shown_frame.set(Some(shown));
+}
+
+/// Reports when each frame drawn by the window is actually shown on the
+/// display, as [`WindowEvent::FramePresented`].
+///
+/// GDK only knows this once the compositor reports it, some time after the
+/// frame was painted, so this remembers which frame was drawn on each frame
+/// clock cycle and checks back on later cycles.
+fn watch_presentation(
+    window: &gtk::Window,
+    shown_frame: &Rc<Cell<Option<ShownFrame>>>,
+    send_event: &flume::Sender<WindowEvent>,
+) {
+    let shown_frame = shown_frame.clone();
+    let send_event = send_event.clone();
+    window.connect_realize(move |window| {
+        let Some(frame_clock) = window.frame_clock() else {
+            return;
+        };
+        let shown_frame = shown_frame.clone();
+        let send_event = send_event.clone();
+        // (frame clock counter, frame painted during it)
+        let pending = RefCell::new(VecDeque::<(i64, ShownFrame)>::new());
+        let last_painted = Cell::new(None::<u64>);
+        frame_clock.connect_after_paint(move |frame_clock| {
+            let mut pending = pending.borrow_mut();
+            if let Some(shown) = shown_frame.get() {
+                // only the first cycle which draws a frame shows it for the first time
+                if last_painted.replace(Some(shown.frame_id)) != Some(shown.frame_id) {
+                    pending.push_back((frame_clock.frame_counter(), shown));
+                }
+            }
+
+            while let Some(&(counter, shown)) = pending.front() {
+                let Some(timings) = frame_clock.timings(counter) else {
+                    // GDK has already forgotten about this cycle
+                    pending.pop_front();
+                    continue;
+                };
+                if !timings.is_complete() {
+                    break;
+                }
+                pending.pop_front();
+                let presentation_time = timings.presentation_time();
+                if presentation_time == 0 {
+                    // the compositor doesn't report presentation times
+                    continue;
+                }
+                // both are on the monotonic clock, but `Instant` can't be built from a raw time
+                let ago = glib::monotonic_time().saturating_sub(presentation_time);
+                let presented_at =
+                    Instant::now() - Duration::from_micros(u64::try_from(ago).unwrap_or_default());
+                _ = send_event.send(WindowEvent::FramePresented(AdwaitaFramePresentation {
+                    frame_id: shown.frame_id,
+                    handed_off_at: shown.handed_off_at,
+                    presented_at,
+                }));
+            }
+        });
+    });
+}
+
+fn watch_monitor(window: &gtk::Window, send_event: &flume::Sender<WindowEvent>) {
+    // the surface only exists once realized
+    window.connect_realize({
+        let send_event = send_event.clone();
+        move |window| {
+            let Some(surface) = window.surface() else {
+                return;
+            };
+            surface.connect_enter_monitor({
+                let send_event = send_event.clone();
+                move |_, monitor| {
+                    _ = send_event.send(WindowEvent::MonitorChanged(monitor_info(monitor)));
+                }
+            });
+        }
+    });
+}
+
+fn monitor_info(monitor: &gdk::Monitor) -> AdwaitaMonitor {
+    let geometry = monitor.geometry();
+    let (width_mm, height_mm) = (monitor.width_mm(), monitor.height_mm());
+    AdwaitaMonitor {
+        connector: monitor.connector().map(String::from),
+        logical_size: UVec2::new(
+            u32::try_from(geometry.width()).unwrap_or(0),
+            u32::try_from(geometry.height()).unwrap_or(0),
+        ),
+        scale_factor: monitor.scale() as f32,
+        physical_size_mm: match (u32::try_from(width_mm), u32::try_from(height_mm)) {
+            (Ok(width), Ok(height)) if width > 0 && height > 0 => Some(UVec2::new(width, height)),
+            _ => None,
+        },
+    }
+}
+
+fn watch_window_state(window: &gtk::Window, send_event: &flume::Sender<WindowEvent>) {
+    let last_state = Rc::new(Cell::new(AdwaitaWindowState::default()));
+    let update = Rc::new({
+        let send_event = send_event.clone();
+        move |window: &gtk::Window| {
+            let state = window_state(window);
+            if last_state.replace(state) != state {
+                _ = send_event.send(WindowEvent::StateChanged(state));
+            }
+        }
+    });
+
+    window.connect_map({
+        let update = update.clone();
+        move |window| update(window)
+    });
+    window.connect_unmap({
+        let update = update.clone();
+        move |window| update(window)
+    });
+    window.connect_is_active_notify({
+        let update = update.clone();
+        move |window| update(window)
+    });
+    window.connect_fullscreened_notify({
+        let update = update.clone();
+        move |window| update(window)
+    });
+    window.connect_maximized_notify({
+        let update = update.clone();
+        move |window| update(window)
+    });
+    // the surface only exists once the window is realized
+    window.connect_realize(move |window| {
+        let Some(toplevel) = window.surface().and_downcast::<gdk::Toplevel>() else {
+            return;
+        };
+        toplevel.connect_state_notify({
+            let window = window.downgrade();
+            let update = update.clone();
+            move |_| {
+                if let Some(window) = window.upgrade() {
+                    update(&window);
+                }
+            }
+        });
+    });
+}
+
+fn make_motion_controller(send_event: &flume::Sender<WindowEvent>) -> gtk::EventControllerMotion {
+    let controller = gtk::EventControllerMotion::new();
+    controller.connect_enter({
+        let send_event = send_event.clone();
+        move |_, x, y| {
+            let position = Vec2::new(x as f32, y as f32);
+            _ = send_event.send(WindowEvent::CursorEntered { position });
+        }
+    });
+    controller.connect_motion({
+        let send_event = send_event.clone();
+        move |_, x, y| {
+            let position = Vec2::new(x as f32, y as f32);
+            _ = send_event.send(WindowEvent::CursorMoved { position });
+        }
+    });
+    controller.connect_leave({
+        let send_event = send_event.clone();
+        move |_| {
+            _ = send_event.send(WindowEvent::CursorLeft);
+        }
+    });
+    controller
+}
+
+fn make_button_controller(send_event: &flume::Sender<WindowEvent>) -> gtk::EventControllerLegacy {
+    // unlike a `GtkGestureClick`, this also sees releases of presses which
+    // another gesture claimed, so no button is left pressed
+    let controller = gtk::EventControllerLegacy::new();
+    controller.connect_event({
+        let send_event = send_event.clone();
+        move |_, event| {
+            let pressed = match event.event_type() {
+                gdk::EventType::ButtonPress => true,
+                gdk::EventType::ButtonRelease => false,
+                _ => return glib::Propagation::Proceed,
+            };
+            if let Some(event) = event.downcast_ref::<gdk::ButtonEvent>() {
+                _ = send_event.send(WindowEvent::MouseButton {
+                    button: event.button(),
+                    pressed,
+                });
+            }
+            glib::Propagation::Proceed
+        }
+    });
+    controller
+}
+
+fn make_scroll_controller(send_event: &flume::Sender<WindowEvent>) -> gtk::EventControllerScroll {
+    let controller = gtk::EventControllerScroll::new(gtk::EventControllerScrollFlags::BOTH_AXES);
+    controller.connect_scroll({
+        let send_event = send_event.clone();
+        move |controller, dx, dy| {
+            _ = send_event.send(WindowEvent::Scroll {
+                delta: Vec2::new(dx as f32, dy as f32),
+                lines: controller.unit() == gdk::ScrollUnit::Wheel,
+            });
+            // let native widgets under the render widget scroll too
+            glib::Propagation::Proceed
+        }
+    });
+    controller
+}
+
+fn make_focus_on_click_controller() -> gtk::GestureClick {
+    // capture, and never claim the sequence, so that the click is still
+    // handled as usual
+    let controller = gtk::GestureClick::builder()
+        .button(0)
+        .propagation_phase(gtk::PropagationPhase::Capture)
+        .build();
+    controller.connect_pressed(|controller, _, _, _| {
+        if let Some(widget) = controller.widget() {
+            if !widget.has_focus() {
+                widget.grab_focus();
+            }
+        }
+    });
+    controller
+}
+
+fn make_secondary_click_controller(send_event: &flume::Sender<WindowEvent>) -> gtk::GestureClick {
+    let controller = gtk::GestureClick::builder()
+        .button(gdk::BUTTON_SECONDARY)
+        .build();
+    controller.connect_pressed({
+        let send_event = send_event.clone();
+        move |_, _, x, y| {
+            _ = send_event.send(WindowEvent::PointerGesture {
+                position: Vec2::new(x as f32, y as f32),
+                kind: AdwaitaPointerGestureKind::SecondaryClick,
+            });
+        }
+    });
+    controller
+}
+
+fn make_long_press_controller(send_event: &flume::Sender<WindowEvent>) -> gtk::GestureLongPress {
+    // not only touch, so that holding on a touchpad works too
+    let controller = gtk::GestureLongPress::builder().touch_only(false).build();
+    controller.connect_pressed({
+        let send_event = send_event.clone();
+        move |_, x, y| {
+            _ = send_event.send(WindowEvent::PointerGesture {
+                position: Vec2::new(x as f32, y as f32),
+                kind: AdwaitaPointerGestureKind::ContextMenu,
+            });
+        }
+    });
+    controller
+}
+
+fn make_stylus_controller(send_event: &flume::Sender<WindowEvent>) -> gtk::GestureStylus {
+    // listen to the tip and the barrel buttons
+    let controller = gtk::GestureStylus::builder().button(0).build();
+    let send = |phase: AdwaitaStylusPhase| {
+        let send_event = send_event.clone();
+        move |controller: &gtk::GestureStylus, x: f64, y: f64| {
+            _ = send_event.send(stylus_event(
+                controller,
+                phase,
+                Vec2::new(x as f32, y as f32),
+            ));
+        }
+    };
+    controller.connect_proximity(send(AdwaitaStylusPhase::Hover));
+    controller.connect_down(send(AdwaitaStylusPhase::Down));
+    controller.connect_motion(send(AdwaitaStylusPhase::Move));
+    controller.connect_up(send(AdwaitaStylusPhase::Up));
+    controller
+}
+
+fn stylus_event(
+    controller: &gtk::GestureStylus,
+    phase: AdwaitaStylusPhase,
+    position: Vec2,
+) -> WindowEvent {
+    let axis = |axis: gdk::AxisUse| controller.axis(axis).map(|value| value as f32);
+    // not every tablet reports every axis, so fall back to what a plain
+    // pointer would mean
+    let pressure = axis(gdk::AxisUse::Pressure).unwrap_or(match phase {
+        AdwaitaStylusPhase::Down | AdwaitaStylusPhase::Move => 1.0,
+        AdwaitaStylusPhase::Hover | AdwaitaStylusPhase::Up => 0.0,
+    });
+    let tilt = Vec2::new(
+        axis(gdk::AxisUse::Xtilt).unwrap_or(0.0),
+        axis(gdk::AxisUse::Ytilt).unwrap_or(0.0),
+    );
+    let button = Some(controller.current_button()).filter(|button| *button != 0);
+    let eraser = controller
+        .device_tool()
+        .is_some_and(|tool| tool.tool_type() == gdk::DeviceToolType::Eraser);
+
+    WindowEvent::Stylus {
+        position,
+        phase,
+        pressure,
+        tilt,
+        button,
+        eraser,
+    }
+}
+
+/// Reads the primary selection when the middle mouse button is pressed.
+fn make_primary_paste_controller(send_event: &flume::Sender<WindowEvent>) -> gtk::GestureClick {
+    let controller = gtk::GestureClick::builder()
+        .button(gdk::BUTTON_MIDDLE)
+        .build();
+    controller.connect_pressed({
+        let send_event = send_event.clone();
+        move |controller, _, x, y| {
+            let position = Vec2::new(x as f32, y as f32);
+            let send_event = send_event.clone();
+            controller
+                .widget()
+                .display()
+                .primary_clipboard()
+                .read_text_async(None::<&gio::Cancellable>, move |result| match result {
+                    Ok(Some(text)) if !text.is_empty() => {
+                        _ = send_event.send(WindowEvent::PrimaryPaste {
+                            position,
+                            text: text.into(),
+                        });
+                    }
+                    Ok(_) => {}
+                    Err(err) => {
+                        // also happens when the selection just isn't text
+                        tracing::debug!("Failed to read primary selection: {err}");
+                    }
+                });
+        }
+    });
+    controller
+}
+
+/// Accepts files dropped from other apps, and refuses drags of anything else.
+fn make_file_drop_target(send_event: &flume::Sender<WindowEvent>) -> gtk::DropTarget {
+    let target = gtk::DropTarget::new(glib::Type::INVALID, gdk::DragAction::COPY);
+    // some apps only offer a single `gio::File`, not a list
+    target.set_types(&[gdk::FileList::static_type(), gio::File::static_type()]);
+    target.connect_drop({
+        let send_event = send_event.clone();
+        move |_, value, x, y| {
+            let files = if let Ok(files) = value.get::<gdk::FileList>() {
+                files.files()
+            } else if let Ok(file) = value.get::<gio::File>() {
+                vec![file]
+            } else {
+                return false;
+            };
+            let paths = files
+                .iter()
+                .filter_map(|file| file.path())
+                .collect::<Vec<_>>();
+            if paths.is_empty() {
+                return false;
+            }
+            _ = send_event.send(WindowEvent::FilesDropped {
+                position: Vec2::new(x as f32, y as f32),
+                paths,
+            });
+            true
+        }
+    });
+    target
+}
+
+/// Forwards key presses and releases to Bevy as they come from GDK, without
+/// any translation.
+fn make_raw_key_controller(
+    filter: AdwaitaKeyFilter,
+    send_event: &flume::Sender<WindowEvent>,
+) -> gtk::EventControllerKey {
+    let controller = gtk::EventControllerKey::new();
+    controller.set_propagation_phase(match filter {
+        // run before any other key handling, so that keys which the input
+        // method or a focused widget consume are still sent
+        AdwaitaKeyFilter::All => gtk::PropagationPhase::Capture,
+        // run after the focused widget, which stops propagation for keys
+        // it consumes
+        AdwaitaKeyFilter::Unhandled => gtk::PropagationPhase::Bubble,
+    });
+    let send_raw_key = {
+        let send_event = send_event.clone();
+        move |controller: &gtk::EventControllerKey,
+              pressed: bool,
+              keyval: gdk::Key,
+              keycode: u32,
+              modifiers: gdk::ModifierType| {
+            _ = send_event.send(WindowEvent::RawKey {
+                pressed,
+                keyval: keyval.into_glib(),
+                keycode,
+                group: controller.group(),
+                modifiers: modifiers.bits(),
+            });
+        }
+    };
+    controller.connect_key_pressed({
+        let send_raw_key = send_raw_key.clone();
+        move |controller, keyval, keycode, modifiers| {
+            send_raw_key(controller, true, keyval, keycode, modifiers);
+            // let shortcuts and widgets handle the key as well
+            glib::Propagation::Proceed
+        }
+    });
+    controller.connect_key_released(move |controller, keyval, keycode, modifiers| {
+        send_raw_key(controller, false, keyval, keycode, modifiers);
+    });
+    controller
+}
+
+/// Forwards text typed into the window to Bevy, after it has gone through the
+/// system input method.
+///
+/// Dead keys and compose sequences are handled by the input method, which only
+/// commits the final composed text, so intermediate keys never show up as
+/// text.
+fn make_text_input_controller(
+    window: &gtk::Window,
+    send_event: &flume::Sender<WindowEvent>,
+) -> gtk::EventControllerKey {
+    // uses the system input method if there is one, and otherwise falls back to
+    // `GtkIMContextSimple`, which supports dead keys and the compose key
+    let im_context = gtk::IMMulticontext::new();
+    im_context.set_client_widget(Some(window));
+    im_context.connect_commit({
+        let send_event = send_event.clone();
+        move |_, text| {
+            _ = send_event.send(WindowEvent::TextInput(text.to_owned()));
+        }
+    });
+
+    window.connect_is_active_notify({
+        let im_context = im_context.clone();
+        move |window| {
+            if window.is_active() {
+                im_context.focus_in();
+            } else {
+                im_context.focus_out();
+                // drop any half-finished compose sequence
+                im_context.reset();
+            }
+        }
+    });
+
+    let controller = gtk::EventControllerKey::new();
+    controller.set_im_context(Some(&im_context));
+    controller
+}
+
+fn make_emoji_chooser(
+    render_target: &gtk::Picture,
+    send_event: &flume::Sender<WindowEvent>,
+) -> gtk::EmojiChooser {
+    let emoji_chooser = gtk::EmojiChooser::new();
+    emoji_chooser.set_parent(render_target);
+    // if the chooser is dismissed instead, nothing is picked or sent
+    emoji_chooser.connect_emoji_picked({
+        let send_event = send_event.clone();
+        move |_, text| {
+            _ = send_event.send(WindowEvent::TextInput(text.to_owned()));
+        }
+    });
+    // popovers must be unparented manually
+    render_target.connect_destroy({
+        let emoji_chooser = emoji_chooser.clone();
+        move |_| emoji_chooser.unparent()
+    });
+    emoji_chooser
+}
+
+/// Background drawn behind the render target.
+#[derive(Debug)]
+struct Backdrop {
+    graphics_offload: gtk::GraphicsOffload,
+    css_provider: gtk::CssProvider,
+    display: gdk::Display,
+    /// CSS class which only this window's backdrop widgets have, so that the
+    /// provider, which applies to the whole display, only styles them.
+    css_class: String,
+}
+
+impl Backdrop {
+    fn new(
+        render_widget: &gtk::Box,
+        clamp: Option<&adw::Clamp>,
+        graphics_offload: gtk::GraphicsOffload,
+    ) -> Self {
+        static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+        let css_class = format!(
+            "bevy-adwaita-backdrop-{}",
+            NEXT_ID.fetch_add(1, Ordering::Relaxed)
+        );
+        render_widget.add_css_class(&css_class);
+        if let Some(clamp) = clamp {
+            clamp.add_css_class(&css_class);
+        }
+        let css_provider = gtk::CssProvider::new();
+        let display = render_widget.display();
+        gtk::style_context_add_provider_for_display(
+            &display,
+            &css_provider,
+            gtk::STYLE_PROVIDER_PRIORITY_APPLICATION,
+        );
+        Self {
+            graphics_offload,
+            css_provider,
+            display,
+            css_class,
+        }
+    }
+
+    fn set_color(&self, color: Option<Color>) {
+        match color {
+            Some(color) => {
+                let [r, g, b, a] = color.to_srgba().to_f32_array();
+                self.css_provider.load_from_string(&format!(
+                    ".{} {{ background-color: rgba({}, {}, {}, {a}); }}",
+                    self.css_class,
+                    r * 255.0,
+                    g * 255.0,
+                    b * 255.0,
+                ));
+                // the offload's own black background would cover up ours
+                self.graphics_offload.set_black_background(false);
+            }
+            None => {
+                self.css_provider.load_from_string("");
+                self.graphics_offload.set_black_background(true);
+            }
+        }
+    }
+}
+
+impl Drop for Backdrop {
+    fn drop(&mut self) {
+        gtk::style_context_remove_provider_for_display(&self.display, &self.css_provider);
+    }
+}
+
+/// Creates the widget which Bevy frames are presented into.
+///
+/// This widget can be packed anywhere in a widget tree; it doesn't need to be
+/// the direct content of a window.
+fn make_render_widget(
+    render_target: &gtk::Picture,
+    render_target_width: &Arc<AtomicI32>,
+    render_target_height: &Arc<AtomicI32>,
+) -> (gtk::Box, gtk::GraphicsOffload) {
+    let graphics_offload = gtk::GraphicsOffload::builder()
+        .black_background(true)
+        .child(render_target)
+        .hexpand(true)
+        .vexpand(true)
+        .build();
+
+    // Use a trick to detect when the actual render target
+    // is resized, and send this new frame size to the app.
+    // https://stackoverflow.com/questions/70488187/get-calculated-size-of-widget-in-gtk-4-0
+    // +-----------------------+
+    // |          WL           |  WL: width_listener  (height 0)
+    // |-----------------------|  HL: height_listener (width 0)
+    // |   |                   |
+    // | H |     graphics      |
+    // | L |     offload       |
+    // |   |                   |
+    // +-----------------------+
+
+    let width_listener = gtk::DrawingArea::builder().hexpand(true).build();
+    width_listener.set_draw_func({
+        let render_target_width = render_target_width.clone();
+        move |_, _, width, _| {
+            render_target_width.store(width, Ordering::SeqCst);
+        }
+    });
+
+    let height_listener = gtk::DrawingArea::builder().vexpand(true).build();
+    height_listener.set_draw_func({
+        let render_target_height = render_target_height.clone();
+        move |_, _, _, height| {
+            render_target_height.store(height, Ordering::SeqCst);
+        }
+    });
+
+    let frame_content_h = gtk::Box::new(gtk::Orientation::Horizontal, 0);
+    frame_content_h.append(&height_listener);
+    frame_content_h.append(&graphics_offload);
+
+    let frame_content_v = gtk::Box::new(gtk::Orientation::Vertical, 0);
+    frame_content_v.append(&width_listener);
+    frame_content_v.append(&frame_content_h);
+
+    (frame_content_v, graphics_offload)
+}
+
+/// Centers the render widget, and stops it from growing wider than
+/// `max_width`.
+fn make_content_clamp(render_widget: &gtk::Box, max_width: u32) -> adw::Clamp {
+    let max_width = i32::try_from(max_width).unwrap_or(i32::MAX);
+    adw::Clamp::builder()
+        .maximum_size(max_width)
+        // grow all the way up to the maximum, instead of easing into it
+        .tightening_threshold(max_width)
+        .child(render_widget)
+        .hexpand(true)
+        .vexpand(true)
+        .build()
+}
+
+/// Window made by the crate, and the parts of it which commands can change.
+/// Non-resizable windows ignore their default size and shrink to fit their
+/// content, so this pins them to their default size with a size request
+/// instead, which lets [`WindowCommand::SetSize`] still resize them.
+fn pin_window_size(window: &gtk::Window) {
+    let (width, height) = window.default_size();
+    window.set_size_request(width, height);
+}
+
+struct DefaultWindow {
+    window: adw::Window,
+    split_view: Option<adw::OverlaySplitView>,
+    search_bar: Option<gtk::SearchBar>,
+    progress_bar: gtk::ProgressBar,
+    diagnostics_label: gtk::Label,
+    toast_overlay: adw::ToastOverlay,
+    view_stack: Option<adw::ViewStack>,
+    action_bar: Option<gtk::ActionBar>,
+    /// Widgets which may be drawn over the render widget.
+    chrome: Vec<gtk::Widget>,
+    /// Label showing the title in the header bar, if it's not bound to the
+    /// window title.
+    header_title_label: Option<gtk::Label>,
+    /// Smallest size which the narrowest layout of the window's breakpoints
+    /// fits into, if it has any.
+    layout_min_size: Option<(i32, i32)>,
+}
+
+fn make_default_window(
+    config: &AdwaitaWindowConfig,
+    render_widget: &gtk::Widget,
+    builders: WindowBuilders,
+    send_event: &flume::Sender<WindowEvent>,
+) -> DefaultWindow {
+    // overlay children don't affect the size of the render widget
+    let overlay = gtk::Overlay::new();
+    overlay.set_child(Some(render_widget));
+    let mut title_label = None;
+    let mut search_bar = None;
+    let mut full_header_bar = None;
+    let mut chrome = Vec::<gtk::Widget>::new();
+
+    let views = builders
+        .views
+        .map(|build_views| make_views(&overlay, build_views, send_event));
+    // either just the render area, or a view stack with it as one of the pages
+    let body: gtk::Widget = views.as_ref().map_or_else(
+        || overlay.clone().upcast(),
+        |views| views.stack.clone().upcast(),
+    );
+    // bars are laid out around the body, which only gets the space between them
+    let toolbar_view = adw::ToolbarView::builder()
+        .top_bar_style(gtk_toolbar_style(config.top_bar_style))
+        .bottom_bar_style(gtk_toolbar_style(config.bottom_bar_style))
+        .build();
+
+    let content: gtk::Widget = match config.header_bar {
+        AdwaitaHeaderBar::Full => {
+            let header_bar = adw::HeaderBar::new();
+            if let Some(layout) = &config.decoration_layout {
+                header_bar.set_decoration_layout(Some(&layout.gtk_layout()));
+            }
+            if let Some(views) = &views {
+                // the switcher takes the place of the title
+                header_bar.set_title_widget(Some(&views.switcher));
+            } else {
+                let title_widget = builders.title.map(|BuildTitle(build_title)| {
+                    build_title(AdwaitaTitleEmitter {
+                        send_event: send_event.clone(),
+                    })
+                });
+                title_label = set_header_title(
+                    &header_bar,
+                    config.header_title,
+                    title_widget,
+                    config.title_with_app_name,
+                );
+            }
+            if !config.menu.is_empty() {
+                // packed first, so that it's next to the window controls
+                header_bar.pack_end(&make_menu_button(&config.menu, send_event));
+            }
+            add_header_buttons(&header_bar, &config.header_buttons, send_event);
+            full_header_bar = Some(header_bar.clone());
+
+            toolbar_view.add_top_bar(&header_bar);
+            if config.header_search {
+                let bar = make_search_bar(&header_bar, send_event);
+                toolbar_view.add_controller(make_search_shortcut(&bar));
+                toolbar_view.add_top_bar(&bar);
+                search_bar = Some(bar);
+            }
+            body
+        }
+        AdwaitaHeaderBar::OverContent => {
+            // this margin makes the window controls looks exactly like in an `adw::HeaderBar`
+            const MARGIN: i32 = 6;
+
+            let window_controls = gtk::WindowControls::builder()
+                .side(gtk::PackType::End)
+                .halign(gtk::Align::End)
+                .valign(gtk::Align::Start)
+                .margin_start(MARGIN)
+                .margin_end(MARGIN)
+                .margin_top(MARGIN)
+                .margin_bottom(MARGIN)
+                .build();
+
+            overlay.add_overlay(&window_controls);
+            chrome.push(window_controls.clone().upcast());
+            if let Some(layout) = &config.decoration_layout {
+                window_controls.set_decoration_layout(Some(&layout.gtk_layout()));
+                if !layout.start().is_empty() {
+                    let start_controls = gtk::WindowControls::builder()
+                        .side(gtk::PackType::Start)
+                        .halign(gtk::Align::Start)
+                        .valign(gtk::Align::Start)
+                        .margin_start(MARGIN)
+                        .margin_end(MARGIN)
+                        .margin_top(MARGIN)
+                        .margin_bottom(MARGIN)
+                        .decoration_layout(layout.gtk_layout())
+                        .build();
+                    overlay.add_overlay(&start_controls);
+                    chrome.push(start_controls.upcast());
+                }
+            }
+            body
+        }
+        AdwaitaHeaderBar::None => {
+            if config.floating_controls {
+                add_floating_controls(&overlay, config.decoration_layout.as_ref());
+            }
+            body
+        }
+    };
+    // toasts are shown at the bottom of the body, whatever the header bar
+    let toast_overlay = adw::ToastOverlay::builder().child(&content).build();
+    toolbar_view.set_content(Some(&toast_overlay));
+    if let Some(views) = &views {
+        toolbar_view.add_bottom_bar(&views.switcher_bar);
+    }
+    let action_bar = config
+        .action_bar
+        .as_ref()
+        .map(|action_bar| make_action_bar(action_bar, send_event));
+    if let Some(action_bar) = &action_bar {
+        toolbar_view.add_bottom_bar(action_bar);
+    }
+    if let Some(BuildBottomBars(build_bottom_bars)) = builders.bottom_bars {
+        for bar in build_bottom_bars() {
+            toolbar_view.add_bottom_bar(&bar);
+        }
+    }
+    let content: gtk::Widget = toolbar_view.upcast();
+
+    // GNOME apps show page load progress as a thin bar at the top of the
+    // content, under the header bar
+    let progress_bar = gtk::ProgressBar::builder()
+        .css_classes(["osd"])
+        .valign(gtk::Align::Start)
+        .can_target(false)
+        .visible(false)
+        .build();
+    overlay.add_overlay(&progress_bar);
+
+    let diagnostics_label = gtk::Label::builder()
+        .css_classes(["osd", "monospace", "caption"])
+        .halign(gtk::Align::End)
+        .valign(gtk::Align::End)
+        .margin_end(6)
+        .margin_bottom(6)
+        .xalign(0.0)
+        .can_target(false)
+        .visible(false)
+        .build();
+    overlay.add_overlay(&diagnostics_label);
+
+    // added after our own overlays, so that user widgets are drawn on top
+    if let Some(BuildOverlay(build_overlay)) = builders.overlay {
+        build_overlay(&overlay);
+    }
+
+    let split_view = builders.sidebar.map(|BuildSidebar(build_sidebar)| {
+        make_split_view(config, &content, build_sidebar(), send_event)
+    });
+    let content = split_view
+        .as_ref()
+        .map_or(content, |split_view| split_view.clone().upcast());
+
+    let window = adw::Window::builder()
+        .handle_menubar_accel(false)
+        .default_width(assert_i32(config.width, "window request width"))
+        .default_height(assert_i32(config.height, "window request height"))
+        .title(config.title.as_str())
+        .resizable(config.user_resizable)
+        .maximized(config.maximized)
+        .fullscreened(config.fullscreen)
+        .content(&content)
+        .build();
+    if !config.user_resizable {
+        pin_window_size(window.upcast_ref());
+    }
+    // with the app name in the window title, the header only shows the part
+    // before it, which we keep up to date ourselves
+    let header_title_label = match title_label {
+        Some(title_label) if config.title_with_app_name => {
+            title_label.set_label(&config.title);
+            Some(title_label)
+        }
+        Some(title_label) => {
+            window
+                .bind_property("title", &title_label, "label")
+                .sync_create()
+                .build();
+            None
+        }
+        None => None,
+    };
+    let mut layout_min_size = None;
+    if let Some(views) = &views {
+        match &full_header_bar {
+            Some(header_bar) => {
+                window.add_breakpoint(make_narrow_breakpoint(header_bar, views));
+                layout_min_size = Some(NARROW_LAYOUT_MIN_SIZE);
+            }
+            // there's nowhere else to put the switcher
+            None => views.switcher_bar.set_reveal(true),
+        }
+    }
+    // the sidebar is always collapsed, so it's shown on top of the content
+    chrome.extend(split_view.as_ref().and_then(adw::OverlaySplitView::sidebar));
+    DefaultWindow {
+        window,
+        split_view,
+        search_bar,
+        progress_bar,
+        diagnostics_label,
+        toast_overlay,
+        view_stack: views.map(|views| views.stack),
+        action_bar,
+        chrome,
+        header_title_label,
+        layout_min_size,
+    }
+}
+
+fn show_about(parent: &gtk::Window, about: &AdwaitaAboutConfig, app_name: Option<&str>) {
+    let application_name = about
+        .application_name
+        .clone()
+        .or_else(|| app_name.map(str::to_owned))
+        .or_else(|| parent.title().map(Into::into))
+        .unwrap_or_default();
+    let window = adw::AboutWindow::builder()
+        .transient_for(parent)
+        .modal(true)
+        .application_name(application_name)
+        .version(about.version.as_str())
+        .developer_name(about.developer_name.as_str())
+        .comments(about.comments.as_str())
+        .website(about.website.as_str())
+        .issue_url(about.issue_url.as_str())
+        .copyright(about.copyright.as_str())
+        .build();
+    if let Some(license_type) = about.license_type {
+        window.set_license_type(license_type);
+    }
+    if let Some(icon) = &about.application_icon {
+        window.set_application_icon(icon);
+    }
+    let developers = about
+        .developers
+        .iter()
+        .map(String::as_str)
+        .collect::<Vec<_>>();
+    window.set_developers(&developers);
+    // the about window isn't part of the application, so closing it doesn't
+    // affect any of our windows
+    window.present();
+}
+
+/// How close the cursor must be to the top of the window for
+/// [`AdwaitaWindowConfig::floating_controls`] to be shown, in logical pixels.
+const FLOATING_CONTROLS_REVEAL_DISTANCE: f64 = 64.0;
+
+fn add_floating_controls(
+    overlay: &gtk::Overlay,
+    decoration_layout: Option<&AdwaitaDecorationLayout>,
+) {
+    let fullscreen_button = gtk::Button::builder()
+        .icon_name("view-fullscreen-symbolic")
+        .tooltip_text("Toggle Fullscreen")
+        .css_classes(["flat", "circular"])
+        .build();
+    fullscreen_button.connect_clicked(|button| {
+        let Some(window) = button.root().and_downcast::<gtk::Window>() else {
+            return;
+        };
+        window.set_fullscreened(!window.is_fullscreen());
+    });
+    let window_controls = gtk::WindowControls::new(gtk::PackType::End);
+    if let Some(layout) = decoration_layout {
+        window_controls.set_decoration_layout(Some(&layout.gtk_layout()));
+    }
+
+    let controls = gtk::Box::builder()
+        .css_classes(["osd", "toolbar"])
+        .spacing(6)
+        .build();
+    controls.append(&fullscreen_button);
+    controls.append(&window_controls);
+    let revealer = gtk::Revealer::builder()
+        .child(&controls)
+        .transition_type(gtk::RevealerTransitionType::Crossfade)
+        .halign(gtk::Align::End)
+        .valign(gtk::Align::Start)
+        .margin_top(6)
+        .margin_end(6)
+        .build();
+    // so that hidden controls never swallow clicks meant for the render area
+    revealer
+        .bind_property("reveal-child", &revealer, "can-target")
+        .sync_create()
+        .build();
+    overlay.add_overlay(&revealer);
+
+    // capture, so that we see motion over the render widget too, without
+    // claiming it
+    let motion = gtk::EventControllerMotion::builder()
+        .propagation_phase(gtk::PropagationPhase::Capture)
+        .build();
+    let update = {
+        let revealer = revealer.downgrade();
+        let fullscreen_button = fullscreen_button.downgrade();
+        move |y: Option<f64>| {
+            let (Some(revealer), Some(fullscreen_button)) =
+                (revealer.upgrade(), fullscreen_button.upgrade())
+            else {
+                return;
+            };
+            let reveal = y.is_some_and(|y| y < FLOATING_CONTROLS_REVEAL_DISTANCE);
+            if reveal && !revealer.reveals_child() {
+                let fullscreen = fullscreen_button
+                    .root()
+                    .and_downcast::<gtk::Window>()
+                    .is_some_and(|window| window.is_fullscreen());
+                fullscreen_button.set_icon_name(if fullscreen {
+                    "view-restore-symbolic"
+                } else {
+                    "view-fullscreen-symbolic"
+                });
+            }
+            revealer.set_reveal_child(reveal);
+        }
+    };
+    motion.connect_enter({
+        let update = update.clone();
+        move |_, _, y| update(Some(y))
+    });
+    motion.connect_motion({
+        let update = update.clone();
+        move |_, _, y| update(Some(y))
+    });
+    motion.connect_leave(move |_| update(None));
+    overlay.add_controller(motion);
+}
+
+/// Picks the size request of a resizable window, from the explicit
+/// `min_size` if there is one, or else from what its layout needs.
+fn window_min_size(
+    entity: Entity,
+    min_size: Option<UVec2>,
+    layout_min_size: Option<(i32, i32)>,
+) -> Option<(i32, i32)> {
+    let Some(min_size) = min_size else {
+        // breakpoints only apply while the window can shrink to them
+        return layout_min_size;
+    };
+    let min_size = (
+        assert_i32(min_size.x, "window min width"),
+        assert_i32(min_size.y, "window min height"),
+    );
+    if let Some(layout_min_size) = layout_min_size {
+        if min_size.0 < layout_min_size.0 || min_size.1 < layout_min_size.1 {
+            tracing::warn!(
+                "Adwaita window {entity} min size {min_size:?} is smaller than \
+                 {layout_min_size:?}, which its narrowest layout needs, so content \
+                 may be cut off"
+            );
+        }
+    }
+    Some(min_size)
+}
+
+/// View stack which switches between the render area and native pages.
+struct Views {
+    stack: adw::ViewStack,
+    /// Switcher shown in the header bar on wide windows.
+    switcher: adw::ViewSwitcher,
+    /// Switcher shown at the bottom on narrow windows.
+    switcher_bar: adw::ViewSwitcherBar,
+}
+
+fn make_views(
+    render_page: &gtk::Overlay,
+    BuildViews {
+        render_view,
+        build_pages,
+    }: BuildViews,
+    send_event: &flume::Sender<WindowEvent>,
+) -> Views {
+    let stack = adw::ViewStack::builder().vexpand(true).build();
+    let add_page = |view: AdwaitaView, widget: &gtk::Widget| {
+        let page = stack.add_titled(widget, Some(view.id.as_str()), &view.title);
+        if let Some(icon_name) = &view.icon_name {
+            warn_on_missing_icon(icon_name);
+        }
+        page.set_icon_name(view.icon_name.as_deref());
+    };
+    add_page(render_view, render_page.upcast_ref());
+    for (view, widget) in build_pages() {
+        add_page(view, &widget);
+    }
+
+    stack.connect_visible_child_name_notify({
+        let send_event = send_event.clone();
+        move |stack| {
+            if let Some(view_id) = stack.visible_child_name() {
+                _ = send_event.send(WindowEvent::ViewChanged(view_id.into()));
+            }
+        }
+    });
+
+    let switcher = adw::ViewSwitcher::builder()
+        .stack(&stack)
+        .policy(adw::ViewSwitcherPolicy::Wide)
+        .build();
+    let switcher_bar = adw::ViewSwitcherBar::builder().stack(&stack).build();
+
+    Views {
+        stack,
+        switcher,
+        switcher_bar,
+    }
+}
+
+fn make_action_bar(
+    AdwaitaActionBar { start, center, end }: &AdwaitaActionBar,
+    send_event: &flume::Sender<WindowEvent>,
+) -> gtk::ActionBar {
+    let make_button = |action: &AdwaitaAction| {
+        let button = match &action.icon_name {
+            Some(icon_name) => {
+                warn_on_missing_icon(icon_name);
+                gtk::Button::builder()
+                    .icon_name(icon_name.as_str())
+                    .tooltip_text(action.label.as_str())
+                    .build()
+            }
+            None => gtk::Button::with_label(&action.label),
+        };
+        if action.suggested {
+            button.add_css_class("suggested-action");
+        }
+        button.connect_clicked({
+            let send_event = send_event.clone();
+            let action_id = action.id.clone();
+            move |_| {
+                _ = send_event.send(WindowEvent::ActionActivated(action_id.clone()));
+            }
+        });
+        button
+    };
+
+    let action_bar = gtk::ActionBar::new();
+    for action in start {
+        action_bar.pack_start(&make_button(action));
+    }
+    // packed in reverse, so that the first button is the furthest from the edge
+    for action in end.iter().rev() {
+        action_bar.pack_end(&make_button(action));
+    }
+    if !center.is_empty() {
+        let center_box = gtk::Box::new(gtk::Orientation::Horizontal, 6);
+        for action in center {
+            center_box.append(&make_button(action));
+        }
+        action_bar.set_center_widget(Some(&center_box));
+    }
+    action_bar
+}
+
+/// Prefix of the actions of menu items, which are only installed on the menu
+/// button so that they don't clash with application actions.
+const MENU_ACTION_GROUP: &str = "menu";
+
+fn make_menu_button(
+    items: &[AdwaitaMenuItem],
+    send_event: &flume::Sender<WindowEvent>,
+) -> gtk::MenuButton {
+    let actions = gio::SimpleActionGroup::new();
+    let menu = make_menu(items, &actions, send_event);
+    let button = gtk::MenuButton::builder()
+        .icon_name("open-menu-symbolic")
+        .tooltip_text("Main Menu")
+        .menu_model(&menu)
+        // opened with F10, like the main menu of other GNOME apps
+        .primary(true)
+        .build();
+    button.insert_action_group(MENU_ACTION_GROUP, Some(&actions));
+    button
+}
+
+fn make_menu(
+    items: &[AdwaitaMenuItem],
+    actions: &gio::SimpleActionGroup,
+    send_event: &flume::Sender<WindowEvent>,
+) -> gio::Menu {
+    let menu = gio::Menu::new();
+    for item in items {
+        if !item.children.is_empty() {
+            let submenu = make_menu(&item.children, actions, send_event);
+            menu.append_submenu(Some(&item.label), &submenu);
+            continue;
+        }
+        if !gio::Action::name_is_valid(&item.id) {
+            tracing::warn!(
+                "Ignoring menu item {:?} with invalid ID {:?}",
+                item.label,
+                item.id
+            );
+            continue;
+        }
+
+        let action = match item.kind {
+            AdwaitaMenuItemKind::Action => gio::SimpleAction::new(&item.id, None),
+            // a boolean state without a parameter is shown as a checkbox
+            AdwaitaMenuItemKind::Toggle(checked) => {
+                gio::SimpleAction::new_stateful(&item.id, None, &checked.to_variant())
+            }
+        };
+        action.connect_activate({
+            let send_event = send_event.clone();
+            let id = item.id.clone();
+            move |action, _| {
+                // connecting to `activate` replaces the default toggling
+                let checked = action
+                    .state()
+                    .and_then(|state| state.get::<bool>())
+                    .map(|checked| {
+                        action.set_state(&(!checked).to_variant());
+                        !checked
+                    });
+                _ = send_event.send(WindowEvent::MenuActivated {
+                    id: id.clone(),
+                    checked,
+                });
+            }
+        });
+        actions.add_action(&action);
+        menu.append(
+            Some(&item.label),
+            Some(&format!("{MENU_ACTION_GROUP}.{}", item.id)),
+        );
+    }
+    menu
+}
+
+fn add_header_buttons(
+    header_bar: &adw::HeaderBar,
+    buttons: &[AdwaitaHeaderButton],
+    send_event: &flume::Sender<WindowEvent>,
+) {
+    let make_button = |header_button: &AdwaitaHeaderButton| {
+        let button = match &header_button.icon_name {
+            Some(icon_name) => {
+                warn_on_missing_icon(icon_name);
+                gtk::Button::builder()
+                    .icon_name(icon_name.as_str())
+                    .tooltip_text(header_button.label.as_str())
+                    .build()
+            }
+            None => gtk::Button::with_label(&header_button.label),
+        };
+        button.connect_clicked({
+            let send_event = send_event.clone();
+            let id = header_button.id.clone();
+            move |_| {
+                _ = send_event.send(WindowEvent::HeaderButtonPressed(id.clone()));
+            }
+        });
+        button
+    };
+
+    for button in buttons
+        .iter()
+        .filter(|button| button.side == AdwaitaHeaderButtonSide::Start)
+    {
+        header_bar.pack_start(&make_button(button));
+    }
+    // `pack_end` packs from the edge inwards, which is already the order
+    // buttons are given in
+    for button in buttons
+        .iter()
+        .filter(|button| button.side == AdwaitaHeaderButtonSide::End)
+    {
+        header_bar.pack_end(&make_button(button));
+    }
+}
+
+fn gtk_toolbar_style(style: AdwaitaToolbarStyle) -> adw::ToolbarStyle {
+    match style {
+        AdwaitaToolbarStyle::Flat => adw::ToolbarStyle::Flat,
+        AdwaitaToolbarStyle::Raised => adw::ToolbarStyle::Raised,
+        AdwaitaToolbarStyle::RaisedBorder => adw::ToolbarStyle::RaisedBorder,
+    }
+}
+
+/// Smallest size which the layout of [`make_narrow_breakpoint`] fits into,
+/// which is also the smallest size that GNOME apps support on phones.
+const NARROW_LAYOUT_MIN_SIZE: (i32, i32) = (360, 294);
+
+/// Moves the view switcher from the header bar to the bottom of the window
+/// when the window is too narrow for it, like in GNOME's own apps.
+fn make_narrow_breakpoint(header_bar: &adw::HeaderBar, views: &Views) -> adw::Breakpoint {
+    let condition = adw::BreakpointCondition::parse("max-width: 550sp")
+        .expect("breakpoint condition should be valid");
+    let breakpoint = adw::Breakpoint::new(condition);
+    breakpoint.add_setter(
+        header_bar,
+        "title-widget",
+        Some(&None::<gtk::Widget>.to_value()),
+    );
+    breakpoint.add_setter(&views.switcher_bar, "reveal", Some(&true.to_value()));
+    breakpoint
+}
+
+/// Makes a search bar which is toggled by a button in the header bar.
+fn make_search_bar(
+    header_bar: &adw::HeaderBar,
+    send_event: &flume::Sender<WindowEvent>,
+) -> gtk::SearchBar {
+    let entry = gtk::SearchEntry::builder().hexpand(true).build();
+    entry.connect_search_changed({
+        let send_event = send_event.clone();
+        move |entry| {
+            _ = send_event.send(WindowEvent::SearchChanged(entry.text().into()));
+        }
+    });
+    entry.connect_activate({
+        let send_event = send_event.clone();
+        move |entry| {
+            _ = send_event.send(WindowEvent::SearchActivated(entry.text().into()));
+        }
+    });
+
+    let clamp = adw::Clamp::builder().child(&entry).build();
+    let search_bar = gtk::SearchBar::builder().child(&clamp).build();
+    search_bar.connect_entry(&entry);
+    search_bar.connect_search_mode_enabled_notify({
+        let send_event = send_event.clone();
+        move |search_bar| {
+            let shown = search_bar.is_search_mode();
+            if !shown {
+                // closing the search clears it, which also sends an empty query
+                entry.set_text("");
+            }
+            _ = send_event.send(WindowEvent::SearchShown(shown));
+        }
+    });
+
+    let toggle = gtk::ToggleButton::builder()
+        .icon_name("system-search-symbolic")
+        .tooltip_text("Search")
+        .build();
+    toggle
+        .bind_property("active", &search_bar, "search-mode-enabled")
+        .bidirectional()
+        .sync_create()
+        .build();
+    header_bar.pack_end(&toggle);
+
+    search_bar
+}
+
+/// Makes the Ctrl+F shortcut which toggles the search bar from anywhere in the
+/// window.
+fn make_search_shortcut(search_bar: &gtk::SearchBar) -> gtk::ShortcutController {
+    let action = gtk::CallbackAction::new({
+        let search_bar = search_bar.downgrade();
+        move |_, _| {
+            if let Some(search_bar) = search_bar.upgrade() {
+                search_bar.set_search_mode(!search_bar.is_search_mode());
+            }
+            glib::Propagation::Stop
+        }
+    });
+    let shortcut = gtk::Shortcut::new(
+        gtk::ShortcutTrigger::parse_string("<Control>f"),
+        Some(action),
+    );
+    let controller = gtk::ShortcutController::new();
+    controller.set_scope(gtk::ShortcutScope::Global);
+    controller.add_shortcut(shortcut);
+    controller
+}
+
+/// Sets up the title of the header bar, returning the label which should show
+/// the window title if we had to make our own.
+fn set_header_title(
+    header_bar: &adw::HeaderBar,
+    alignment: AdwaitaHeaderTitle,
+    title_widget: Option<gtk::Widget>,
+    force_label: bool,
+) -> Option<gtk::Label> {
+    match (alignment, title_widget) {
+        (AdwaitaHeaderTitle::Centered, None) if force_label => {
+            let label = make_title_label();
+            header_bar.set_title_widget(Some(&label));
+            Some(label)
+        }
+        (AdwaitaHeaderTitle::Centered, None) => None,
+        (AdwaitaHeaderTitle::Centered, Some(title_widget)) => {
+            header_bar.set_title_widget(Some(&title_widget));
+            None
+        }
+        (AdwaitaHeaderTitle::Start, title_widget) => {
+            // an empty title widget replaces the default centered title
+            header_bar.set_title_widget(Some(&gtk::Box::new(gtk::Orientation::Horizontal, 0)));
+            match title_widget {
+                Some(title_widget) => {
+                    header_bar.pack_start(&title_widget);
+                    None
+                }
+                None => {
+                    let label = make_title_label();
+                    header_bar.pack_start(&label);
+                    Some(label)
+                }
+            }
+        }
+    }
+}
+
+fn make_title_label() -> gtk::Label {
+    gtk::Label::builder()
+        .css_classes(["title"])
+        .ellipsize(gtk::pango::EllipsizeMode::End)
+        .build()
+}
+
+/// Title of the whole window, which the shell shows e.g. in the taskbar, for
+/// a window whose header shows `title`.
+fn window_title(title: &str, app_name: Option<&str>) -> String {
+    match app_name {
+        Some(app_name) if app_name != title => format!("{title} \u{2014} {app_name}"),
+        _ => title.to_owned(),
+    }
+}
+
+fn make_split_view(
+    config: &AdwaitaWindowConfig,
+    content: &gtk::Widget,
+    sidebar: gtk::Widget,
+    send_event: &flume::Sender<WindowEvent>,
+) -> adw::OverlaySplitView {
+    // the show gesture only starts from the edge of the window, and the hide
+    // gesture only from the sidebar itself, so drags which start further into
+    // the content area still go to the render widget
+    let split_view = adw::OverlaySplitView::builder()
+        .collapsed(true)
+        .show_sidebar(false)
+        .enable_show_gesture(config.sidebar_swipe_gestures)
+        .enable_hide_gesture(config.sidebar_swipe_gestures)
+        .content(content)
+        .sidebar(&sidebar)
+        .build();
+    split_view.connect_show_sidebar_notify({
+        let send_event = send_event.clone();
+        move |split_view| {
+            _ = send_event.send(WindowEvent::SidebarShown(split_view.shows_sidebar()));
+        }
+    });
+    split_view
 }
 
-fn assert_i32(n: u32, value_name: &str) -> i32 {
+pub fn assert_i32(n: u32, value_name: &str) -> i32 {
     i32::try_from(n).unwrap_or_else(|_| panic!("{value_name} must fit into an `i32`, was {n}"))
 }
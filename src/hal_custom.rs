@@ -57,16 +57,44 @@ pub unsafe fn open_adapter(
     }
 }
 
+/// Gets which of `extensions` are not supported by `adapter`.
+pub fn missing_extensions(
+    adapter: &vulkan::Adapter,
+    extensions: impl IntoIterator<Item = &'static CStr>,
+) -> Vec<&'static CStr> {
+    let supported = unsafe {
+        adapter
+            .shared_instance()
+            .raw_instance()
+            .enumerate_device_extension_properties(adapter.raw_physical_device())
+    }
+    .unwrap_or_default();
+
+    extensions
+        .into_iter()
+        .filter(|&extension| {
+            !supported.iter().any(|properties| {
+                let name = unsafe { CStr::from_ptr(properties.extension_name.as_ptr()) };
+                name == extension
+            })
+        })
+        .collect()
+}
+
+/// Merges the user's requested features and limits in `settings` with what
+/// `adapter` supports, in the same way that Bevy does.
+///
+/// This is computed from the HAL adapter, before the device is opened, so that
+/// the device is opened with exactly the features that wgpu will later expect.
 pub fn make_device_descriptor<'a>(
     settings: &'a WgpuSettings,
-    adapter: &wgpu::Adapter,
-    adapter_info: &wgpu::AdapterInfo,
+    adapter: &wgpu_hal::ExposedAdapter<vulkan::Api>,
 ) -> wgpu::DeviceDescriptor<'a> {
     let mut features = wgpu::Features::empty();
     let mut limits = settings.limits.clone();
     if matches!(settings.priority, WgpuSettingsPriority::Functionality) {
-        features = adapter.features();
-        if adapter_info.device_type == wgpu::DeviceType::DiscreteGpu {
+        features = adapter.features;
+        if adapter.info.device_type == wgpu::DeviceType::DiscreteGpu {
             // `MAPPABLE_PRIMARY_BUFFERS` can have a significant, negative performance impact for
             // discrete GPUs due to having to transfer data across the PCI-E bus and so it
             // should not be automatically enabled in this case. It is however beneficial for
@@ -82,7 +110,7 @@ pub fn make_device_descriptor<'a>(
         features -= wgpu::Features::RAY_QUERY;
         features -= wgpu::Features::RAY_TRACING_ACCELERATION_STRUCTURE;
 
-        limits = adapter.limits();
+        limits = adapter.capabilities.limits.clone();
     }
 
     // Enforce the disabled features
@@ -0,0 +1,197 @@
+use bevy::input::keyboard::{Key, KeyCode, NativeKey, NativeKeyCode};
+use gtk::{gdk, glib::translate::FromGlib};
+
+/// Offset between XKB keycodes, which GDK reports on both Wayland and X11, and
+/// Linux evdev scancodes.
+const XKB_EVDEV_OFFSET: u32 = 8;
+
+/// Translates a GDK hardware keycode into the physical key at that position on
+/// a US layout, which is what [`KeyCode`] describes.
+pub fn key_code(keycode: u32) -> KeyCode {
+    let Some(scancode) = keycode.checked_sub(XKB_EVDEV_OFFSET) else {
+        return KeyCode::Unidentified(NativeKeyCode::Xkb(keycode));
+    };
+    // from `linux/input-event-codes.h`
+    match scancode {
+        1 => KeyCode::Escape,
+        2 => KeyCode::Digit1,
+        3 => KeyCode::Digit2,
+        4 => KeyCode::Digit3,
+        5 => KeyCode::Digit4,
+        6 => KeyCode::Digit5,
+        7 => KeyCode::Digit6,
+        8 => KeyCode::Digit7,
+        9 => KeyCode::Digit8,
+        10 => KeyCode::Digit9,
+        11 => KeyCode::Digit0,
+        12 => KeyCode::Minus,
+        13 => KeyCode::Equal,
+        14 => KeyCode::Backspace,
+        15 => KeyCode::Tab,
+        16 => KeyCode::KeyQ,
+        17 => KeyCode::KeyW,
+        18 => KeyCode::KeyE,
+        19 => KeyCode::KeyR,
+        20 => KeyCode::KeyT,
+        21 => KeyCode::KeyY,
+        22 => KeyCode::KeyU,
+        23 => KeyCode::KeyI,
+        24 => KeyCode::KeyO,
+        25 => KeyCode::KeyP,
+        26 => KeyCode::BracketLeft,
+        27 => KeyCode::BracketRight,
+        28 => KeyCode::Enter,
+        29 => KeyCode::ControlLeft,
+        30 => KeyCode::KeyA,
+        31 => KeyCode::KeyS,
+        32 => KeyCode::KeyD,
+        33 => KeyCode::KeyF,
+        34 => KeyCode::KeyG,
+        35 => KeyCode::KeyH,
+        36 => KeyCode::KeyJ,
+        37 => KeyCode::KeyK,
+        38 => KeyCode::KeyL,
+        39 => KeyCode::Semicolon,
+        40 => KeyCode::Quote,
+        41 => KeyCode::Backquote,
+        42 => KeyCode::ShiftLeft,
+        43 => KeyCode::Backslash,
+        44 => KeyCode::KeyZ,
+        45 => KeyCode::KeyX,
+        46 => KeyCode::KeyC,
+        47 => KeyCode::KeyV,
+        48 => KeyCode::KeyB,
+        49 => KeyCode::KeyN,
+        50 => KeyCode::KeyM,
+        51 => KeyCode::Comma,
+        52 => KeyCode::Period,
+        53 => KeyCode::Slash,
+        54 => KeyCode::ShiftRight,
+        55 => KeyCode::NumpadMultiply,
+        56 => KeyCode::AltLeft,
+        57 => KeyCode::Space,
+        58 => KeyCode::CapsLock,
+        59 => KeyCode::F1,
+        60 => KeyCode::F2,
+        61 => KeyCode::F3,
+        62 => KeyCode::F4,
+        63 => KeyCode::F5,
+        64 => KeyCode::F6,
+        65 => KeyCode::F7,
+        66 => KeyCode::F8,
+        67 => KeyCode::F9,
+        68 => KeyCode::F10,
+        69 => KeyCode::NumLock,
+        70 => KeyCode::ScrollLock,
+        71 => KeyCode::Numpad7,
+        72 => KeyCode::Numpad8,
+        73 => KeyCode::Numpad9,
+        74 => KeyCode::NumpadSubtract,
+        75 => KeyCode::Numpad4,
+        76 => KeyCode::Numpad5,
+        77 => KeyCode::Numpad6,
+        78 => KeyCode::NumpadAdd,
+        79 => KeyCode::Numpad1,
+        80 => KeyCode::Numpad2,
+        81 => KeyCode::Numpad3,
+        82 => KeyCode::Numpad0,
+        83 => KeyCode::NumpadDecimal,
+        86 => KeyCode::IntlBackslash,
+        87 => KeyCode::F11,
+        88 => KeyCode::F12,
+        89 => KeyCode::IntlRo,
+        96 => KeyCode::NumpadEnter,
+        97 => KeyCode::ControlRight,
+        98 => KeyCode::NumpadDivide,
+        99 => KeyCode::PrintScreen,
+        100 => KeyCode::AltRight,
+        102 => KeyCode::Home,
+        103 => KeyCode::ArrowUp,
+        104 => KeyCode::PageUp,
+        105 => KeyCode::ArrowLeft,
+        106 => KeyCode::ArrowRight,
+        107 => KeyCode::End,
+        108 => KeyCode::ArrowDown,
+        109 => KeyCode::PageDown,
+        110 => KeyCode::Insert,
+        111 => KeyCode::Delete,
+        113 => KeyCode::AudioVolumeMute,
+        114 => KeyCode::AudioVolumeDown,
+        115 => KeyCode::AudioVolumeUp,
+        117 => KeyCode::NumpadEqual,
+        119 => KeyCode::Pause,
+        121 => KeyCode::NumpadComma,
+        124 => KeyCode::IntlYen,
+        125 => KeyCode::SuperLeft,
+        126 => KeyCode::SuperRight,
+        127 => KeyCode::ContextMenu,
+        163 => KeyCode::MediaTrackNext,
+        164 => KeyCode::MediaPlayPause,
+        165 => KeyCode::MediaTrackPrevious,
+        166 => KeyCode::MediaStop,
+        183 => KeyCode::F13,
+        184 => KeyCode::F14,
+        185 => KeyCode::F15,
+        186 => KeyCode::F16,
+        187 => KeyCode::F17,
+        188 => KeyCode::F18,
+        189 => KeyCode::F19,
+        190 => KeyCode::F20,
+        191 => KeyCode::F21,
+        192 => KeyCode::F22,
+        193 => KeyCode::F23,
+        194 => KeyCode::F24,
+        _ => KeyCode::Unidentified(NativeKeyCode::Xkb(keycode)),
+    }
+}
+
+/// Translates a GDK keyval, with modifiers already applied, into the logical
+/// key it produces.
+pub fn logical_key(keyval: u32) -> Key {
+    // SAFETY: any `u32` is a valid keyval, even if it has no name
+    let key = unsafe { gdk::Key::from_glib(keyval) };
+    match key {
+        gdk::Key::Return | gdk::Key::KP_Enter | gdk::Key::ISO_Enter => Key::Enter,
+        gdk::Key::Tab | gdk::Key::KP_Tab | gdk::Key::ISO_Left_Tab => Key::Tab,
+        gdk::Key::space | gdk::Key::KP_Space => Key::Space,
+        gdk::Key::BackSpace => Key::Backspace,
+        gdk::Key::Escape => Key::Escape,
+        gdk::Key::Delete | gdk::Key::KP_Delete => Key::Delete,
+        gdk::Key::Insert | gdk::Key::KP_Insert => Key::Insert,
+        gdk::Key::Home | gdk::Key::KP_Home => Key::Home,
+        gdk::Key::End | gdk::Key::KP_End => Key::End,
+        gdk::Key::Page_Up | gdk::Key::KP_Page_Up => Key::PageUp,
+        gdk::Key::Page_Down | gdk::Key::KP_Page_Down => Key::PageDown,
+        gdk::Key::Up | gdk::Key::KP_Up => Key::ArrowUp,
+        gdk::Key::Down | gdk::Key::KP_Down => Key::ArrowDown,
+        gdk::Key::Left | gdk::Key::KP_Left => Key::ArrowLeft,
+        gdk::Key::Right | gdk::Key::KP_Right => Key::ArrowRight,
+        gdk::Key::Shift_L | gdk::Key::Shift_R => Key::Shift,
+        gdk::Key::Control_L | gdk::Key::Control_R => Key::Control,
+        gdk::Key::Alt_L | gdk::Key::Alt_R => Key::Alt,
+        gdk::Key::Super_L | gdk::Key::Super_R => Key::Super,
+        gdk::Key::ISO_Level3_Shift => Key::AltGraph,
+        gdk::Key::Caps_Lock => Key::CapsLock,
+        gdk::Key::Num_Lock => Key::NumLock,
+        gdk::Key::Scroll_Lock => Key::ScrollLock,
+        gdk::Key::Menu => Key::ContextMenu,
+        gdk::Key::Print => Key::PrintScreen,
+        gdk::Key::Pause => Key::Pause,
+        gdk::Key::F1 => Key::F1,
+        gdk::Key::F2 => Key::F2,
+        gdk::Key::F3 => Key::F3,
+        gdk::Key::F4 => Key::F4,
+        gdk::Key::F5 => Key::F5,
+        gdk::Key::F6 => Key::F6,
+        gdk::Key::F7 => Key::F7,
+        gdk::Key::F8 => Key::F8,
+        gdk::Key::F9 => Key::F9,
+        gdk::Key::F10 => Key::F10,
+        gdk::Key::F11 => Key::F11,
+        gdk::Key::F12 => Key::F12,
+        _ => match key.to_unicode() {
+            Some(c) if !c.is_control() => Key::Character(c.to_string().into()),
+            _ => Key::Unidentified(NativeKey::Xkb(keyval)),
+        },
+    }
+}
@@ -1,42 +1,303 @@
 mod adwaita_app;
 mod hal_custom;
+mod keyboard;
+mod paintable;
+#[cfg(feature = "pipewire")]
+mod pipewire_stream;
 mod render;
 
+pub use adwaita_app::WindowCommand as AdwaitaWindowCommand;
+#[cfg(feature = "pipewire")]
+pub use pipewire_stream::AdwaitaPipeWireError;
+pub use render::{AdwaitaAdapterSelection, AdwaitaRendererError};
+pub use {adw, gtk};
+
 use std::{
     any::type_name,
+    collections::{HashMap, HashSet},
+    io,
+    os::fd::{BorrowedFd, OwnedFd},
+    path::PathBuf,
     sync::{
-        atomic::{AtomicBool, AtomicI32, Ordering},
-        Arc,
+        atomic::{AtomicBool, AtomicI32, AtomicU32, AtomicU64, Ordering},
+        Arc, Condvar, Mutex, MutexGuard, PoisonError,
     },
     thread,
+    time::{Duration, Instant},
 };
 
-use adwaita_app::{WindowCommand, WindowOpen};
+use adwaita_app::{
+    AppCommand, AppConfig, AppEvent, AppRequest, BuildBottomBars, BuildOverlay, BuildSidebar,
+    BuildTitle, BuildViews, BuildWindow, RunOnMainThread, WindowBuilders, WindowCommand,
+    WindowEvent, WindowMessage, WindowOpen,
+};
 use atomicbox::AtomicOptionBox;
 use bevy::{
-    ecs::system::EntityCommand,
+    ecs::{
+        entity::EntityHashMap,
+        system::{EntityCommand, SystemParam},
+    },
+    input::{
+        keyboard::KeyboardInput,
+        mouse::{MouseButtonInput, MouseScrollUnit, MouseWheel},
+        ButtonState,
+    },
+    log::Level,
     prelude::*,
     render::{
-        camera::{ManualTextureViewHandle, ManualTextureViews, RenderTarget},
-        renderer::RenderDevice,
+        camera::{
+            CameraUpdateSystem, ManualTextureView, ManualTextureViewHandle, ManualTextureViews,
+            RenderTarget, Viewport,
+        },
+        render_asset::{prepare_assets, RenderAssets},
+        render_resource::{Texture, TextureUsages, TextureView},
+        renderer::{render_system, RenderAdapter, RenderDevice, RenderQueue},
         settings::WgpuSettings,
+        texture::{DefaultImageSampler, GpuImage},
         Extract, Render, RenderApp, RenderPlugin, RenderSet,
     },
-    window::{ExitCondition, WindowRef},
+    window::{
+        CursorEntered, CursorLeft, CursorMoved, ExitCondition, RequestRedraw, WindowFocused,
+        WindowRef, WindowResolution,
+    },
+    winit::WinitSettings,
 };
-use render::{DmabufInfo, FrameInfo};
+use gtk::{gdk, gio};
+use render::{FrameInfo, SoftwareFrame};
 
 #[derive(Clone)]
 pub struct AdwaitaWindowPlugin {
     pub primary_window_config: Option<AdwaitaWindowConfig>,
+    /// Whether to wait until the first [`Camera`] is spawned before opening
+    /// the window from [`AdwaitaWindowPlugin::primary_window_config`], instead
+    /// of opening it at startup.
+    ///
+    /// This avoids showing an empty window while the app is still loading.
+    /// Cameras with [`NoAdwaitaRetarget`] don't count.
+    pub open_primary_on_first_camera: bool,
+    /// When to send [`AppExit`] as Adwaita windows close.
+    ///
+    /// - [`ExitCondition::OnPrimaryClosed`] exits as soon as the
+    ///   [`PrimaryAdwaitaWindow`] closes, like a typical single-window app.
+    /// - [`ExitCondition::OnAllClosed`], the default, exits once every
+    ///   [`AdwaitaWindow`] has closed.
+    /// - [`ExitCondition::DontExit`] keeps the app running headless, e.g. to
+    ///   open a new window later.
+    ///
+    /// Windows with [`AdwaitaWindowConfig::hide_on_close`] are only hidden
+    /// when closed, so they don't count as closing here.
     pub exit_condition: ExitCondition,
+    /// Whether cameras targeting [`WindowRef::Primary`] are automatically
+    /// made to render to the [`PrimaryAdwaitaWindow`], and follow it as the
+    /// marker moves between windows.
+    ///
+    /// Cameras targeting [`WindowRef::Entity`] of an [`AdwaitaWindow`] are
+    /// also made to render to that window, as soon as both the camera and the
+    /// window exist.
+    ///
+    /// If disabled, cameras are never touched, and you must point them at a
+    /// window yourself, e.g. with `camera.target = window.render_target()`
+    /// once the [`AdwaitaWindow`] component is inserted, and again whenever
+    /// you want them to render to another window. [`NoAdwaitaRetarget`] opts
+    /// out individual cameras instead.
+    pub auto_retarget_cameras: bool,
+    /// Whether to insert a Bevy [`Window`] onto each Adwaita window entity,
+    /// kept in sync with the GTK window, so that code which expects a
+    /// [`Window`] keeps working.
+    ///
+    /// This only syncs from GTK into the [`Window`]: size, scale factor, focus,
+    /// cursor position and title. Changes made to the [`Window`] are not
+    /// applied to the GTK window, and winit-specific fields are left at their
+    /// defaults.
+    ///
+    /// `bevy_winit` creates a real window for every [`Window`], so if you
+    /// enable this, you must not use [`WinitPlugin`] - use e.g.
+    /// [`ScheduleRunnerPlugin`] to drive the app instead.
+    ///
+    /// [`WinitPlugin`]: bevy::winit::WinitPlugin
+    /// [`ScheduleRunnerPlugin`]: bevy::app::ScheduleRunnerPlugin
+    pub bevy_window_bridge: bool,
+    /// Application ID of the GTK application which owns all Adwaita windows,
+    /// e.g. `com.example.MyApp`.
+    pub application_id: Option<String>,
+    /// Flags of the GTK application which owns all Adwaita windows.
+    ///
+    /// Incompatible combinations of flags are logged as warnings.
+    pub application_flags: gio::ApplicationFlags,
+    /// Name of the thread which runs GTK, as shown in profilers and panic
+    /// messages.
+    pub gtk_thread_name: String,
+    /// Niceness to run the GTK thread with, where lower values mean higher
+    /// priority.
+    ///
+    /// If [`None`], the thread inherits the niceness of the thread which built
+    /// the plugin. Lowering the niceness below the process' current niceness
+    /// usually requires elevated privileges, and failures are logged.
+    pub gtk_thread_nice: Option<i32>,
+    /// What paces Bevy's main loop.
+    pub frame_pacing: AdwaitaFramePacing,
+    /// Which log messages from GTK, GDK, Adwaita and GLib to route into Bevy's
+    /// logging, instead of printing them to stderr.
+    ///
+    /// If [`None`], GLib logs the way it normally does.
+    pub log_capture: Option<AdwaitaLogCapture>,
+    /// Whether to open the GTK Inspector at startup, for debugging the widget
+    /// tree of Adwaita windows and the paintables presenting Bevy's frames.
+    ///
+    /// The inspector can also be opened later by sending
+    /// [`AdwaitaOpenInspector`], without needing the inspector keybinding to
+    /// be enabled in the system settings.
+    pub open_inspector: bool,
+    /// GResource bundles (`.gresource` files) to register at startup, e.g.
+    /// for icons bundled with the app.
+    ///
+    /// Bundles which fail to load are logged as warnings.
+    pub resources: Vec<PathBuf>,
+    /// Extra directories to look for icons in, laid out like an icon theme,
+    /// e.g. `assets/icons/hicolor/scalable/actions/my-icon-symbolic.svg`.
+    ///
+    /// With this, `icon_name`s such as [`AdwaitaAction::icon_name`] resolve to
+    /// the app's own icons without installing them system-wide. Icons which
+    /// can't be found are logged as warnings when a window uses them.
+    pub icon_search_paths: Vec<PathBuf>,
+    /// Paths inside [`AdwaitaWindowPlugin::resources`] to look for icons in,
+    /// e.g. `/com/example/MyApp/icons`, laid out the same as
+    /// [`AdwaitaWindowPlugin::icon_search_paths`].
+    ///
+    /// GTK already looks in `icons` under the resource path of the
+    /// application ID.
+    pub icon_resource_paths: Vec<String>,
+    /// Color scheme to use with [`AdwaitaColorSchemePreference::FollowSystem`]
+    /// if the system doesn't report which one it prefers.
+    ///
+    /// Outside of GNOME, e.g. on some wlroots compositors, the settings portal
+    /// which reports this may not be running, which is logged once at startup.
+    /// The default of [`AdwaitaColorSchemePreference::FollowSystem`] falls back
+    /// to the light scheme.
+    pub color_scheme_fallback: AdwaitaColorSchemePreference,
+    /// Splash window to show as soon as GTK starts, until the first Adwaita
+    /// window presents a frame.
+    ///
+    /// This covers up the time it takes to initialize rendering and render
+    /// the first frame. If [`None`], no splash window is shown.
+    pub splash: Option<AdwaitaSplash>,
+}
+
+/// Lightweight window shown while the app starts up, set with
+/// [`AdwaitaWindowPlugin::splash`].
+///
+/// It's drawn by GTK alone, so it shows up before Bevy has rendered anything.
+#[derive(Debug, Clone)]
+pub struct AdwaitaSplash {
+    /// Image to show, e.g. the app's logo, in any format GTK can load.
+    ///
+    /// A spinner is always shown under it. If [`None`], only the spinner is
+    /// shown.
+    pub image: Option<PathBuf>,
+    /// Size of the splash window, in logical pixels.
+    pub size: UVec2,
+}
+
+impl Default for AdwaitaSplash {
+    fn default() -> Self {
+        Self {
+            image: None,
+            size: UVec2::new(360, 240),
+        }
+    }
+}
+
+/// Which GLib log messages [`AdwaitaWindowPlugin`] routes into Bevy's
+/// logging.
+///
+/// Captured messages are logged with the `glib` target, and their GLib log
+/// domain in the `domain` field. GLib's critical messages are logged as
+/// errors.
+#[derive(Debug, Clone)]
+pub struct AdwaitaLogCapture {
+    /// Log domains to capture, e.g. `Gtk`, `Gdk` or `Adwaita`. Messages from
+    /// other domains are logged by GLib as usual.
+    ///
+    /// If empty, all domains are captured.
+    pub domains: Vec<String>,
+    /// Most verbose level to capture. More verbose messages in captured
+    /// domains are dropped.
+    pub min_level: Level,
+}
+
+impl Default for AdwaitaLogCapture {
+    fn default() -> Self {
+        Self {
+            domains: Vec::new(),
+            min_level: Level::INFO,
+        }
+    }
+}
+
+/// What decides when Bevy's main loop runs the next update.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AdwaitaFramePacing {
+    /// Bevy's own loop decides, e.g. according to the winit plugin's
+    /// `UpdateMode`.
+    #[default]
+    Bevy,
+    /// Each update waits until the frame clock of the [`PrimaryAdwaitaWindow`]
+    /// ticks, so that exactly one update and render happens per frame
+    /// displayed by the compositor, and animations match the display's refresh
+    /// rate.
+    ///
+    /// The tradeoffs versus Bevy's own loop are:
+    /// - the app can update no faster than the display refreshes, and while
+    ///   the primary window is hidden (when GTK stops ticking its frame clock),
+    ///   updates run at a low fallback rate instead
+    /// - updates stall in [`First`] while waiting for a tick, so time spent
+    ///   waiting shows up as a long frame in diagnostics
+    /// - the rendered frame is presented one tick after the one it was
+    ///   started on, so latency is about one refresh interval
+    ///
+    /// The primary window must have [`PrimaryAdwaitaWindow`] inserted before
+    /// it is opened. Windows opened by [`AdwaitaWindowPlugin::primary_window_config`]
+    /// already do this.
+    FrameClock,
+    /// Bevy updates, renders and hands off frames as fast as it can, and GTK
+    /// presents whichever frame is the latest when it draws.
+    ///
+    /// This is meant for profiling the throughput of the rendering pipeline,
+    /// not for shipping. Bevy's loop is set to update continuously with
+    /// [`WinitSettings::game`]. [`AdwaitaWindowConfig::max_frames_in_flight`]
+    /// and [`AdwaitaFrameOverflow::Block`] and [`AdwaitaFrameOverflow::Skip`]
+    /// are ignored, so rendering never
+    /// waits on GTK, and frames which GTK doesn't get around to presenting are
+    /// dropped. Compare [`AdwaitaFrameStats::produced_frame_rate`] with
+    /// [`AdwaitaFrameStats::presented_frame_rate`] to see how many frames
+    /// were rendered versus displayed.
+    ///
+    /// GTK still runs on its own thread and only ever holds the latest frame,
+    /// so it stays responsive however fast Bevy renders. To be sure that it
+    /// isn't starved of CPU time, see [`AdwaitaWindowPlugin::gtk_thread_nice`].
+    Uncapped,
 }
 
 impl Default for AdwaitaWindowPlugin {
     fn default() -> Self {
         Self {
             primary_window_config: Some(AdwaitaWindowConfig::default()),
+            open_primary_on_first_camera: false,
             exit_condition: ExitCondition::OnAllClosed,
+            auto_retarget_cameras: true,
+            bevy_window_bridge: false,
+            application_id: None,
+            application_flags: gio::ApplicationFlags::empty(),
+            gtk_thread_name: "adwaita-main".into(),
+            gtk_thread_nice: None,
+            frame_pacing: AdwaitaFramePacing::default(),
+            log_capture: Some(AdwaitaLogCapture::default()),
+            open_inspector: false,
+            resources: Vec::new(),
+            icon_search_paths: Vec::new(),
+            icon_resource_paths: Vec::new(),
+            color_scheme_fallback: AdwaitaColorSchemePreference::default(),
+            splash: None,
         }
     }
 }
@@ -44,12 +305,187 @@ impl Default for AdwaitaWindowPlugin {
 impl Plugin for AdwaitaWindowPlugin {
     fn build(&self, app: &mut App) {
         let (send_window_open, recv_window_open) = flume::bounded::<WindowOpen>(1);
-        thread::spawn(|| adwaita_app::main_thread_loop(recv_window_open));
+        let (send_app_event, recv_app_event) = flume::unbounded::<AppEvent>();
+        let (send_app_command, recv_app_command) = flume::unbounded::<AppCommand>();
+        let app_config = AppConfig {
+            application_id: self.application_id.clone(),
+            flags: self.application_flags,
+            log_capture: self.log_capture.clone(),
+            open_inspector: self.open_inspector,
+            resources: self.resources.clone(),
+            icon_search_paths: self.icon_search_paths.clone(),
+            icon_resource_paths: self.icon_resource_paths.clone(),
+            color_scheme_fallback: self.color_scheme_fallback,
+            splash: self.splash.clone(),
+        };
+        let gtk_thread_nice = self.gtk_thread_nice;
+        let gtk_send_app_event = send_app_event.clone();
+        let gtk_thread = thread::Builder::new()
+            .name(self.gtk_thread_name.clone())
+            .spawn(move || {
+                if let Some(nice) = gtk_thread_nice {
+                    set_current_thread_nice(nice);
+                }
+                adwaita_app::main_thread_loop(
+                    app_config,
+                    recv_window_open,
+                    recv_app_command,
+                    gtk_send_app_event,
+                );
+            })
+            .expect("failed to spawn Adwaita main thread");
+        app.insert_resource(GtkThread(Some(gtk_thread)))
+            .add_systems(Last, shut_down_on_exit);
+
+        app.register_type::<PrimaryAdwaitaWindow>()
+            .register_type::<NoAdwaitaRetarget>()
+            .register_type::<AdwaitaWindowConfig>()
+            .register_type::<AdwaitaHeaderBar>()
+            .register_type::<AdwaitaCursorIcon>()
+            .register_type::<AdwaitaHeaderButton>()
+            .register_type::<AdwaitaHeaderButtonSide>()
+            .register_type::<AdwaitaMenuItem>()
+            .register_type::<AdwaitaMenuItemKind>()
+            .register_type::<AdwaitaHeaderTitle>()
+            .register_type::<AdwaitaToolbarStyle>()
+            .register_type::<AdwaitaActionBar>()
+            .register_type::<AdwaitaAction>()
+            .register_type::<AdwaitaDecorationLayout>()
+            .register_type::<AdwaitaWindowButton>()
+            .register_type::<AdwaitaWindowHint>()
+            .register_type::<AdwaitaFrameOverflow>()
+            .register_type::<AdwaitaAdaptiveRenderScale>()
+            .register_type::<AdwaitaCursorPosition>()
+            .register_type::<AdwaitaWindowState>()
+            .register_type::<AdwaitaSafeArea>()
+            .register_type::<AdwaitaMonitor>()
+            .register_type::<AdwaitaFrameStats>()
+            .register_type::<AdwaitaFramePresentation>()
+            .register_type::<AdwaitaRenderTargetSize>()
+            .register_type::<AdwaitaDisplayBackend>()
+            .register_type::<AdwaitaKeyCombo>()
+            .register_type::<AdwaitaKeymap>()
+            .register_type::<AdwaitaRequest>()
+            .register_type::<AdwaitaCapture>()
+            .register_type::<AdwaitaColorScheme>()
+            .register_type::<AdwaitaSystemSettings>()
+            .register_type::<AdwaitaReducedMotion>()
+            .register_type::<AdwaitaColorSchemePreference>()
+            .register_type::<AdwaitaTearing>()
+            .register_type::<AdwaitaKeyFilter>()
+            .register_type::<AdwaitaRenderTargetSharing>()
+            .insert_resource(SendWindowOpen(send_window_open))
+            .insert_resource(RecvAppEvent(recv_app_event))
+            .insert_resource(AdwaitaRequests {
+                next_id: AtomicU64::new(0),
+                send_app_command: send_app_command.clone(),
+                send_app_event,
+            })
+            .insert_resource(SendAppCommand(send_app_command))
+            .init_resource::<AdwaitaColorScheme>()
+            .init_resource::<AdwaitaSystemSettings>()
+            .init_resource::<AdwaitaReducedMotion>()
+            .add_event::<AdwaitaError>()
+            .add_event::<AdwaitaWindowError>()
+            .add_event::<AdwaitaDisplayLost>()
+            .add_event::<AdwaitaWindowStateChanged>()
+            .add_event::<AdwaitaWindowReady>()
+            .add_event::<AdwaitaCloseRequested>()
+            .add_event::<AdwaitaSidebarToggled>()
+            .add_event::<AdwaitaRawKey>()
+            // usually added by `InputPlugin` and `WindowPlugin` already
+            .add_event::<KeyboardInput>()
+            .add_event::<MouseButtonInput>()
+            .add_event::<MouseWheel>()
+            .add_event::<CursorMoved>()
+            .add_event::<CursorEntered>()
+            .add_event::<CursorLeft>()
+            .add_event::<WindowFocused>()
+            .add_event::<AdwaitaTextInput>()
+            .add_event::<AdwaitaPrimaryPaste>()
+            .add_event::<AdwaitaDragEnded>()
+            .add_event::<AdwaitaFilesDropped>()
+            .add_event::<AdwaitaPointerGesture>()
+            .add_event::<AdwaitaStylus>()
+            .add_event::<AdwaitaTitleWidgetEvent>()
+            .add_event::<AdwaitaSearchQueryChanged>()
+            .add_event::<AdwaitaSearchActivated>()
+            .add_event::<AdwaitaSearchToggled>()
+            .add_event::<AdwaitaViewChanged>()
+            .add_event::<AdwaitaActionActivated>()
+            .add_event::<AdwaitaHeaderButtonPressed>()
+            .add_event::<AdwaitaMenuActivated>()
+            .add_event::<AdwaitaCameraTargetLost>()
+            .add_event::<AdwaitaSystemSettingsChanged>()
+            .add_event::<AdwaitaColorSchemeChanged>()
+            .add_event::<AdwaitaReducedMotionChanged>()
+            .add_event::<AdwaitaResponse>()
+            .add_event::<AdwaitaOpenInspector>()
+            .add_event::<AdwaitaFramePresented>()
+            .add_event::<AdwaitaCaptureReady>()
+            .add_event::<AdwaitaSafeAreaChanged>()
+            .add_event::<AdwaitaMonitorChanged>()
+            .add_event::<AdwaitaGpuLost>()
+            .init_resource::<GpuLost>()
+            .init_resource::<CapturedFrames>()
+            .add_systems(
+                PreUpdate,
+                (
+                    forward_app_events,
+                    forward_window_events,
+                    forward_captured_frames,
+                    poll_windows,
+                    (
+                        update_render_target_sizes,
+                        (update_frame_stats, update_adaptive_render_scales).chain(),
+                    ),
+                )
+                    .chain(),
+            )
+            .add_systems(
+                PostUpdate,
+                (
+                    (apply_config_changes, pause_hidden_windows).chain(),
+                    update_diagnostics_overlays,
+                    clamp_camera_viewports.before(CameraUpdateSystem),
+                    clear_transparent_window_cameras,
+                    install_keymap.run_if(resource_exists_and_changed::<AdwaitaKeymap>),
+                    apply_color_scheme.run_if(resource_changed::<AdwaitaColorScheme>),
+                    open_inspector.run_if(on_event::<AdwaitaOpenInspector>()),
+                    clamp_msaa.run_if(resource_exists_and_changed::<Msaa>),
+                ),
+            )
+            .observe(report_lost_camera_targets);
+
+        if self.auto_retarget_cameras {
+            app.observe(update_default_camera_render_target)
+                .observe(update_existing_cameras_render_target)
+                .observe(update_cameras_render_target_on_open)
+                .observe(update_cameras_render_target_on_demote);
+        }
+
+        app.add_systems(PreUpdate, report_gpu_lost.before(poll_windows));
+        #[cfg(feature = "pipewire")]
+        app.add_systems(PreUpdate, update_pipewire_streams.after(poll_windows));
+
+        if self.bevy_window_bridge {
+            app.add_systems(
+                PreUpdate,
+                (insert_bevy_windows, sync_bevy_windows)
+                    .chain()
+                    .after(poll_windows),
+            );
+        }
 
-        app.insert_resource(SendWindowOpen(send_window_open))
-            .add_systems(PreUpdate, poll_windows)
-            .observe(update_default_camera_render_target)
-            .observe(update_existing_cameras_render_target);
+        if self.frame_pacing == AdwaitaFramePacing::FrameClock {
+            // only the latest tick matters, we don't want to catch up on missed ones
+            let (send, recv) = flume::bounded::<()>(1);
+            app.insert_resource(FrameClockTicks { send, recv })
+                .add_systems(First, wait_for_frame_clock);
+        }
+        if self.frame_pacing == AdwaitaFramePacing::Uncapped {
+            app.insert_resource(WinitSettings::game());
+        }
 
         match self.exit_condition {
             ExitCondition::OnPrimaryClosed => {
@@ -61,19 +497,128 @@ impl Plugin for AdwaitaWindowPlugin {
             ExitCondition::DontExit => {}
         }
 
+        let captured_frames = app.world().resource::<CapturedFrames>().clone();
         let render_app = app.sub_app_mut(RenderApp);
         render_app
+            .init_resource::<RenderWindows>()
+            .insert_resource(captured_frames)
+            .configure_sets(
+                Render,
+                AdwaitaFrameHandoffSet
+                    .after(RenderSet::Render)
+                    .before(RenderSet::PostCleanup),
+            )
             .add_systems(ExtractSchedule, extract_windows)
-            .add_systems(Render, send_frame_to_windows.after(RenderSet::Render))
+            .add_systems(
+                Render,
+                (
+                    wait_for_frames_in_flight
+                        .run_if(not(resource_exists::<UncappedFramePacing>))
+                        .in_set(RenderSet::Prepare),
+                    wait_for_free_buffers.in_set(RenderSet::Prepare),
+                    (
+                        begin_gpu_frame_timer.before(render_system),
+                        end_gpu_frame_timer.after(render_system),
+                    )
+                        .in_set(RenderSet::Render)
+                        .run_if(resource_exists::<render::GpuFrameTimer>),
+                    prepare_secondary_images
+                        .in_set(RenderSet::PrepareAssets)
+                        .after(prepare_assets::<GpuImage>),
+                    (
+                        copy_to_shared_targets,
+                        read_back_software_frames,
+                        capture_frames,
+                        send_frame_to_windows,
+                    )
+                        .chain()
+                        .in_set(AdwaitaFrameHandoffSet),
+                ),
+            )
             .add_systems(Last, put_back_next_frame_if_not_sent);
+        if self.frame_pacing == AdwaitaFramePacing::Uncapped {
+            render_app.insert_resource(UncappedFramePacing);
+        }
 
-        if let Some(config) = self.primary_window_config.clone() {
+        if self.open_primary_on_first_camera {
+            app.insert_resource(LazyPrimaryWindow(self.primary_window_config.clone()))
+                .observe(open_primary_on_first_camera);
+        } else if let Some(config) = self.primary_window_config.clone() {
             let world = app.world_mut();
-            let entity = world.spawn_empty().id();
+            // the marker must be there before opening, for frame clock pacing
+            let entity = world.spawn(PrimaryAdwaitaWindow).id();
             AdwaitaWindow::open(config).apply(entity, world);
-            world.entity_mut(entity).insert(PrimaryAdwaitaWindow);
         }
     }
+
+    fn finish(&self, app: &mut App) {
+        let gpu_lost = app.world().resource::<GpuLost>().clone();
+        let render_app = app.sub_app_mut(RenderApp);
+        let Some(render_device) = render_app.world().get_resource::<RenderDevice>() else {
+            warn!("No render device, GPU loss will not be detected");
+            return;
+        };
+        render_device.wgpu_device().set_device_lost_callback({
+            let gpu_lost = gpu_lost.clone();
+            move |reason, message| {
+                // the device is also "lost" when it's dropped on exit
+                if matches!(reason, wgpu::DeviceLostReason::Unknown) {
+                    gpu_lost.set(message);
+                }
+            }
+        });
+        let gpu_frame_timer = render_device
+            .features()
+            .contains(wgpu::Features::TIMESTAMP_QUERY)
+            .then(|| {
+                let (send, recv) = flume::bounded(GPU_FRAME_TIMES_CAPACITY);
+                let render_queue = render_app.world().resource::<RenderQueue>();
+                (
+                    render::GpuFrameTimer::new(render_device, render_queue, send),
+                    GpuFrameTimes(recv),
+                )
+            });
+        render_app.insert_resource(gpu_lost);
+        if let Some((timer, times)) = gpu_frame_timer {
+            render_app.insert_resource(timer);
+            app.insert_resource(times);
+        }
+    }
+}
+
+/// Config of the primary window which is opened once the first camera is
+/// spawned, with [`AdwaitaWindowPlugin::open_primary_on_first_camera`].
+#[derive(Debug, Resource)]
+struct LazyPrimaryWindow(Option<AdwaitaWindowConfig>);
+
+fn open_primary_on_first_camera(
+    trigger: Trigger<OnInsert, Camera>,
+    mut commands: Commands,
+    mut lazy_primary_window: ResMut<LazyPrimaryWindow>,
+    opted_out: Query<(), With<NoAdwaitaRetarget>>,
+) {
+    if opted_out.contains(trigger.entity()) {
+        return;
+    }
+    let Some(config) = lazy_primary_window.0.take() else {
+        return;
+    };
+
+    info!("First camera was spawned, opening primary Adwaita window");
+    // the marker must be there before opening, for frame clock pacing
+    commands
+        .spawn(PrimaryAdwaitaWindow)
+        .add(AdwaitaWindow::open(config));
+}
+
+fn set_current_thread_nice(nice: i32) {
+    // on Linux, `setpriority` with a thread ID only affects that thread
+    let result =
+        unsafe { libc::setpriority(libc::PRIO_PROCESS, libc::gettid() as libc::id_t, nice) };
+    if result != 0 {
+        let err = io::Error::last_os_error();
+        warn!("Failed to set niceness of Adwaita main thread to {nice}: {err}");
+    }
 }
 
 impl AdwaitaWindowPlugin {
@@ -86,321 +631,6012 @@ impl AdwaitaWindowPlugin {
         }
     }
 
+    /// Creates the [`RenderPlugin`] which renders into Adwaita windows.
+    ///
+    /// Features and limits in `settings` are respected in the same way as
+    /// Bevy's default renderer, including [`WgpuSettings::priority`]. The
+    /// Vulkan device extensions needed to export render targets as dmabufs
+    /// are always enabled in addition to these, so requesting your own
+    /// features will not disable dmabuf sharing, and vice versa.
+    ///
+    /// Multisampling is configured with Bevy's [`Msaa`] resource as usual. If
+    /// the GPU doesn't support the requested sample count, it's lowered to
+    /// the highest one it does support, with a warning.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the renderer could not be created, e.g. if there is no
+    /// Vulkan GPU. See [`AdwaitaWindowPlugin::try_render_plugin`] for a
+    /// non-panicking version.
     #[must_use]
     pub fn render_plugin(settings: WgpuSettings) -> RenderPlugin {
-        let render_creation = render::create_renderer(settings);
-        RenderPlugin {
+        Self::try_render_plugin(settings)
+            .unwrap_or_else(|err| panic!("failed to create Adwaita renderer: {err}"))
+    }
+
+    /// Creates the [`RenderPlugin`] which renders into Adwaita windows.
+    ///
+    /// # Errors
+    ///
+    /// Errors if the renderer could not be created, e.g. if there is no
+    /// Vulkan GPU.
+    pub fn try_render_plugin(settings: WgpuSettings) -> Result<RenderPlugin, AdwaitaRendererError> {
+        Self::try_render_plugin_with_adapter(settings, AdwaitaAdapterSelection::default())
+    }
+
+    /// Creates the [`RenderPlugin`] which renders into Adwaita windows, on the
+    /// GPU picked by `selection`.
+    ///
+    /// # Errors
+    ///
+    /// Errors if the renderer could not be created, e.g. if no GPU matches
+    /// `selection`.
+    pub fn try_render_plugin_with_adapter(
+        settings: WgpuSettings,
+        selection: AdwaitaAdapterSelection,
+    ) -> Result<RenderPlugin, AdwaitaRendererError> {
+        Self::try_render_plugin_with_options(
+            settings,
+            AdwaitaRenderOptions {
+                adapter: selection,
+                ..default()
+            },
+        )
+    }
+
+    /// Creates the [`RenderPlugin`] which renders into Adwaita windows, with
+    /// all of `options`.
+    ///
+    /// # Errors
+    ///
+    /// Errors if the renderer could not be created, e.g. if no GPU matches
+    /// [`AdwaitaRenderOptions::adapter`], or none supports sharing render
+    /// targets with GTK and [`AdwaitaRenderOptions::software_fallback`] is
+    /// disabled.
+    pub fn try_render_plugin_with_options(
+        settings: WgpuSettings,
+        options: AdwaitaRenderOptions,
+    ) -> Result<RenderPlugin, AdwaitaRendererError> {
+        let render_creation =
+            render::create_renderer(settings, options.adapter, options.software_fallback)?;
+        Ok(RenderPlugin {
             render_creation,
+            synchronous_pipeline_compilation: options.synchronous_pipeline_compilation,
+        })
+    }
+}
+
+/// Options for the renderer created by
+/// [`AdwaitaWindowPlugin::try_render_plugin_with_options`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AdwaitaRenderOptions {
+    /// Which GPU to render on.
+    pub adapter: AdwaitaAdapterSelection,
+    /// Whether to compile every render pipeline as soon as it's queued,
+    /// blocking the frame, instead of in the background.
+    ///
+    /// By default, pipelines compile in the background and anything using
+    /// them isn't drawn until they're ready, so objects may pop in a few
+    /// frames late, but frames are never held up. With this enabled,
+    /// everything is drawn as soon as it's spawned, but each new pipeline
+    /// stalls the frame while it compiles, which causes stutter unless all
+    /// pipelines are compiled up front, e.g. behind a loading screen.
+    pub synchronous_pipeline_compilation: bool,
+    /// Whether to still create the renderer if no GPU can export render
+    /// targets as dmabufs, e.g. with some NVIDIA drivers.
+    ///
+    /// All windows then present by reading frames back into CPU memory, as
+    /// with [`AdwaitaWindowConfig::force_software_present`], which is much
+    /// slower but still shows something. Each window also sends an
+    /// [`AdwaitaWindowError`] with [`AdwaitaWindowErrorKind::DmabufUnsupported`]
+    /// when it first allocates its render target. If disabled, creating the
+    /// renderer fails with [`AdwaitaRendererError::MissingExtensions`]
+    /// instead. Enabled by default.
+    pub software_fallback: bool,
+}
+
+impl Default for AdwaitaRenderOptions {
+    fn default() -> Self {
+        Self {
+            adapter: AdwaitaAdapterSelection::default(),
             synchronous_pipeline_compilation: false,
+            software_fallback: true,
         }
     }
 }
 
 #[derive(Debug, Component)]
 pub struct AdwaitaWindow {
-    send_command: flume::Sender<WindowCommand>,
+    send_command: flume::Sender<WindowMessage>,
     render_target_width: Arc<AtomicI32>,
     render_target_height: Arc<AtomicI32>,
-    scale_factor: Arc<AtomicI32>,
+    /// Bits of the fractional scale factor as an [`f32`], or 0 if unknown.
+    scale_factor: Arc<AtomicU32>,
     shared_next_frame: Arc<AtomicOptionBox<FrameInfo>>,
     closed: Arc<AtomicBool>,
+    focused: Arc<AtomicBool>,
+    fullscreen: Arc<AtomicBool>,
+    maximized: Arc<AtomicBool>,
+    pending_title: Arc<AtomicOptionBox<String>>,
+    recv_event: flume::Receiver<WindowEvent>,
     render_target_handle: ManualTextureViewHandle,
     last_render_target_size: UVec2,
     next_frame_to_render: Arc<AtomicOptionBox<FrameInfo>>,
+    frames_in_flight: Arc<FramesInFlight>,
+    max_frames_in_flight: Option<u32>,
+    next_frame_id: AtomicU64,
+    produced_frame_id: Arc<AtomicU64>,
+    /// Swapchain buffer which the frame with `produced_frame_id` was rendered
+    /// into.
+    #[cfg(feature = "pipewire")]
+    produced_buffer_index: Arc<std::sync::atomic::AtomicUsize>,
+    presented_frame_id: Arc<AtomicU64>,
+    send_capture: flume::Sender<AdwaitaCapture>,
+    /// Captures which haven't been extracted into the render world yet.
+    pending_captures: flume::Receiver<AdwaitaCapture>,
+    last_config: AdwaitaWindowConfig,
+    secondary_targets: HashMap<String, SecondaryRenderTarget>,
+    removed_secondary_targets: Vec<ManualTextureViewHandle>,
+    rendering_paused: bool,
+    /// Set by [`AdwaitaWindow::set_rendering_enabled`].
+    rendering_enabled: bool,
+    /// Keyvals of the keys currently held down, by hardware keycode, to
+    /// filter out key repeats and release them when focus is lost.
+    pressed_keys: HashMap<u32, u32>,
+    /// Position of the pointer which was last sent as a [`CursorMoved`], in
+    /// pixels of the render target.
+    cursor_position: Option<Vec2>,
+    /// Whether this frame isn't rendered to stay under
+    /// [`AdwaitaWindowConfig::max_fps`].
+    throttled: bool,
+    /// When we started skipping frames with [`AdwaitaFrameOverflow::Skip`],
+    /// if we're waiting for GTK to show the last frame.
+    skipping_since: Option<Instant>,
+    /// When the last frame which counts against
+    /// [`AdwaitaWindowConfig::max_fps`] was started.
+    last_frame_at: Option<Instant>,
+    opened_at: Instant,
+    /// Tearing mode in effect, which may differ from the config's.
+    tearing: AdwaitaTearing,
+    /// [`AdwaitaWindowConfig::transparent`] as the window was opened with.
+    transparent: bool,
+    /// Whether the render target has been allocated, and
+    /// [`AdwaitaWindowReady`] sent.
+    ready: bool,
+    /// Whether a [`WindowFocused`] has been sent yet, so the focus state the
+    /// window is first mapped with is reported even if it's unfocused.
+    focus_reported: bool,
+    /// Whether we've warned that GTK didn't lay out the render area within
+    /// [`AdwaitaWindowConfig::first_size_timeout`].
+    first_size_timed_out: bool,
+    software_present: Option<SoftwarePresent>,
+    /// Size which the render target is waiting to be resized to, and when we
+    /// first saw it.
+    pending_resize: Option<(UVec2, Instant)>,
+    /// See [`AdwaitaFrameStats::render_target_allocations`].
+    render_target_allocations: u64,
+    /// Render scale currently in use, which may differ from the config's if
+    /// it's adjusted adaptively.
+    render_scale: f32,
+    adaptive_render_scale: render::AdaptiveRenderScale,
+    /// Window whose frames this window presents, instead of its own.
+    mirror_of: Option<Entity>,
+    /// DRM format modifier of the last render target, so we only log it when
+    /// it changes.
+    last_dmabuf_modifier: Option<u64>,
+    /// Whether GTK couldn't import a tiled or compressed render target, so we
+    /// only allocate linear ones from now on.
+    linear_dmabuf_fallback: bool,
+    /// Render targets which frames are rendered into in turn, so that GTK
+    /// never presents the one which Bevy is rendering into.
+    swapchain: Vec<SwapchainBuffer>,
+    /// Index into `swapchain` of the buffer being rendered into this frame.
+    current_buffer: usize,
+    /// Render target which Bevy renders into before it's copied into the
+    /// current swapchain buffer, with [`AdwaitaRenderTargetSharing::Copy`].
+    copy_source: Option<(ManualTextureView, Texture)>,
+    /// When the diagnostics overlay was last updated, with
+    /// [`AdwaitaWindowConfig::show_diagnostics`].
+    diagnostics_updated_at: Option<Instant>,
+    #[cfg(feature = "pipewire")]
+    pipewire_stream: Option<pipewire_stream::PipeWireStream>,
 }
 
-#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Component, Reflect)]
-#[reflect(Default, Component)]
-pub struct PrimaryAdwaitaWindow;
-
-#[derive(Debug, Clone, Reflect)]
-#[reflect(Default)]
-pub struct AdwaitaWindowConfig {
-    pub width: u32,
-    pub height: u32,
-    pub title: String,
-    pub resizable: bool,
-    pub maximized: bool,
-    pub fullscreen: bool,
-    pub header_bar: AdwaitaHeaderBar,
+#[derive(Debug)]
+struct SwapchainBuffer {
+    manual_texture_view: ManualTextureView,
+    texture: Texture,
+    dmabuf: render::DmabufInfo,
+    users: Arc<render::BufferUsers>,
 }
 
-impl Default for AdwaitaWindowConfig {
-    fn default() -> Self {
-        Self {
-            width: 1280,
-            height: 720,
-            title: "App".into(),
-            resizable: true,
-            maximized: false,
-            fullscreen: false,
-            header_bar: AdwaitaHeaderBar::default(),
-        }
-    }
+/// State for a window which presents frames by reading them back to the CPU,
+/// instead of sharing them as dmabufs.
+#[derive(Debug)]
+struct SoftwarePresent {
+    /// Texture which Bevy renders into, if allocated yet.
+    texture: Option<Texture>,
+    shared_frame: Arc<AtomicOptionBox<SoftwareFrame>>,
+    /// Nanoseconds spent reading back the last frame.
+    readback_nanos: Arc<AtomicU64>,
 }
 
-#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Reflect)]
-#[reflect(Default)]
-pub enum AdwaitaHeaderBar {
-    #[default]
-    Full,
-    OverContent,
-    None,
+#[derive(Debug)]
+struct SecondaryRenderTarget {
+    handle: ManualTextureViewHandle,
+    size: UVec2,
+    /// Texture behind the handle, if allocated yet.
+    texture: Option<Texture>,
+    /// Image which shows this render target, if one was requested.
+    image: Option<Handle<Image>>,
 }
 
-#[derive(Debug, Resource)]
-struct SendWindowOpen(flume::Sender<WindowOpen>);
-
-impl AdwaitaWindow {
-    #[must_use]
-    pub fn open(config: AdwaitaWindowConfig) -> impl EntityCommand {
-        move |entity, world: &mut World| {
-            info!(
-                "Creating new Adwaita window \"{}\" ({entity})",
-                config.title
-            );
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Component, Reflect)]
+#[reflect(Default, Component)]
+pub struct PrimaryAdwaitaWindow;
 
-            let (send_command, recv_command) = flume::bounded::<WindowCommand>(16);
-            let render_target_width = Arc::new(AtomicI32::new(-1));
-            let render_target_height = Arc::new(AtomicI32::new(-1));
-            let scale_factor = Arc::new(AtomicI32::new(-1));
-            let shared_next_frame = Arc::new(AtomicOptionBox::<FrameInfo>::none());
-            let closed = Arc::new(AtomicBool::new(false));
-            let request = WindowOpen {
-                config,
-                recv_command,
-                render_target_width: render_target_width.clone(),
-                render_target_height: render_target_height.clone(),
-                shared_next_frame: shared_next_frame.clone(),
-                scale_factor: scale_factor.clone(),
-                closed: closed.clone(),
-            };
+/// Stops a camera from being retargeted to an Adwaita window.
+///
+/// By default, cameras targeting [`WindowRef::Primary`] are made to render to
+/// the primary Adwaita window instead, since there is no primary Bevy
+/// [`Window`], and cameras targeting [`WindowRef::Entity`] of an
+/// [`AdwaitaWindow`] are made to render to that window. Add this to a camera
+/// which should keep its target, e.g. one rendering offscreen, or disable
+/// [`AdwaitaWindowPlugin::auto_retarget_cameras`] to opt out every camera.
+/// This must be inserted along with or before the [`Camera`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Component, Reflect)]
+#[reflect(Default, Component)]
+pub struct NoAdwaitaRetarget;
 
-            let manual_texture_views = world.resource::<ManualTextureViews>();
-            let render_target_handle = loop {
-                let handle = ManualTextureViewHandle(rand::random());
-                if !manual_texture_views.contains_key(&handle) {
-                    break handle;
-                }
-            };
+/// Summary of an open [`AdwaitaWindow`], as gathered by [`AdwaitaWindows`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct AdwaitaWindowInfo<'a> {
+    /// Entity of the [`AdwaitaWindow`].
+    pub entity: Entity,
+    /// [`AdwaitaWindowConfig::title`] of the window.
+    pub title: &'a str,
+    /// See [`AdwaitaRenderTargetSize`].
+    pub size: AdwaitaRenderTargetSize,
+    /// See [`AdwaitaWindowState`], which includes whether the window is
+    /// focused.
+    pub state: AdwaitaWindowState,
+    /// Whether this is the [`PrimaryAdwaitaWindow`].
+    pub primary: bool,
+    /// See [`AdwaitaWindow::is_ready`].
+    pub ready: bool,
+}
 
-            world.entity_mut(entity).insert(AdwaitaWindow {
-                send_command,
-                render_target_width,
-                render_target_height,
-                scale_factor,
-                shared_next_frame,
-                closed,
-                render_target_handle,
-                last_render_target_size: UVec2::new(0, 0),
-                next_frame_to_render: Arc::new(AtomicOptionBox::none()),
-            });
-            world
-                .resource::<SendWindowOpen>()
-                .0
-                .send(request)
-                .expect("Adwaita main thread dropped");
-        }
-    }
+/// Read-only view over all open [`AdwaitaWindow`]s, e.g. for building a
+/// windows menu or a debug overlay.
+#[derive(SystemParam)]
+pub struct AdwaitaWindows<'w, 's> {
+    windows: Query<
+        'w,
+        's,
+        (
+            Entity,
+            &'static AdwaitaWindow,
+            Option<&'static AdwaitaRenderTargetSize>,
+            Option<&'static AdwaitaWindowState>,
+            Has<PrimaryAdwaitaWindow>,
+        ),
+    >,
+}
 
-    #[must_use]
-    pub const fn render_target_handle(&self) -> ManualTextureViewHandle {
-        self.render_target_handle
+impl AdwaitaWindows<'_, '_> {
+    /// Iterates over every open window, in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = AdwaitaWindowInfo<'_>> {
+        self.windows.iter().filter_map(window_info)
     }
 
+    /// Gets the window on `entity`, if it's an open [`AdwaitaWindow`].
     #[must_use]
-    pub const fn render_target(&self) -> RenderTarget {
-        RenderTarget::TextureView(self.render_target_handle)
-    }
-
-    pub fn set_maximized(&self, maximized: bool) {
-        _ = self
-            .send_command
-            .send(WindowCommand::SetMaximized(maximized));
+    pub fn get(&self, entity: Entity) -> Option<AdwaitaWindowInfo<'_>> {
+        self.windows.get(entity).ok().and_then(window_info)
     }
+}
 
-    pub fn maximize(&self) {
-        self.set_maximized(true);
+fn window_info<'a>(
+    (entity, window, size, state, primary): (
+        Entity,
+        &'a AdwaitaWindow,
+        Option<&AdwaitaRenderTargetSize>,
+        Option<&AdwaitaWindowState>,
+        bool,
+    ),
+) -> Option<AdwaitaWindowInfo<'a>> {
+    if window.is_closed() {
+        return None;
     }
+    Some(AdwaitaWindowInfo {
+        entity,
+        title: &window.last_config.title,
+        size: size.copied().unwrap_or_default(),
+        state: state.copied().unwrap_or_default(),
+        primary,
+        ready: window.is_ready(),
+    })
+}
 
-    pub fn unmaximize(&self) {
-        self.set_maximized(false);
-    }
+/// Latest known position of the pointer over an [`AdwaitaWindow`].
+///
+/// This is inserted onto the window entity when it is opened, and updated
+/// every frame in [`PreUpdate`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Component, Reflect)]
+#[reflect(Default, Component)]
+pub struct AdwaitaCursorPosition {
+    /// Position of the pointer, in physical pixels relative to the top-left of
+    /// the render target, or [`None`] if the pointer is not over the render
+    /// target.
+    ///
+    /// This matches [`Window::physical_cursor_position`].
+    pub position: Option<Vec2>,
+    /// Last position that the pointer was seen at over the render target, even
+    /// if it has since left.
+    pub last_position: Vec2,
+}
 
-    pub fn set_fullscreen(&self, fullscreen: bool) {
-        _ = self
-            .send_command
-            .send(WindowCommand::SetFullscreen(fullscreen));
-    }
+/// Size of the render target of an [`AdwaitaWindow`], for laying out UI and
+/// computing aspect ratios.
+///
+/// This is inserted onto the window entity when it is opened, and updated
+/// every frame in [`PreUpdate`]. Until the window has been laid out by GTK,
+/// all sizes are zero.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Component, Reflect)]
+#[reflect(Default, Component)]
+pub struct AdwaitaRenderTargetSize {
+    /// Size of the currently allocated render target, in physical pixels.
+    ///
+    /// This includes [`AdwaitaWindowConfig::render_scale`], so it won't match
+    /// the widget's size in device pixels unless the render scale is `1.0`.
+    ///
+    /// Since reallocation is debounced (see
+    /// [`AdwaitaWindowConfig::resize_debounce`]), this may briefly lag
+    /// behind [`AdwaitaRenderTargetSize::logical`] while resizing.
+    pub physical: UVec2,
+    /// Size of the widget which the render target is presented in, in
+    /// logical pixels.
+    pub logical: UVec2,
+    /// Number of physical pixels per logical pixel.
+    pub scale_factor: f32,
+    /// Render scale currently in use, from
+    /// [`AdwaitaWindowConfig::render_scale`] or
+    /// [`AdwaitaWindowConfig::adaptive_render_scale`].
+    pub render_scale: f32,
+}
 
-    pub fn fullscreen(&self) {
-        self.set_fullscreen(true);
-    }
+/// Dmabuf which the render target of an [`AdwaitaWindow`] is exported as,
+/// e.g. to hand it to PipeWire or another process.
+///
+/// Obtained from [`AdwaitaWindow::dmabuf`].
+#[derive(Debug)]
+pub struct AdwaitaDmabuf {
+    /// Size of the buffer in pixels.
+    pub size: UVec2,
+    /// DRM fourcc code of the pixel format.
+    pub fourcc: u32,
+    /// DRM format modifier of the buffer's layout.
+    pub modifier: u64,
+    /// Offset of the first pixel in the buffer, in bytes.
+    pub offset: u32,
+    /// Bytes per row of pixels.
+    pub stride: u32,
+    /// Duplicate of the dmabuf fd, owned by the caller.
+    ///
+    /// Closing this doesn't affect the window. However, the buffer is only
+    /// rendered into until the render target is reallocated, e.g. when the
+    /// window is resized, after which [`AdwaitaWindow::dmabuf`] returns a new
+    /// buffer.
+    pub fd: OwnedFd,
+}
 
-    pub fn unfullscreen(&self) {
-        self.set_fullscreen(false);
-    }
+/// Statistics about the frames rendered to and presented by an
+/// [`AdwaitaWindow`].
+///
+/// This is inserted onto the window entity when it is opened, and updated
+/// every frame in [`PreUpdate`].
+#[derive(Debug, Clone, Default, PartialEq, Component, Reflect)]
+#[reflect(Default, Component)]
+pub struct AdwaitaFrameStats {
+    /// See [`AdwaitaWindow::produced_frame_id`].
+    pub produced_frame_id: u64,
+    /// See [`AdwaitaWindow::presented_frame_id`].
+    pub presented_frame_id: u64,
+    /// How long it took to read back the last frame into CPU memory, if the
+    /// window uses [`AdwaitaWindowConfig::force_software_present`].
+    pub software_readback_time: Option<Duration>,
+    /// How many frames per second Bevy has rendered to this window, averaged
+    /// over about the last second.
+    ///
+    /// Unlike Bevy's own frame time diagnostics, this only counts frames which
+    /// were actually handed off to GTK.
+    pub produced_frame_rate: f32,
+    /// How many frames per second GTK has presented in this window, averaged
+    /// over about the last second.
+    ///
+    /// This can't exceed the display's refresh rate, so with
+    /// [`AdwaitaFramePacing::Uncapped`] it's usually lower than
+    /// [`AdwaitaFrameStats::produced_frame_rate`].
+    pub presented_frame_rate: f32,
+    /// Timing of the latest frame which is known to have been shown on the
+    /// display.
+    ///
+    /// Every such frame is also sent as an [`AdwaitaFramePresented`] event.
+    pub last_presentation: Option<AdwaitaFramePresentation>,
+    /// How many times the render target has been allocated since the window
+    /// opened, including the first time.
+    ///
+    /// This should only grow once a live resize ends, not on every size change
+    /// during it - see [`AdwaitaWindowConfig::resize_debounce`].
+    pub render_target_allocations: u64,
+    #[reflect(ignore)]
+    frame_rate_sample: Option<FrameRateSample>,
+}
 
-    pub fn set_title(&self, title: impl Into<String>) {
-        let title = title.into();
-        _ = self.send_command.send(WindowCommand::SetTitle(title));
-    }
+/// When a frame rendered by Bevy was handed off to GTK, and when the
+/// compositor actually showed it on the display.
+///
+/// The difference between these is the latency added after rendering, and
+/// the difference between an input's timestamp and
+/// [`AdwaitaFramePresentation::presented_at`] is the full input-to-photon
+/// latency.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Reflect)]
+pub struct AdwaitaFramePresentation {
+    /// See [`AdwaitaWindow::produced_frame_id`].
+    pub frame_id: u64,
+    /// When Bevy finished rendering the frame and handed it off to GTK.
+    pub handed_off_at: Instant,
+    /// When the frame was shown on the display, as reported by the
+    /// compositor.
+    pub presented_at: Instant,
 }
 
-fn update_default_camera_render_target(
-    trigger: Trigger<OnInsert, Camera>,
-    mut cameras: Query<&mut Camera>,
-    primary_windows: Query<&AdwaitaWindow, With<PrimaryAdwaitaWindow>>,
-) {
-    let Ok(primary_window) = primary_windows.get_single() else {
-        return;
-    };
+/// Sent when a frame is known to have been shown on the display of an
+/// [`AdwaitaWindow`].
+///
+/// These arrive a few display refreshes after the frame was shown, once the
+/// compositor reports its timings. Compositors which don't report
+/// presentation times never send this. Frames which were dropped or only
+/// shown while another frame was also drawn are not reported.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Event)]
+pub struct AdwaitaFramePresented {
+    /// Entity of the [`AdwaitaWindow`] which presented the frame.
+    pub window: Entity,
+    pub presentation: AdwaitaFramePresentation,
+}
 
-    let entity = trigger.entity();
-    let mut camera = cameras
-        .get_mut(entity)
-        .expect("we are inserting this component into this entity");
+/// ID of a capture requested with [`AdwaitaWindow::request_capture`], whose
+/// image arrives later as an [`AdwaitaCaptureReady`] with the same ID.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Reflect)]
+pub struct AdwaitaCapture(u64);
 
-    if matches!(camera.target, RenderTarget::Window(WindowRef::Primary)) {
-        camera.target = primary_window.render_target();
-    }
+/// Sent when a capture requested with [`AdwaitaWindow::request_capture`] has
+/// been read back from the GPU.
+#[derive(Debug, Clone, Event)]
+pub struct AdwaitaCaptureReady {
+    /// Entity of the [`AdwaitaWindow`] which was captured.
+    pub window: Entity,
+    /// ID returned when the capture was requested.
+    pub id: AdwaitaCapture,
+    /// Contents of the render target, without any of the GTK widgets around
+    /// it.
+    pub image: Image,
 }
 
-fn update_existing_cameras_render_target(
-    trigger: Trigger<OnInsert, PrimaryAdwaitaWindow>,
-    windows: Query<&AdwaitaWindow>,
-    mut cameras: Query<&mut Camera>,
-) {
-    let entity = trigger.entity();
-    let window = windows.get(entity).unwrap_or_else(|_| {
-        panic!(
-            "inserting `{}` onto {entity} without `{}`",
-            type_name::<PrimaryAdwaitaWindow>(),
-            type_name::<AdwaitaWindow>()
-        )
-    });
+/// Frame IDs at the start of the interval which the frame rates in
+/// [`AdwaitaFrameStats`] are measured over.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct FrameRateSample {
+    at: Instant,
+    produced_frame_id: u64,
+    presented_frame_id: u64,
+}
 
-    for mut camera in &mut cameras {
-        if matches!(camera.target, RenderTarget::Window(WindowRef::Primary)) {
-            camera.target = window.render_target();
-        }
-    }
+/// State of an [`AdwaitaWindow`] as reported by GTK.
+///
+/// This is inserted onto the window entity when it is opened, and updated in
+/// [`PreUpdate`] whenever GTK reports a change, at which point an
+/// [`AdwaitaWindowStateChanged`] event is also sent.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Component, Reflect)]
+#[reflect(Default, Component)]
+pub struct AdwaitaWindowState {
+    /// Whether the window is mapped, i.e. shown on screen.
+    pub mapped: bool,
+    /// Whether the window is minimized.
+    ///
+    /// Not all compositors report this; notably, Wayland compositors usually
+    /// don't, in which case see [`AdwaitaWindowState::suspended`].
+    pub minimized: bool,
+    /// Whether the compositor has suspended the window because it is not
+    /// visible, e.g. because it is fully occluded or on another workspace.
+    pub suspended: bool,
+    /// Whether the window has keyboard focus.
+    ///
+    /// Changes to this are also sent as [`WindowFocused`].
+    pub focused: bool,
+    pub fullscreen: bool,
+    pub maximized: bool,
 }
 
-fn exit_on_primary_closed(
-    mut app_exit_events: EventWriter<AppExit>,
-    windows: Query<(), (With<AdwaitaWindow>, With<PrimaryAdwaitaWindow>)>,
-) {
-    if windows.is_empty() {
-        info!("Primary Adwaita window was closed, exiting");
-        app_exit_events.send(AppExit::Success);
+impl AdwaitaWindowState {
+    /// Whether any part of the window may currently be visible to the user.
+    #[must_use]
+    pub fn is_visible(&self) -> bool {
+        self.mapped && !self.minimized && !self.suspended
     }
 }
 
-fn exit_on_all_closed(
-    mut app_exit_events: EventWriter<AppExit>,
-    windows: Query<(), With<AdwaitaWindow>>,
-) {
-    if windows.is_empty() {
-        info!("No Adwaita windows are open, exiting");
-        app_exit_events.send(AppExit::Success);
-    }
+/// Insets from each edge of the render target of an [`AdwaitaWindow`] which
+/// are covered by window chrome, in logical pixels.
+///
+/// UI which must stay visible and clickable should be laid out inside these
+/// insets, e.g. by using them as the padding of the root UI node. Only chrome
+/// drawn over the render target counts, such as the window controls of an
+/// [`AdwaitaHeaderBar::OverContent`] header bar and a sidebar while it's
+/// shown. An [`AdwaitaHeaderBar::Full`] header bar sits above the render
+/// target, so it doesn't count.
+///
+/// This is inserted onto the window entity when it is opened, and updated in
+/// [`PreUpdate`] whenever the chrome moves or is shown or hidden, at which
+/// point an [`AdwaitaSafeAreaChanged`] event is also sent.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Component, Reflect)]
+#[reflect(Default, Component)]
+pub struct AdwaitaSafeArea {
+    pub top: f32,
+    pub bottom: f32,
+    pub left: f32,
+    pub right: f32,
 }
 
-fn poll_windows(
-    mut commands: Commands,
-    mut windows: Query<(Entity, &mut AdwaitaWindow)>,
-    render_device: Res<RenderDevice>,
-    mut manual_texture_views: ResMut<ManualTextureViews>,
-) {
-    for (entity, mut window) in &mut windows {
-        if window.closed.load(Ordering::SeqCst) {
-            info!("Adwaita window {entity} closed");
-            commands.entity(entity).despawn_recursive();
-            continue;
-        }
+/// Sent when the [`AdwaitaSafeArea`] of a window changes.
+#[derive(Debug, Clone, Copy, PartialEq, Event)]
+pub struct AdwaitaSafeAreaChanged {
+    /// Entity of the [`AdwaitaWindow`] whose safe area changed.
+    pub window: Entity,
+    pub safe_area: AdwaitaSafeArea,
+}
 
-        let (width, height, scale_factor) = (
-            window.render_target_width.load(Ordering::SeqCst),
-            window.render_target_height.load(Ordering::SeqCst),
-            window.scale_factor.load(Ordering::SeqCst),
-        );
-        let (Ok(width), Ok(height), Ok(scale_factor)) = (
-            u32::try_from(width),
-            u32::try_from(height),
-            u32::try_from(scale_factor),
-        ) else {
-            continue;
-        };
+/// Physical properties of the monitor which an [`AdwaitaWindow`] is on, for
+/// rendering in real-world units, e.g. in CAD or design apps.
+///
+/// This is inserted onto the window entity when it is opened, and updated in
+/// [`PreUpdate`] whenever the window moves onto another monitor, at which
+/// point an [`AdwaitaMonitorChanged`] event is also sent. If the window spans
+/// several monitors, this is the one it most recently moved onto. Until the
+/// window is first shown on a monitor, this is the default.
+#[derive(Debug, Clone, Default, PartialEq, Component, Reflect)]
+#[reflect(Default, Component)]
+pub struct AdwaitaMonitor {
+    /// Name of the connector the monitor is plugged into, e.g. `DP-1`, if
+    /// known.
+    pub connector: Option<String>,
+    /// Size of the monitor in logical pixels.
+    pub logical_size: UVec2,
+    /// Scale factor of the monitor, which may be fractional.
+    pub scale_factor: f32,
+    /// Physical size of the monitor in millimeters, if it's known.
+    ///
+    /// This comes from the monitor's EDID, so some monitors, projectors and
+    /// virtual machines don't report it, or report a wrong size.
+    pub physical_size_mm: Option<UVec2>,
+}
 
-        let size = UVec2::new(width.max(1) * scale_factor, height.max(1) * scale_factor);
-        if size == window.last_render_target_size {
-            continue;
+impl AdwaitaMonitor {
+    /// Gets how many physical pixels fit into a millimeter along each axis,
+    /// if the physical size is known.
+    ///
+    /// This is independent of [`AdwaitaMonitor::scale_factor`]: it's the
+    /// density of the hardware pixels themselves.
+    #[must_use]
+    pub fn pixels_per_mm(&self) -> Option<Vec2> {
+        let physical_size_mm = self.physical_size_mm?;
+        let pixels = self.logical_size.as_vec2() * self.scale_factor;
+        if pixels.cmple(Vec2::ZERO).any() {
+            return None;
         }
-        info!("Window resized to {size}");
-        window.last_render_target_size = size;
+        Some(pixels / physical_size_mm.as_vec2())
+    }
 
-        let (manual_texture_view, dmabuf_fd) =
-            render::setup_render_target(size, render_device.as_ref());
-        // give a shared ref of this texture view to the Adwaita app
-        // so that, even if *we* drop it while the window is rendering this frame,
-        // the GPU resources won't be deallocated until the window *also* drops it
-        let texture_view = manual_texture_view.texture_view.clone();
-        manual_texture_views.insert(window.render_target_handle.clone(), manual_texture_view);
-        let next_frame_info = FrameInfo {
-            dmabuf: DmabufInfo {
-                size,
-                fd: dmabuf_fd,
-            },
-            _texture_view: texture_view,
-        };
-        info!("Stored next frame info {next_frame_info:?}");
-        window
-            .next_frame_to_render
-            .store(Some(Box::new(next_frame_info)), Ordering::SeqCst);
+    /// Gets the monitor's physical pixels per inch along each axis, if the
+    /// physical size is known.
+    #[must_use]
+    pub fn dpi(&self) -> Option<Vec2> {
+        const MM_PER_INCH: f32 = 25.4;
+        self.pixels_per_mm()
+            .map(|pixels_per_mm| pixels_per_mm * MM_PER_INCH)
     }
 }
 
-#[derive(Debug, Component)]
-struct RenderWindow {
-    shared_next_frame: Arc<AtomicOptionBox<FrameInfo>>,
-    next_frame_to_render: Arc<AtomicOptionBox<FrameInfo>>,
-    next_frame_to_send: Option<Box<FrameInfo>>,
+/// Sent when an [`AdwaitaWindow`] moves onto another monitor.
+#[derive(Debug, Clone, PartialEq, Event)]
+pub struct AdwaitaMonitorChanged {
+    /// Entity of the [`AdwaitaWindow`] which moved.
+    pub window: Entity,
+    pub monitor: AdwaitaMonitor,
 }
 
-fn extract_windows(mut commands: Commands, windows: Extract<Query<&AdwaitaWindow>>) {
-    info!("-- RUNNING extract_windows");
-    for window in &windows {
-        let Some(next_frame_to_send) = window.next_frame_to_render.take(Ordering::SeqCst) else {
-            continue;
-        };
-        info!("--extract: Got next frame info {next_frame_to_send:?}");
+/// Sent once when an [`AdwaitaWindow`]'s render target is first allocated
+/// with a valid size.
+///
+/// Opening a window is asynchronous, so cameras spawned in the same frame
+/// may render before there's anything to render into. Spawn cameras and UI
+/// in a system which runs on this event instead, e.g. with
+/// `run_if(on_event::<AdwaitaWindowReady>())`, or check
+/// [`AdwaitaWindow::is_ready`].
+///
+/// Windows which mirror another window never become ready, since they don't
+/// have a render target of their own.
+#[derive(Debug, Clone, Event)]
+pub struct AdwaitaWindowReady {
+    /// Entity of the [`AdwaitaWindow`] which is ready.
+    pub window: Entity,
+}
 
-        commands.spawn(RenderWindow {
-            shared_next_frame: window.shared_next_frame.clone(),
-            next_frame_to_render: window.next_frame_to_render.clone(),
-            next_frame_to_send: Some(next_frame_to_send),
-        });
-    }
+/// Sent when the user asks to close a window with
+/// [`AdwaitaWindowConfig::intercept_close`], which is kept open until
+/// [`AdwaitaWindow::confirm_close`] is called.
+#[derive(Debug, Clone, Event)]
+pub struct AdwaitaCloseRequested {
+    /// Entity of the [`AdwaitaWindow`] which the user asked to close.
+    pub window: Entity,
 }
 
-fn send_frame_to_windows(mut windows: Query<&mut RenderWindow>) {
-    info!("-- RUNNING send_frame_info_to_windows");
-    for mut window in &mut windows {
-        let Some(next_frame_info) = window.next_frame_to_send.take() else {
-            continue;
-        };
+/// Sent when the [`AdwaitaWindowState`] of a window changes.
+#[derive(Debug, Clone, Event)]
+pub struct AdwaitaWindowStateChanged {
+    /// Entity of the [`AdwaitaWindow`] whose state changed.
+    pub window: Entity,
+    /// State of the window before the change.
+    pub old: AdwaitaWindowState,
+    /// State of the window after the change.
+    pub new: AdwaitaWindowState,
+}
 
-        info!("Sending next frame {next_frame_info:?} now.");
-        window
-            .shared_next_frame
-            .store(Some(next_frame_info), Ordering::SeqCst);
-    }
+/// Sent when the sidebar of a window opened with
+/// [`AdwaitaWindow::open_with_sidebar`] is shown or hidden, either by the user
+/// or by [`AdwaitaWindow::set_sidebar_shown`].
+#[derive(Debug, Clone, Event)]
+pub struct AdwaitaSidebarToggled {
+    /// Entity of the [`AdwaitaWindow`] whose sidebar was toggled.
+    pub window: Entity,
+    /// Whether the sidebar is now shown.
+    pub shown: bool,
 }
 
-fn put_back_next_frame_if_not_sent(mut windows: Query<&mut RenderWindow>) {
-    for mut window in &mut windows {
-        if let Some(frame_info) = window.next_frame_to_send.take() {
+/// Sent for every key press and release in a window with
+/// [`AdwaitaWindowConfig::emit_raw_keys`] enabled, carrying the key exactly as
+/// GDK reports it.
+///
+/// This is an escape hatch for apps which need to handle custom keyboard
+/// layouts or exotic bindings themselves. By default, keys are sent before
+/// GTK handles them, so they are sent even if a focused GTK widget, e.g. a
+/// text entry in an overlay, or the input method consumes them. See
+/// [`AdwaitaWindowConfig::raw_key_filter`] to change this. For text input,
+/// use [`AdwaitaTextInput`] instead.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Event)]
+pub struct AdwaitaRawKey {
+    /// Entity of the [`AdwaitaWindow`] which received the key.
+    pub window: Entity,
+    /// Whether the key was pressed, or released.
+    pub pressed: bool,
+    /// GDK keyval, i.e. the symbol which the key produces in the current
+    /// layout and group, with modifiers applied.
+    ///
+    /// Convert this to a [`gdk::Key`] with `from_glib` to get its name or
+    /// Unicode character.
+    ///
+    /// [`gdk::Key`]: gtk::gdk::Key
+    pub keyval: u32,
+    /// Hardware keycode of the key, which doesn't depend on the layout.
+    pub keycode: u32,
+    /// Keyboard layout group which the keyval was resolved in.
+    pub group: u32,
+    /// Bits of the [`gdk::ModifierType`] held when the key was pressed.
+    ///
+    /// [`gdk::ModifierType`]: gtk::gdk::ModifierType
+    pub modifiers: u32,
+}
+
+/// Sent when text is typed into a window with
+/// [`AdwaitaWindowConfig::text_input`] enabled.
+///
+/// Text goes through the system input method, so dead keys, compose sequences
+/// and IMEs work as the user expects, and only the final committed text is
+/// sent. For example, typing a dead acute accent then `e` sends a single
+/// `"é"`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Event)]
+pub struct AdwaitaTextInput {
+    /// Entity of the [`AdwaitaWindow`] which the text was typed into.
+    pub window: Entity,
+    /// Text committed by the input method.
+    pub text: String,
+}
+
+/// Sent when the render target of a window with
+/// [`AdwaitaWindowConfig::primary_paste`] is middle-clicked while the primary
+/// selection contains text.
+///
+/// The primary selection is whatever text was last selected in any app, and
+/// is separate from the clipboard. If it's empty or doesn't contain text, no
+/// event is sent.
+#[derive(Debug, Clone, PartialEq, Event)]
+pub struct AdwaitaPrimaryPaste {
+    /// Entity of the [`AdwaitaWindow`] which was middle-clicked.
+    pub window: Entity,
+    /// Position of the click, in physical pixels relative to the top-left of
+    /// the render target, as in [`AdwaitaCursorPosition`].
+    pub position: Vec2,
+    /// Text in the primary selection.
+    pub text: String,
+}
+
+/// What to show in an about window, shown with [`AdwaitaWindow::show_about`].
+///
+/// Empty fields aren't shown.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AdwaitaAboutConfig {
+    /// Name of the app. If [`None`], [`AdwaitaWindowConfig::app_name`] or
+    /// else the window's title is used.
+    pub application_name: Option<String>,
+    /// Name of the icon shown above the app's name, e.g. the app's ID.
+    pub application_icon: Option<String>,
+    /// Version of the app, e.g. `1.2.0`.
+    pub version: String,
+    /// Name of the developer or team, shown under the app's name.
+    pub developer_name: String,
+    /// Short description of the app.
+    pub comments: String,
+    /// URL of the app's website.
+    pub website: String,
+    /// URL where users can report issues.
+    pub issue_url: String,
+    /// Copyright notice, e.g. `© 2024 Jane Doe`.
+    pub copyright: String,
+    /// License which the app is released under.
+    pub license_type: Option<gtk::License>,
+    /// Names of the app's developers, each optionally followed by an email
+    /// address or URL in angle brackets, e.g. `Jane Doe <jane@example.com>`.
+    pub developers: Vec<String>,
+}
+
+/// Content which is dragged out of a window with
+/// [`AdwaitaWindow::start_drag`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum AdwaitaDragContent {
+    /// Plain text.
+    Text(String),
+    /// Files or other resources, e.g. `file:///home/user/export.png`, which
+    /// file managers accept as a file drop.
+    Uris(Vec<String>),
+    /// Arbitrary data in a custom format.
+    Bytes {
+        /// MIME type which the receiving app must accept, e.g.
+        /// `application/x-my-app-item`.
+        mime_type: String,
+        data: Vec<u8>,
+    },
+}
+
+/// Sent when files are dropped onto the render target of a window with
+/// [`AdwaitaWindowConfig::file_drop`].
+#[derive(Debug, Clone, PartialEq, Event)]
+pub struct AdwaitaFilesDropped {
+    /// Entity of the [`AdwaitaWindow`] which the files were dropped onto.
+    pub window: Entity,
+    /// Local paths of the dropped files, in the order they were dragged.
+    ///
+    /// Files without a local path, e.g. on a remote location which isn't
+    /// mounted, are left out. If none of the files have one, no event is sent.
+    pub paths: Vec<PathBuf>,
+    /// Position of the drop, in physical pixels relative to the top-left of
+    /// the render target, as in [`AdwaitaCursorPosition`].
+    pub position: Vec2,
+}
+
+/// Sent when a drag started with [`AdwaitaWindow::start_drag`] ends.
+#[derive(Debug, Clone, PartialEq, Event)]
+pub struct AdwaitaDragEnded {
+    /// Entity of the [`AdwaitaWindow`] which the drag started from.
+    pub window: Entity,
+    /// Whether the content was dropped somewhere which accepted it. If
+    /// [`false`], the drag was cancelled, e.g. by pressing `Escape` or
+    /// dropping it where it isn't accepted, or couldn't be started at all.
+    pub accepted: bool,
+}
+
+/// Sent for input from a drawing tablet's stylus over the render target of a
+/// window with [`AdwaitaWindowConfig::stylus_input`].
+///
+/// The same input is also sent as ordinary cursor and mouse button input,
+/// which loses everything but the position.
+#[derive(Debug, Clone, PartialEq, Event)]
+pub struct AdwaitaStylus {
+    /// Entity of the [`AdwaitaWindow`] which the stylus is over.
+    pub window: Entity,
+    /// Position of the stylus, in physical pixels relative to the top-left of
+    /// the render target, as in [`AdwaitaCursorPosition`].
+    pub position: Vec2,
+    pub phase: AdwaitaStylusPhase,
+    /// How hard the stylus is pressed down, from `0.0` to `1.0`.
+    ///
+    /// If the tablet doesn't report pressure, this is `1.0` while the stylus
+    /// touches the tablet and `0.0` otherwise.
+    pub pressure: f32,
+    /// How far the stylus is tilted towards the right and bottom of the
+    /// tablet, from `-1.0` to `1.0` on each axis.
+    ///
+    /// If the tablet doesn't report tilt, this is zero.
+    pub tilt: Vec2,
+    /// Button which is held down, numbered like mouse buttons, i.e. `1` for
+    /// the tip and usually `2` and `3` for the barrel buttons.
+    pub button: Option<u32>,
+    /// Whether the eraser end of the stylus is being used.
+    pub eraser: bool,
+}
+
+/// What the stylus did in an [`AdwaitaStylus`] event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AdwaitaStylusPhase {
+    /// The stylus moved while hovering over the tablet, without touching it.
+    Hover,
+    /// The stylus started touching the tablet, or a button was pressed.
+    Down,
+    /// The stylus moved while touching the tablet, or with a button held.
+    Move,
+    /// The stylus stopped touching the tablet, or a button was released.
+    Up,
+}
+
+/// Sent when GTK recognizes a gesture on the render target of a window with
+/// [`AdwaitaWindowConfig::pointer_gestures`].
+///
+/// These are distinct from raw touch input, and from pinch and rotate
+/// gestures.
+#[derive(Debug, Clone, PartialEq, Event)]
+pub struct AdwaitaPointerGesture {
+    /// Entity of the [`AdwaitaWindow`] which the gesture happened in.
+    pub window: Entity,
+    /// Position of the gesture, in physical pixels relative to the top-left
+    /// of the render target, as in [`AdwaitaCursorPosition`].
+    pub position: Vec2,
+    pub kind: AdwaitaPointerGestureKind,
+}
+
+/// What kind of [`AdwaitaPointerGesture`] was recognized.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AdwaitaPointerGestureKind {
+    /// The secondary (usually right) mouse button was clicked.
+    ///
+    /// With tap-to-click enabled, tapping a touchpad with two fingers is
+    /// turned into a secondary click by the system, so this is also sent for
+    /// those. Treat this like a right mouse button press.
+    SecondaryClick,
+    /// A finger was held on a touchscreen, or the primary button was held on
+    /// a mouse or touchpad, without moving. This is where touch-friendly apps
+    /// show a context menu.
+    ///
+    /// If the pointer moves too far before the hold is recognized, nothing is
+    /// sent.
+    ContextMenu,
+}
+
+/// Capabilities of the display which Adwaita windows are shown on.
+///
+/// This resource is inserted once the GTK side has connected to the display,
+/// which is shortly after startup, so it may not exist in the first few
+/// frames.
+#[derive(Debug, Clone, PartialEq, Eq, Resource)]
+pub struct AdwaitaDisplayCaps {
+    /// Windowing system which the display belongs to.
+    pub backend: AdwaitaDisplayBackend,
+    /// Whether the display can import dmabufs at all.
+    pub dmabuf: bool,
+    /// Whether the display can import dmabufs in the format and modifier that
+    /// render targets are exported in.
+    ///
+    /// If this is `false`, windows will likely stay black.
+    pub dmabuf_format_supported: bool,
+    /// DRM format modifiers which the display can import render targets
+    /// with.
+    ///
+    /// Render targets use one of these which isn't linear, if the GPU also
+    /// supports it. See [`AdwaitaWindowConfig::force_linear_dmabuf`].
+    pub dmabuf_modifiers: Vec<u64>,
+    /// Whether a compositing manager is running.
+    pub composited: bool,
+    /// Whether windows can use an alpha channel.
+    pub rgba: bool,
+}
+
+/// Windowing system which a display belongs to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Reflect)]
+pub enum AdwaitaDisplayBackend {
+    Wayland,
+    X11,
+    Other,
+}
+
+/// Error which can occur when sending a command to an [`AdwaitaWindow`], e.g.
+/// with [`AdwaitaWindow::send_command`] or [`AdwaitaWindow::set_title`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, thiserror::Error)]
+pub enum AdwaitaCommandError {
+    /// The window has closed, and will be despawned soon.
+    #[error("window is closed")]
+    Disconnected,
+    /// The GTK thread hasn't kept up with the commands sent to this window.
+    #[error("too many commands are waiting to be applied")]
+    Full,
+    /// A [`AdwaitaWindowCommand::SetSize`] had a component which doesn't fit
+    /// into an `i32`, which is what GTK takes sizes as.
+    #[error("size {0} is too large for GTK")]
+    SizeTooLarge(UVec2),
+}
+
+/// Sent when an operation on the GTK side of an [`AdwaitaWindow`] fails.
+///
+/// These failures are also logged, but this lets the app react to them, e.g.
+/// by showing a message when the window would otherwise stay black.
+#[derive(Debug, Clone, Event)]
+pub struct AdwaitaError {
+    /// Entity of the [`AdwaitaWindow`] which the error occurred in.
+    pub window: Entity,
+    /// Name of the operation which failed, e.g. `import dmabuf texture`.
+    pub operation: &'static str,
+    /// Error message reported by GLib.
+    pub message: String,
+}
+
+/// Sent when an [`AdwaitaWindow`] fails in a way that it can't present
+/// frames as requested, instead of panicking.
+///
+/// The error is also logged. The rest of the app keeps running, so you can
+/// react to this e.g. by opening a regular [`Window`](bevy::window::Window)
+/// instead.
+#[derive(Debug, Clone, PartialEq, Eq, Event)]
+pub struct AdwaitaWindowError {
+    /// Entity of the [`AdwaitaWindow`] which failed.
+    pub window: Entity,
+    pub kind: AdwaitaWindowErrorKind,
+}
+
+/// Way in which an [`AdwaitaWindow`] can fail, as sent in
+/// [`AdwaitaWindowError`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash, thiserror::Error)]
+pub enum AdwaitaWindowErrorKind {
+    /// The GTK thread isn't running, e.g. because Adwaita failed to
+    /// initialize, so the window couldn't be opened.
+    ///
+    /// The window is despawned like any other closed window.
+    #[error("Adwaita main thread is not running")]
+    ChannelClosed,
+    /// The render device can't export render targets as dmabufs, e.g.
+    /// because it isn't a Vulkan device.
+    ///
+    /// The window falls back to software present.
+    #[error("render device can't share render targets with GTK via dmabuf")]
+    DmabufUnsupported,
+    /// The driver failed to allocate or export a render target.
+    ///
+    /// The window falls back to software present.
+    #[error("failed to create render target: {0}")]
+    TextureCreation(String),
+}
+
+/// Light or dark color scheme of the Adwaita widgets in all windows.
+///
+/// Change [`AdwaitaColorScheme::preference`] to override the system's
+/// scheme, e.g. to match an in-app setting. The other fields are updated from
+/// GTK, and report the scheme that is actually in effect. Note that this
+/// applies to the whole application, not to individual windows.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Resource, Reflect)]
+#[reflect(Default, Resource)]
+pub struct AdwaitaColorScheme {
+    pub preference: AdwaitaColorSchemePreference,
+    /// Whether widgets are currently drawn with the dark scheme.
+    ///
+    /// With [`AdwaitaColorSchemePreference::FollowSystem`], this is whether
+    /// the system prefers the dark scheme.
+    pub dark: bool,
+    /// Whether the system reports a preferred color scheme at all.
+    ///
+    /// If not, e.g. because the settings portal isn't running, following the
+    /// system uses [`AdwaitaWindowPlugin::color_scheme_fallback`] instead, so
+    /// [`AdwaitaColorScheme::dark`] doesn't reflect an actual preference of
+    /// the user.
+    pub system_supported: bool,
+}
+
+/// Sent when [`AdwaitaColorScheme::dark`] changes, e.g. because the user
+/// switched the system to the dark style, so that the app can switch its own
+/// theme and clear color to match.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Event)]
+pub struct AdwaitaColorSchemeChanged {
+    /// Whether widgets are now drawn with the dark scheme.
+    pub dark: bool,
+}
+
+/// User preferences from the system's GTK settings, which UI in the Bevy app
+/// should respect instead of hardcoding its own values.
+///
+/// This is updated from GTK whenever any of the settings change, at which
+/// point an [`AdwaitaSystemSettingsChanged`] event is also sent. Until GTK
+/// reports the initial settings, this holds GTK's defaults.
+#[derive(Debug, Clone, Copy, PartialEq, Resource, Reflect)]
+#[reflect(Default, Resource)]
+pub struct AdwaitaSystemSettings {
+    /// Maximum time between two clicks for them to count as a double click.
+    pub double_click_time: Duration,
+    /// Maximum distance in logical pixels between two clicks for them to count
+    /// as a double click.
+    pub double_click_distance: u32,
+    /// Distance in logical pixels which the cursor must move with a button
+    /// held down before it counts as a drag.
+    pub drag_threshold: u32,
+    /// Whether the text cursor should blink.
+    pub cursor_blink: bool,
+    /// Length of one full blink cycle of the text cursor.
+    pub cursor_blink_time: Duration,
+    /// How long the text cursor blinks for after the last user input, before
+    /// it stays solid.
+    pub cursor_blink_timeout: Duration,
+    /// Factor to scale font sizes by, from the user's text scaling setting.
+    pub text_scale: f32,
+}
+
+impl Default for AdwaitaSystemSettings {
+    fn default() -> Self {
+        Self {
+            double_click_time: Duration::from_millis(400),
+            double_click_distance: 5,
+            drag_threshold: 8,
+            cursor_blink: true,
+            cursor_blink_time: Duration::from_millis(1200),
+            cursor_blink_timeout: Duration::from_secs(10),
+            text_scale: 1.0,
+        }
+    }
+}
+
+/// Whether the user has asked for reduced motion, e.g. with GNOME's "Reduce
+/// Animation" accessibility setting.
+///
+/// When this is set, the app should avoid heavy motion such as camera shake,
+/// screen transitions and parallax. This is updated from GTK whenever the
+/// setting changes, at which point an [`AdwaitaReducedMotionChanged`] event
+/// is also sent.
+///
+/// The crate's own animations, such as
+/// [`AdwaitaWindowConfig::fade_transition`] and the search bar reveal, are
+/// skipped while this is set.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Resource, Reflect)]
+#[reflect(Default, Resource)]
+pub struct AdwaitaReducedMotion {
+    /// Whether motion should be reduced.
+    pub reduced: bool,
+}
+
+/// Sent when [`AdwaitaReducedMotion`] changes.
+#[derive(Debug, Clone, Event)]
+pub struct AdwaitaReducedMotionChanged {
+    /// Whether motion should now be reduced.
+    pub reduced: bool,
+}
+
+/// Sent when [`AdwaitaSystemSettings`] changes.
+#[derive(Debug, Clone, Event)]
+pub struct AdwaitaSystemSettingsChanged {
+    /// Settings before the change.
+    pub old: AdwaitaSystemSettings,
+    /// Settings after the change.
+    pub new: AdwaitaSystemSettings,
+}
+
+/// Which color scheme Adwaita widgets should use.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Reflect)]
+#[reflect(Default)]
+pub enum AdwaitaColorSchemePreference {
+    /// Use whichever scheme the system prefers.
+    #[default]
+    FollowSystem,
+    /// Always use the light scheme.
+    ForceLight,
+    /// Always use the dark scheme.
+    ForceDark,
+}
+
+/// Sent when the connection to the display (i.e. the Wayland compositor) is
+/// lost.
+///
+/// All Adwaita windows are closed when this happens, and no new windows can be
+/// opened afterwards. Depending on [`AdwaitaWindowPlugin::exit_condition`],
+/// the app may exit as a result of all windows closing.
+#[derive(Debug, Clone, Event)]
+pub struct AdwaitaDisplayLost;
+
+/// Sent once when the GPU device used for rendering is lost, e.g. because
+/// the driver reset or the GPU didn't come back properly after a suspend.
+///
+/// wgpu can't recover a lost device, and Bevy can't replace its render device
+/// while running, so nothing is rendered after this. Instead of handing GTK
+/// frames which may be garbage, every Adwaita window stops presenting and
+/// shows its backdrop. Apps should usually save what they can and restart.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Event)]
+pub struct AdwaitaGpuLost {
+    /// Why the device was lost, as reported by the driver.
+    pub message: String,
+}
+
+/// Send this to open the GTK Inspector, or bring it to the front if it's
+/// already open.
+///
+/// See [`AdwaitaWindowPlugin::open_inspector`].
+#[derive(Debug, Clone, Default, Event)]
+pub struct AdwaitaOpenInspector;
+
+/// Key combination in the format of a GTK accelerator, e.g. `<Control>q` or
+/// `<Shift><Alt>F1`.
+///
+/// See [`gtk::accelerator_parse`] for the full syntax. Invalid combinations
+/// are logged and ignored when the [`AdwaitaKeymap`] is installed.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Reflect)]
+pub struct AdwaitaKeyCombo(pub String);
+
+impl AdwaitaKeyCombo {
+    #[must_use]
+    pub fn new(accelerator: impl Into<String>) -> Self {
+        Self(accelerator.into())
+    }
+}
+
+/// Keyboard shortcuts for the whole application, mapping key combinations to
+/// action IDs.
+///
+/// Whenever this resource is inserted or changed, all of its bindings are
+/// installed as accelerators on the GTK application, replacing the previously
+/// installed keymap. When a shortcut is pressed in any Adwaita window, an
+/// [`AdwaitaActionActivated`] event is sent with its action ID.
+///
+/// Action IDs must be valid [`gio::Action`] names, e.g. `quit` or
+/// `toggle-fullscreen`. Removing this resource does not uninstall the
+/// accelerators; clear the bindings instead.
+#[derive(Debug, Clone, Default, Resource, Reflect)]
+#[reflect(Default, Resource)]
+pub struct AdwaitaKeymap {
+    pub bindings: HashMap<AdwaitaKeyCombo, String>,
+}
+
+impl AdwaitaKeymap {
+    /// Binds `combo` to activate `action`, replacing any previous binding of
+    /// `combo`.
+    pub fn bind(&mut self, combo: AdwaitaKeyCombo, action: impl Into<String>) -> &mut Self {
+        self.bindings.insert(combo, action.into());
+        self
+    }
+}
+
+/// Sent when an action is activated, either by a shortcut in the
+/// [`AdwaitaKeymap`] or by a button in an [`AdwaitaWindowConfig::action_bar`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Event)]
+pub struct AdwaitaActionActivated {
+    /// Entity of the [`AdwaitaWindow`] whose button was clicked, or [`None`]
+    /// if the action was activated for the whole application by a shortcut.
+    pub window: Option<Entity>,
+    /// ID of the action, without the `app.` prefix.
+    pub action: String,
+}
+
+/// ID of a request made through [`AdwaitaRequests`], whose result arrives
+/// later as an [`AdwaitaResponse`] with the same ID.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Reflect)]
+pub struct AdwaitaRequest(u64);
+
+/// Sent when the GTK side finishes a request made through
+/// [`AdwaitaRequests`].
+///
+/// Exactly one response is sent for every request, even if the request fails
+/// or the GTK side has already shut down.
+#[derive(Debug, Clone, PartialEq, Eq, Event)]
+pub struct AdwaitaResponse {
+    /// ID returned when the request was made.
+    pub id: AdwaitaRequest,
+    /// Result of the request, or why it failed.
+    pub payload: Result<AdwaitaResponsePayload, String>,
+}
+
+/// Result of a successful request, depending on which kind of request was
+/// made.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AdwaitaResponsePayload {
+    /// Text on the clipboard, from [`AdwaitaRequests::read_clipboard_text`],
+    /// or [`None`] if the clipboard doesn't contain text.
+    ClipboardText(Option<String>),
+    /// Index of the workspace a window is on, from
+    /// [`AdwaitaRequests::window_workspace`].
+    Workspace(u32),
+    /// File picked by the user, from [`AdwaitaRequests::open_file`] or
+    /// [`AdwaitaRequests::save_file`], or [`None`] if they cancelled the
+    /// dialog.
+    ///
+    /// This is also [`None`] if the file has no local path, e.g. if it's on a
+    /// remote location which isn't mounted.
+    File(Option<PathBuf>),
+}
+
+/// Native file chooser dialog, shown with [`AdwaitaRequests::open_file`] or
+/// [`AdwaitaRequests::save_file`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AdwaitaFileDialog {
+    /// Title of the dialog, or GTK's default if empty.
+    pub title: String,
+    /// Filters which the user can pick between to narrow down the shown
+    /// files, starting with the first one. If empty, all files are shown.
+    pub filters: Vec<AdwaitaFileFilter>,
+    /// Name which saved files start out with, e.g. `Untitled.level`. This is
+    /// ignored when opening files.
+    pub suggested_name: Option<String>,
+}
+
+/// Filter in an [`AdwaitaFileDialog`], showing files which match any of its
+/// patterns or MIME types.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AdwaitaFileFilter {
+    /// Name of the filter shown to the user, e.g. `Images`.
+    pub name: String,
+    /// Glob patterns of file names, e.g. `*.png`.
+    pub patterns: Vec<String>,
+    /// MIME types, e.g. `image/*`.
+    pub mime_types: Vec<String>,
+}
+
+/// Makes requests to the GTK side whose results aren't available
+/// immediately, such as reading the clipboard.
+///
+/// Each request returns an [`AdwaitaRequest`] ID straight away, and its result
+/// is later sent as an [`AdwaitaResponse`] event with the same ID.
+#[derive(Debug, Resource)]
+pub struct AdwaitaRequests {
+    next_id: AtomicU64,
+    send_app_command: flume::Sender<AppCommand>,
+    // loops failures back to `forward_app_events`, so that they're reported
+    // like any other response
+    send_app_event: flume::Sender<AppEvent>,
+}
+
+impl AdwaitaRequests {
+    /// Reads the text currently on the clipboard, responding with
+    /// [`AdwaitaResponsePayload::ClipboardText`].
+    pub fn read_clipboard_text(&self) -> AdwaitaRequest {
+        self.send(AppRequest::ReadClipboardText)
+    }
+
+    /// Finds which workspace (virtual desktop) an open window is on,
+    /// responding with [`AdwaitaResponsePayload::Workspace`].
+    ///
+    /// This uses the EWMH `_NET_WM_DESKTOP` property, so it's only supported
+    /// on X11 with the `x11` feature enabled, and fails elsewhere. See
+    /// [`AdwaitaWindow::move_to_workspace`].
+    pub fn window_workspace(&self, window: Entity) -> AdwaitaRequest {
+        self.send(AppRequest::Workspace(window))
+    }
+
+    /// Asks the user to pick an existing file, with a native dialog over
+    /// `window`, responding with [`AdwaitaResponsePayload::File`].
+    ///
+    /// The response is only sent once the user closes the dialog, which can
+    /// take as long as they like.
+    pub fn open_file(&self, window: Entity, dialog: AdwaitaFileDialog) -> AdwaitaRequest {
+        self.send(AppRequest::OpenFile { window, dialog })
+    }
+
+    /// Asks the user where to save a file, with a native dialog over
+    /// `window`, responding with [`AdwaitaResponsePayload::File`].
+    ///
+    /// The file isn't created; that's left to you once you get its path.
+    /// Like [`AdwaitaRequests::open_file`], the response is only sent once the
+    /// user closes the dialog.
+    pub fn save_file(&self, window: Entity, dialog: AdwaitaFileDialog) -> AdwaitaRequest {
+        self.send(AppRequest::SaveFile { window, dialog })
+    }
+
+    fn send(&self, request: AppRequest) -> AdwaitaRequest {
+        let id = AdwaitaRequest(self.next_id.fetch_add(1, Ordering::Relaxed));
+        if self
+            .send_app_command
+            .send(AppCommand::Request { id, request })
+            .is_err()
+        {
+            _ = self.send_app_event.send(AppEvent::Response {
+                id,
+                payload: Err("GTK thread has shut down".into()),
+            });
+        }
+        id
+    }
+}
+
+/// Configuration of an [`AdwaitaWindow`].
+///
+/// This is inserted onto the window entity when it is opened. Changing it
+/// afterwards updates the open window to match, although some fields (such as
+/// [`AdwaitaWindowConfig::header_bar`]) can only be set when opening a window.
+#[derive(Debug, Clone, Component, Reflect)]
+#[reflect(Default, Component)]
+pub struct AdwaitaWindowConfig {
+    pub width: u32,
+    pub height: u32,
+    /// Smallest size the user can shrink the window to, in logical pixels.
+    /// This can only be set when opening a window.
+    ///
+    /// If [`None`], windows with an adaptive layout, such as those opened with
+    /// [`AdwaitaWindow::open_with_views`], can be shrunk to the smallest size
+    /// their narrowest layout supports, and other windows to whatever their
+    /// content allows. A size smaller than what the narrowest layout supports
+    /// is still used, but logs a warning.
+    pub min_size: Option<UVec2>,
+    /// Largest size of the render target, in logical pixels.
+    ///
+    /// GTK can't stop the user from growing a window, so instead, once the
+    /// render area is larger than this, the render target is shrunk to fit
+    /// within it, keeping the render area's aspect ratio, and is scaled up to
+    /// fill the render area. Use this to cap the cost of rendering, e.g. on
+    /// very large monitors. Changes are applied the next frame.
+    pub max_size: Option<UVec2>,
+    pub title: String,
+    /// Name of the icon shown for the window, e.g. in the window switcher,
+    /// or [`None`] to use the application's.
+    ///
+    /// GTK only supports named icons, so ship your icon in an icon theme
+    /// directory added with [`AdwaitaWindowPlugin::icon_search_paths`]. On
+    /// Wayland, most compositors ignore this and show the icon of the desktop
+    /// file which matches [`AdwaitaWindowPlugin::application_id`] instead,
+    /// so set that too. The application ID is shared by all windows, since
+    /// they belong to the same application.
+    pub icon_name: Option<String>,
+    /// Whether the user can resize the window, e.g. by dragging its edges.
+    ///
+    /// Either way, the window can still be resized with
+    /// [`AdwaitaWindow::set_size`].
+    pub user_resizable: bool,
+    pub maximized: bool,
+    pub fullscreen: bool,
+    pub header_bar: AdwaitaHeaderBar,
+    /// Buttons added to the header bar, next to the window controls.
+    ///
+    /// Clicking a button sends an [`AdwaitaHeaderButtonPressed`] event. These
+    /// are only shown with [`AdwaitaHeaderBar::Full`], and can only be set
+    /// when opening a window.
+    pub header_buttons: Vec<AdwaitaHeaderButton>,
+    /// Items of the main menu, opened from a button at the end of the header
+    /// bar.
+    ///
+    /// Activating an item sends an [`AdwaitaMenuActivated`] event. If empty,
+    /// there is no menu button. Like [`AdwaitaWindowConfig::header_buttons`],
+    /// this is only shown with [`AdwaitaHeaderBar::Full`], and can only be
+    /// set when opening a window.
+    pub menu: Vec<AdwaitaMenuItem>,
+    /// Whether to show floating window controls and a fullscreen toggle in
+    /// the top right corner while the cursor is near it, with
+    /// [`AdwaitaHeaderBar::None`], like media players do.
+    ///
+    /// The controls are hidden as soon as the cursor moves away, and don't
+    /// take any input while hidden, so input over the render area is still
+    /// forwarded as usual. Since they're only shown briefly, they aren't
+    /// included in [`AdwaitaSafeArea`]. This can only be set when opening a
+    /// window.
+    pub floating_controls: bool,
+    /// How many rendered frames may be waiting to be presented by GTK before
+    /// rendering stalls until GTK catches up.
+    ///
+    /// Lower values reduce input latency, higher values give smoother
+    /// throughput. If [`None`], rendering never stalls and GTK always presents
+    /// the latest frame, dropping any older ones.
+    pub max_frames_in_flight: Option<u32>,
+    /// How many render targets are rendered into in turn.
+    ///
+    /// Bevy renders each frame into the next render target which GTK is done
+    /// with, i.e. which isn't waiting to be presented, being presented, or
+    /// still held by GDK. If there's none, rendering waits up to 100ms for
+    /// one, so a frame is only presented while it's still being rendered if
+    /// GTK holds on to every buffer for longer than that. With 3 buffers,
+    /// Bevy can render two frames ahead of the one being presented, which
+    /// avoids waiting with pipelined rendering, at the cost of more memory.
+    /// This is clamped between `2` and `3`, and changes are applied by
+    /// reallocating the render targets. Windows which present frames through
+    /// software always use a single render target.
+    pub swapchain_buffers: u32,
+    /// Most frames per second to render to this window, independent of the
+    /// display's refresh rate, e.g. to save power on high refresh rate
+    /// displays. This can be changed at any time.
+    ///
+    /// Updates which come sooner than this allows still run, but cameras
+    /// targeting the window are made inactive for them, so nothing is
+    /// rendered or handed off to GTK. When Bevy only updates on demand, a
+    /// skipped update requests another one with [`RequestRedraw`], so that the
+    /// latest state is still rendered once the interval has passed.
+    ///
+    /// If [`None`] or `0`, frames aren't capped.
+    pub max_fps: Option<u32>,
+    /// Most frames per second to render to this window while it isn't
+    /// focused, instead of [`AdwaitaWindowConfig::max_fps`].
+    ///
+    /// Use this for windows which stay visible in the background, e.g. a
+    /// companion window which should keep updating, but doesn't need to at
+    /// full rate. The cap switches as soon as the window gains or loses
+    /// focus. This can be changed at any time.
+    ///
+    /// If [`None`], the window is capped the same whether it's focused or
+    /// not.
+    pub unfocused_max_fps: Option<u32>,
+    /// Whether GTK shows new frames in step with the display's frame clock,
+    /// instead of as soon as they arrive.
+    ///
+    /// When Bevy doesn't update at the display's refresh rate, a frame which
+    /// arrives just after the display refreshes has to wait a whole refresh
+    /// longer to be shown than one which arrives just before, which looks like
+    /// judder. With this set, every frame is held until the next tick of the
+    /// frame clock, and the last frame is drawn again until a new one
+    /// arrives, which evens this out at the cost of up to one refresh of extra
+    /// latency. This is separate from how Bevy itself is paced. This can only
+    /// be set when opening a window.
+    pub present_smoothing: bool,
+    /// Whether frames may tear while the window is fullscreen, for the lowest
+    /// possible latency.
+    ///
+    /// Windowed, the compositor always presents tear-free. GTK presents the
+    /// window through its own surface and doesn't support the tearing control
+    /// protocol yet, so [`AdwaitaTearing::LowLatency`] currently falls back to
+    /// [`AdwaitaTearing::TearFree`] with a warning; check
+    /// [`AdwaitaWindow::tearing`] for the mode in effect. This can only be set
+    /// when opening a window.
+    pub tearing: AdwaitaTearing,
+    /// What happens to a newly rendered frame when GTK hasn't picked up the
+    /// previous one yet. This can be changed at any time.
+    pub frame_overflow: AdwaitaFrameOverflow,
+    /// Color drawn behind the rendered frame, visible before the first frame
+    /// is presented and around the frame when it doesn't fill the window.
+    ///
+    /// If [`None`], the backdrop is black, or transparent with
+    /// [`AdwaitaWindowConfig::transparent`].
+    pub backdrop_color: Option<Color>,
+    /// Whether the render area is transparent wherever the frame is, so that
+    /// what's behind it - usually the window's background - shows through.
+    /// This can only be set when opening a window.
+    ///
+    /// The render target stores linear colors with premultiplied alpha, which
+    /// is what Bevy's alpha blending produces over a transparent clear color,
+    /// and GTK presents it as such. Cameras rendering to the window with
+    /// [`ClearColorConfig::Default`] are made to clear to [`Color::NONE`]
+    /// instead; clear them to another transparent color with
+    /// [`ClearColorConfig::Custom`] if you need to.
+    ///
+    /// With 8 bits per channel, linear colors have less precision in dark
+    /// shades than sRGB ones, so dark gradients may band slightly. Frames
+    /// captured from, or streamed out of, the window also hold linear colors.
+    ///
+    /// Without this, the render target stores sRGB colors, whose alpha is
+    /// only presented correctly where it's fully opaque or fully transparent.
+    pub transparent: bool,
+    /// Widest the render area can be, in logical pixels, before it stops
+    /// growing and is centered in the window instead, like the content of
+    /// GNOME apps on wide windows. This can only be set when opening a window.
+    ///
+    /// The render target is sized to the clamped area, not the whole window.
+    /// The space either side of it is drawn with
+    /// [`AdwaitaWindowConfig::backdrop_color`] if it's set, or the window's
+    /// background otherwise.
+    ///
+    /// If [`None`], the render area fills the window's width.
+    pub max_content_width: Option<u32>,
+    /// Whether to stop rendering to this window while it is not
+    /// [visible](AdwaitaWindowState::is_visible), to save GPU time.
+    ///
+    /// While paused, all active cameras targeting this window are made
+    /// inactive, and are made active again as soon as the window becomes
+    /// visible.
+    pub pause_rendering_when_hidden: bool,
+    /// Whether closing the window only hides it, instead of closing it for
+    /// good.
+    ///
+    /// A hidden window keeps its entity, and can be shown again with
+    /// [`AdwaitaWindow::present`]. Combine this with
+    /// [`AdwaitaWindowConfig::pause_rendering_when_hidden`] to avoid rendering
+    /// to the window while it's hidden.
+    pub hide_on_close: bool,
+    /// Whether closing the window, e.g. with its close button, only sends an
+    /// [`AdwaitaCloseRequested`] event and keeps the window open.
+    ///
+    /// The app can then save its state or ask the user for confirmation, and
+    /// call [`AdwaitaWindow::confirm_close`] to actually close the window. If
+    /// the app never does, the window can't be closed by the user. This can
+    /// only be set when opening a window.
+    pub intercept_close: bool,
+    /// Whether the sidebar of a window opened with
+    /// [`AdwaitaWindow::open_with_sidebar`] can be shown by swiping from the
+    /// left edge of the window, and hidden by swiping it back, on touchscreens
+    /// and touchpads.
+    ///
+    /// Only swipes which start at the edge of the window show the sidebar, so
+    /// drags starting elsewhere in the render area are unaffected.
+    pub sidebar_swipe_gestures: bool,
+    /// Whether to present frames by reading them back into CPU memory every
+    /// frame, instead of sharing them with GTK as dmabufs.
+    ///
+    /// This is much slower, and is only meant for diagnosing whether a problem
+    /// is caused by dmabuf sharing. The time spent reading back each frame is
+    /// reported in [`AdwaitaFrameStats::software_readback_time`]. This can
+    /// only be set when opening a window.
+    pub force_software_present: bool,
+    /// Extra usages of the render target texture, e.g.
+    /// [`TextureUsages::TEXTURE_BINDING`] to sample from it in a later
+    /// pass.
+    ///
+    /// [`TextureUsages::RENDER_ATTACHMENT`] and [`TextureUsages::COPY_SRC`] are
+    /// always included. Usages which the render target's format can't
+    /// support are logged and ignored - notably, the format is sRGB, so
+    /// [`TextureUsages::STORAGE_BINDING`] is never supported. Changes are
+    /// applied the next time the render target is reallocated.
+    #[reflect(ignore)]
+    pub render_target_usages: TextureUsages,
+    /// Whether to always export the render target with a linear layout.
+    ///
+    /// By default, the render target uses a tiled or compressed layout if
+    /// both the GPU and the display support one, which is usually much
+    /// faster to render into. Set this if a driver shows corrupted frames
+    /// with those layouts. Changes are applied the next time the render
+    /// target is reallocated.
+    ///
+    /// If GTK can't import a frame, which usually happens when the display
+    /// is driven by a different GPU than Bevy renders on (PRIME), the window
+    /// falls back to linear render targets by itself, since those can be
+    /// shared between GPUs. If even those can't be imported, it falls back
+    /// to [`force_software_present`](Self::force_software_present).
+    pub force_linear_dmabuf: bool,
+    /// How Bevy's rendered frames get into the render target shared with
+    /// GTK. Changes are applied the next time the render target is
+    /// reallocated.
+    pub render_target_sharing: AdwaitaRenderTargetSharing,
+    /// Whether to show live diagnostics over the render area, such as how
+    /// frames are shared with GTK, frame rates and sizes, e.g. to take a
+    /// screenshot for a bug report.
+    ///
+    /// This is only shown in the crate's own window, not one built with
+    /// [`AdwaitaWindow::open_embedded`]. Bind this to a key to toggle it.
+    /// This can be changed at any time.
+    pub show_diagnostics: bool,
+    /// How long the window's size must stay the same before the render target
+    /// is reallocated to match it.
+    ///
+    /// While the user is resizing the window, the old render target keeps
+    /// being rendered to and is scaled to fit, which avoids reallocating it
+    /// on every single size change. The first render target is always
+    /// allocated immediately. If zero, the render target is reallocated on
+    /// every size change.
+    pub resize_debounce: Duration,
+    /// How long to wait after opening for GTK to lay out the render area,
+    /// before warning that it may never be laid out.
+    ///
+    /// Until then, no render target can be allocated, and the window stays
+    /// black. This usually means that a custom window builder didn't put the
+    /// render widget into the window, or that setting up the window failed.
+    /// If [`None`], this never warns.
+    pub first_size_timeout: Option<Duration>,
+    /// Whether to render at [`AdwaitaWindowConfig::width`] and
+    /// [`AdwaitaWindowConfig::height`] after
+    /// [`AdwaitaWindowConfig::first_size_timeout`] passes without GTK laying
+    /// out the render area, e.g. so that a [mirror](AdwaitaWindow::open_mirror)
+    /// or PipeWire stream of the window still gets frames.
+    ///
+    /// The render target is still resized to match the render area if GTK
+    /// lays it out later.
+    pub fall_back_to_config_size: bool,
+    /// Whether the window asks to be focused and raised when it opens.
+    ///
+    /// If `false`, the window opens without requesting focus, e.g. for a
+    /// secondary window which shouldn't take focus away from the main one.
+    /// The compositor has the final say either way, so check
+    /// [`AdwaitaWindowState::focused`] for the resulting focus state. This can
+    /// only be set when opening a window.
+    pub focus_on_open: bool,
+    /// What kind of window this is, which affects how the compositor places,
+    /// stacks and decorates it. This can only be set when opening a window.
+    pub window_hint: AdwaitaWindowHint,
+    /// Class name in the window's `WM_CLASS`, which X11 window managers match
+    /// rules against, e.g. to always open a window maximized on a certain
+    /// monitor.
+    ///
+    /// By default, this is the program name with its first letter
+    /// capitalized. This is only supported on X11 with the `x11` feature
+    /// enabled, and is distinct from the application ID, which Wayland
+    /// compositors use instead. This can only be set when opening a window.
+    pub wm_class: Option<String>,
+    /// Instance name in the window's `WM_CLASS`, which defaults to the
+    /// program name.
+    ///
+    /// See [`AdwaitaWindowConfig::wm_class`].
+    pub wm_instance: Option<String>,
+    /// Handle of the window's render target, i.e. what
+    /// [`AdwaitaWindow::render_target`] returns.
+    ///
+    /// By default, a random unused handle is picked, which changes on every
+    /// run. Set this to keep the handle stable, e.g. so that cameras in a
+    /// saved scene keep targeting the window. If the handle is already in use,
+    /// a warning is logged and a random one is picked instead. This can only be
+    /// set when opening a window.
+    pub render_target_handle: Option<ManualTextureViewHandle>,
+    /// Whether to hide the window from the taskbar, e.g. for a splash or HUD
+    /// window.
+    ///
+    /// This is only supported on X11 with the `x11` feature enabled, and is
+    /// otherwise ignored with a warning.
+    pub skip_taskbar: bool,
+    /// Whether to hide the window from the pager, i.e. workspace switcher.
+    ///
+    /// This is only supported on X11 with the `x11` feature enabled, and is
+    /// otherwise ignored with a warning.
+    pub skip_pager: bool,
+    /// Entity of another [`AdwaitaWindow`] which this window is transient for,
+    /// e.g. for a dialog or tool window which should stay above its parent.
+    ///
+    /// The window manager may center the window over its parent, and keeps
+    /// it stacked above. If the parent isn't open yet, this takes effect once
+    /// it opens, and if the parent closes, the window is no longer transient
+    /// for anything.
+    pub transient_for: Option<Entity>,
+    /// Resolution to render at, relative to the window's size in device
+    /// pixels.
+    ///
+    /// Below `1.0`, Bevy renders to a smaller render target which GTK then
+    /// upscales with linear filtering, trading sharpness for performance in
+    /// demanding scenes. Above `1.0`, the frame is supersampled. This is
+    /// clamped between `0.25` and `2.0`, and can be changed at any time.
+    ///
+    /// If [`AdwaitaWindowConfig::adaptive_render_scale`] is set, this is only
+    /// the initial render scale.
+    pub render_scale: f32,
+    /// If set, the render scale is adjusted automatically to keep the GPU time
+    /// of each frame close to a target, which keeps the app responsive on
+    /// weaker GPUs.
+    ///
+    /// The render scale currently in use is reported in
+    /// [`AdwaitaRenderTargetSize::render_scale`].
+    pub adaptive_render_scale: Option<AdwaitaAdaptiveRenderScale>,
+    /// Whether to send an [`AdwaitaRawKey`] event for every key pressed and
+    /// released in this window. This can only be set when opening a window.
+    pub emit_raw_keys: bool,
+    /// Whether to send Bevy [`KeyboardInput`] events for keys pressed and
+    /// released in this window, which also drive [`ButtonInput<KeyCode>`].
+    ///
+    /// GTK repeats key presses while a key is held, but these repeats are
+    /// never sent, so there's always exactly one press per release. Modifier
+    /// keys such as Shift and Ctrl are sent like any other key, and every held
+    /// key is released when the window loses focus, so that no key stays
+    /// pressed while GTK isn't sending us key events. This can only be set
+    /// when opening a window.
+    pub keyboard_input: bool,
+    /// Whether to send Bevy [`CursorMoved`], [`CursorEntered`],
+    /// [`CursorLeft`], [`MouseButtonInput`] and [`MouseWheel`] events for
+    /// pointer input over the render area, which also drive
+    /// [`ButtonInput<MouseButton>`].
+    ///
+    /// Cursor positions are in pixels of the render target, so they can be
+    /// passed straight to [`Camera::viewport_to_world`] for cameras rendering
+    /// to this window, even with a render scale or while the render target
+    /// is being reallocated after a resize. This can only be set when
+    /// opening a window.
+    pub pointer_input: bool,
+    /// Which keys are sent as [`AdwaitaRawKey`] and [`KeyboardInput`] events,
+    /// depending on whether GTK handled them. This can only be set when
+    /// opening a window.
+    ///
+    /// Pointer input, such as cursor movement and gestures, is only ever sent
+    /// while the pointer is over the render area itself, so clicking a native
+    /// widget, e.g. a header bar button or an overlay, never counts as a click
+    /// in the render area.
+    pub raw_key_filter: AdwaitaKeyFilter,
+    /// Whether to send an [`AdwaitaTextInput`] event for text typed into this
+    /// window. This can only be set when opening a window.
+    pub text_input: bool,
+    /// Whether clicking the render area moves keyboard focus to it, away from
+    /// any native widget, as in [`AdwaitaWindow::focus_render_area`]. This can
+    /// only be set when opening a window.
+    pub focus_render_area_on_click: bool,
+    /// Whether middle-clicking the render target sends the text in the
+    /// primary selection as an [`AdwaitaPrimaryPaste`] event, as most Linux
+    /// apps paste it. This can only be set when opening a window.
+    pub primary_paste: bool,
+    /// Whether files can be dragged onto the render target from other apps,
+    /// sending them as an [`AdwaitaFilesDropped`] event. Drags of anything
+    /// other than files, e.g. text, are refused. This can only be set when
+    /// opening a window.
+    pub file_drop: bool,
+    /// Whether to recognize secondary clicks and long presses on the render
+    /// target, and send them as [`AdwaitaPointerGesture`] events. This can only
+    /// be set when opening a window.
+    ///
+    /// Disable this to handle the raw input yourself, e.g. in games where
+    /// holding a button still shouldn't open a context menu.
+    pub pointer_gestures: bool,
+    /// Whether to send [`AdwaitaStylus`] events for input from drawing
+    /// tablets over the render target, with their pressure, tilt and buttons.
+    /// This can only be set when opening a window.
+    pub stylus_input: bool,
+    /// Human-readable name of the application, shown by the shell, e.g. in
+    /// the GNOME activities overview, and in about dialogs. If unset, the
+    /// window's title is used.
+    ///
+    /// This is different from both the title and the application ID. Since it
+    /// applies to the whole application, only the first window to open sets
+    /// it.
+    pub app_name: Option<String>,
+    /// Whether to append the application name to the window title, as in
+    /// `Document — App`, following the GNOME convention for document-based
+    /// apps.
+    ///
+    /// [`AdwaitaWindowConfig::title`] and [`AdwaitaWindow::set_title`] then
+    /// only set the document part, which is all that the header bar shows,
+    /// while the shell shows the full title e.g. in the taskbar. The name is
+    /// the one set by [`AdwaitaWindowConfig::app_name`], and isn't appended
+    /// if it's the same as the title. This can only be set when opening a
+    /// window.
+    pub title_with_app_name: bool,
+    /// If set, the window fades in over this long when it's shown, and fades
+    /// out before it's hidden by [`AdwaitaWindowConfig::hide_on_close`].
+    ///
+    /// Showing the window while it's fading out fades it back in from its
+    /// current opacity. The fade is skipped while [`AdwaitaReducedMotion`] is
+    /// set. This can only be set when opening a window.
+    pub fade_transition: Option<Duration>,
+    /// Where the title is placed in an [`AdwaitaHeaderBar::Full`] header bar.
+    /// This can only be set when opening a window.
+    pub header_title: AdwaitaHeaderTitle,
+    /// Whether to add a search bar below an [`AdwaitaHeaderBar::Full`] header
+    /// bar, toggled by a search button in the header bar, by Ctrl+F, or by
+    /// [`AdwaitaWindow::set_search_shown`].
+    ///
+    /// Changes to the query are sent as [`AdwaitaSearchQueryChanged`] events,
+    /// and pressing Enter sends an [`AdwaitaSearchActivated`] event. Hiding the
+    /// search bar clears the query. This can only be set when opening a
+    /// window.
+    pub header_search: bool,
+    /// Which window buttons are shown in the header bar, and on which side.
+    ///
+    /// If [`None`], the layout follows the system setting. This applies to both
+    /// [`AdwaitaHeaderBar::Full`] and [`AdwaitaHeaderBar::OverContent`], and
+    /// can only be set when opening a window.
+    pub decoration_layout: Option<AdwaitaDecorationLayout>,
+    /// Name of the cursor theme to use, e.g. `Adwaita`, or [`None`] to follow
+    /// the system setting.
+    ///
+    /// GTK only has one cursor theme per display, so this applies to every
+    /// window of the app, and whichever window set it last wins.
+    pub cursor_theme_name: Option<String>,
+    /// Size of cursors in logical pixels, e.g. `48` for large cursors, or
+    /// [`None`] to follow the system setting.
+    ///
+    /// Like [`AdwaitaWindowConfig::cursor_theme_name`], this applies to every
+    /// window of the app.
+    pub cursor_theme_size: Option<u32>,
+    /// Cursor shown while the pointer is over the render area. This can be
+    /// changed at any time.
+    pub cursor_icon: AdwaitaCursorIcon,
+    /// Whether the cursor is shown while the pointer is over the render area,
+    /// e.g. hide it for a first-person camera. This can be changed at any
+    /// time.
+    ///
+    /// The cursor is only hidden, not confined to the window, and pointer
+    /// motion is still forwarded as usual.
+    pub cursor_visible: bool,
+    /// How the top bars, i.e. an [`AdwaitaHeaderBar::Full`] header bar and the
+    /// search bar, are separated from the content. This can only be set when
+    /// opening a window.
+    pub top_bar_style: AdwaitaToolbarStyle,
+    /// How bottom bars, e.g. those added with
+    /// [`AdwaitaWindow::open_with_bottom_bars`], are separated from the
+    /// content. This can only be set when opening a window.
+    pub bottom_bar_style: AdwaitaToolbarStyle,
+    /// Bar of buttons along the bottom of the window, below the render area,
+    /// e.g. for the primary actions on narrow layouts.
+    ///
+    /// Clicking a button sends an [`AdwaitaActionActivated`] event. The bar
+    /// starts shown, and can be hidden with
+    /// [`AdwaitaWindow::set_action_bar_shown`], which gives its space back to
+    /// the render area. It's placed above any bars added with
+    /// [`AdwaitaWindow::open_with_bottom_bars`], and is independent of the
+    /// header bar. This can only be set when opening a window.
+    pub action_bar: Option<AdwaitaActionBar>,
+}
+
+/// What happens to a frame which Bevy has rendered while GTK still hasn't
+/// picked up the previous one, i.e. when Bevy renders faster than GTK
+/// presents.
+///
+/// Windows which present frames through software, e.g. with
+/// [`AdwaitaWindowConfig::force_software_present`], have a single render
+/// target, which every frame is read back from as soon as it's rendered. So
+/// they can't keep the previous frame or hold the new one back, and
+/// [`AdwaitaFrameOverflow::DropNewest`] and [`AdwaitaFrameOverflow::Block`]
+/// behave like [`AdwaitaFrameOverflow::DropOldest`] for them.
+///
+/// See [`AdwaitaWindowConfig::frame_overflow`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Reflect)]
+#[reflect(Default)]
+pub enum AdwaitaFrameOverflow {
+    /// The previous frame is dropped, and GTK presents the new one.
+    ///
+    /// This gives the lowest latency, since GTK always presents the latest
+    /// frame, so it's the best fit for interactive apps.
+    #[default]
+    DropOldest,
+    /// The new frame is dropped, and GTK presents the previous one.
+    ///
+    /// Latency grows by up to a frame whenever Bevy gets ahead, since GTK
+    /// presents frames which are older than the latest one. The previous
+    /// frame is safe from being overwritten, since Bevy only renders into
+    /// swapchain buffers which GTK is done with - see
+    /// [`AdwaitaWindowConfig::swapchain_buffers`]. This doesn't apply to
+    /// windows which present through software, as above.
+    DropNewest,
+    /// The render world waits until GTK picks up the previous frame before
+    /// handing over the new one, so that no frame is dropped, e.g. for
+    /// recording.
+    ///
+    /// Bevy's frame rate is limited to what GTK presents, and latency is
+    /// highest since every frame waits in line. GTK stops presenting while
+    /// the window is hidden, so the wait gives up after 100ms and drops the
+    /// previous frame instead.
+    Block,
+    /// Bevy doesn't render the window at all until GTK has shown the previous
+    /// frame, so no GPU time is spent on frames which would be dropped.
+    ///
+    /// This is like [`AdwaitaFrameOverflow::Block`], but without stalling
+    /// the render world, so it saves the most power, e.g. when a fast GPU
+    /// feeds a slow display on a laptop. Frames are shown once GTK paints
+    /// them, so this works best with
+    /// [`AdwaitaWindowConfig::present_smoothing`], where that's aligned to
+    /// the display's refresh. Like with [`AdwaitaFrameOverflow::Block`],
+    /// rendering only waits up to 100ms for GTK, since it stops presenting
+    /// while the window is hidden.
+    Skip,
+}
+
+/// Settings for [`AdwaitaWindowConfig::adaptive_render_scale`].
+///
+/// Every so often, the average time which the GPU spent rendering each frame
+/// is measured. If it is noticeably above
+/// [`AdwaitaAdaptiveRenderScale::target_frame_time`], the render scale is
+/// lowered to about where it would meet the target. If it stays low enough
+/// that the next step up would still be well below the target for about a
+/// second, the render scale is raised a step. Every change reallocates the
+/// render target, so the scale only changes when it's clearly worth it.
+///
+/// This is GPU time rather than the time between frames, so it isn't held up
+/// by vsync or a frame rate cap, but it covers everything rendered in that
+/// frame, including other windows. Measuring it needs GPU timestamp queries
+/// ([`wgpu::Features::TIMESTAMP_QUERY`]) - if the render device doesn't
+/// support them, a warning is logged and the render scale stays as it is.
+#[derive(Debug, Clone, Copy, PartialEq, Reflect)]
+#[reflect(Default)]
+pub struct AdwaitaAdaptiveRenderScale {
+    /// GPU time per frame to aim for.
+    ///
+    /// This should leave some headroom below the display's refresh interval,
+    /// for the CPU side of rendering and for the compositor.
+    pub target_frame_time: Duration,
+    /// Lowest render scale to drop to.
+    pub min_scale: f32,
+    /// Highest render scale to rise to.
+    pub max_scale: f32,
+}
+
+impl Default for AdwaitaAdaptiveRenderScale {
+    fn default() -> Self {
+        Self {
+            target_frame_time: Duration::from_millis(12),
+            min_scale: 0.5,
+            max_scale: 1.0,
+        }
+    }
+}
+
+impl AdwaitaAdaptiveRenderScale {
+    /// Gets the lowest and highest render scales, clamped to the range allowed
+    /// by [`AdwaitaWindowConfig::render_scale`].
+    #[must_use]
+    pub fn scale_range(&self) -> (f32, f32) {
+        let min_scale = render::clamp_render_scale(self.min_scale);
+        let max_scale = render::clamp_render_scale(self.max_scale).max(min_scale);
+        (min_scale, max_scale)
+    }
+}
+
+/// What kind of window an [`AdwaitaWindow`] is.
+///
+/// GTK 4 only has a limited notion of window types, so these are
+/// approximated: non-[`Normal`] windows are made transient for the active
+/// window of the application when they open, which keeps them stacked above
+/// it. With the `x11` feature, X11 is also told about the window type.
+///
+/// [`Normal`]: AdwaitaWindowHint::Normal
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Reflect)]
+#[reflect(Default)]
+pub enum AdwaitaWindowHint {
+    /// Regular top-level window.
+    #[default]
+    Normal,
+    /// Dialog which belongs to another window.
+    Dialog,
+    /// Utility window such as a tool palette, which belongs to another window
+    /// and is not shown in the taskbar.
+    ///
+    /// Hiding it from the taskbar and pager is only possible on X11 with the
+    /// `x11` feature enabled, but unlike
+    /// [`AdwaitaWindowConfig::skip_taskbar`], there's no warning if it isn't.
+    Utility,
+}
+
+impl Default for AdwaitaWindowConfig {
+    fn default() -> Self {
+        Self {
+            width: 1280,
+            height: 720,
+            min_size: None,
+            max_size: None,
+            title: "App".into(),
+            icon_name: None,
+            user_resizable: true,
+            maximized: false,
+            fullscreen: false,
+            header_bar: AdwaitaHeaderBar::default(),
+            header_buttons: Vec::new(),
+            menu: Vec::new(),
+            floating_controls: false,
+            max_frames_in_flight: None,
+            swapchain_buffers: 2,
+            max_fps: None,
+            unfocused_max_fps: None,
+            present_smoothing: false,
+            tearing: AdwaitaTearing::default(),
+            frame_overflow: AdwaitaFrameOverflow::default(),
+            backdrop_color: None,
+            transparent: false,
+            max_content_width: None,
+            pause_rendering_when_hidden: false,
+            hide_on_close: false,
+            intercept_close: false,
+            sidebar_swipe_gestures: false,
+            force_software_present: false,
+            render_target_usages: TextureUsages::empty(),
+            force_linear_dmabuf: false,
+            render_target_sharing: AdwaitaRenderTargetSharing::default(),
+            show_diagnostics: false,
+            resize_debounce: Duration::from_millis(50),
+            first_size_timeout: Some(Duration::from_secs(3)),
+            fall_back_to_config_size: false,
+            focus_on_open: true,
+            window_hint: AdwaitaWindowHint::default(),
+            wm_class: None,
+            wm_instance: None,
+            render_target_handle: None,
+            skip_taskbar: false,
+            skip_pager: false,
+            transient_for: None,
+            render_scale: 1.0,
+            adaptive_render_scale: None,
+            emit_raw_keys: false,
+            keyboard_input: true,
+            pointer_input: true,
+            raw_key_filter: AdwaitaKeyFilter::default(),
+            text_input: false,
+            focus_render_area_on_click: true,
+            primary_paste: false,
+            file_drop: false,
+            pointer_gestures: true,
+            stylus_input: true,
+            app_name: None,
+            title_with_app_name: false,
+            fade_transition: None,
+            header_title: AdwaitaHeaderTitle::default(),
+            header_search: false,
+            decoration_layout: None,
+            cursor_theme_name: None,
+            cursor_theme_size: None,
+            cursor_icon: AdwaitaCursorIcon::default(),
+            cursor_visible: true,
+            top_bar_style: AdwaitaToolbarStyle::default(),
+            bottom_bar_style: AdwaitaToolbarStyle::default(),
+            action_bar: None,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Reflect)]
+#[reflect(Default)]
+pub enum AdwaitaHeaderBar {
+    #[default]
+    Full,
+    OverContent,
+    None,
+}
+
+/// Cursor shown over the render area of a window, with
+/// [`AdwaitaWindowConfig::cursor_icon`].
+///
+/// Each of these is a cursor from the CSS spec, which cursor themes provide.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Reflect)]
+#[reflect(Default)]
+pub enum AdwaitaCursorIcon {
+    /// The theme's regular arrow.
+    #[default]
+    Default,
+    /// Pointing hand, e.g. over something clickable.
+    Pointer,
+    /// I-beam, over text which can be selected or edited.
+    Text,
+    Crosshair,
+    /// Something can be moved in any direction.
+    Move,
+    /// Open hand, over something which can be grabbed.
+    Grab,
+    /// Closed hand, while grabbing something.
+    Grabbing,
+    /// The app is busy and can't be interacted with.
+    Wait,
+    /// The app is busy, but can still be interacted with.
+    Progress,
+    Help,
+    NotAllowed,
+    ZoomIn,
+    ZoomOut,
+    /// A column can be resized horizontally.
+    ColResize,
+    /// A row can be resized vertically.
+    RowResize,
+    AllScroll,
+}
+
+impl AdwaitaCursorIcon {
+    /// Name of this cursor in a cursor theme.
+    #[must_use]
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Default => "default",
+            Self::Pointer => "pointer",
+            Self::Text => "text",
+            Self::Crosshair => "crosshair",
+            Self::Move => "move",
+            Self::Grab => "grab",
+            Self::Grabbing => "grabbing",
+            Self::Wait => "wait",
+            Self::Progress => "progress",
+            Self::Help => "help",
+            Self::NotAllowed => "not-allowed",
+            Self::ZoomIn => "zoom-in",
+            Self::ZoomOut => "zoom-out",
+            Self::ColResize => "col-resize",
+            Self::RowResize => "row-resize",
+            Self::AllScroll => "all-scroll",
+        }
+    }
+}
+
+/// Button in the header bar of a window, added with
+/// [`AdwaitaWindowConfig::header_buttons`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Reflect)]
+pub struct AdwaitaHeaderButton {
+    /// ID of the button, as sent in [`AdwaitaHeaderButtonPressed`].
+    pub id: String,
+    /// Label of the button, or its tooltip if it has an icon.
+    pub label: String,
+    /// Name of the icon shown instead of the label, e.g.
+    /// `emblem-system-symbolic`.
+    pub icon_name: Option<String>,
+    /// Which end of the header bar the button is packed at.
+    pub side: AdwaitaHeaderButtonSide,
+}
+
+impl AdwaitaHeaderButton {
+    /// Creates a button with a text label, packed at the start of the header
+    /// bar.
+    #[must_use]
+    pub fn new(id: impl Into<String>, label: impl Into<String>) -> Self {
+        Self {
+            id: id.into(),
+            label: label.into(),
+            icon_name: None,
+            side: AdwaitaHeaderButtonSide::default(),
+        }
+    }
+
+    /// Shows an icon instead of the label, which becomes the tooltip.
+    #[must_use]
+    pub fn with_icon(mut self, icon_name: impl Into<String>) -> Self {
+        self.icon_name = Some(icon_name.into());
+        self
+    }
+
+    /// Packs the button at the end of the header bar instead.
+    #[must_use]
+    pub fn at_end(mut self) -> Self {
+        self.side = AdwaitaHeaderButtonSide::End;
+        self
+    }
+}
+
+/// Which end of the header bar an [`AdwaitaHeaderButton`] is packed at.
+///
+/// Buttons on the same side keep the order they're given in, starting from
+/// the window controls on that side.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Reflect)]
+#[reflect(Default)]
+pub enum AdwaitaHeaderButtonSide {
+    /// Left in left-to-right locales.
+    #[default]
+    Start,
+    /// Right in left-to-right locales.
+    End,
+}
+
+/// Sent when a button in the [`AdwaitaWindowConfig::header_buttons`] of a
+/// window is clicked.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Event)]
+pub struct AdwaitaHeaderButtonPressed {
+    /// Entity of the [`AdwaitaWindow`] whose button was clicked.
+    pub window: Entity,
+    /// [`AdwaitaHeaderButton::id`] of the button.
+    pub id: String,
+}
+
+/// Item in the [`AdwaitaWindowConfig::menu`] of a window.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Reflect)]
+pub struct AdwaitaMenuItem {
+    /// ID of the item, as sent in [`AdwaitaMenuActivated`].
+    ///
+    /// IDs of items without children must be valid [`gio::Action`] names,
+    /// e.g. `new-game` or `show-grid`. Items with invalid IDs are logged and
+    /// left out.
+    pub id: String,
+    pub label: String,
+    pub kind: AdwaitaMenuItemKind,
+    /// Items of the submenu which this item opens. If not empty, this item
+    /// can't be activated itself, and its [`AdwaitaMenuItem::kind`] is
+    /// ignored.
+    pub children: Vec<AdwaitaMenuItem>,
+}
+
+impl AdwaitaMenuItem {
+    /// Creates an item which is activated when clicked.
+    #[must_use]
+    pub fn new(id: impl Into<String>, label: impl Into<String>) -> Self {
+        Self {
+            id: id.into(),
+            label: label.into(),
+            kind: AdwaitaMenuItemKind::default(),
+            children: Vec::new(),
+        }
+    }
+
+    /// Creates an item with a checkbox, which starts out checked if
+    /// `checked` is set.
+    #[must_use]
+    pub fn toggle(id: impl Into<String>, label: impl Into<String>, checked: bool) -> Self {
+        Self {
+            kind: AdwaitaMenuItemKind::Toggle(checked),
+            ..Self::new(id, label)
+        }
+    }
+
+    /// Creates an item which opens a submenu of `children`.
+    #[must_use]
+    pub fn submenu(
+        id: impl Into<String>,
+        label: impl Into<String>,
+        children: impl IntoIterator<Item = AdwaitaMenuItem>,
+    ) -> Self {
+        Self {
+            children: children.into_iter().collect(),
+            ..Self::new(id, label)
+        }
+    }
+}
+
+/// What an [`AdwaitaMenuItem`] does when it's activated.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Reflect)]
+#[reflect(Default)]
+pub enum AdwaitaMenuItemKind {
+    /// The item is activated once per click.
+    #[default]
+    Action,
+    /// The item has a checkbox which is toggled on every click. This is the
+    /// initial state; GTK keeps track of it after the window opens.
+    Toggle(bool),
+}
+
+/// Sent when an item in the [`AdwaitaWindowConfig::menu`] of a window is
+/// activated.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Event)]
+pub struct AdwaitaMenuActivated {
+    /// Entity of the [`AdwaitaWindow`] whose menu item was activated.
+    pub window: Entity,
+    /// [`AdwaitaMenuItem::id`] of the item.
+    pub id: String,
+    /// New state of the checkbox, for an [`AdwaitaMenuItemKind::Toggle`]
+    /// item, or [`None`] for an [`AdwaitaMenuItemKind::Action`].
+    pub checked: Option<bool>,
+}
+
+/// Sent when the query in the search bar of a window with
+/// [`AdwaitaWindowConfig::header_search`] changes, including when it's
+/// cleared.
+///
+/// GTK waits for the user to briefly stop typing before sending this, so
+/// searching on every event is fine.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Event)]
+pub struct AdwaitaSearchQueryChanged {
+    /// Entity of the [`AdwaitaWindow`] whose search bar changed.
+    pub window: Entity,
+    /// New query, or empty if the search was cleared.
+    pub query: String,
+}
+
+/// Sent when the user presses Enter in the search bar of a window with
+/// [`AdwaitaWindowConfig::header_search`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Event)]
+pub struct AdwaitaSearchActivated {
+    /// Entity of the [`AdwaitaWindow`] whose search bar was activated.
+    pub window: Entity,
+    /// Query at the time the search was activated.
+    pub query: String,
+}
+
+/// Sent when the search bar of a window with
+/// [`AdwaitaWindowConfig::header_search`] is shown or hidden.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Event)]
+pub struct AdwaitaSearchToggled {
+    /// Entity of the [`AdwaitaWindow`] whose search bar was toggled.
+    pub window: Entity,
+    /// Whether the search bar is now shown.
+    pub shown: bool,
+}
+
+/// Page in a window opened with [`AdwaitaWindow::open_with_views`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct AdwaitaView {
+    /// Unique ID of the view, as sent in [`AdwaitaViewChanged`].
+    pub id: String,
+    /// Title shown in the view switcher.
+    pub title: String,
+    /// Name of the icon shown in the view switcher, e.g.
+    /// `applications-graphics-symbolic`.
+    pub icon_name: Option<String>,
+}
+
+impl AdwaitaView {
+    /// Creates a view without an icon.
+    #[must_use]
+    pub fn new(id: impl Into<String>, title: impl Into<String>) -> Self {
+        Self {
+            id: id.into(),
+            title: title.into(),
+            icon_name: None,
+        }
+    }
+
+    /// Sets the name of the icon shown in the view switcher.
+    #[must_use]
+    pub fn with_icon(mut self, icon_name: impl Into<String>) -> Self {
+        self.icon_name = Some(icon_name.into());
+        self
+    }
+}
+
+/// Sent when the visible view of a window opened with
+/// [`AdwaitaWindow::open_with_views`] changes, either by the user or by
+/// [`AdwaitaWindow::set_view`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Event)]
+pub struct AdwaitaViewChanged {
+    /// Entity of the [`AdwaitaWindow`] whose view changed.
+    pub window: Entity,
+    /// [`AdwaitaView::id`] of the now visible view.
+    pub view_id: String,
+}
+
+/// Buttons of an [`AdwaitaWindowConfig::action_bar`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Reflect)]
+#[reflect(Default)]
+pub struct AdwaitaActionBar {
+    /// Buttons at the start of the bar, i.e. on the left in left-to-right
+    /// locales.
+    pub start: Vec<AdwaitaAction>,
+    /// Buttons in the center of the bar.
+    pub center: Vec<AdwaitaAction>,
+    /// Buttons at the end of the bar.
+    pub end: Vec<AdwaitaAction>,
+}
+
+/// Button in an [`AdwaitaActionBar`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Reflect)]
+pub struct AdwaitaAction {
+    /// ID of the action, as sent in [`AdwaitaActionActivated::action`].
+    pub id: String,
+    /// Label of the button, or its tooltip if it has an icon.
+    pub label: String,
+    /// Name of the icon shown instead of the label, e.g.
+    /// `document-save-symbolic`.
+    pub icon_name: Option<String>,
+    /// Whether this is the action the user most likely wants, which is
+    /// highlighted in the accent color.
+    pub suggested: bool,
+}
+
+impl AdwaitaAction {
+    /// Creates an action with a text label.
+    #[must_use]
+    pub fn new(id: impl Into<String>, label: impl Into<String>) -> Self {
+        Self {
+            id: id.into(),
+            label: label.into(),
+            icon_name: None,
+            suggested: false,
+        }
+    }
+
+    /// Shows an icon instead of the label, which becomes the tooltip.
+    #[must_use]
+    pub fn with_icon(mut self, icon_name: impl Into<String>) -> Self {
+        self.icon_name = Some(icon_name.into());
+        self
+    }
+
+    /// Highlights this as the suggested action.
+    #[must_use]
+    pub fn suggested(mut self) -> Self {
+        self.suggested = true;
+        self
+    }
+}
+
+/// Sent when an [`AdwaitaWindow`] closes while a camera is still rendering to
+/// it or one of its secondary render targets.
+///
+/// The camera is left as is, and renders nothing until it's given a new
+/// [`Camera::target`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Event)]
+pub struct AdwaitaCameraTargetLost {
+    /// Entity of the camera which lost its render target.
+    pub camera: Entity,
+    /// Entity of the [`AdwaitaWindow`] which closed.
+    pub window: Entity,
+}
+
+/// Button which can be placed in the header bar by an
+/// [`AdwaitaDecorationLayout`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Reflect)]
+pub enum AdwaitaWindowButton {
+    /// Application icon, which is not shown by most GNOME setups.
+    Icon,
+    Minimize,
+    Maximize,
+    Close,
+}
+
+impl AdwaitaWindowButton {
+    fn gtk_name(self) -> &'static str {
+        match self {
+            Self::Icon => "icon",
+            Self::Minimize => "minimize",
+            Self::Maximize => "maximize",
+            Self::Close => "close",
+        }
+    }
+}
+
+/// Which window buttons are shown in the header bar, at the start and end
+/// sides, overriding the system layout.
+///
+/// Each button is shown at most once. If a button is given more than once, only
+/// its first occurrence is kept, looking at the start side before the end.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Reflect)]
+pub struct AdwaitaDecorationLayout {
+    start: Vec<AdwaitaWindowButton>,
+    end: Vec<AdwaitaWindowButton>,
+}
+
+impl AdwaitaDecorationLayout {
+    /// Creates a layout with these buttons at the start and end sides, in
+    /// order.
+    #[must_use]
+    pub fn new(
+        start: impl IntoIterator<Item = AdwaitaWindowButton>,
+        end: impl IntoIterator<Item = AdwaitaWindowButton>,
+    ) -> Self {
+        let mut seen = HashSet::new();
+        let mut start = start.into_iter().collect::<Vec<_>>();
+        start.retain(|button| seen.insert(*button));
+        let mut end = end.into_iter().collect::<Vec<_>>();
+        end.retain(|button| seen.insert(*button));
+        Self { start, end }
+    }
+
+    /// Shows only a close button at the end side.
+    #[must_use]
+    pub fn close_only() -> Self {
+        Self::new([], [AdwaitaWindowButton::Close])
+    }
+
+    /// Shows no window buttons at all.
+    #[must_use]
+    pub fn empty() -> Self {
+        Self::new([], [])
+    }
+
+    /// Buttons at the start side, i.e. the left in left-to-right locales.
+    #[must_use]
+    pub fn start(&self) -> &[AdwaitaWindowButton] {
+        &self.start
+    }
+
+    /// Buttons at the end side, i.e. the right in left-to-right locales.
+    #[must_use]
+    pub fn end(&self) -> &[AdwaitaWindowButton] {
+        &self.end
+    }
+
+    /// Formats this as a GTK decoration layout string, e.g.
+    /// `icon:minimize,maximize,close`.
+    fn gtk_layout(&self) -> String {
+        let side = |buttons: &[AdwaitaWindowButton]| {
+            buttons
+                .iter()
+                .map(|button| button.gtk_name())
+                .collect::<Vec<_>>()
+                .join(",")
+        };
+        format!("{}:{}", side(&self.start), side(&self.end))
+    }
+}
+
+/// How the bars at the top or bottom of the crate's own window are drawn
+/// against the content between them.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Reflect)]
+#[reflect(Default)]
+pub enum AdwaitaToolbarStyle {
+    /// Bars are transparent and blend into the content, as for most header
+    /// bars.
+    #[default]
+    Flat,
+    /// Bars have a background and a shadow, as for bottom action bars.
+    Raised,
+    /// Like [`AdwaitaToolbarStyle::Raised`], with a border instead of a
+    /// shadow, for content which a shadow would look wrong over.
+    RaisedBorder,
+}
+
+/// How frames get into the render target shared with GTK, set with
+/// [`AdwaitaWindowConfig::render_target_sharing`].
+///
+/// Only the Vulkan backend can share render targets, so what matters is how
+/// well the driver supports rendering into an image whose memory is exported
+/// as a dmabuf.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Reflect)]
+#[reflect(Default)]
+pub enum AdwaitaRenderTargetSharing {
+    /// Bevy renders straight into the shared render target, which GTK imports
+    /// from its dmabuf.
+    ///
+    /// This is the fastest, and is safe with any driver which correctly
+    /// supports rendering into exported images, as most current drivers do.
+    #[default]
+    Direct,
+    /// Bevy renders into a render target which wgpu allocates as usual, and
+    /// copies each frame into the shared render target once rendered.
+    ///
+    /// This costs one extra copy per frame, but the shared render target is
+    /// then only ever a copy destination. Try this if windows stay black or
+    /// show garbage with [`AdwaitaRenderTargetSharing::Direct`], but work with
+    /// [`AdwaitaWindowConfig::force_software_present`].
+    Copy,
+}
+
+/// Which keys are sent as [`AdwaitaRawKey`] and [`KeyboardInput`] events, set
+/// with [`AdwaitaWindowConfig::raw_key_filter`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Reflect)]
+#[reflect(Default)]
+pub enum AdwaitaKeyFilter {
+    /// Every key is sent before GTK handles it, even if a native widget then
+    /// consumes it, e.g. while typing into the search bar.
+    #[default]
+    All,
+    /// Only keys which no native widget, input method or shortcut consumed
+    /// are sent, so typing into the search bar doesn't also control the app.
+    ///
+    /// A widget may consume a press but not its release, so the app may see
+    /// a release of a key it never saw pressed.
+    Unhandled,
+}
+
+/// Whether frames may tear in exchange for lower latency, set with
+/// [`AdwaitaWindowConfig::tearing`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Reflect)]
+#[reflect(Default)]
+pub enum AdwaitaTearing {
+    /// Every frame is shown whole, in step with the display's refresh.
+    #[default]
+    TearFree,
+    /// While fullscreen, frames may be shown as soon as they're ready, even
+    /// partway through the display's refresh, through the Wayland tearing
+    /// control protocol.
+    LowLatency,
+}
+
+/// Where the title of an [`AdwaitaHeaderBar::Full`] header bar is placed.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Reflect)]
+#[reflect(Default)]
+pub enum AdwaitaHeaderTitle {
+    /// Centered in the header bar, as in most GNOME apps.
+    #[default]
+    Centered,
+    /// At the start of the header bar, i.e. left-aligned in left-to-right
+    /// locales.
+    Start,
+}
+
+/// Forwards interactions with a custom title widget into Bevy, as
+/// [`AdwaitaTitleWidgetEvent`]s.
+///
+/// This is given to the builder passed to
+/// [`AdwaitaWindow::open_with_title_widget`], and can be cloned into the
+/// widget's signal handlers.
+#[derive(Debug, Clone)]
+pub struct AdwaitaTitleEmitter {
+    send_event: flume::Sender<WindowEvent>,
+}
+
+impl AdwaitaTitleEmitter {
+    /// Sends an [`AdwaitaTitleWidgetEvent`] with this `key` and `value`, e.g.
+    /// `("search", text)` when a search entry's text changes.
+    pub fn emit(&self, key: impl Into<String>, value: impl Into<String>) {
+        _ = self.send_event.send(WindowEvent::TitleWidget {
+            key: key.into(),
+            value: value.into(),
+        });
+    }
+}
+
+/// Sent when a custom title widget emits an interaction through its
+/// [`AdwaitaTitleEmitter`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Event)]
+pub struct AdwaitaTitleWidgetEvent {
+    /// Entity of the [`AdwaitaWindow`] whose title widget emitted this.
+    pub window: Entity,
+    /// What kind of interaction this is, as chosen by the title widget.
+    pub key: String,
+    /// Value of the interaction, e.g. the current search text or the name
+    /// of the selected segment.
+    pub value: String,
+}
+
+/// Handles to the GTK objects which present Bevy frames for a window.
+///
+/// This is only accessible on the GTK main thread, via
+/// [`AdwaitaWindow::open_embedded`].
+#[derive(Debug, Clone)]
+pub struct AdwaitaRenderArea {
+    /// Widget which displays [`AdwaitaRenderArea::paintable`], and reports its
+    /// size back to Bevy so that the render target matches it.
+    pub widget: gtk::Widget,
+    /// Paintable which always draws the latest frame rendered by Bevy.
+    ///
+    /// This can be used independently of [`AdwaitaRenderArea::widget`], e.g.
+    /// as the content of another [`gtk::Picture`] for a preview. Note that the
+    /// render target is still sized according to `widget`, so `widget` should
+    /// still be part of a visible widget tree.
+    ///
+    /// The paintable stays valid for as long as you hold a reference to it,
+    /// but once the [`AdwaitaWindow`] closes, it is cleared and draws
+    /// nothing.
+    pub paintable: gdk::Paintable,
+}
+
+/// Set in the [`Render`] schedule in which rendered frames are handed off to
+/// GTK to be presented.
+///
+/// By default, this runs after [`RenderSet::Render`], so any rendering
+/// which writes to a window's render target must happen before then. If you
+/// write to the render target later, e.g. in [`RenderSet::Cleanup`], order
+/// this set after your own systems:
+///
+/// ```ignore
+/// render_app.configure_sets(Render, AdwaitaFrameHandoffSet.after(my_system));
+/// ```
+///
+/// This set must always run before [`RenderSet::PostCleanup`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, SystemSet)]
+pub struct AdwaitaFrameHandoffSet;
+
+#[derive(Debug, Resource)]
+struct SendWindowOpen(flume::Sender<WindowOpen>);
+
+#[derive(Debug, Resource)]
+struct RecvAppEvent(flume::Receiver<AppEvent>);
+
+#[derive(Debug, Resource)]
+struct SendAppCommand(flume::Sender<AppCommand>);
+
+/// Number of frames Bevy has handed off to a window which GTK hasn't picked up
+/// yet.
+///
+/// GTK resets this whenever it picks up the latest frame, waking up the render
+/// world if it's waiting for frames to be taken.
+#[derive(Debug, Default)]
+pub(crate) struct FramesInFlight {
+    count: Mutex<u32>,
+    taken: Condvar,
+}
+
+impl FramesInFlight {
+    fn lock(&self) -> MutexGuard<'_, u32> {
+        self.count.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    pub(crate) fn get(&self) -> u32 {
+        *self.lock()
+    }
+
+    fn add_one(&self) {
+        *self.lock() += 1;
+    }
+
+    /// Marks every frame in flight as taken, returning how many there were.
+    pub(crate) fn take(&self) -> u32 {
+        let count = std::mem::take(&mut *self.lock());
+        self.taken.notify_all();
+        count
+    }
+
+    /// Blocks until fewer than `max` frames are in flight, or until
+    /// `deadline`.
+    fn wait_below(&self, max: u32, deadline: Instant) {
+        let timeout = deadline.saturating_duration_since(Instant::now());
+        let _guard = self
+            .taken
+            .wait_timeout_while(self.lock(), timeout, |count| *count >= max)
+            .unwrap_or_else(PoisonError::into_inner);
+    }
+}
+
+/// Handle of the thread running GTK, until it's joined on exit.
+#[derive(Debug, Resource)]
+struct GtkThread(Option<thread::JoinHandle<()>>);
+
+/// Set from wgpu's device lost callback, in both the main and render worlds.
+#[derive(Debug, Clone, Default, Resource)]
+struct GpuLost(Arc<Mutex<Option<String>>>);
+
+impl GpuLost {
+    fn set(&self, message: String) {
+        *self.0.lock().unwrap_or_else(PoisonError::into_inner) = Some(message);
+    }
+
+    fn message(&self) -> Option<String> {
+        self.0
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    fn is_lost(&self) -> bool {
+        self.message().is_some()
+    }
+}
+
+/// GPU time of each frame which [`render::GpuFrameTimer`] measured, if the
+/// render device supports timestamp queries.
+#[derive(Debug, Resource)]
+struct GpuFrameTimes(flume::Receiver<Duration>);
+
+/// How many measurements [`GpuFrameTimes`] holds before dropping new ones,
+/// which only happens if nothing reads them.
+const GPU_FRAME_TIMES_CAPACITY: usize = 64;
+
+/// Captures which the render world has read back, in both the main and render
+/// worlds.
+#[derive(Debug, Clone, Resource)]
+struct CapturedFrames {
+    send: flume::Sender<AdwaitaCaptureReady>,
+    recv: flume::Receiver<AdwaitaCaptureReady>,
+}
+
+impl Default for CapturedFrames {
+    fn default() -> Self {
+        let (send, recv) = flume::unbounded();
+        Self { send, recv }
+    }
+}
+
+#[derive(Debug, Resource)]
+struct FrameClockTicks {
+    send: flume::Sender<()>,
+    recv: flume::Receiver<()>,
+}
+
+/// How many commands can be waiting for the GTK thread to apply them to a
+/// window, before [`AdwaitaWindow::send_command`] fails with
+/// [`AdwaitaCommandError::Full`].
+const COMMAND_CAPACITY: usize = 64;
+
+impl AdwaitaWindow {
+    #[must_use]
+    pub fn open(config: AdwaitaWindowConfig) -> impl EntityCommand {
+        Self::open_with(config, WindowBuilders::default(), None)
+    }
+
+    /// Opens a window whose widget tree is built by `build_window`, instead of
+    /// the default Adwaita window.
+    ///
+    /// `build_window` is run on the GTK main thread, and is given the
+    /// [`AdwaitaRenderArea`] which Bevy frames are presented into. It can pack
+    /// the render widget anywhere in its own widget tree, and must return the
+    /// toplevel window containing it. That window is presented for you.
+    ///
+    /// Only the parts of `config` which don't describe the window's own layout
+    /// are used, since `build_window` is responsible for creating the window
+    /// (e.g. [`AdwaitaWindowConfig::header_bar`] is ignored).
+    #[must_use]
+    pub fn open_embedded(
+        config: AdwaitaWindowConfig,
+        build_window: impl FnOnce(AdwaitaRenderArea) -> gtk::Window + Send + 'static,
+    ) -> impl EntityCommand {
+        Self::open_with(
+            config,
+            WindowBuilders {
+                window: Some(BuildWindow(Box::new(build_window))),
+                ..default()
+            },
+            None,
+        )
+    }
+
+    /// Opens a window like [`AdwaitaWindow::open`], but lets you add native
+    /// GTK widgets on top of the render area.
+    ///
+    /// `build_overlay` is called on the GTK main thread with the
+    /// [`gtk::Overlay`] whose child is the render area, after the crate has
+    /// added its own overlays (e.g. window controls for
+    /// [`AdwaitaHeaderBar::OverContent`]). Overlay widgets are positioned
+    /// relative to the render area, and don't affect its size.
+    ///
+    /// Input over an overlay widget goes to that widget, not to the render
+    /// area: moving the pointer onto one counts as leaving the render area
+    /// for [`AdwaitaCursorPosition`]. Set [`gtk::Widget::set_can_target`] to
+    /// `false` on purely decorative widgets to let input through.
+    #[must_use]
+    pub fn open_with_overlay(
+        config: AdwaitaWindowConfig,
+        build_overlay: impl FnOnce(&gtk::Overlay) + Send + 'static,
+    ) -> impl EntityCommand {
+        Self::open_with(
+            config,
+            WindowBuilders {
+                overlay: Some(BuildOverlay(Box::new(build_overlay))),
+                ..default()
+            },
+            None,
+        )
+    }
+
+    /// Opens a window like [`AdwaitaWindow::open`], with a sidebar which
+    /// slides in over the render area.
+    ///
+    /// `build_sidebar` is called on the GTK main thread, and returns the
+    /// content of the sidebar. The sidebar starts hidden, and can be shown
+    /// with [`AdwaitaWindow::set_sidebar_shown`], or by swiping if
+    /// [`AdwaitaWindowConfig::sidebar_swipe_gestures`] is enabled. Whenever
+    /// it is shown or hidden, an [`AdwaitaSidebarToggled`] event is sent.
+    #[must_use]
+    pub fn open_with_sidebar(
+        config: AdwaitaWindowConfig,
+        build_sidebar: impl FnOnce() -> gtk::Widget + Send + 'static,
+    ) -> impl EntityCommand {
+        Self::open_with(
+            config,
+            WindowBuilders {
+                sidebar: Some(BuildSidebar(Box::new(build_sidebar))),
+                ..default()
+            },
+            None,
+        )
+    }
+
+    /// Opens a window like [`AdwaitaWindow::open`], with a custom widget as
+    /// the title of its header bar, e.g. a search entry or a segmented
+    /// control.
+    ///
+    /// `build_title` is called on the GTK main thread, and is given an
+    /// [`AdwaitaTitleEmitter`] which the widget's signal handlers can use to
+    /// forward interactions into Bevy as [`AdwaitaTitleWidgetEvent`]s. The
+    /// widget is placed according to [`AdwaitaWindowConfig::header_title`].
+    /// This only has an effect with [`AdwaitaHeaderBar::Full`].
+    #[must_use]
+    pub fn open_with_title_widget(
+        config: AdwaitaWindowConfig,
+        build_title: impl FnOnce(AdwaitaTitleEmitter) -> gtk::Widget + Send + 'static,
+    ) -> impl EntityCommand {
+        Self::open_with(
+            config,
+            WindowBuilders {
+                title: Some(BuildTitle(Box::new(build_title))),
+                ..default()
+            },
+            None,
+        )
+    }
+
+    /// Opens a window like [`AdwaitaWindow::open`], with bars along the
+    /// bottom of the window, below the render area, e.g. an action bar with
+    /// the primary actions on mobile.
+    ///
+    /// `build_bottom_bars` is called on the GTK main thread, and returns the
+    /// bars from top to bottom, e.g. [`gtk::ActionBar`]s or
+    /// [`adw::HeaderBar`]s. They are styled according to
+    /// [`AdwaitaWindowConfig::bottom_bar_style`], and the render area only
+    /// fills the space above them.
+    #[must_use]
+    pub fn open_with_bottom_bars(
+        config: AdwaitaWindowConfig,
+        build_bottom_bars: impl FnOnce() -> Vec<gtk::Widget> + Send + 'static,
+    ) -> impl EntityCommand {
+        Self::open_with(
+            config,
+            WindowBuilders {
+                bottom_bars: Some(BuildBottomBars(Box::new(build_bottom_bars))),
+                ..default()
+            },
+            None,
+        )
+    }
+
+    /// Opens a window like [`AdwaitaWindow::open`], whose content is a stack
+    /// of views which the user switches between, e.g. "Design", "Preview" and
+    /// "Code" modes in an editor.
+    ///
+    /// The render area is the page described by `render_view`, and
+    /// `build_pages` is called on the GTK main thread to build the other,
+    /// native GTK pages. With [`AdwaitaHeaderBar::Full`], a view switcher
+    /// replaces the window title in the header bar, and moves to a bar at the
+    /// bottom of the window when it's too narrow - otherwise, it is always at
+    /// the bottom. Whenever the visible view changes, an [`AdwaitaViewChanged`]
+    /// event is sent.
+    ///
+    /// While another page is visible, the render area keeps its last size and
+    /// Bevy keeps rendering into it.
+    #[must_use]
+    pub fn open_with_views(
+        config: AdwaitaWindowConfig,
+        render_view: AdwaitaView,
+        build_pages: impl FnOnce() -> Vec<(AdwaitaView, gtk::Widget)> + Send + 'static,
+    ) -> impl EntityCommand {
+        Self::open_with(
+            config,
+            WindowBuilders {
+                views: Some(BuildViews {
+                    render_view,
+                    build_pages: Box::new(build_pages),
+                }),
+                ..default()
+            },
+            None,
+        )
+    }
+
+    /// Opens a window which mirrors the frames presented by the window
+    /// `source`, e.g. to show the same render on a second monitor for a
+    /// presentation.
+    ///
+    /// Bevy doesn't render anything for a mirror window, and it has no render
+    /// target of its own. Instead, GTK presents `source`'s latest frame into
+    /// it, scaled to fit the mirror's size, without copying the frame. If the
+    /// aspect ratios differ, the gaps are filled with
+    /// [`AdwaitaWindowConfig::backdrop_color`].
+    ///
+    /// Frame pacing follows `source` alone: the mirror redraws at its own
+    /// refresh rate, showing whichever frame `source` presented last, so on a
+    /// display with a different refresh rate some frames may be shown twice or
+    /// skipped. If `source` stops rendering, e.g. due to
+    /// [`AdwaitaWindowConfig::pause_rendering_when_hidden`], the mirror keeps
+    /// showing its last frame, and once `source` closes, the mirror is cleared.
+    #[must_use]
+    pub fn open_mirror(config: AdwaitaWindowConfig, source: Entity) -> impl EntityCommand {
+        Self::open_with(config, WindowBuilders::default(), Some(source))
+    }
+
+    fn open_with(
+        config: AdwaitaWindowConfig,
+        builders: WindowBuilders,
+        mirror_of: Option<Entity>,
+    ) -> impl EntityCommand {
+        move |entity, world: &mut World| {
+            info!(
+                "Creating new Adwaita window \"{}\" ({entity})",
+                config.title
+            );
+
+            let (send_command, recv_command) = flume::bounded::<WindowMessage>(COMMAND_CAPACITY);
+            let render_target_width = Arc::new(AtomicI32::new(-1));
+            let render_target_height = Arc::new(AtomicI32::new(-1));
+            let scale_factor = Arc::new(AtomicU32::new(0));
+            let shared_next_frame = Arc::new(AtomicOptionBox::<FrameInfo>::none());
+            let closed = Arc::new(AtomicBool::new(false));
+            let focused = Arc::new(AtomicBool::new(false));
+            let fullscreen = Arc::new(AtomicBool::new(config.fullscreen));
+            let maximized = Arc::new(AtomicBool::new(config.maximized));
+            let pending_title = Arc::new(AtomicOptionBox::<String>::none());
+            let (send_event, recv_event) = flume::unbounded::<WindowEvent>();
+            let frames_in_flight = Arc::new(FramesInFlight::default());
+            let produced_frame_id = Arc::new(AtomicU64::new(0));
+            let presented_frame_id = Arc::new(AtomicU64::new(0));
+            let max_frames_in_flight = config.max_frames_in_flight;
+            let last_config = config.clone();
+            if config.tearing == AdwaitaTearing::LowLatency {
+                warn!(
+                    "Adwaita window {entity} requested low latency tearing, but GTK doesn't \
+                     support the tearing control protocol, so presenting tear-free instead"
+                );
+            }
+            let software_present = config.force_software_present.then(|| {
+                warn!("Adwaita window {entity} uses software present (slow)");
+                SoftwarePresent {
+                    texture: None,
+                    shared_frame: Arc::new(AtomicOptionBox::none()),
+                    readback_nanos: Arc::new(AtomicU64::new(0)),
+                }
+            });
+            let shared_software_frame = software_present
+                .as_ref()
+                .map(|software| software.shared_frame.clone());
+            let send_frame_tick = world
+                .get::<PrimaryAdwaitaWindow>(entity)
+                .and(world.get_resource::<FrameClockTicks>())
+                .map(|ticks| ticks.send.clone());
+            let request = WindowOpen {
+                entity,
+                config: config.clone(),
+                builders,
+                recv_command,
+                render_target_width: render_target_width.clone(),
+                render_target_height: render_target_height.clone(),
+                shared_next_frame: shared_next_frame.clone(),
+                scale_factor: scale_factor.clone(),
+                closed: closed.clone(),
+                focused: focused.clone(),
+                fullscreen: fullscreen.clone(),
+                maximized: maximized.clone(),
+                pending_title: pending_title.clone(),
+                send_event,
+                frames_in_flight: frames_in_flight.clone(),
+                presented_frame_id: presented_frame_id.clone(),
+                shared_software_frame,
+                send_frame_tick,
+                mirror_of,
+            };
+
+            let render_target_handle = match config.render_target_handle {
+                Some(handle) if is_texture_view_handle_free(world, handle) => handle,
+                Some(handle) => {
+                    warn!(
+                        "Adwaita window {entity} render target handle {handle:?} is already in \
+                         use, picking a random one instead"
+                    );
+                    unique_texture_view_handle(world.resource::<ManualTextureViews>())
+                }
+                None => unique_texture_view_handle(world.resource::<ManualTextureViews>()),
+            };
+            let (send_capture, pending_captures) = flume::unbounded();
+
+            world.entity_mut(entity).insert((
+                AdwaitaWindow {
+                    send_command,
+                    render_target_width,
+                    render_target_height,
+                    scale_factor,
+                    shared_next_frame,
+                    closed,
+                    focused,
+                    fullscreen,
+                    maximized,
+                    pending_title,
+                    recv_event,
+                    render_target_handle,
+                    last_render_target_size: UVec2::new(0, 0),
+                    next_frame_to_render: Arc::new(AtomicOptionBox::none()),
+                    frames_in_flight,
+                    max_frames_in_flight,
+                    next_frame_id: AtomicU64::new(1),
+                    produced_frame_id,
+                    #[cfg(feature = "pipewire")]
+                    produced_buffer_index: Arc::default(),
+                    presented_frame_id,
+                    send_capture,
+                    pending_captures,
+                    last_config,
+                    secondary_targets: HashMap::new(),
+                    removed_secondary_targets: Vec::new(),
+                    rendering_paused: false,
+                    rendering_enabled: true,
+                    pressed_keys: HashMap::new(),
+                    cursor_position: None,
+                    throttled: false,
+                    skipping_since: None,
+                    last_frame_at: None,
+                    opened_at: Instant::now(),
+                    tearing: AdwaitaTearing::TearFree,
+                    transparent: config.transparent,
+                    ready: false,
+                    focus_reported: false,
+                    first_size_timed_out: false,
+                    software_present,
+                    pending_resize: None,
+                    render_target_allocations: 0,
+                    render_scale: initial_render_scale(&config),
+                    mirror_of,
+                    adaptive_render_scale: render::AdaptiveRenderScale::default(),
+                    last_dmabuf_modifier: None,
+                    linear_dmabuf_fallback: false,
+                    swapchain: Vec::new(),
+                    current_buffer: 0,
+                    copy_source: None,
+                    diagnostics_updated_at: None,
+                    #[cfg(feature = "pipewire")]
+                    pipewire_stream: None,
+                },
+                config,
+                AdwaitaCursorPosition::default(),
+                AdwaitaWindowState::default(),
+                AdwaitaSafeArea::default(),
+                AdwaitaMonitor::default(),
+                AdwaitaFrameStats::default(),
+                AdwaitaRenderTargetSize::default(),
+            ));
+            if let Err(flume::SendError(request)) =
+                world.resource::<SendWindowOpen>().0.send(request)
+            {
+                let kind = AdwaitaWindowErrorKind::ChannelClosed;
+                error!("Failed to open Adwaita window {entity}: {kind}");
+                // despawned in `poll_windows` like any other closed window
+                request.closed.store(true, Ordering::SeqCst);
+                world.send_event(AdwaitaWindowError {
+                    window: entity,
+                    kind,
+                });
+            }
+        }
+    }
+
+    /// Sends a command to change this window on the GTK side.
+    ///
+    /// Commands are applied in the order they are sent, the next time the GTK
+    /// thread polls this window. All other setters on [`AdwaitaWindow`] send
+    /// their command through this.
+    ///
+    /// # Errors
+    ///
+    /// Errors if the window has closed, if too many commands were sent since
+    /// the GTK thread last polled it, or if the command's size is too large
+    /// for GTK. In any case, the command is dropped.
+    pub fn send_command(&self, command: AdwaitaWindowCommand) -> Result<(), AdwaitaCommandError> {
+        if let AdwaitaWindowCommand::SetSize(size) = command {
+            if i32::try_from(size.max_element()).is_err() {
+                return Err(AdwaitaCommandError::SizeTooLarge(size));
+            }
+        }
+        self.send_message(WindowMessage::Command(command))
+    }
+
+    /// Runs `f` on the GTK main thread with this window's [`gtk::Window`],
+    /// in order with the commands sent to this window.
+    ///
+    /// This is the escape hatch for anything this crate doesn't wrap, e.g.
+    /// adding your own widgets or tweaking GTK properties. `f` runs inside
+    /// the GTK main loop, which also presents frames for every window, so it
+    /// must return quickly: don't block on the Bevy app, and don't run a
+    /// nested main loop. Results can be sent back to Bevy over a channel,
+    /// such as one created with [`flume`].
+    ///
+    /// If the window closes before `f` runs, it's dropped without running.
+    ///
+    /// # Errors
+    ///
+    /// Errors in the same cases as [`AdwaitaWindow::send_command`].
+    pub fn run_on_main_thread(
+        &self,
+        f: impl FnOnce(&gtk::Window) + Send + 'static,
+    ) -> Result<(), AdwaitaCommandError> {
+        self.send_message(WindowMessage::Run(RunOnMainThread(Box::new(f))))
+    }
+
+    /// Runs `f` on the GTK main thread with this window's [`gtk::Window`] and
+    /// its [`gdk::Surface`], as soon as the window is realized.
+    ///
+    /// This is for interop which the crate doesn't wrap, such as custom
+    /// Vulkan or EGL work or adding subsurfaces. The [`gdk::Display`] is
+    /// available from [`gdk::Surface::display`], and platform handles such as
+    /// the `wl_surface` can be taken from them with the `gdk4-wayland` or
+    /// `gdk4-x11` crates.
+    ///
+    /// The same rules as [`AdwaitaWindow::run_on_main_thread`] apply. On top of
+    /// that, the surface is only valid until the window is unrealized, e.g.
+    /// when it's closed, and GTK may create a new surface if the window is
+    /// realized again. Any raw handle taken from the surface or display must
+    /// not outlive them, and must only be used from the GTK main thread unless
+    /// the platform API says otherwise. Don't destroy, reconfigure or commit
+    /// the surface yourself, since GTK manages it.
+    ///
+    /// # Errors
+    ///
+    /// Errors in the same cases as [`AdwaitaWindow::send_command`].
+    pub fn run_with_surface(
+        &self,
+        f: impl FnOnce(&gtk::Window, &gdk::Surface) + Send + 'static,
+    ) -> Result<(), AdwaitaCommandError> {
+        self.run_on_main_thread(move |window| adwaita_app::run_with_surface(window, f))
+    }
+
+    fn send_message(&self, message: WindowMessage) -> Result<(), AdwaitaCommandError> {
+        self.send_command
+            .try_send(message)
+            .map_err(|err| match err {
+                flume::TrySendError::Full(_) => AdwaitaCommandError::Full,
+                flume::TrySendError::Disconnected(_) => AdwaitaCommandError::Disconnected,
+            })
+    }
+
+    #[must_use]
+    pub const fn render_target_handle(&self) -> ManualTextureViewHandle {
+        self.render_target_handle
+    }
+
+    #[must_use]
+    pub const fn render_target(&self) -> RenderTarget {
+        RenderTarget::TextureView(self.render_target_handle)
+    }
+
+    /// Adds a secondary render target called `name` to this window, returning
+    /// its handle.
+    ///
+    /// Secondary render targets always have the same size as the main
+    /// [`AdwaitaWindow::render_target`], but are never presented by GTK. Use
+    /// them to render separate passes (e.g. UI and 3D) which you composite
+    /// into the main render target yourself.
+    ///
+    /// The texture behind the handle is allocated in [`PreUpdate`], once the
+    /// window has a size. If a target with this name already exists, its
+    /// handle is returned instead.
+    pub fn add_secondary_render_target(
+        &mut self,
+        name: impl Into<String>,
+        manual_texture_views: &ManualTextureViews,
+    ) -> ManualTextureViewHandle {
+        let name = name.into();
+        if let Some(target) = self.secondary_targets.get(&name) {
+            return target.handle;
+        }
+
+        let mut handle = unique_texture_view_handle(manual_texture_views);
+        while handle == self.render_target_handle
+            || self.secondary_targets.values().any(|t| t.handle == handle)
+        {
+            handle = unique_texture_view_handle(manual_texture_views);
+        }
+        self.secondary_targets.insert(
+            name,
+            SecondaryRenderTarget {
+                handle,
+                size: UVec2::ZERO,
+                texture: None,
+                image: None,
+            },
+        );
+        handle
+    }
+
+    /// Removes the secondary render target called `name` from this window,
+    /// returning its handle if it existed.
+    ///
+    /// The texture behind the handle is freed in [`PreUpdate`].
+    pub fn remove_secondary_render_target(
+        &mut self,
+        name: &str,
+    ) -> Option<ManualTextureViewHandle> {
+        let target = self.secondary_targets.remove(name)?;
+        self.removed_secondary_targets.push(target.handle);
+        Some(target.handle)
+    }
+
+    /// Gets the render target for the secondary render target called `name`.
+    #[must_use]
+    pub fn secondary_render_target(&self, name: &str) -> Option<RenderTarget> {
+        self.secondary_targets
+            .get(name)
+            .map(|target| RenderTarget::TextureView(target.handle))
+    }
+
+    /// Gets an [`Image`] which shows the contents of the secondary render
+    /// target called `name`, e.g. to display it in a `UiImage` for a
+    /// picture-in-picture preview. The same handle is returned every time.
+    ///
+    /// The image is read-only: its data in [`Assets<Image>`] is a 1x1
+    /// placeholder, and its size doesn't follow the render target's, so size
+    /// the UI node showing it explicitly, e.g. from
+    /// [`AdwaitaRenderTargetSize`]. On the GPU, it is swapped out for the
+    /// render target's texture every frame.
+    ///
+    /// Secondary render targets are never exported to GTK, so they can be
+    /// sampled freely. However, Bevy only orders rendering by camera, so the
+    /// camera rendering into this target must have a lower [`Camera::order`]
+    /// than the camera displaying it, otherwise the image shows the previous
+    /// frame. The main [`AdwaitaWindow::render_target`] can't be used this
+    /// way, since GTK may be presenting it at the same time.
+    pub fn secondary_render_target_image(
+        &mut self,
+        name: &str,
+        images: &mut Assets<Image>,
+    ) -> Option<Handle<Image>> {
+        let target = self.secondary_targets.get_mut(name)?;
+        let image = target
+            .image
+            .get_or_insert_with(|| images.add(render::placeholder_image()));
+        Some(image.clone())
+    }
+
+    /// Gets the window which this window mirrors, if it was opened with
+    /// [`AdwaitaWindow::open_mirror`].
+    #[must_use]
+    pub const fn mirror_of(&self) -> Option<Entity> {
+        self.mirror_of
+    }
+
+    /// Iterates over the names and handles of all secondary render targets.
+    pub fn secondary_render_targets(
+        &self,
+    ) -> impl Iterator<Item = (&str, ManualTextureViewHandle)> {
+        self.secondary_targets
+            .iter()
+            .map(|(name, target)| (name.as_str(), target.handle))
+    }
+
+    pub fn set_maximized(&self, maximized: bool) -> Result<(), AdwaitaCommandError> {
+        self.send_command(WindowCommand::SetMaximized(maximized))
+    }
+
+    pub fn maximize(&self) -> Result<(), AdwaitaCommandError> {
+        self.set_maximized(true)
+    }
+
+    pub fn unmaximize(&self) -> Result<(), AdwaitaCommandError> {
+        self.set_maximized(false)
+    }
+
+    /// Requests a copy of the next frame rendered into this window, which is
+    /// sent as an [`AdwaitaCaptureReady`] once it's read back from the GPU.
+    ///
+    /// The frame is read back once it has finished rendering, from the same
+    /// render target that GTK presents, so it matches what is shown. While
+    /// the window isn't rendering, e.g. because it's hidden or throttled, the
+    /// capture waits until it renders again. Windows which mirror another
+    /// window never render, so capture the mirrored window instead.
+    ///
+    /// Reading back stalls the render world, so this is meant for one-off
+    /// screenshots, not recording every frame.
+    pub fn request_capture(&self) -> AdwaitaCapture {
+        static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+        let id = AdwaitaCapture(NEXT_ID.fetch_add(1, Ordering::Relaxed));
+        // we hold the receiver, so this can't fail
+        _ = self.send_capture.send(id);
+        id
+    }
+
+    /// Whether the window currently has keyboard focus.
+    ///
+    /// See [`WindowFocused`] to be notified when this changes.
+    #[must_use]
+    pub fn is_focused(&self) -> bool {
+        self.focused.load(Ordering::SeqCst)
+    }
+
+    /// Whether the window is currently maximized.
+    ///
+    /// This follows the actual state of the window, so it also changes when
+    /// the user maximizes the window through the window manager, and only
+    /// changes after [`AdwaitaWindow::set_maximized`] once the window manager
+    /// has applied it.
+    #[must_use]
+    pub fn is_maximized(&self) -> bool {
+        self.maximized.load(Ordering::SeqCst)
+    }
+
+    pub fn set_fullscreen(&self, fullscreen: bool) -> Result<(), AdwaitaCommandError> {
+        self.send_command(WindowCommand::SetFullscreen(fullscreen))
+    }
+
+    pub fn fullscreen(&self) -> Result<(), AdwaitaCommandError> {
+        self.set_fullscreen(true)
+    }
+
+    pub fn unfullscreen(&self) -> Result<(), AdwaitaCommandError> {
+        self.set_fullscreen(false)
+    }
+
+    /// Whether the window is currently fullscreen.
+    ///
+    /// Like [`AdwaitaWindow::is_maximized`], this follows the actual state of
+    /// the window rather than the last requested one.
+    #[must_use]
+    pub fn is_fullscreen(&self) -> bool {
+        self.fullscreen.load(Ordering::SeqCst)
+    }
+
+    /// Sets the window's title, e.g. to show the current document or FPS.
+    ///
+    /// Unlike other commands, this never fails with
+    /// [`AdwaitaCommandError::Full`], so it's fine to call every frame: if
+    /// GTK hasn't applied the last title yet, it's replaced by this one.
+    ///
+    /// # Errors
+    ///
+    /// Errors if the window has closed.
+    pub fn set_title(&self, title: impl Into<String>) -> Result<(), AdwaitaCommandError> {
+        if self.is_closed() {
+            return Err(AdwaitaCommandError::Disconnected);
+        }
+        self.pending_title
+            .store(Some(Box::new(title.into())), Ordering::SeqCst);
+        Ok(())
+    }
+
+    /// Sets the name of the icon shown for the window, or uses the
+    /// application's if [`None`].
+    ///
+    /// See [`AdwaitaWindowConfig::icon_name`].
+    pub fn set_icon_name(&self, icon_name: Option<String>) -> Result<(), AdwaitaCommandError> {
+        self.send_command(WindowCommand::SetIconName(icon_name))
+    }
+
+    /// Gets the frame cap which currently applies, depending on whether the
+    /// window is focused.
+    fn max_fps(&self) -> Option<u32> {
+        match self.last_config.unfocused_max_fps {
+            Some(max_fps) if !self.focused.load(Ordering::SeqCst) => Some(max_fps),
+            _ => self.last_config.max_fps,
+        }
+    }
+
+    /// Checks if the frame started at `now` should be skipped to stay under
+    /// [`AdwaitaWindowConfig::max_fps`], and if not, counts it.
+    fn throttle_frame(&mut self, now: Instant) -> bool {
+        let Some(interval) = max_fps_interval(self.max_fps()) else {
+            self.last_frame_at = None;
+            return false;
+        };
+        self.last_frame_at = match self.last_frame_at {
+            Some(last) if now.duration_since(last) < interval => return true,
+            // keep to the interval on average, even if frames land just after
+            // it, but don't catch up after a long gap
+            Some(last) if now.duration_since(last) < interval * 2 => Some(last + interval),
+            _ => Some(now),
+        };
+        false
+    }
+
+    /// Checks if the frame started at `now` should be skipped with
+    /// [`AdwaitaFrameOverflow::Skip`], because GTK hasn't shown the last frame
+    /// we handed off yet.
+    fn skip_frame(&mut self, now: Instant) -> bool {
+        if self.last_config.frame_overflow != AdwaitaFrameOverflow::Skip
+            || self.presented_frame_id() >= self.produced_frame_id()
+        {
+            self.skipping_since = None;
+            return false;
+        }
+        let since = *self.skipping_since.get_or_insert(now);
+        if now.duration_since(since) >= FRAMES_IN_FLIGHT_TIMEOUT {
+            self.skipping_since = None;
+            return false;
+        }
+        true
+    }
+
+    /// Steps down to a render target layout which GTK can import, after it
+    /// failed to import one laid out with `modifier`.
+    fn fall_back_from_dmabuf(&mut self, entity: Entity, modifier: u64) {
+        if self.software_present.is_some() {
+            return;
+        }
+        if modifier != render::DRM_FORMAT_MOD_LINEAR {
+            if self.linear_dmabuf_fallback {
+                // a frame from before we reallocated
+                return;
+            }
+            warn!(
+                "Adwaita window {entity} couldn't present a render target with DRM format \
+                 modifier {modifier:#018x}, which can happen if the display is on another GPU, \
+                 so falling back to linear render targets"
+            );
+            self.linear_dmabuf_fallback = true;
+        } else {
+            warn!(
+                "Adwaita window {entity} couldn't present a linear render target, so falling \
+                 back to software present (slow)"
+            );
+            self.use_software_present();
+        }
+        // force the render target to be reallocated on the next poll
+        self.swapchain.clear();
+        self.last_render_target_size = UVec2::ZERO;
+    }
+
+    /// Switches to reading frames back into CPU memory, for when render
+    /// targets can't be shared with GTK as dmabufs at all.
+    fn use_software_present(&mut self) {
+        let software = SoftwarePresent {
+            texture: None,
+            shared_frame: Arc::new(AtomicOptionBox::none()),
+            readback_nanos: Arc::new(AtomicU64::new(0)),
+        };
+        _ = self
+            .send_command
+            .try_send(WindowMessage::UseSoftwarePresent(
+                software.shared_frame.clone(),
+            ));
+        self.software_present = Some(software);
+    }
+
+    /// Gets the size to render at if GTK still hasn't laid out the render area
+    /// after [`AdwaitaWindowConfig::first_size_timeout`], warning about it
+    /// once.
+    fn first_size_fallback(&mut self, entity: Entity, now: Instant) -> Option<UVec2> {
+        if self.last_render_target_size != UVec2::ZERO {
+            return None;
+        }
+        let timeout = self.last_config.first_size_timeout?;
+        if now.duration_since(self.opened_at) < timeout {
+            return None;
+        }
+        let fallback = self
+            .last_config
+            .fall_back_to_config_size
+            .then(|| UVec2::new(self.last_config.width, self.last_config.height))
+            .filter(|size| size.cmpgt(UVec2::ZERO).all());
+        if !self.first_size_timed_out {
+            self.first_size_timed_out = true;
+            warn!(
+                "Adwaita window {entity} render area wasn't laid out within {timeout:?} of \
+                 opening, so nothing can be rendered to it; the render widget may not be in \
+                 the window, or setting it up may have failed{}",
+                if fallback.is_some() {
+                    ", falling back to the configured size"
+                } else {
+                    ""
+                }
+            );
+        }
+        fallback
+    }
+
+    /// Size in device pixels which the render target should have, from the
+    /// render widget's size and scale factor as last reported by GTK, or
+    /// [`None`] if GTK hasn't laid it out yet.
+    fn render_target_size(&mut self, entity: Entity, now: Instant) -> Option<UVec2> {
+        let (width, height, scale_factor) = (
+            self.render_target_width.load(Ordering::SeqCst),
+            self.render_target_height.load(Ordering::SeqCst),
+            f32::from_bits(self.scale_factor.load(Ordering::SeqCst)),
+        );
+        let (width, height, scale_factor) = match (u32::try_from(width), u32::try_from(height)) {
+            (Ok(width), Ok(height)) if width > 0 && height > 0 && scale_factor > 0.0 => {
+                (width, height, scale_factor)
+            }
+            // while resizing, GTK may briefly lay us out at 0 width or height -
+            // keep the last good render target (which GTK keeps presenting)
+            // until we get a real size, instead of flashing a tiny one
+            _ => match self.first_size_fallback(entity, now) {
+                Some(size) if scale_factor > 0.0 => (size.x, size.y, scale_factor),
+                Some(size) => (size.x, size.y, 1.0),
+                None => return None,
+            },
+        };
+
+        let (width, height) = match self.last_config.max_size {
+            // shrink both sides by the same factor, so the frame still fills
+            // the render area when it's scaled up
+            Some(max_size) if width > max_size.x || height > max_size.y => {
+                let factor =
+                    (max_size.x as f32 / width as f32).min(max_size.y as f32 / height as f32);
+                (
+                    ((width as f32 * factor).round() as u32).max(1),
+                    ((height as f32 * factor).round() as u32).max(1),
+                )
+            }
+            _ => (width, height),
+        };
+
+        // with fractional scaling, allocate at the exact device pixel size so
+        // that the compositor doesn't have to rescale (and blur) the frame
+        Some(render::scaled_size(
+            render::physical_size(UVec2::new(width, height), scale_factor),
+            self.render_scale,
+        ))
+    }
+
+    /// Checks if the render target should be resized to `size` now, or if we
+    /// should keep waiting for it to stop changing.
+    fn settle_resize(&mut self, size: UVec2, now: Instant) -> bool {
+        let debounce = self.last_config.resize_debounce;
+        if size == self.last_render_target_size
+            // render *something* as soon as we can
+            || self.last_render_target_size == UVec2::ZERO
+            || debounce.is_zero()
+        {
+            self.pending_resize = None;
+            return true;
+        }
+
+        match self.pending_resize {
+            Some((pending_size, since)) if pending_size == size => {
+                let settled = now.duration_since(since) >= debounce;
+                if settled {
+                    self.pending_resize = None;
+                }
+                settled
+            }
+            _ => {
+                self.pending_resize = Some((size, now));
+                false
+            }
+        }
+    }
+
+    /// Checks if the render target should be reallocated at `size`, and if so,
+    /// records that it is.
+    fn reallocate_render_target(
+        &mut self,
+        size: UVec2,
+        settled: bool,
+        buffer_count_changed: bool,
+    ) -> bool {
+        if !settled || (size == self.last_render_target_size && !buffer_count_changed) {
+            return false;
+        }
+        self.last_render_target_size = size;
+        self.render_target_allocations += 1;
+        true
+    }
+
+    /// Number of physical pixels per logical pixel on the monitor which the
+    /// window is on, e.g. `2.0` on a HiDPI display or `1.25` with fractional
+    /// scaling, or `1.0` until GTK reports it.
+    ///
+    /// The render target is already allocated at this scale, so use this to
+    /// size UI which is laid out in physical pixels. When the window moves to
+    /// a monitor with a different scale, the render target is reallocated,
+    /// and [`AdwaitaRenderTargetSize::scale_factor`] changes too.
+    #[must_use]
+    pub fn scale_factor(&self) -> f64 {
+        f64::from(self.scale_factor_f32())
+    }
+
+    fn scale_factor_f32(&self) -> f32 {
+        match f32::from_bits(self.scale_factor.load(Ordering::SeqCst)) {
+            scale_factor if scale_factor > 0.0 => scale_factor,
+            _ => 1.0,
+        }
+    }
+
+    fn logical_to_physical(&self, position: Vec2) -> Vec2 {
+        position * self.scale_factor_f32()
+    }
+
+    /// Maps a position in logical pixels relative to the render widget into
+    /// pixels of the render target, which cameras rendering to it use.
+    ///
+    /// The render target may be smaller than the widget with a render scale,
+    /// and briefly lags behind it while resizing, so this scales by the size
+    /// which is actually allocated rather than the scale factor.
+    fn logical_to_render_target(&self, position: Vec2) -> Vec2 {
+        let logical = Vec2::new(
+            self.render_target_width.load(Ordering::SeqCst) as f32,
+            self.render_target_height.load(Ordering::SeqCst) as f32,
+        );
+        let physical = self.last_render_target_size.as_vec2();
+        if logical.cmple(Vec2::ZERO).any() || physical.cmple(Vec2::ZERO).any() {
+            return self.logical_to_physical(position);
+        }
+        position * physical / logical
+    }
+
+    /// Whether the GTK side of this window has closed.
+    ///
+    /// Once closed, the window entity is despawned in [`PreUpdate`] of the
+    /// next frame, so systems may see a closed window for up to one frame.
+    /// Commands sent to it in the meantime fail with
+    /// [`AdwaitaCommandError::Disconnected`].
+    #[must_use]
+    pub fn is_closed(&self) -> bool {
+        self.closed.load(Ordering::SeqCst)
+    }
+
+    /// Whether the GTK side of this window is still open.
+    ///
+    /// See [`AdwaitaWindow::is_closed`].
+    #[must_use]
+    pub fn is_open(&self) -> bool {
+        !self.is_closed()
+    }
+
+    /// Gets the tearing mode in effect for this window, after falling back
+    /// from [`AdwaitaWindowConfig::tearing`] if it isn't supported.
+    #[must_use]
+    pub fn tearing(&self) -> AdwaitaTearing {
+        self.tearing
+    }
+
+    /// Whether this window's render target has been allocated with a valid
+    /// size, so cameras targeting it will render.
+    ///
+    /// See [`AdwaitaWindowReady`].
+    #[must_use]
+    pub fn is_ready(&self) -> bool {
+        self.ready
+    }
+
+    /// Sends a [`KeyboardInput`] for a key press or release from GTK, unless
+    /// it's a repeat of a key which is already held down.
+    fn forward_key(
+        &mut self,
+        entity: Entity,
+        pressed: bool,
+        keyval: u32,
+        keycode: u32,
+        keyboard_events: &mut EventWriter<KeyboardInput>,
+    ) {
+        let (state, keyval) = if pressed {
+            // Bevy expects one press per release, but GTK sends a press for
+            // every key repeat
+            if self.pressed_keys.insert(keycode, keyval).is_some() {
+                return;
+            }
+            (ButtonState::Pressed, keyval)
+        } else {
+            // the modifiers may have changed since the key was pressed, but
+            // the release should be for the same logical key
+            let pressed_keyval = self.pressed_keys.remove(&keycode);
+            (ButtonState::Released, pressed_keyval.unwrap_or(keyval))
+        };
+        keyboard_events.send(KeyboardInput {
+            key_code: keyboard::key_code(keycode),
+            logical_key: keyboard::logical_key(keyval),
+            state,
+            window: entity,
+        });
+    }
+
+    /// Releases every key which is held down, e.g. when the window loses
+    /// focus and GTK stops sending us key events, so that no key is stuck in
+    /// [`ButtonInput<KeyCode>`].
+    fn release_keys(&mut self, entity: Entity, keyboard_events: &mut EventWriter<KeyboardInput>) {
+        for (keycode, keyval) in self.pressed_keys.drain() {
+            keyboard_events.send(KeyboardInput {
+                key_code: keyboard::key_code(keycode),
+                logical_key: keyboard::logical_key(keyval),
+                state: ButtonState::Released,
+                window: entity,
+            });
+        }
+    }
+
+    /// Sets whether Bevy renders frames to this window.
+    ///
+    /// While disabled, the window keeps showing the last frame it was given,
+    /// and all active cameras targeting it are made inactive, like with
+    /// [`AdwaitaWindowConfig::pause_rendering_when_hidden`]. Other windows
+    /// keep rendering as usual, so this can freeze e.g. a background window
+    /// in a multi-window app.
+    pub fn set_rendering_enabled(&mut self, enabled: bool) {
+        self.rendering_enabled = enabled;
+    }
+
+    /// Whether Bevy renders frames to this window.
+    ///
+    /// See [`AdwaitaWindow::set_rendering_enabled`].
+    #[must_use]
+    pub fn is_rendering_enabled(&self) -> bool {
+        self.rendering_enabled
+    }
+
+    /// ID of the latest frame which Bevy finished rendering for this window.
+    ///
+    /// Frame IDs start at 1 and increase by 1 for every frame rendered, so
+    /// comparing this with [`AdwaitaWindow::presented_frame_id`] tells you how
+    /// far behind presentation is. This is 0 if no frame has been rendered
+    /// yet.
+    #[must_use]
+    pub fn produced_frame_id(&self) -> u64 {
+        self.produced_frame_id.load(Ordering::SeqCst)
+    }
+
+    /// ID of the latest frame which GTK presented for this window.
+    ///
+    /// GTK only presents the latest frame it has, so frames between two
+    /// consecutive presented IDs were dropped. This is 0 if no frame has been
+    /// presented yet.
+    #[must_use]
+    pub fn presented_frame_id(&self) -> u64 {
+        self.presented_frame_id.load(Ordering::SeqCst)
+    }
+
+    /// Gets the dmabuf which the render target being rendered into this frame
+    /// is exported as, with a duplicated fd.
+    ///
+    /// This is [`None`] if no render target has been allocated yet, or if the
+    /// window presents frames through software or mirrors another window.
+    ///
+    /// Frames are rendered into each of the
+    /// [`AdwaitaWindowConfig::swapchain_buffers`] in turn, so this returns a
+    /// different buffer every frame - see [`AdwaitaWindow::dmabufs`] to get
+    /// all of them. The buffer is rendered into and read by GTK without any
+    /// synchronization with other consumers, so a consumer may see a frame
+    /// which is still being rendered.
+    ///
+    /// # Errors
+    ///
+    /// Errors if the fd could not be duplicated.
+    pub fn dmabuf(&self) -> io::Result<Option<AdwaitaDmabuf>> {
+        self.swapchain
+            .get(self.current_buffer)
+            .map(|buffer| export_dmabuf(&buffer.dmabuf))
+            .transpose()
+    }
+
+    /// Gets the dmabufs of every render target in the swapchain, in the
+    /// order that frames are rendered into them, with duplicated fds.
+    ///
+    /// See [`AdwaitaWindow::dmabuf`].
+    ///
+    /// # Errors
+    ///
+    /// Errors if any fd could not be duplicated.
+    pub fn dmabufs(&self) -> io::Result<Vec<AdwaitaDmabuf>> {
+        self.swapchain
+            .iter()
+            .map(|buffer| export_dmabuf(&buffer.dmabuf))
+            .collect()
+    }
+
+    /// Starts publishing this window's render target as a PipeWire video
+    /// source named `name`, replacing any stream which was already running.
+    ///
+    /// The stream can be used as a virtual camera, or captured by e.g. OBS
+    /// without having to capture the whole window. Frames are shared as
+    /// dmabufs without being copied, so this does nothing for windows which
+    /// don't have one - see [`AdwaitaWindow::dmabuf`].
+    ///
+    /// # Errors
+    ///
+    /// Errors if PipeWire isn't running, or the stream could not be created.
+    #[cfg(feature = "pipewire")]
+    pub fn start_pipewire_stream(
+        &mut self,
+        name: impl Into<String>,
+    ) -> Result<(), AdwaitaPipeWireError> {
+        self.pipewire_stream = None;
+        let stream = pipewire_stream::PipeWireStream::start(name.into())?;
+        if let Ok(dmabufs) = self.dmabufs() {
+            if !dmabufs.is_empty() {
+                stream.set_buffers(dmabufs);
+            }
+        }
+        self.pipewire_stream = Some(stream);
+        Ok(())
+    }
+
+    /// Stops the PipeWire stream started by
+    /// [`AdwaitaWindow::start_pipewire_stream`], if there is one.
+    #[cfg(feature = "pipewire")]
+    pub fn stop_pipewire_stream(&mut self) {
+        self.pipewire_stream = None;
+    }
+
+    /// ID of the PipeWire node which this window's stream is published as,
+    /// which consumers can connect to.
+    ///
+    /// This is [`None`] if there is no stream, or it hasn't been assigned a
+    /// node yet, which happens shortly after it receives its first frame.
+    #[cfg(feature = "pipewire")]
+    #[must_use]
+    pub fn pipewire_node_id(&self) -> Option<u32> {
+        self.pipewire_stream
+            .as_ref()
+            .and_then(pipewire_stream::PipeWireStream::node_id)
+    }
+
+    /// Resizes the window to `size` in logical pixels, even if it's not
+    /// [`AdwaitaWindowConfig::user_resizable`].
+    ///
+    /// Errors with [`AdwaitaCommandError::SizeTooLarge`] if either component
+    /// doesn't fit into an `i32`.
+    pub fn set_size(&self, size: UVec2) -> Result<(), AdwaitaCommandError> {
+        self.send_command(WindowCommand::SetSize(size))
+    }
+
+    pub fn set_user_resizable(&self, user_resizable: bool) -> Result<(), AdwaitaCommandError> {
+        self.send_command(WindowCommand::SetUserResizable(user_resizable))
+    }
+
+    pub fn set_skip_taskbar(&self, skip_taskbar: bool) -> Result<(), AdwaitaCommandError> {
+        self.send_command(WindowCommand::SetSkipTaskbar(skip_taskbar))
+    }
+
+    /// Sets which window this one is transient for.
+    ///
+    /// See [`AdwaitaWindowConfig::transient_for`].
+    pub fn set_transient_for(&self, parent: Option<Entity>) -> Result<(), AdwaitaCommandError> {
+        self.send_command(WindowCommand::SetTransientFor(parent))
+    }
+
+    pub fn set_skip_pager(&self, skip_pager: bool) -> Result<(), AdwaitaCommandError> {
+        self.send_command(WindowCommand::SetSkipPager(skip_pager))
+    }
+
+    pub fn set_hide_on_close(&self, hide_on_close: bool) -> Result<(), AdwaitaCommandError> {
+        self.send_command(WindowCommand::SetHideOnClose(hide_on_close))
+    }
+
+    /// Switches to the view with ID `view_id`, in a window opened with
+    /// [`AdwaitaWindow::open_with_views`].
+    pub fn set_view(&self, view_id: impl Into<String>) -> Result<(), AdwaitaCommandError> {
+        self.send_command(WindowCommand::SetView(view_id.into()))
+    }
+
+    /// Shows progress of a long operation, e.g. loading assets or exporting,
+    /// between `0.0` and `1.0`, or hides it if [`None`].
+    ///
+    /// Progress is shown as a bar at the top of the render area of windows
+    /// created by the crate, and on the application's icon in docks and
+    /// taskbars which support Unity's `LauncherEntry` API. The latter needs an
+    /// [`AdwaitaWindowPlugin::application_id`] which matches the app's
+    /// desktop file, and is silently skipped otherwise.
+    pub fn set_progress(&self, progress: Option<f32>) -> Result<(), AdwaitaCommandError> {
+        let progress = progress.map(|progress| progress.clamp(0.0, 1.0));
+        self.send_command(WindowCommand::SetProgress(progress))
+    }
+
+    /// Sets the cursor shown while the pointer is over the render area.
+    ///
+    /// The cursor changes straight away if the pointer is already over the
+    /// render area, and otherwise the next time it enters. While the cursor
+    /// is hidden with [`AdwaitaWindow::set_cursor_visible`], this is the
+    /// cursor shown once it's visible again.
+    pub fn set_cursor_icon(&self, icon: AdwaitaCursorIcon) -> Result<(), AdwaitaCommandError> {
+        self.send_command(WindowCommand::SetCursorIcon(icon))
+    }
+
+    /// Shows or hides the cursor while the pointer is over the render area.
+    ///
+    /// See [`AdwaitaWindowConfig::cursor_visible`].
+    pub fn set_cursor_visible(&self, visible: bool) -> Result<(), AdwaitaCommandError> {
+        self.send_command(WindowCommand::SetCursorVisible(visible))
+    }
+
+    /// Sets the cursor theme and cursor size, or follows the system setting
+    /// for either if [`None`].
+    ///
+    /// See [`AdwaitaWindowConfig::cursor_theme_name`].
+    pub fn set_cursor_theme(
+        &self,
+        name: Option<String>,
+        size: Option<u32>,
+    ) -> Result<(), AdwaitaCommandError> {
+        self.send_command(WindowCommand::SetCursorTheme { name, size })
+    }
+
+    /// Shows GTK's emoji chooser over the render target of a window with
+    /// [`AdwaitaWindowConfig::text_input`], like pressing `Ctrl+.` in a GTK
+    /// text field.
+    ///
+    /// The chosen emoji is sent as an [`AdwaitaTextInput`] event, like typed
+    /// text. If the chooser is dismissed, nothing is sent.
+    pub fn show_emoji_chooser(&self) -> Result<(), AdwaitaCommandError> {
+        self.send_command(WindowCommand::ShowEmojiChooser)
+    }
+
+    /// Moves the window to another workspace (virtual desktop), where `0` is
+    /// the first one, or `u32::MAX` to show it on all of them.
+    ///
+    /// This uses the EWMH `_NET_WM_DESKTOP` mechanism, so it's only supported
+    /// on X11 with the `x11` feature enabled, and is ignored with a warning
+    /// elsewhere. See [`AdwaitaRequests::window_workspace`].
+    pub fn move_to_workspace(&self, workspace: u32) -> Result<(), AdwaitaCommandError> {
+        self.send_command(WindowCommand::MoveToWorkspace(workspace))
+    }
+
+    /// Shows a native about window for the app on top of this window, e.g.
+    /// from an "About" menu item.
+    ///
+    /// The about window is modal, but Bevy keeps rendering into this window
+    /// while it's shown, and closing it doesn't affect this window.
+    pub fn show_about(&self, about: AdwaitaAboutConfig) -> Result<(), AdwaitaCommandError> {
+        self.send_command(WindowCommand::ShowAbout(about))
+    }
+
+    /// Puts `text` on the clipboard, e.g. for a "Copy" button.
+    ///
+    /// The clipboard is shared by the whole display, so this replaces what
+    /// any app put there before. Read it back with
+    /// [`AdwaitaRequests::read_clipboard_text`], which responds with [`None`]
+    /// if the clipboard doesn't contain text.
+    pub fn set_clipboard_text(&self, text: impl Into<String>) -> Result<(), AdwaitaCommandError> {
+        self.send_command(WindowCommand::SetClipboardText(text.into()))
+    }
+
+    /// Shows a toast with `title` at the bottom of the render area, e.g.
+    /// "Saved" or "Connection lost", for `timeout_secs` seconds, or until the
+    /// user dismisses it if `0`.
+    ///
+    /// If a toast is already shown, this one is queued after it. Toasts are
+    /// only supported in windows created by the crate, not with
+    /// [`AdwaitaWindow::open_embedded`].
+    pub fn show_toast(
+        &self,
+        title: impl Into<String>,
+        timeout_secs: u32,
+    ) -> Result<(), AdwaitaCommandError> {
+        self.send_command(WindowCommand::ShowToast {
+            title: title.into(),
+            timeout_secs,
+        })
+    }
+
+    /// Moves keyboard focus back to the render area, away from any native
+    /// widget such as the search entry or a widget in the sidebar.
+    ///
+    /// Raw key events from [`AdwaitaWindowConfig::emit_raw_keys`] are sent
+    /// wherever focus is, but native widgets which take text, like entries,
+    /// consume typed text before it becomes [`AdwaitaTextInput`]. The render
+    /// area is focused when the window opens and, with
+    /// [`AdwaitaWindowConfig::focus_render_area_on_click`], whenever it's
+    /// clicked, so this is only needed to move focus back without a click,
+    /// e.g. after the search is activated.
+    pub fn focus_render_area(&self) -> Result<(), AdwaitaCommandError> {
+        self.send_command(WindowCommand::FocusRenderArea)
+    }
+
+    /// Starts dragging `content` out of this window from where the pointer
+    /// currently is, e.g. to drop an exported file into a file manager.
+    ///
+    /// Call this while a mouse button is held, once your own hit testing
+    /// decides that the pointer is dragging something; compositors usually
+    /// refuse to start a drag otherwise. An [`AdwaitaDragEnded`] event is sent
+    /// once the drag is dropped or cancelled.
+    pub fn start_drag(&self, content: AdwaitaDragContent) -> Result<(), AdwaitaCommandError> {
+        self.send_command(WindowCommand::StartDrag(content))
+    }
+
+    /// Shows or hides the search bar of a window with
+    /// [`AdwaitaWindowConfig::header_search`].
+    pub fn set_search_shown(&self, shown: bool) -> Result<(), AdwaitaCommandError> {
+        self.send_command(WindowCommand::SetSearchShown(shown))
+    }
+
+    /// Shows or hides the [`AdwaitaWindowConfig::action_bar`] of a window.
+    pub fn set_action_bar_shown(&self, shown: bool) -> Result<(), AdwaitaCommandError> {
+        self.send_command(WindowCommand::SetActionBarShown(shown))
+    }
+
+    /// Shows or hides the sidebar of a window opened with
+    /// [`AdwaitaWindow::open_with_sidebar`].
+    pub fn set_sidebar_shown(&self, shown: bool) -> Result<(), AdwaitaCommandError> {
+        self.send_command(WindowCommand::SetSidebarShown(shown))
+    }
+
+    /// Closes the window, as if the user had closed it, e.g. after an
+    /// [`AdwaitaCloseRequested`] event with
+    /// [`AdwaitaWindowConfig::intercept_close`].
+    ///
+    /// With [`AdwaitaWindowConfig::hide_on_close`], this only hides the
+    /// window.
+    pub fn confirm_close(&self) -> Result<(), AdwaitaCommandError> {
+        self.send_command(WindowCommand::Close)
+    }
+
+    /// Shows the window if it was hidden, and brings it to the front.
+    pub fn present(&self) -> Result<(), AdwaitaCommandError> {
+        self.send_command(WindowCommand::Present)
+    }
+
+    pub fn set_backdrop_color(&self, color: Option<Color>) -> Result<(), AdwaitaCommandError> {
+        let color = color.or(self.transparent.then_some(Color::NONE));
+        self.send_command(WindowCommand::SetBackdropColor(color))
+    }
+}
+
+fn is_texture_view_handle_free(world: &mut World, handle: ManualTextureViewHandle) -> bool {
+    // windows only insert their render target once it's allocated, so check
+    // them as well
+    !world.resource::<ManualTextureViews>().contains_key(&handle)
+        && !world.query::<&AdwaitaWindow>().iter(world).any(|window| {
+            window.render_target_handle == handle
+                || window
+                    .secondary_targets
+                    .values()
+                    .any(|t| t.handle == handle)
+        })
+}
+
+fn unique_texture_view_handle(
+    manual_texture_views: &ManualTextureViews,
+) -> ManualTextureViewHandle {
+    loop {
+        let handle = ManualTextureViewHandle(rand::random());
+        if !manual_texture_views.contains_key(&handle) {
+            break handle;
+        }
+    }
+}
+
+/// Marks a camera which was retargeted from [`WindowRef::Primary`] to the
+/// [`PrimaryAdwaitaWindow`], so that it follows the marker to another window.
+#[derive(Debug, Component)]
+struct FollowsPrimaryWindow;
+
+type PrimaryWindows<'w, 's> =
+    Query<'w, 's, (Entity, &'static AdwaitaWindow), With<PrimaryAdwaitaWindow>>;
+type RetargetedCameras<'w, 's> = Query<
+    'w,
+    's,
+    (Entity, &'static mut Camera, Has<FollowsPrimaryWindow>),
+    Without<NoAdwaitaRetarget>,
+>;
+
+fn update_default_camera_render_target(
+    trigger: Trigger<OnInsert, Camera>,
+    mut commands: Commands,
+    mut cameras: Query<&mut Camera, Without<NoAdwaitaRetarget>>,
+    primary_windows: PrimaryWindows,
+    windows: Query<&AdwaitaWindow>,
+) {
+    let entity = trigger.entity();
+    let Ok(mut camera) = cameras.get_mut(entity) else {
+        // opted out of retargeting
+        return;
+    };
+
+    match camera.target {
+        RenderTarget::Window(WindowRef::Primary) => {
+            let Some((_, primary_window)) = select_primary_window(&primary_windows, None) else {
+                return;
+            };
+            camera.target = primary_window.render_target();
+            commands.entity(entity).insert(FollowsPrimaryWindow);
+        }
+        RenderTarget::Window(WindowRef::Entity(window_entity)) => {
+            // if this isn't an Adwaita window (yet), leave it alone, and
+            // retarget it once the window opens
+            if let Ok(window) = windows.get(window_entity) {
+                camera.target = window.render_target();
+            }
+        }
+        _ => {}
+    }
+}
+
+fn update_existing_cameras_render_target(
+    trigger: Trigger<OnInsert, PrimaryAdwaitaWindow>,
+    mut commands: Commands,
+    primary_windows: PrimaryWindows,
+    mut cameras: RetargetedCameras,
+) {
+    // if the window isn't open yet, this is done once it is
+    if primary_windows.contains(trigger.entity()) {
+        let primary_window = select_primary_window(&primary_windows, None);
+        retarget_primary_cameras(primary_window, &mut cameras, &mut commands);
+    }
+}
+
+fn update_cameras_render_target_on_open(
+    trigger: Trigger<OnInsert, AdwaitaWindow>,
+    mut commands: Commands,
+    primary_windows: PrimaryWindows,
+    windows: Query<&AdwaitaWindow>,
+    mut cameras: RetargetedCameras,
+) {
+    let entity = trigger.entity();
+    if primary_windows.contains(entity) {
+        let primary_window = select_primary_window(&primary_windows, None);
+        retarget_primary_cameras(primary_window, &mut cameras, &mut commands);
+    }
+
+    // cameras which were spawned before the window opened
+    let Ok(window) = windows.get(entity) else {
+        return;
+    };
+    let window_ref = RenderTarget::Window(WindowRef::Entity(entity));
+    for (_, mut camera, _) in &mut cameras {
+        if camera.target == window_ref {
+            camera.target = window.render_target();
+        }
+    }
+}
+
+fn update_cameras_render_target_on_demote(
+    trigger: Trigger<OnRemove, PrimaryAdwaitaWindow>,
+    mut commands: Commands,
+    primary_windows: PrimaryWindows,
+    mut cameras: RetargetedCameras,
+) {
+    // the marker is still on the entity while this runs
+    let primary_window = select_primary_window(&primary_windows, Some(trigger.entity()));
+    retarget_primary_cameras(primary_window, &mut cameras, &mut commands);
+}
+
+/// Points cameras targeting [`WindowRef::Primary`], and cameras which were
+/// already retargeted from it, at `primary_window`.
+///
+/// If there is no primary window, retargeted cameras go back to targeting
+/// [`WindowRef::Primary`], so that they're picked up by the next one.
+fn retarget_primary_cameras(
+    primary_window: Option<(Entity, &AdwaitaWindow)>,
+    cameras: &mut RetargetedCameras,
+    commands: &mut Commands,
+) {
+    let target = primary_window.map_or(RenderTarget::Window(WindowRef::Primary), |(_, window)| {
+        window.render_target()
+    });
+
+    for (entity, mut camera, follows_primary) in cameras {
+        if !follows_primary && !matches!(camera.target, RenderTarget::Window(WindowRef::Primary)) {
+            continue;
+        }
+        if camera.target != target {
+            camera.target = target.clone();
+        }
+        if primary_window.is_some() && !follows_primary {
+            commands.entity(entity).insert(FollowsPrimaryWindow);
+        }
+    }
+}
+
+fn report_lost_camera_targets(
+    trigger: Trigger<OnRemove, AdwaitaWindow>,
+    windows: Query<&AdwaitaWindow>,
+    cameras: Query<(Entity, &Camera)>,
+    mut target_lost: EventWriter<AdwaitaCameraTargetLost>,
+) {
+    let entity = trigger.entity();
+    let Ok(window) = windows.get(entity) else {
+        return;
+    };
+
+    for (camera_entity, camera) in &cameras {
+        let RenderTarget::TextureView(handle) = camera.target else {
+            continue;
+        };
+        let targets_window = handle == window.render_target_handle
+            || window
+                .secondary_targets
+                .values()
+                .any(|target| target.handle == handle);
+        if targets_window {
+            warn!("Camera {camera_entity} renders to Adwaita window {entity}, which closed");
+            target_lost.send(AdwaitaCameraTargetLost {
+                camera: camera_entity,
+                window: entity,
+            });
+        }
+    }
+}
+
+/// Picks the window which cameras targeting [`WindowRef::Primary`] render to,
+/// ignoring `excluding`.
+///
+/// There should only be one [`PrimaryAdwaitaWindow`], but if there are more,
+/// e.g. while moving the marker from one window to another, the one with the
+/// lowest entity is used so that the choice is deterministic.
+fn select_primary_window<'a>(
+    primary_windows: &'a PrimaryWindows,
+    excluding: Option<Entity>,
+) -> Option<(Entity, &'a AdwaitaWindow)> {
+    let mut count = 0;
+    let selected = primary_windows
+        .iter()
+        .filter(|(entity, _)| Some(*entity) != excluding)
+        .inspect(|_| count += 1)
+        .min_by_key(|(entity, _)| *entity);
+    if count > 1 {
+        if let Some((entity, _)) = selected {
+            warn!(
+                "Found {count} entities with `{}`, using {entity}",
+                type_name::<PrimaryAdwaitaWindow>()
+            );
+        }
+    }
+    selected
+}
+
+fn warn_on_command_error(entity: Entity, result: Result<(), AdwaitaCommandError>) {
+    if let Err(err) = result {
+        warn!("Failed to apply config change to Adwaita window {entity}: {err}");
+    }
+}
+
+/// Warns about changes to config fields which can only be set when opening a
+/// window, and which are ignored otherwise.
+macro_rules! warn_fixed {
+    ($entity:expr, $config:expr, $last:expr, $($field:ident: $name:literal),* $(,)?) => {
+        $(
+            if $config.$field != $last.$field {
+                warn!(
+                    "Adwaita window {} {} cannot be changed after opening, ignoring change to {:?}",
+                    $entity, $name, $config.$field
+                );
+            }
+        )*
+    };
+}
+
+fn apply_config_changes(
+    mut windows: Query<
+        (Entity, &mut AdwaitaWindow, &AdwaitaWindowConfig),
+        Changed<AdwaitaWindowConfig>,
+    >,
+) {
+    for (entity, mut window, config) in &mut windows {
+        let last = &window.last_config;
+        if config.title != last.title {
+            warn_on_command_error(entity, window.set_title(config.title.clone()));
+        }
+        if config.icon_name != last.icon_name {
+            warn_on_command_error(entity, window.set_icon_name(config.icon_name.clone()));
+        }
+        if (config.width, config.height) != (last.width, last.height) {
+            warn_on_command_error(
+                entity,
+                window.set_size(UVec2::new(config.width, config.height)),
+            );
+        }
+        if config.user_resizable != last.user_resizable {
+            warn_on_command_error(entity, window.set_user_resizable(config.user_resizable));
+        }
+        if config.maximized != last.maximized {
+            warn_on_command_error(entity, window.set_maximized(config.maximized));
+        }
+        if config.fullscreen != last.fullscreen {
+            warn_on_command_error(entity, window.set_fullscreen(config.fullscreen));
+        }
+        if config.transient_for != last.transient_for {
+            warn_on_command_error(entity, window.set_transient_for(config.transient_for));
+        }
+        if config.skip_taskbar != last.skip_taskbar {
+            warn_on_command_error(entity, window.set_skip_taskbar(config.skip_taskbar));
+        }
+        if config.cursor_icon != last.cursor_icon {
+            warn_on_command_error(entity, window.set_cursor_icon(config.cursor_icon));
+        }
+        if config.cursor_visible != last.cursor_visible {
+            warn_on_command_error(entity, window.set_cursor_visible(config.cursor_visible));
+        }
+        if (&config.cursor_theme_name, config.cursor_theme_size)
+            != (&last.cursor_theme_name, last.cursor_theme_size)
+        {
+            warn_on_command_error(
+                entity,
+                window.set_cursor_theme(config.cursor_theme_name.clone(), config.cursor_theme_size),
+            );
+        }
+        if config.skip_pager != last.skip_pager {
+            warn_on_command_error(entity, window.set_skip_pager(config.skip_pager));
+        }
+        if config.hide_on_close != last.hide_on_close {
+            warn_on_command_error(entity, window.set_hide_on_close(config.hide_on_close));
+        }
+        if config.backdrop_color != last.backdrop_color {
+            warn_on_command_error(entity, window.set_backdrop_color(config.backdrop_color));
+        }
+        warn_fixed!(
+            entity,
+            config,
+            last,
+            intercept_close: "close interception",
+            render_target_handle: "render target handle",
+            force_software_present: "software present",
+            emit_raw_keys: "raw key events",
+            keyboard_input: "keyboard input",
+            pointer_input: "pointer input",
+            raw_key_filter: "raw key filter",
+            pointer_gestures: "pointer gestures",
+            stylus_input: "stylus input",
+            primary_paste: "primary paste",
+            file_drop: "file drop",
+            text_input: "text input",
+            focus_render_area_on_click: "focus on click",
+            fade_transition: "fade transition",
+            wm_class: "WM class",
+            wm_instance: "WM instance",
+            transparent: "transparency",
+            tearing: "tearing",
+            present_smoothing: "present smoothing",
+            min_size: "min size",
+            header_title: "header title",
+            top_bar_style: "top bar style",
+            bottom_bar_style: "bottom bar style",
+            action_bar: "action bar",
+            header_search: "header search",
+            decoration_layout: "decoration layout",
+            title_with_app_name: "title with app name",
+            max_content_width: "max content width",
+            header_bar: "header bar",
+            header_buttons: "header buttons",
+            menu: "menu",
+            floating_controls: "floating controls",
+        );
+        if config.render_scale != last.render_scale
+            || config.adaptive_render_scale != last.adaptive_render_scale
+        {
+            window.render_scale = initial_render_scale(config);
+            window.adaptive_render_scale.reset();
+        }
+        window.max_frames_in_flight = config.max_frames_in_flight;
+        window.last_config = config.clone();
+    }
+}
+
+/// Marks a camera which was made inactive because the window it renders to is
+/// paused or throttled, so that we know to make it active again afterwards.
+#[derive(Debug, Component)]
+struct PausedByWindow;
+
+fn pause_hidden_windows(
+    mut commands: Commands,
+    mut windows: Query<(
+        Entity,
+        &mut AdwaitaWindow,
+        &AdwaitaWindowConfig,
+        &AdwaitaWindowState,
+    )>,
+    mut cameras: Query<(Entity, &mut Camera, Has<PausedByWindow>)>,
+    mut request_redraw: EventWriter<RequestRedraw>,
+) {
+    for (entity, mut window, config, state) in &mut windows {
+        let paused = !window.rendering_enabled
+            || (config.pause_rendering_when_hidden && !state.is_visible());
+        if paused != window.rendering_paused {
+            debug!(
+                "Rendering to Adwaita window {entity} {}",
+                if paused { "paused" } else { "resumed" }
+            );
+            window.rendering_paused = paused;
+        }
+        if window.throttled && !paused {
+            request_redraw.send(RequestRedraw);
+        }
+        let inactive = paused || window.throttled;
+
+        // check every frame, since cameras may be spawned while we're paused
+        let target = RenderTarget::TextureView(window.render_target_handle);
+        for (camera_entity, mut camera, was_paused) in &mut cameras {
+            if camera.target != target {
+                continue;
+            }
+
+            if inactive && camera.is_active {
+                camera.is_active = false;
+                commands.entity(camera_entity).insert(PausedByWindow);
+            } else if !inactive && was_paused {
+                camera.is_active = true;
+                commands.entity(camera_entity).remove::<PausedByWindow>();
+            }
+        }
+    }
+}
+
+/// How long to wait for the frame clock to tick before updating anyway.
+///
+/// GTK stops ticking the frame clock while the window is hidden, and we don't
+/// want to stop updating entirely.
+const FRAME_CLOCK_TIMEOUT: Duration = Duration::from_millis(100);
+
+fn wait_for_frame_clock(ticks: Res<FrameClockTicks>) {
+    let _span = info_span!("wait_for_frame_clock").entered();
+    _ = ticks.recv.recv_timeout(FRAME_CLOCK_TIMEOUT);
+}
+
+/// Once the app exits, stops rendering into windows, waits for the GPU to
+/// finish what it's already doing, releases every render target, and only
+/// then shuts down GTK, so that nothing is freed while GTK may still show it.
+fn shut_down_on_exit(
+    mut exit_events: EventReader<AppExit>,
+    mut windows: Query<&mut AdwaitaWindow>,
+    render_device: Res<RenderDevice>,
+    mut manual_texture_views: ResMut<ManualTextureViews>,
+    send_app_command: Res<SendAppCommand>,
+    mut gtk_thread: ResMut<GtkThread>,
+) {
+    if exit_events.is_empty() {
+        return;
+    }
+    exit_events.clear();
+    let Some(gtk_thread) = gtk_thread.0.take() else {
+        return;
+    };
+
+    info!("Shutting down Adwaita windows");
+    for mut window in &mut windows {
+        // extraction skips paused windows, so nothing new is rendered or
+        // handed off to GTK
+        window.rendering_paused = true;
+        _ = window.send_command.try_send(WindowMessage::ClearFrame);
+    }
+    render_device.wgpu_device().poll(wgpu::Maintain::Wait);
+    for mut window in &mut windows {
+        manual_texture_views.remove(&window.render_target_handle);
+        for target in window.secondary_targets.values() {
+            manual_texture_views.remove(&target.handle);
+        }
+        window.swapchain.clear();
+        window.copy_source = None;
+        window.software_present = None;
+    }
+
+    _ = send_app_command.0.send(AppCommand::Shutdown);
+    if gtk_thread.join().is_err() {
+        error!("GTK thread panicked while shutting down");
+    }
+}
+
+/// Exits once the primary window has closed.
+///
+/// This only starts checking once the first window has opened, so that an
+/// app which starts without any windows, e.g. with
+/// [`AdwaitaWindowPlugin::primary_window_config`] set to [`None`], doesn't
+/// exit before it gets a chance to open one.
+fn exit_on_primary_closed(
+    mut app_exit_events: EventWriter<AppExit>,
+    windows: Query<(), (With<AdwaitaWindow>, With<PrimaryAdwaitaWindow>)>,
+    mut had_window: Local<bool>,
+) {
+    if !windows.is_empty() {
+        *had_window = true;
+    } else if *had_window {
+        info!("Primary Adwaita window was closed, exiting");
+        app_exit_events.send(AppExit::Success);
+    }
+}
+
+/// Exits once every window has closed, after the first one has opened, like
+/// [`exit_on_primary_closed`].
+fn exit_on_all_closed(
+    mut app_exit_events: EventWriter<AppExit>,
+    windows: Query<(), With<AdwaitaWindow>>,
+    mut had_window: Local<bool>,
+) {
+    if !windows.is_empty() {
+        *had_window = true;
+    } else if *had_window {
+        info!("No Adwaita windows are open, exiting");
+        app_exit_events.send(AppExit::Success);
+    }
+}
+
+fn forward_app_events(
+    mut commands: Commands,
+    recv_app_event: Res<RecvAppEvent>,
+    mut color_scheme: ResMut<AdwaitaColorScheme>,
+    mut system_settings: ResMut<AdwaitaSystemSettings>,
+    mut reduced_motion: ResMut<AdwaitaReducedMotion>,
+    mut display_lost_events: EventWriter<AdwaitaDisplayLost>,
+    mut action_events: EventWriter<AdwaitaActionActivated>,
+    mut settings_events: EventWriter<AdwaitaSystemSettingsChanged>,
+    mut reduced_motion_events: EventWriter<AdwaitaReducedMotionChanged>,
+    mut color_scheme_events: EventWriter<AdwaitaColorSchemeChanged>,
+    mut response_events: EventWriter<AdwaitaResponse>,
+) {
+    for event in recv_app_event.0.try_iter() {
+        match event {
+            AppEvent::DisplayCaps(caps) => {
+                commands.insert_resource(caps);
+            }
+            AppEvent::DisplayLost => {
+                warn!("Lost connection to the display, all Adwaita windows will close");
+                display_lost_events.send(AdwaitaDisplayLost);
+            }
+            AppEvent::ActionActivated(action) => {
+                action_events.send(AdwaitaActionActivated {
+                    window: None,
+                    action,
+                });
+            }
+            AppEvent::ColorScheme {
+                dark,
+                system_supported,
+            } => {
+                // `apply_color_scheme` only sends the preference when it changes,
+                // so this won't be echoed back to GTK
+                if color_scheme.dark != dark {
+                    color_scheme.dark = dark;
+                    color_scheme_events.send(AdwaitaColorSchemeChanged { dark });
+                }
+                if color_scheme.system_supported != system_supported {
+                    color_scheme.system_supported = system_supported;
+                }
+            }
+            AppEvent::SystemSettings(new) => {
+                let old = *system_settings;
+                if old != new {
+                    *system_settings = new;
+                    settings_events.send(AdwaitaSystemSettingsChanged { old, new });
+                }
+            }
+            AppEvent::ReducedMotion(reduced) => {
+                if reduced_motion.reduced != reduced {
+                    reduced_motion.reduced = reduced;
+                    reduced_motion_events.send(AdwaitaReducedMotionChanged { reduced });
+                }
+            }
+            AppEvent::Response { id, payload } => {
+                response_events.send(AdwaitaResponse { id, payload });
+            }
+        }
+    }
+}
+
+fn apply_color_scheme(
+    color_scheme: Res<AdwaitaColorScheme>,
+    send_app_command: Res<SendAppCommand>,
+    mut last_preference: Local<Option<AdwaitaColorSchemePreference>>,
+) {
+    if *last_preference == Some(color_scheme.preference) {
+        return;
+    }
+    *last_preference = Some(color_scheme.preference);
+    _ = send_app_command
+        .0
+        .send(AppCommand::SetColorScheme(color_scheme.preference));
+}
+
+fn open_inspector(
+    mut open_events: EventReader<AdwaitaOpenInspector>,
+    send_app_command: Res<SendAppCommand>,
+) {
+    open_events.clear();
+    _ = send_app_command.0.send(AppCommand::OpenInspector);
+}
+
+fn install_keymap(keymap: Res<AdwaitaKeymap>, send_app_command: Res<SendAppCommand>) {
+    let bindings = keymap
+        .bindings
+        .iter()
+        .map(|(combo, action)| (combo.0.clone(), action.clone()))
+        .collect();
+    _ = send_app_command.0.send(AppCommand::SetKeymap(bindings));
+}
+
+/// Writers for every event which can be forwarded from a window.
+#[derive(SystemParam)]
+struct WindowEventWriters<'w> {
+    error: EventWriter<'w, AdwaitaError>,
+    state: EventWriter<'w, AdwaitaWindowStateChanged>,
+    sidebar: EventWriter<'w, AdwaitaSidebarToggled>,
+    close_requested: EventWriter<'w, AdwaitaCloseRequested>,
+    raw_key: EventWriter<'w, AdwaitaRawKey>,
+    keyboard: EventWriter<'w, KeyboardInput>,
+    mouse_button: EventWriter<'w, MouseButtonInput>,
+    mouse_wheel: EventWriter<'w, MouseWheel>,
+    cursor_moved: EventWriter<'w, CursorMoved>,
+    cursor_entered: EventWriter<'w, CursorEntered>,
+    cursor_left: EventWriter<'w, CursorLeft>,
+    focused: EventWriter<'w, WindowFocused>,
+    text_input: EventWriter<'w, AdwaitaTextInput>,
+    primary_paste: EventWriter<'w, AdwaitaPrimaryPaste>,
+    drag_ended: EventWriter<'w, AdwaitaDragEnded>,
+    files_dropped: EventWriter<'w, AdwaitaFilesDropped>,
+    pointer_gesture: EventWriter<'w, AdwaitaPointerGesture>,
+    stylus: EventWriter<'w, AdwaitaStylus>,
+    title_widget: EventWriter<'w, AdwaitaTitleWidgetEvent>,
+    search_query: EventWriter<'w, AdwaitaSearchQueryChanged>,
+    search_activated: EventWriter<'w, AdwaitaSearchActivated>,
+    search_toggled: EventWriter<'w, AdwaitaSearchToggled>,
+    view_changed: EventWriter<'w, AdwaitaViewChanged>,
+    action_activated: EventWriter<'w, AdwaitaActionActivated>,
+    header_button: EventWriter<'w, AdwaitaHeaderButtonPressed>,
+    menu: EventWriter<'w, AdwaitaMenuActivated>,
+    frame_presented: EventWriter<'w, AdwaitaFramePresented>,
+    safe_area: EventWriter<'w, AdwaitaSafeAreaChanged>,
+    monitor: EventWriter<'w, AdwaitaMonitorChanged>,
+}
+
+fn forward_window_events(
+    mut windows: Query<(
+        Entity,
+        &mut AdwaitaWindow,
+        Option<&mut AdwaitaCursorPosition>,
+        Option<&mut AdwaitaWindowState>,
+        Option<&mut AdwaitaFrameStats>,
+        Option<&mut AdwaitaSafeArea>,
+        Option<&mut AdwaitaMonitor>,
+    )>,
+    mut events: WindowEventWriters,
+) {
+    for (entity, mut window, mut cursor, mut state, mut stats, mut safe_area, mut monitor) in
+        &mut windows
+    {
+        let mut failed_modifier = None;
+        // so that we can mutate the window while draining
+        let recv_event = window.recv_event.clone();
+        for event in recv_event.try_iter() {
+            match event {
+                WindowEvent::Error { operation, message } => {
+                    error!("Adwaita window {entity} failed to {operation}: {message}");
+                    events.error.send(AdwaitaError {
+                        window: entity,
+                        operation,
+                        message,
+                    });
+                }
+                WindowEvent::DmabufImportFailed { modifier } => {
+                    failed_modifier = Some(modifier);
+                }
+                WindowEvent::CursorMoved { position } | WindowEvent::CursorEntered { position } => {
+                    if let Some(cursor) = cursor.as_mut() {
+                        let position = window.logical_to_physical(position);
+                        cursor.position = Some(position);
+                        cursor.last_position = position;
+                    }
+                    if window.last_config.pointer_input {
+                        if window.cursor_position.is_none() {
+                            events.cursor_entered.send(CursorEntered { window: entity });
+                        }
+                        let position = window.logical_to_render_target(position);
+                        let delta = window.cursor_position.map(|last| position - last);
+                        window.cursor_position = Some(position);
+                        events.cursor_moved.send(CursorMoved {
+                            window: entity,
+                            position,
+                            delta,
+                        });
+                    }
+                }
+                WindowEvent::CursorLeft => {
+                    if let Some(cursor) = cursor.as_mut() {
+                        cursor.position = None;
+                    }
+                    if window.cursor_position.take().is_some() {
+                        events.cursor_left.send(CursorLeft { window: entity });
+                    }
+                }
+                WindowEvent::MouseButton { button, pressed } => {
+                    events.mouse_button.send(MouseButtonInput {
+                        button: mouse_button(button),
+                        state: if pressed {
+                            ButtonState::Pressed
+                        } else {
+                            ButtonState::Released
+                        },
+                        window: entity,
+                    });
+                }
+                WindowEvent::Scroll { delta, lines } => {
+                    // Bevy's deltas point left and up
+                    let (unit, delta) = if lines {
+                        (MouseScrollUnit::Line, -delta)
+                    } else {
+                        (MouseScrollUnit::Pixel, -window.logical_to_physical(delta))
+                    };
+                    events.mouse_wheel.send(MouseWheel {
+                        unit,
+                        x: delta.x,
+                        y: delta.y,
+                        window: entity,
+                    });
+                }
+                WindowEvent::CloseRequested => {
+                    events
+                        .close_requested
+                        .send(AdwaitaCloseRequested { window: entity });
+                }
+                WindowEvent::SidebarShown(shown) => {
+                    events.sidebar.send(AdwaitaSidebarToggled {
+                        window: entity,
+                        shown,
+                    });
+                }
+                WindowEvent::TitleWidget { key, value } => {
+                    events.title_widget.send(AdwaitaTitleWidgetEvent {
+                        window: entity,
+                        key,
+                        value,
+                    });
+                }
+                WindowEvent::SearchChanged(query) => {
+                    events.search_query.send(AdwaitaSearchQueryChanged {
+                        window: entity,
+                        query,
+                    });
+                }
+                WindowEvent::SearchActivated(query) => {
+                    events.search_activated.send(AdwaitaSearchActivated {
+                        window: entity,
+                        query,
+                    });
+                }
+                WindowEvent::SearchShown(shown) => {
+                    events.search_toggled.send(AdwaitaSearchToggled {
+                        window: entity,
+                        shown,
+                    });
+                }
+                WindowEvent::ActionActivated(action) => {
+                    events.action_activated.send(AdwaitaActionActivated {
+                        window: Some(entity),
+                        action,
+                    });
+                }
+                WindowEvent::HeaderButtonPressed(id) => {
+                    events
+                        .header_button
+                        .send(AdwaitaHeaderButtonPressed { window: entity, id });
+                }
+                WindowEvent::MenuActivated { id, checked } => {
+                    events.menu.send(AdwaitaMenuActivated {
+                        window: entity,
+                        id,
+                        checked,
+                    });
+                }
+                WindowEvent::ViewChanged(view_id) => {
+                    events.view_changed.send(AdwaitaViewChanged {
+                        window: entity,
+                        view_id,
+                    });
+                }
+                WindowEvent::PointerGesture { position, kind } => {
+                    events.pointer_gesture.send(AdwaitaPointerGesture {
+                        window: entity,
+                        position: window.logical_to_physical(position),
+                        kind,
+                    });
+                }
+                WindowEvent::Stylus {
+                    position,
+                    phase,
+                    pressure,
+                    tilt,
+                    button,
+                    eraser,
+                } => {
+                    events.stylus.send(AdwaitaStylus {
+                        window: entity,
+                        position: window.logical_to_physical(position),
+                        phase,
+                        pressure,
+                        tilt,
+                        button,
+                        eraser,
+                    });
+                }
+                WindowEvent::PrimaryPaste { position, text } => {
+                    events.primary_paste.send(AdwaitaPrimaryPaste {
+                        window: entity,
+                        position: window.logical_to_physical(position),
+                        text,
+                    });
+                }
+                WindowEvent::FilesDropped { position, paths } => {
+                    events.files_dropped.send(AdwaitaFilesDropped {
+                        window: entity,
+                        paths,
+                        position: window.logical_to_physical(position),
+                    });
+                }
+                WindowEvent::DragEnded { accepted } => {
+                    events.drag_ended.send(AdwaitaDragEnded {
+                        window: entity,
+                        accepted,
+                    });
+                }
+                WindowEvent::TextInput(text) => {
+                    events.text_input.send(AdwaitaTextInput {
+                        window: entity,
+                        text,
+                    });
+                }
+                WindowEvent::RawKey {
+                    pressed,
+                    keyval,
+                    keycode,
+                    group,
+                    modifiers,
+                } => {
+                    if window.last_config.keyboard_input {
+                        window.forward_key(entity, pressed, keyval, keycode, &mut events.keyboard);
+                    }
+                    if window.last_config.emit_raw_keys {
+                        events.raw_key.send(AdwaitaRawKey {
+                            window: entity,
+                            pressed,
+                            keyval,
+                            keycode,
+                            group,
+                            modifiers,
+                        });
+                    }
+                }
+                WindowEvent::FramePresented(presentation) => {
+                    if let Some(stats) = stats.as_mut() {
+                        stats.last_presentation = Some(presentation);
+                    }
+                    events.frame_presented.send(AdwaitaFramePresented {
+                        window: entity,
+                        presentation,
+                    });
+                }
+                WindowEvent::MonitorChanged(new) => {
+                    if let Some(monitor) = monitor.as_mut() {
+                        monitor.clone_from(&new);
+                    }
+                    events.monitor.send(AdwaitaMonitorChanged {
+                        window: entity,
+                        monitor: new,
+                    });
+                }
+                WindowEvent::SafeAreaChanged(new) => {
+                    if let Some(safe_area) = safe_area.as_mut() {
+                        **safe_area = new;
+                    }
+                    events.safe_area.send(AdwaitaSafeAreaChanged {
+                        window: entity,
+                        safe_area: new,
+                    });
+                }
+                WindowEvent::StateChanged(new) => {
+                    let Some(state) = state.as_mut() else {
+                        continue;
+                    };
+                    let old = **state;
+                    if old.focused && !new.focused {
+                        window.release_keys(entity, &mut events.keyboard);
+                    }
+                    // GTK only reports focus changes, so also report the focus
+                    // state the window was first mapped with
+                    if old.focused != new.focused || (new.mapped && !window.focus_reported) {
+                        window.focus_reported = true;
+                        events.focused.send(WindowFocused {
+                            window: entity,
+                            focused: new.focused,
+                        });
+                    }
+                    if old != new {
+                        **state = new;
+                        events.state.send(AdwaitaWindowStateChanged {
+                            window: entity,
+                            old,
+                            new,
+                        });
+                    }
+                }
+            }
+        }
+        if let Some(modifier) = failed_modifier {
+            window.fall_back_from_dmabuf(entity, modifier);
+        }
+    }
+}
+
+/// Lowers [`Msaa`] to a sample count which the adapter supports, since Bevy
+/// fails to create the multisampled textures of every camera otherwise.
+///
+/// Bevy resolves multisampled frames into the render targets, so this is the
+/// only place the sample count matters to us.
+fn clamp_msaa(mut msaa: ResMut<Msaa>, render_adapter: Option<Res<RenderAdapter>>) {
+    let Some(render_adapter) = render_adapter else {
+        return;
+    };
+    let supported = render::supported_msaa(*msaa, &render_adapter);
+    if supported != *msaa {
+        warn!(
+            "MSAA with {} samples is not supported by this GPU, falling back to {} samples",
+            msaa.samples(),
+            supported.samples()
+        );
+        *msaa = supported;
+    }
+}
+
+fn forward_captured_frames(
+    captured_frames: Res<CapturedFrames>,
+    mut events: EventWriter<AdwaitaCaptureReady>,
+) {
+    events.send_batch(captured_frames.recv.try_iter());
+}
+
+fn report_gpu_lost(
+    gpu_lost: Res<GpuLost>,
+    windows: Query<(Entity, &AdwaitaWindow)>,
+    mut lost_events: EventWriter<AdwaitaGpuLost>,
+    mut reported: Local<bool>,
+) {
+    if *reported {
+        return;
+    }
+    let Some(message) = gpu_lost.message() else {
+        return;
+    };
+    *reported = true;
+
+    error!("Lost the GPU device, Adwaita windows will stop presenting: {message}");
+    for (entity, window) in &windows {
+        // whatever is in the render targets now can't be trusted
+        if let Err(err) = window.send_message(WindowMessage::ClearFrame) {
+            warn!("Failed to clear the frame of Adwaita window {entity}: {err}");
+        }
+    }
+    lost_events.send(AdwaitaGpuLost { message });
+}
+
+/// Reallocates render targets when windows are resized, and picks the
+/// swapchain buffer to render into this frame.
+///
+/// Everything that depends on the render target's size is derived from what
+/// this system sets up, in the same frame:
+/// - cameras compute their target size from [`ManualTextureViews`] in
+///   [`CameraUpdateSystem`], which runs in [`PostUpdate`], after this
+/// - [`clamp_camera_viewports`] keeps camera viewports inside the new size
+///   before that
+/// - the [`FrameInfo`] sent to GTK carries the size of its own buffer
+///
+/// The render world only sees these once they're all extracted together, and
+/// with pipelined rendering, the frame being rendered keeps its own clones of
+/// the old texture views. So a frame is never rendered with a camera sized for
+/// one render target into a texture of another size.
+fn poll_windows(
+    mut commands: Commands,
+    mut windows: Query<(Entity, &mut AdwaitaWindow)>,
+    gpu_lost: Res<GpuLost>,
+    render_device: Res<RenderDevice>,
+    mut manual_texture_views: ResMut<ManualTextureViews>,
+    mut images: Option<ResMut<Assets<Image>>>,
+    display_caps: Option<Res<AdwaitaDisplayCaps>>,
+    mut ready: EventWriter<AdwaitaWindowReady>,
+    mut errors: EventWriter<AdwaitaWindowError>,
+    uncapped: Option<Res<UncappedFramePacing>>,
+) {
+    for (entity, mut window) in &mut windows {
+        if window.is_closed() {
+            info!("Adwaita window {entity} closed");
+            manual_texture_views.remove(&window.render_target_handle);
+            for target in window.secondary_targets.values() {
+                manual_texture_views.remove(&target.handle);
+            }
+            commands.entity(entity).despawn_recursive();
+            continue;
+        }
+
+        for handle in window.removed_secondary_targets.drain(..) {
+            manual_texture_views.remove(&handle);
+        }
+        if window.mirror_of.is_some() {
+            // GTK presents the source window's frames into this one
+            continue;
+        }
+        if gpu_lost.is_lost() {
+            // don't allocate or export anything on a dead device
+            continue;
+        }
+        // don't advance the swapchain either, since cameras still target the
+        // buffer which GTK is presenting until the next frame we render
+        let now = Instant::now();
+        window.throttled =
+            (uncapped.is_none() && window.skip_frame(now)) || window.throttle_frame(now);
+        if window.throttled {
+            continue;
+        }
+
+        let Some(size) = window.render_target_size(entity, now) else {
+            continue;
+        };
+        let settled = window.settle_resize(size, Instant::now());
+        // secondary targets always match the main render target
+        let secondary_size = if settled {
+            size
+        } else {
+            window.last_render_target_size
+        };
+        for target in window.secondary_targets.values_mut() {
+            if target.size != secondary_size && secondary_size != UVec2::ZERO {
+                target.size = secondary_size;
+                let (manual_texture_view, texture) =
+                    render::setup_secondary_render_target(secondary_size, render_device.as_ref());
+                manual_texture_views.insert(target.handle, manual_texture_view);
+                target.texture = Some(texture);
+                // invalidate anything which cached the old texture, e.g. UI
+                // bind groups
+                if let (Some(image), Some(images)) = (&target.image, images.as_mut()) {
+                    images.get_mut(image);
+                }
+            }
+        }
+
+        let usages = render::render_target_usages(window.last_config.render_target_usages);
+        let transparent = window.transparent;
+        let buffer_count = clamp_swapchain_buffers(window.last_config.swapchain_buffers);
+        let buffer_count_changed = window.software_present.is_none()
+            && !window.swapchain.is_empty()
+            && window.swapchain.len() != buffer_count;
+        if window.reallocate_render_target(size, settled, buffer_count_changed) {
+            info!("Window resized to {size}");
+            if !window.ready {
+                window.ready = true;
+                ready.send(AdwaitaWindowReady { window: entity });
+            }
+
+            if let Some(software) = &mut window.software_present {
+                let (manual_texture_view, texture) = render::setup_software_render_target(
+                    size,
+                    usages,
+                    transparent,
+                    render_device.as_ref(),
+                );
+                manual_texture_views.insert(window.render_target_handle, manual_texture_view);
+                software.texture = Some(texture);
+                continue;
+            }
+
+            // until we know what the display can import, stick with linear
+            let modifiers = match &display_caps {
+                Some(caps)
+                    if !window.last_config.force_linear_dmabuf
+                        && !window.linear_dmabuf_fallback =>
+                {
+                    caps.dmabuf_modifiers.as_slice()
+                }
+                _ => &[],
+            };
+            let copy = window.last_config.render_target_sharing == AdwaitaRenderTargetSharing::Copy;
+            let shared_usages = if copy {
+                // only ever copied into, but GTK may still want to sample it
+                usages | TextureUsages::COPY_DST
+            } else {
+                usages
+            };
+            let swapchain = (0..buffer_count)
+                .map(|_| {
+                    let _span =
+                        info_span!("setup_render_target", window = %entity, %size).entered();
+                    let (manual_texture_view, texture, dmabuf) = render::setup_render_target(
+                        size,
+                        shared_usages,
+                        modifiers,
+                        transparent,
+                        render_device.as_ref(),
+                    )?;
+                    Ok(SwapchainBuffer {
+                        manual_texture_view,
+                        texture,
+                        dmabuf,
+                        users: Arc::default(),
+                    })
+                })
+                .collect::<Result<Vec<_>, AdwaitaWindowErrorKind>>();
+            let swapchain = match swapchain {
+                Ok(swapchain) => swapchain,
+                Err(kind) => {
+                    error!(
+                        "Adwaita window {entity} couldn't share its render target with GTK, so \
+                         falling back to software present (slow): {kind}"
+                    );
+                    errors.send(AdwaitaWindowError {
+                        window: entity,
+                        kind,
+                    });
+                    window.use_software_present();
+                    // allocated as a software target on the next poll
+                    window.swapchain.clear();
+                    window.last_render_target_size = UVec2::ZERO;
+                    continue;
+                }
+            };
+            window.copy_source = copy.then(|| {
+                render::setup_copy_source_render_target(
+                    size,
+                    usages,
+                    transparent,
+                    render_device.as_ref(),
+                )
+            });
+            let modifier = swapchain[0].dmabuf.modifier;
+            if window.last_dmabuf_modifier != Some(modifier) {
+                info!(
+                    "Adwaita window {entity} render target uses DRM format modifier {modifier:#018x}"
+                );
+                window.last_dmabuf_modifier = Some(modifier);
+            }
+            window.swapchain = swapchain;
+            // start from the end so that the first frame goes into buffer 0
+            window.current_buffer = buffer_count - 1;
+
+            #[cfg(feature = "pipewire")]
+            if let Some(stream) = &window.pipewire_stream {
+                match window.dmabufs() {
+                    Ok(dmabufs) => stream.set_buffers(dmabufs),
+                    Err(err) => warn!("Failed to share dmabufs of {entity} with PipeWire: {err}"),
+                }
+            }
+        }
+
+        if window.swapchain.is_empty() || window.rendering_paused {
+            continue;
+        }
+        // a frame which was never rendered, e.g. because rendering was paused
+        // right after its buffer was picked, would keep that buffer busy
+        drop(window.next_frame_to_render.take(Ordering::SeqCst));
+        // render this frame into the next buffer which GTK is done with - if
+        // there's none, e.g. because the last frame is still being rendered
+        // with pipelined rendering, the render world waits for the next one
+        let buffer_count = window.swapchain.len();
+        window.current_buffer = next_free_buffer(window.current_buffer, buffer_count, |index| {
+            window.swapchain[index].users.is_busy()
+        })
+        .unwrap_or((window.current_buffer + 1) % buffer_count);
+        let buffer = &window.swapchain[window.current_buffer];
+        // give a shared ref of this texture view to the Adwaita app
+        // so that, even if *we* drop it while the window is rendering this frame,
+        // the GPU resources won't be deallocated until the window *also* drops it
+        // - see `FrameInfo::texture_view` for everything that keeps it alive
+        let next_frame_info = FrameInfo {
+            // assigned when it's extracted
+            frame_id: 0,
+            buffer_index: window.current_buffer,
+            buffer_use: buffer.users.acquire(),
+            dmabuf: buffer.dmabuf,
+            // assigned when it's sent
+            handed_off_at: Instant::now(),
+            texture_view: buffer.manual_texture_view.texture_view.clone(),
+        };
+        let render_into = match &window.copy_source {
+            Some((manual_texture_view, _)) => manual_texture_view,
+            None => &buffer.manual_texture_view,
+        };
+        manual_texture_views.insert(window.render_target_handle, render_into.clone());
+        debug!("Stored next frame info {next_frame_info:?}");
+        window
+            .next_frame_to_render
+            .store(Some(Box::new(next_frame_info)), Ordering::SeqCst);
+    }
+}
+
+/// Picks the first buffer after `current`, out of a swapchain of `len`, which
+/// isn't busy, going round to `current` itself last.
+fn next_free_buffer(current: usize, len: usize, is_busy: impl Fn(usize) -> bool) -> Option<usize> {
+    (1..=len)
+        .map(|offset| (current + offset) % len)
+        .find(|&index| !is_busy(index))
+}
+
+fn clamp_swapchain_buffers(buffers: u32) -> usize {
+    let (min, max) = render::SWAPCHAIN_BUFFERS_RANGE;
+    buffers.clamp(min, max) as usize
+}
+
+/// Duplicates the fd of `info`, so that it can be handed out without affecting
+/// the render target.
+fn export_dmabuf(info: &render::DmabufInfo) -> io::Result<AdwaitaDmabuf> {
+    // SAFETY: the fd stays open while the swapchain holds its texture view
+    let fd = unsafe { BorrowedFd::borrow_raw(info.fd) }.try_clone_to_owned()?;
+    Ok(AdwaitaDmabuf {
+        size: info.size,
+        fourcc: render::DMABUF_FORMAT,
+        modifier: info.modifier,
+        offset: info.offset,
+        stride: info.stride,
+        fd,
+    })
+}
+
+/// Makes cameras rendering to [`AdwaitaWindowConfig::transparent`] windows
+/// clear to [`Color::NONE`], if they'd clear to the [`ClearColor`] otherwise.
+fn clear_transparent_window_cameras(
+    windows: Query<&AdwaitaWindow>,
+    mut cameras: Query<&mut Camera, Changed<Camera>>,
+) {
+    for mut camera in &mut cameras {
+        if !matches!(camera.clear_color, ClearColorConfig::Default) {
+            continue;
+        }
+        let RenderTarget::TextureView(handle) = camera.target else {
+            continue;
+        };
+        if windows
+            .iter()
+            .any(|window| window.transparent && window.render_target_handle == handle)
+        {
+            camera.clear_color = ClearColorConfig::Custom(Color::NONE);
+        }
+    }
+}
+
+/// Viewport which a camera had before [`clamp_camera_viewports`] shrank it,
+/// so that it can be restored once the render target is large enough again.
+#[derive(Debug, Component)]
+struct ClampedViewport {
+    original: Viewport,
+    /// What the viewport was clamped to, so we can tell if the user has set
+    /// a new one since.
+    clamped: Viewport,
+}
+
+/// Shrinks the viewports of cameras rendering to Adwaita windows so that they
+/// fit inside the render target, which may have just shrunk in
+/// [`poll_windows`].
+///
+/// Otherwise the render pass would be set up with a viewport which is larger
+/// than its attachment, which wgpu rejects. The viewport which the user set
+/// is kept in [`ClampedViewport`], and put back as soon as it fits again.
+fn clamp_camera_viewports(
+    mut commands: Commands,
+    windows: Query<&AdwaitaWindow>,
+    mut cameras: Query<(Entity, &mut Camera, Option<&mut ClampedViewport>)>,
+) {
+    for (entity, mut camera, clamped) in &mut cameras {
+        let RenderTarget::TextureView(handle) = camera.target else {
+            continue;
+        };
+        let Some(target_size) = windows
+            .iter()
+            .find(|window| window.render_target_handle == handle)
+            .map(|window| window.last_render_target_size)
+        else {
+            continue;
+        };
+        if target_size == UVec2::ZERO {
+            continue;
+        }
+        // unless the user has set a new viewport since we clamped it
+        let original = match (&clamped, &camera.viewport) {
+            (Some(clamped), Some(viewport)) if same_rect(viewport, &clamped.clamped) => {
+                Some(clamped.original.clone())
+            }
+            (_, viewport) => viewport.clone(),
+        };
+        let Some(original) = original else {
+            if clamped.is_some() {
+                commands.entity(entity).remove::<ClampedViewport>();
+            }
+            continue;
+        };
+
+        let position = original.physical_position.min(target_size - UVec2::ONE);
+        let size = original
+            .physical_size
+            .min(target_size - position)
+            .max(UVec2::ONE);
+        let viewport = Viewport {
+            physical_position: position,
+            physical_size: size,
+            ..original.clone()
+        };
+        if !camera
+            .viewport
+            .as_ref()
+            .is_some_and(|current| same_rect(current, &viewport))
+        {
+            camera.viewport = Some(viewport.clone());
+        }
+
+        if same_rect(&viewport, &original) {
+            if clamped.is_some() {
+                commands.entity(entity).remove::<ClampedViewport>();
+            }
+        } else if let Some(mut clamped) = clamped {
+            clamped.original = original;
+            clamped.clamped = viewport;
+        } else {
+            commands.entity(entity).insert(ClampedViewport {
+                original,
+                clamped: viewport,
+            });
+        }
+    }
+}
+
+fn same_rect(a: &Viewport, b: &Viewport) -> bool {
+    a.physical_position == b.physical_position && a.physical_size == b.physical_size
+}
+
+/// How often the diagnostics overlay is updated, so that it's readable.
+const DIAGNOSTICS_INTERVAL: Duration = Duration::from_millis(500);
+
+fn update_diagnostics_overlays(
+    mut windows: Query<(
+        &mut AdwaitaWindow,
+        &AdwaitaWindowConfig,
+        Option<&AdwaitaFrameStats>,
+        Option<&AdwaitaRenderTargetSize>,
+    )>,
+    display_caps: Option<Res<AdwaitaDisplayCaps>>,
+) {
+    let now = Instant::now();
+    for (mut window, config, stats, size) in &mut windows {
+        if !config.show_diagnostics {
+            if window.diagnostics_updated_at.take().is_some() {
+                _ = window
+                    .send_command
+                    .try_send(WindowMessage::Diagnostics(None));
+            }
+            continue;
+        }
+        if window
+            .diagnostics_updated_at
+            .is_some_and(|at| now.duration_since(at) < DIAGNOSTICS_INTERVAL)
+        {
+            continue;
+        }
+
+        window.diagnostics_updated_at = Some(now);
+        let text = diagnostics_text(&window, display_caps.as_deref(), stats, size);
+        _ = window
+            .send_command
+            .try_send(WindowMessage::Diagnostics(Some(text)));
+    }
+}
+
+fn diagnostics_text(
+    window: &AdwaitaWindow,
+    display_caps: Option<&AdwaitaDisplayCaps>,
+    stats: Option<&AdwaitaFrameStats>,
+    size: Option<&AdwaitaRenderTargetSize>,
+) -> String {
+    let backend =
+        display_caps.map_or_else(|| "unknown".into(), |caps| format!("{:?}", caps.backend));
+    let present = if window.software_present.is_some() {
+        "software".to_owned()
+    } else {
+        let modifier = window
+            .last_dmabuf_modifier
+            .map_or_else(|| "none".into(), |modifier| format!("{modifier:#018x}"));
+        format!(
+            "dmabuf {:?}, modifier {modifier}",
+            window.last_config.render_target_sharing
+        )
+    };
+    let mut lines = vec![
+        format!("display  {backend}, tearing {:?}", window.tearing),
+        format!("present  {present}"),
+    ];
+    if let Some(size) = size {
+        lines.push(format!(
+            "size     {}x{} px, {}x{} logical @ {}, render scale {}",
+            size.physical.x,
+            size.physical.y,
+            size.logical.x,
+            size.logical.y,
+            size.scale_factor,
+            size.render_scale,
+        ));
+    }
+    if let Some(stats) = stats {
+        lines.push(format!(
+            "fps      {:.1} produced, {:.1} presented",
+            stats.produced_frame_rate, stats.presented_frame_rate,
+        ));
+        lines.push(format!(
+            "frames   {} produced, {} presented",
+            stats.produced_frame_id, stats.presented_frame_id,
+        ));
+    }
+    lines.join("\n")
+}
+
+fn mouse_button(button: u32) -> MouseButton {
+    match button {
+        gdk::BUTTON_PRIMARY => MouseButton::Left,
+        gdk::BUTTON_MIDDLE => MouseButton::Middle,
+        gdk::BUTTON_SECONDARY => MouseButton::Right,
+        8 => MouseButton::Back,
+        9 => MouseButton::Forward,
+        button => MouseButton::Other(u16::try_from(button).unwrap_or(u16::MAX)),
+    }
+}
+
+fn update_render_target_sizes(mut windows: Query<(&AdwaitaWindow, &mut AdwaitaRenderTargetSize)>) {
+    for (window, mut size) in &mut windows {
+        let logical = UVec2::new(
+            u32::try_from(window.render_target_width.load(Ordering::SeqCst)).unwrap_or(0),
+            u32::try_from(window.render_target_height.load(Ordering::SeqCst)).unwrap_or(0),
+        );
+        size.set_if_neq(AdwaitaRenderTargetSize {
+            physical: window.last_render_target_size,
+            logical,
+            scale_factor: window.scale_factor_f32(),
+            render_scale: window.render_scale,
+        });
+    }
+}
+
+fn initial_render_scale(config: &AdwaitaWindowConfig) -> f32 {
+    let render_scale = render::clamp_render_scale(config.render_scale);
+    match &config.adaptive_render_scale {
+        Some(adaptive) => {
+            let (min_scale, max_scale) = adaptive.scale_range();
+            render_scale.clamp(min_scale, max_scale)
+        }
+        None => render_scale,
+    }
+}
+
+fn max_fps_interval(max_fps: Option<u32>) -> Option<Duration> {
+    max_fps
+        .filter(|max_fps| *max_fps > 0)
+        .map(|max_fps| Duration::from_secs_f64(1.0 / f64::from(max_fps)))
+}
+
+fn update_adaptive_render_scales(
+    mut windows: Query<(Entity, &mut AdwaitaWindow)>,
+    gpu_frame_times: Option<Res<GpuFrameTimes>>,
+    mut warned_unsupported: Local<bool>,
+) {
+    let frame_times =
+        gpu_frame_times.map(|gpu_frame_times| gpu_frame_times.0.try_iter().collect::<Vec<_>>());
+    let now = Instant::now();
+    for (entity, mut window) in &mut windows {
+        let window = &mut *window;
+        let Some(adaptive) = &window.last_config.adaptive_render_scale else {
+            continue;
+        };
+        let Some(frame_times) = &frame_times else {
+            if !*warned_unsupported {
+                *warned_unsupported = true;
+                warn!(
+                    "Adaptive render scale of Adwaita window {entity} needs GPU timestamp \
+                     queries, which the render device doesn't support, so the render scale won't \
+                     change"
+                );
+            }
+            continue;
+        };
+        if window.rendering_paused {
+            window.adaptive_render_scale.reset();
+            continue;
+        }
+
+        let render_scale =
+            window
+                .adaptive_render_scale
+                .update(adaptive, window.render_scale, frame_times, now);
+        if render_scale != window.render_scale {
+            debug!(
+                "Adaptive render scale changed from {} to {render_scale}",
+                window.render_scale
+            );
+            window.render_scale = render_scale;
+        }
+    }
+}
+
+/// How often the frame rates in [`AdwaitaFrameStats`] are updated.
+const FRAME_RATE_INTERVAL: Duration = Duration::from_secs(1);
+
+fn update_frame_stats(mut windows: Query<(&AdwaitaWindow, &mut AdwaitaFrameStats)>) {
+    let now = Instant::now();
+    for (window, mut stats) in &mut windows {
+        stats.produced_frame_id = window.produced_frame_id();
+        stats.presented_frame_id = window.presented_frame_id();
+        stats.render_target_allocations = window.render_target_allocations;
+        let sample = FrameRateSample {
+            at: now,
+            produced_frame_id: stats.produced_frame_id,
+            presented_frame_id: stats.presented_frame_id,
+        };
+        match stats.frame_rate_sample {
+            Some(last) if now - last.at < FRAME_RATE_INTERVAL => {}
+            Some(last) => {
+                let secs = (now - last.at).as_secs_f32();
+                let rate = |from: u64, to: u64| to.saturating_sub(from) as f32 / secs;
+                stats.produced_frame_rate = rate(last.produced_frame_id, sample.produced_frame_id);
+                stats.presented_frame_rate =
+                    rate(last.presented_frame_id, sample.presented_frame_id);
+                stats.frame_rate_sample = Some(sample);
+            }
+            None => stats.frame_rate_sample = Some(sample),
+        }
+        stats.software_readback_time = window
+            .software_present
+            .as_ref()
+            .map(|software| Duration::from_nanos(software.readback_nanos.load(Ordering::SeqCst)));
+    }
+}
+
+#[cfg(feature = "pipewire")]
+fn update_pipewire_streams(mut windows: Query<&mut AdwaitaWindow>) {
+    for mut window in &mut windows {
+        let produced_frame_id = window.produced_frame_id();
+        // may already be the buffer of a newer frame, which is fine since
+        // that one has been rendered too
+        let buffer_index = window.produced_buffer_index.load(Ordering::SeqCst);
+        if let Some(stream) = &mut window.pipewire_stream {
+            stream.new_frame(produced_frame_id, buffer_index);
+        }
+    }
+}
+
+fn insert_bevy_windows(
+    mut commands: Commands,
+    windows: Query<(Entity, &AdwaitaWindowConfig), (With<AdwaitaWindow>, Without<Window>)>,
+) {
+    for (entity, config) in &windows {
+        commands.entity(entity).insert(Window {
+            title: config.title.clone(),
+            resizable: config.user_resizable,
+            resolution: WindowResolution::new(config.width as f32, config.height as f32),
+            ..default()
+        });
+    }
+}
+
+fn sync_bevy_windows(
+    mut windows: Query<(
+        &AdwaitaWindow,
+        &AdwaitaWindowConfig,
+        &AdwaitaCursorPosition,
+        &mut Window,
+    )>,
+) {
+    for (adwaita_window, config, cursor, mut window) in &mut windows {
+        let scale_factor = adwaita_window.scale_factor_f32();
+        if window.resolution.scale_factor() != scale_factor {
+            window.resolution.set_scale_factor(scale_factor);
+        }
+
+        let size = adwaita_window.last_render_target_size;
+        if size != UVec2::ZERO
+            && UVec2::new(
+                window.resolution.physical_width(),
+                window.resolution.physical_height(),
+            ) != size
+        {
+            window.resolution.set_physical_resolution(size.x, size.y);
+        }
+
+        let focused = adwaita_window.focused.load(Ordering::SeqCst);
+        if window.focused != focused {
+            window.focused = focused;
+        }
+
+        if window.physical_cursor_position() != cursor.position {
+            window.set_physical_cursor_position(cursor.position.map(|p| p.as_dvec2()));
+        }
+
+        if window.title != config.title {
+            window.title.clone_from(&config.title);
+        }
+    }
+}
+
+/// Render world state of every window which is rendered this frame, keyed by
+/// the window's entity in the main world.
+///
+/// Unlike render world entities, this is kept between frames, so extracting
+/// doesn't have to allocate anything for windows which already existed.
+#[derive(Debug, Default, Resource)]
+struct RenderWindows(EntityHashMap<RenderWindow>);
+
+#[derive(Debug)]
+struct RenderWindow {
+    shared_next_frame: Arc<AtomicOptionBox<FrameInfo>>,
+    next_frame_to_render: Arc<AtomicOptionBox<FrameInfo>>,
+    next_frame_to_send: Option<Box<FrameInfo>>,
+    entity: Entity,
+    size: UVec2,
+    frame_id: u64,
+    produced_frame_id: Arc<AtomicU64>,
+    #[cfg(feature = "pipewire")]
+    produced_buffer_index: Arc<std::sync::atomic::AtomicUsize>,
+    has_render_target: bool,
+    frames_in_flight: Arc<FramesInFlight>,
+    max_frames_in_flight: Option<u32>,
+    frame_overflow: AdwaitaFrameOverflow,
+    software_present: Option<RenderSoftwarePresent>,
+    /// Render target which Bevy rendered into, and the shared render target
+    /// to copy it into, with [`AdwaitaRenderTargetSharing::Copy`].
+    copy_to_shared: Option<(Texture, Texture)>,
+    /// Captures to read back this frame, and the texture to read them back
+    /// from.
+    captures: Option<(Vec<AdwaitaCapture>, Texture)>,
+}
+
+#[derive(Debug)]
+struct RenderSoftwarePresent {
+    texture: Texture,
+    shared_frame: Arc<AtomicOptionBox<SoftwareFrame>>,
+    readback_nanos: Arc<AtomicU64>,
+}
+
+#[derive(Debug, Component)]
+struct RenderSecondaryImage {
+    image: AssetId<Image>,
+    texture: Texture,
+    texture_view: TextureView,
+    size: UVec2,
+}
+
+fn extract_windows(
+    mut commands: Commands,
+    windows: Extract<Query<(Entity, &AdwaitaWindow)>>,
+    manual_texture_views: Extract<Res<ManualTextureViews>>,
+    mut render_windows: ResMut<RenderWindows>,
+) {
+    for (entity, window) in &windows {
+        for target in window.secondary_targets.values() {
+            let (Some(image), Some(texture), Some(manual_texture_view)) = (
+                &target.image,
+                &target.texture,
+                manual_texture_views.get(&target.handle),
+            ) else {
+                continue;
+            };
+            commands.spawn(RenderSecondaryImage {
+                image: image.id(),
+                texture: texture.clone(),
+                texture_view: manual_texture_view.texture_view.clone(),
+                size: target.size,
+            });
+        }
+
+        if window.rendering_paused || window.throttled || window.mirror_of.is_some() {
+            // nothing is rendered into the target, so there's nothing new
+            // for GTK to present
+            render_windows.0.remove(&entity);
+            continue;
+        }
+
+        let size = window.last_render_target_size;
+        let frame_id = window.next_frame_id.fetch_add(1, Ordering::SeqCst);
+        let _span = info_span!("extract_window", window = %entity, %size, frame_id).entered();
+
+        let mut next_frame_to_send = window.next_frame_to_render.take(Ordering::SeqCst);
+        if let Some(next_frame_to_send) = &mut next_frame_to_send {
+            next_frame_to_send.frame_id = frame_id;
+            debug!("Extracted next frame info {next_frame_to_send:?}");
+        }
+
+        let software_present = window.software_present.as_ref().and_then(|software| {
+            Some(RenderSoftwarePresent {
+                texture: software.texture.clone()?,
+                shared_frame: software.shared_frame.clone(),
+                readback_nanos: software.readback_nanos.clone(),
+            })
+        });
+        // the texture which GTK presents this frame, once it's been rendered
+        // and copied into
+        let capture_source = software_present
+            .as_ref()
+            .map(|software| software.texture.clone())
+            .or_else(|| {
+                window
+                    .swapchain
+                    .get(window.current_buffer)
+                    .map(|buffer| buffer.texture.clone())
+            });
+        // captures wait in the channel until there's something to capture
+        let captures = capture_source
+            .filter(|_| size != UVec2::ZERO && !window.pending_captures.is_empty())
+            .map(|texture| (window.pending_captures.try_iter().collect(), texture));
+        let render_window = render_windows
+            .0
+            .entry(entity)
+            .or_insert_with(|| RenderWindow {
+                shared_next_frame: window.shared_next_frame.clone(),
+                next_frame_to_render: window.next_frame_to_render.clone(),
+                next_frame_to_send: None,
+                entity,
+                size,
+                frame_id,
+                produced_frame_id: window.produced_frame_id.clone(),
+                #[cfg(feature = "pipewire")]
+                produced_buffer_index: window.produced_buffer_index.clone(),
+                has_render_target: false,
+                frames_in_flight: window.frames_in_flight.clone(),
+                max_frames_in_flight: None,
+                frame_overflow: AdwaitaFrameOverflow::default(),
+                software_present: None,
+                copy_to_shared: None,
+                captures: None,
+            });
+        render_window.next_frame_to_send = next_frame_to_send;
+        render_window.size = size;
+        render_window.frame_id = frame_id;
+        render_window.has_render_target = size != UVec2::ZERO;
+        render_window.max_frames_in_flight = window.max_frames_in_flight;
+        render_window.frame_overflow = window.last_config.frame_overflow;
+        render_window.software_present = software_present;
+        render_window.copy_to_shared = window
+            .copy_source
+            .as_ref()
+            .zip(window.swapchain.get(window.current_buffer))
+            .map(|((_, source), buffer)| (source.clone(), buffer.texture.clone()));
+        render_window.captures = captures;
+    }
+
+    render_windows
+        .0
+        .retain(|entity, _| windows.contains(*entity));
+}
+
+/// Replaces the placeholder [`GpuImage`]s of secondary render target images
+/// with the render targets themselves.
+///
+/// This runs after the placeholders are prepared, which happens again
+/// whenever they are marked as changed.
+fn prepare_secondary_images(
+    secondary_images: Query<&RenderSecondaryImage>,
+    mut gpu_images: ResMut<RenderAssets<GpuImage>>,
+    default_sampler: Res<DefaultImageSampler>,
+) {
+    for secondary_image in &secondary_images {
+        gpu_images.insert(
+            secondary_image.image,
+            GpuImage {
+                texture: secondary_image.texture.clone(),
+                texture_view: secondary_image.texture_view.clone(),
+                texture_format: secondary_image.texture.format(),
+                sampler: (**default_sampler).clone(),
+                size: secondary_image.size,
+                mip_level_count: 1,
+            },
+        );
+    }
+}
+
+/// How long we're willing to stall the render world for while waiting for the
+/// GTK side to present a frame, before we give up and render anyway.
+///
+/// GTK stops presenting while the window is hidden, so we can't wait forever.
+const FRAMES_IN_FLIGHT_TIMEOUT: Duration = Duration::from_millis(100);
+
+fn wait_for_frames_in_flight(windows: Res<RenderWindows>) {
+    // shared by every window, so that we never stall for longer than the
+    // timeout in total
+    let deadline = Instant::now() + FRAMES_IN_FLIGHT_TIMEOUT;
+    for window in windows.0.values() {
+        let Some(max_frames_in_flight) = window.max_frames_in_flight else {
+            continue;
+        };
+        if !window.has_render_target {
+            continue;
+        }
+
+        let _span = info_span!(
+            "wait_for_frames_in_flight",
+            window = %window.entity,
+            size = %window.size,
+        )
+        .entered();
+        window
+            .frames_in_flight
+            .wait_below(max_frames_in_flight.max(1), deadline);
+    }
+}
+
+/// Waits until GTK is done with the swapchain buffer which each window renders
+/// into this frame, in case [`poll_windows`] had to pick one which was still
+/// in use.
+fn wait_for_free_buffers(windows: Res<RenderWindows>) {
+    let deadline = Instant::now() + FRAMES_IN_FLIGHT_TIMEOUT;
+    for window in windows.0.values() {
+        let Some(frame_info) = &window.next_frame_to_send else {
+            continue;
+        };
+
+        let _span = info_span!(
+            "wait_for_free_buffer",
+            window = %window.entity,
+            buffer = frame_info.buffer_index,
+        )
+        .entered();
+        if !frame_info.buffer_use.wait_until_sole(deadline) {
+            debug!(
+                "GTK didn't release swapchain buffer {} of Adwaita window {} in time, rendering \
+                 into it anyway",
+                frame_info.buffer_index, window.entity
+            );
+        }
+    }
+}
+
+fn copy_to_shared_targets(
+    windows: Res<RenderWindows>,
+    render_device: Res<RenderDevice>,
+    render_queue: Res<RenderQueue>,
+) {
+    for window in windows.0.values() {
+        let Some((source, target)) = &window.copy_to_shared else {
+            continue;
+        };
+        if !window.has_render_target {
+            continue;
+        }
+
+        let _span = info_span!(
+            "copy_to_shared_target",
+            window = %window.entity,
+            size = %window.size,
+            frame_id = window.frame_id,
+        )
+        .entered();
+        render::copy_to_shared_target(source, target, window.size, &render_device, &render_queue);
+    }
+}
+
+fn begin_gpu_frame_timer(
+    mut timer: ResMut<render::GpuFrameTimer>,
+    render_device: Res<RenderDevice>,
+    render_queue: Res<RenderQueue>,
+) {
+    timer.begin(&render_device, &render_queue);
+}
+
+fn end_gpu_frame_timer(
+    mut timer: ResMut<render::GpuFrameTimer>,
+    render_device: Res<RenderDevice>,
+    render_queue: Res<RenderQueue>,
+) {
+    timer.end(&render_device, &render_queue);
+}
+
+fn read_back_software_frames(
+    windows: Res<RenderWindows>,
+    render_device: Res<RenderDevice>,
+    render_queue: Res<RenderQueue>,
+) {
+    for window in windows.0.values() {
+        let Some(software) = &window.software_present else {
+            continue;
+        };
+
+        let _span = info_span!(
+            "read_back_software_frame",
+            window = %window.entity,
+            size = %window.size,
+            frame_id = window.frame_id,
+        )
+        .entered();
+        let start = Instant::now();
+        let frame = match render::read_back_texture(
+            &software.texture,
+            window.size,
+            window.frame_id,
+            &render_device,
+            &render_queue,
+        ) {
+            Ok(frame) => frame,
+            Err(err) => {
+                warn!(
+                    "Failed to read back frame of Adwaita window {}: {err}",
+                    window.entity
+                );
+                continue;
+            }
+        };
+        let elapsed = u64::try_from(start.elapsed().as_nanos()).unwrap_or(u64::MAX);
+        software.readback_nanos.store(elapsed, Ordering::SeqCst);
+        software
+            .shared_frame
+            .store(Some(Box::new(frame)), Ordering::SeqCst);
+    }
+}
+
+fn capture_frames(
+    windows: Res<RenderWindows>,
+    render_device: Res<RenderDevice>,
+    render_queue: Res<RenderQueue>,
+    captured_frames: Res<CapturedFrames>,
+) {
+    for window in windows.0.values() {
+        let Some((captures, texture)) = &window.captures else {
+            continue;
+        };
+
+        let _span = info_span!(
+            "capture_frame",
+            window = %window.entity,
+            size = %window.size,
+            frame_id = window.frame_id,
+        )
+        .entered();
+        let image =
+            match render::read_back_image(texture, window.size, &render_device, &render_queue) {
+                Ok(image) => image,
+                Err(err) => {
+                    warn!(
+                        "Failed to capture frame of Adwaita window {}: {err}",
+                        window.entity
+                    );
+                    continue;
+                }
+            };
+        for &id in captures {
+            _ = captured_frames.send.send(AdwaitaCaptureReady {
+                window: window.entity,
+                id,
+                image: image.clone(),
+            });
+        }
+    }
+}
+
+/// Marker resource in the render world for [`AdwaitaFramePacing::Uncapped`].
+#[derive(Debug, Resource)]
+struct UncappedFramePacing;
+
+fn send_frame_to_windows(
+    mut windows: ResMut<RenderWindows>,
+    uncapped: Option<Res<UncappedFramePacing>>,
+    gpu_lost: Option<Res<GpuLost>>,
+) {
+    if gpu_lost.is_some_and(|gpu_lost| gpu_lost.is_lost()) {
+        // the frames were rendered on a lost device, so they may be garbage
+        return;
+    }
+    for window in windows.0.values_mut() {
+        let _span = info_span!(
+            "send_frame",
+            window = %window.entity,
+            size = %window.size,
+            frame_id = window.frame_id,
+        )
+        .entered();
+        if let Some(mut next_frame_info) = window.next_frame_to_send.take() {
+            // GTK resets the frames in flight once it picks up the latest frame
+            let frame_overflow = if uncapped.is_some() {
+                AdwaitaFrameOverflow::DropOldest
+            } else {
+                window.frame_overflow
+            };
+            match frame_overflow {
+                // skipped frames are never rendered in the first place
+                AdwaitaFrameOverflow::DropOldest | AdwaitaFrameOverflow::Skip => {}
+                AdwaitaFrameOverflow::DropNewest => {
+                    if window.frames_in_flight.get() > 0 {
+                        debug!("Previous frame wasn't taken yet, dropping {next_frame_info:?}");
+                        continue;
+                    }
+                }
+                AdwaitaFrameOverflow::Block => {
+                    let _span = info_span!("wait_for_frame_taken").entered();
+                    let deadline = Instant::now() + FRAMES_IN_FLIGHT_TIMEOUT;
+                    window.frames_in_flight.wait_below(1, deadline);
+                }
+            }
+            next_frame_info.handed_off_at = Instant::now();
+            #[cfg(feature = "pipewire")]
+            window
+                .produced_buffer_index
+                .store(next_frame_info.buffer_index, Ordering::SeqCst);
+            debug!("Sending next frame {next_frame_info:?}");
+            window
+                .shared_next_frame
+                .store(Some(next_frame_info), Ordering::SeqCst);
+        } else if !window.has_render_target {
+            continue;
+        }
+
+        // the GTK side resets this when it presents the latest frame
+        window.frames_in_flight.add_one();
+        window
+            .produced_frame_id
+            .store(window.frame_id, Ordering::SeqCst);
+    }
+}
+
+fn put_back_next_frame_if_not_sent(mut windows: ResMut<RenderWindows>) {
+    for window in windows.0.values_mut() {
+        if let Some(frame_info) = window.next_frame_to_send.take() {
             window
                 .next_frame_to_render
                 .store(Some(frame_info), Ordering::SeqCst);
@@ -419,3 +6655,829 @@ fn put_back_next_frame_if_not_sent(mut windows: Query<&mut RenderWindow>) {
 //                                                         | | put it back
 //                                 after RenderSet::Render |
 //                            take and send `next_to_send` |
+
+#[cfg(test)]
+mod tests {
+    use bevy::{
+        app::SubApp,
+        core_pipeline::{core_3d::Camera3dBundle, prepass::DepthPrepass},
+        log::LogPlugin,
+        pbr::{PbrBundle, PointLightBundle, StandardMaterial},
+        render::{
+            camera::ExtractedCamera,
+            pipelined_rendering::PipelinedRenderingPlugin,
+            settings::RenderCreation,
+            view::{ViewDepthTexture, ViewTarget},
+        },
+        winit::WinitPlugin,
+    };
+
+    use super::*;
+
+    /// World with just enough set up to open windows, without a GTK thread.
+    ///
+    /// Window open requests which would go to the GTK thread arrive on the
+    /// returned receiver instead.
+    fn test_world() -> (World, flume::Receiver<WindowOpen>) {
+        let mut world = World::new();
+        let (send_window_open, recv_window_open) = flume::unbounded();
+        world.insert_resource(SendWindowOpen(send_window_open));
+        world.init_resource::<ManualTextureViews>();
+        (world, recv_window_open)
+    }
+
+    fn open_window(world: &mut World, config: AdwaitaWindowConfig) -> Entity {
+        let entity = world.spawn_empty().id();
+        AdwaitaWindow::open(config).apply(entity, world);
+        entity
+    }
+
+    #[test]
+    fn plugin_added_twice() {
+        const GTK_THREAD_NAME: &str = "adw-dup-test";
+        let gtk_threads = || {
+            std::fs::read_dir("/proc/self/task")
+                .unwrap()
+                .filter_map(|task| std::fs::read_to_string(task.unwrap().path().join("comm")).ok())
+                .filter(|name| name.trim_end() == GTK_THREAD_NAME)
+                .count()
+        };
+        let plugin = || AdwaitaWindowPlugin {
+            gtk_thread_name: GTK_THREAD_NAME.into(),
+            ..default()
+        };
+
+        let mut app = App::new();
+        app.insert_sub_app(RenderApp, SubApp::new());
+        let err = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            app.add_plugins((plugin(), plugin()));
+        }))
+        .unwrap_err();
+        let message = err
+            .downcast_ref::<String>()
+            .map(String::as_str)
+            .or_else(|| err.downcast_ref::<&str>().copied())
+            .unwrap();
+        assert!(
+            message.contains(type_name::<AdwaitaWindowPlugin>()),
+            "{message}"
+        );
+
+        // the first one was built, and the second one never was
+        assert!(app.world().resource::<GtkThread>().0.is_some());
+        assert!(app.world().contains_resource::<SendWindowOpen>());
+        // the thread may already have exited if there's no display to open
+        assert!(gtk_threads() <= 1);
+    }
+
+    #[test]
+    fn open_first_window_later() {
+        let (mut world, recv_window_open) = test_world();
+        world.init_resource::<Events<AppExit>>();
+        let mut schedule = Schedule::default();
+        schedule.add_systems(exit_on_all_closed);
+
+        // no windows yet, but we shouldn't exit before the app opens one
+        schedule.run(&mut world);
+        schedule.run(&mut world);
+        assert!(world.resource::<Events<AppExit>>().is_empty());
+
+        let entity = open_window(&mut world, AdwaitaWindowConfig::default());
+        let request = recv_window_open.try_recv().unwrap();
+        assert_eq!(entity, request.entity);
+        schedule.run(&mut world);
+        assert!(world.resource::<Events<AppExit>>().is_empty());
+
+        world.despawn(entity);
+        schedule.run(&mut world);
+        assert!(!world.resource::<Events<AppExit>>().is_empty());
+    }
+
+    #[test]
+    fn set_size_too_large() {
+        let (mut world, recv_window_open) = test_world();
+        let entity = open_window(&mut world, AdwaitaWindowConfig::default());
+        let recv_command = recv_window_open.try_recv().unwrap().recv_command;
+        let window = world.get::<AdwaitaWindow>(entity).unwrap();
+
+        let size = UVec2::new(u32::MAX, 1);
+        assert_eq!(
+            Err(AdwaitaCommandError::SizeTooLarge(size)),
+            window.set_size(size)
+        );
+        assert!(recv_command.is_empty());
+
+        let size = UVec2::new(i32::MAX.unsigned_abs(), 1);
+        assert_eq!(Ok(()), window.set_size(size));
+        assert!(matches!(
+            recv_command.try_recv(),
+            Ok(WindowMessage::Command(AdwaitaWindowCommand::SetSize(sent))) if sent == size
+        ));
+    }
+
+    #[test]
+    fn cameras_follow_primary_window() {
+        let (mut world, _recv_window_open) = test_world();
+        world.observe(update_default_camera_render_target);
+        world.observe(update_existing_cameras_render_target);
+        world.observe(update_cameras_render_target_on_open);
+        world.observe(update_cameras_render_target_on_demote);
+        world.flush_commands();
+
+        let first = open_window(&mut world, AdwaitaWindowConfig::default());
+        let second = open_window(&mut world, AdwaitaWindowConfig::default());
+        world.entity_mut(first).insert(PrimaryAdwaitaWindow);
+        world.flush_commands();
+        let first_target = world.get::<AdwaitaWindow>(first).unwrap().render_target();
+        let second_target = world.get::<AdwaitaWindow>(second).unwrap().render_target();
+
+        let primary_ref = RenderTarget::Window(WindowRef::Primary);
+        let spawn_camera = |world: &mut World, target: RenderTarget| {
+            let camera = world
+                .spawn(Camera {
+                    target,
+                    ..default()
+                })
+                .id();
+            world.flush_commands();
+            camera
+        };
+        let follows_primary = spawn_camera(&mut world, primary_ref.clone());
+        let on_first = spawn_camera(&mut world, RenderTarget::Window(WindowRef::Entity(first)));
+        let opted_out = world
+            .spawn((
+                Camera {
+                    target: primary_ref.clone(),
+                    ..default()
+                },
+                NoAdwaitaRetarget,
+            ))
+            .id();
+        world.flush_commands();
+        let targets = |world: &World| {
+            [follows_primary, on_first, opted_out]
+                .map(|camera| world.get::<Camera>(camera).unwrap().target.clone())
+        };
+
+        assert_eq!(
+            [
+                first_target.clone(),
+                first_target.clone(),
+                primary_ref.clone()
+            ],
+            targets(&world),
+        );
+
+        // only the camera which followed the marker moves with it
+        world.entity_mut(first).remove::<PrimaryAdwaitaWindow>();
+        world.entity_mut(second).insert(PrimaryAdwaitaWindow);
+        world.flush_commands();
+        assert_eq!(
+            [
+                second_target.clone(),
+                first_target.clone(),
+                primary_ref.clone()
+            ],
+            targets(&world),
+        );
+
+        // and waits for the next primary window when there's none
+        world.entity_mut(second).remove::<PrimaryAdwaitaWindow>();
+        world.flush_commands();
+        assert_eq!(
+            [
+                primary_ref.clone(),
+                first_target.clone(),
+                primary_ref.clone()
+            ],
+            targets(&world),
+        );
+        world.entity_mut(first).insert(PrimaryAdwaitaWindow);
+        world.flush_commands();
+        assert_eq!(
+            [first_target.clone(), first_target, primary_ref],
+            targets(&world),
+        );
+    }
+
+    #[test]
+    fn cameras_target_window_entities() {
+        let (mut world, _recv_window_open) = test_world();
+        world.observe(update_default_camera_render_target);
+        world.observe(update_cameras_render_target_on_open);
+        world.flush_commands();
+
+        let first = world.spawn_empty().id();
+        let second = world.spawn_empty().id();
+        let spawn_camera = |world: &mut World, window: Entity| {
+            world
+                .spawn(Camera {
+                    target: RenderTarget::Window(WindowRef::Entity(window)),
+                    ..default()
+                })
+                .id()
+        };
+        // spawned before their window opens
+        let early = [
+            spawn_camera(&mut world, first),
+            spawn_camera(&mut world, second),
+        ];
+        AdwaitaWindow::open(AdwaitaWindowConfig::default()).apply(first, &mut world);
+        AdwaitaWindow::open(AdwaitaWindowConfig::default()).apply(second, &mut world);
+        world.flush_commands();
+        // and after
+        let late = [
+            spawn_camera(&mut world, first),
+            spawn_camera(&mut world, second),
+        ];
+        world.flush_commands();
+
+        let handles = [first, second].map(|window| {
+            world
+                .get::<AdwaitaWindow>(window)
+                .unwrap()
+                .render_target_handle
+        });
+        assert_ne!(handles[0], handles[1]);
+        for cameras in [early, late] {
+            let targets = cameras.map(|camera| world.get::<Camera>(camera).unwrap().target.clone());
+            assert_eq!(handles.map(RenderTarget::TextureView), targets);
+        }
+    }
+
+    #[test]
+    fn msaa_lowered_to_supported() {
+        let Some((RenderCreation::Manual(_, _, _, render_adapter, _), _, _)) = test_renderer()
+        else {
+            return;
+        };
+
+        assert_eq!(
+            Msaa::Off,
+            render::supported_msaa(Msaa::Off, &render_adapter)
+        );
+        // every adapter supports 4 samples for these formats
+        assert_eq!(
+            Msaa::Sample4,
+            render::supported_msaa(Msaa::Sample4, &render_adapter)
+        );
+        let highest = render::supported_msaa(Msaa::Sample8, &render_adapter);
+        assert!(
+            matches!(highest, Msaa::Sample8 | Msaa::Sample4),
+            "{highest:?}"
+        );
+        // and supported counts are left alone
+        assert_eq!(highest, render::supported_msaa(highest, &render_adapter));
+    }
+
+    /// What the render world saw of a camera rendering into a [`SceneTest`]
+    /// render target.
+    #[derive(Debug, Clone, Copy)]
+    struct RenderedView {
+        target_size: Option<UVec2>,
+        main_samples: u32,
+        depth_size: UVec2,
+        depth_samples: u32,
+    }
+
+    #[derive(Debug, Clone, Default, Resource)]
+    struct RenderedViews(Arc<Mutex<Vec<RenderedView>>>);
+
+    fn record_rendered_views(
+        views: Query<(&ExtractedCamera, &ViewTarget, &ViewDepthTexture)>,
+        rendered: Res<RenderedViews>,
+    ) {
+        let mut rendered = rendered.0.lock().unwrap();
+        for (camera, target, depth) in &views {
+            let depth_size = depth.texture.size();
+            rendered.push(RenderedView {
+                target_size: camera.physical_target_size,
+                main_samples: target
+                    .sampled_main_texture()
+                    .map_or(1, |texture| texture.sample_count()),
+                depth_size: UVec2::new(depth_size.width, depth_size.height),
+                depth_samples: depth.texture.sample_count(),
+            });
+        }
+    }
+
+    /// App which renders a 3D scene, with a depth prepass, into render
+    /// targets which are shared as dmabufs, like an Adwaita window's.
+    struct SceneTest {
+        app: App,
+        handle: ManualTextureViewHandle,
+        errors: Arc<Mutex<Vec<String>>>,
+        rendered: RenderedViews,
+    }
+
+    impl SceneTest {
+        fn new(msaa: Msaa) -> Option<Self> {
+            let (render_creation, _, errors) = test_renderer()?;
+            let mut app = App::new();
+            app.add_plugins(
+                DefaultPlugins
+                    .set(AdwaitaWindowPlugin::window_plugin())
+                    .set(RenderPlugin {
+                        render_creation,
+                        synchronous_pipeline_compilation: true,
+                    })
+                    .disable::<LogPlugin>()
+                    .disable::<WinitPlugin>()
+                    .disable::<PipelinedRenderingPlugin>(),
+            )
+            .insert_resource(msaa);
+            let rendered = RenderedViews::default();
+            app.sub_app_mut(RenderApp)
+                .insert_resource(rendered.clone())
+                .add_systems(Render, record_rendered_views.in_set(RenderSet::Cleanup));
+            app.finish();
+            app.cleanup();
+
+            let handle = unique_texture_view_handle(app.world().resource::<ManualTextureViews>());
+            let world = app.world_mut();
+            let mesh = world.resource_mut::<Assets<Mesh>>().add(Cuboid::default());
+            let material = world
+                .resource_mut::<Assets<StandardMaterial>>()
+                .add(Color::WHITE);
+            world.spawn(PbrBundle {
+                mesh,
+                material,
+                ..default()
+            });
+            world.spawn(PointLightBundle {
+                transform: Transform::from_xyz(2.0, 4.0, 2.0),
+                ..default()
+            });
+            world.spawn((
+                Camera3dBundle {
+                    camera: Camera {
+                        target: RenderTarget::TextureView(handle),
+                        ..default()
+                    },
+                    transform: Transform::from_xyz(0.0, 1.5, 4.0).looking_at(Vec3::ZERO, Vec3::Y),
+                    ..default()
+                },
+                DepthPrepass,
+            ));
+            Some(Self {
+                app,
+                handle,
+                errors,
+                rendered,
+            })
+        }
+
+        /// Renders into a new render target of `size`, as when the window is
+        /// resized, and returns it along with what the camera was rendered
+        /// with.
+        fn render_at(&mut self, size: UVec2) -> (Texture, render::DmabufInfo, RenderedView) {
+            let render_device = self.app.world().resource::<RenderDevice>().clone();
+            let (manual_texture_view, texture, dmabuf) = render::setup_render_target(
+                size,
+                TextureUsages::empty(),
+                &[],
+                false,
+                &render_device,
+            )
+            .unwrap();
+            self.app
+                .world_mut()
+                .resource_mut::<ManualTextureViews>()
+                .insert(self.handle, manual_texture_view);
+
+            self.rendered.0.lock().unwrap().clear();
+            // the first frames upload the scene and prepare its pipelines
+            for _ in 0..5 {
+                self.app.update();
+            }
+            render_device.wgpu_device().poll(wgpu::Maintain::Wait);
+            let errors = self.errors.lock().unwrap();
+            assert!(errors.is_empty(), "{errors:?}");
+            let rendered = *self
+                .rendered
+                .0
+                .lock()
+                .unwrap()
+                .last()
+                .expect("camera should have rendered");
+            (texture, dmabuf, rendered)
+        }
+    }
+
+    #[test]
+    fn msaa_resolves_into_render_target() {
+        let Some(mut test) = SceneTest::new(Msaa::Sample4) else {
+            return;
+        };
+        let size = UVec2::new(640, 480);
+        let (texture, dmabuf, rendered) = test.render_at(size);
+        // the camera renders multisampled, and resolves into what's exported
+        assert_eq!(4, rendered.main_samples);
+        assert_eq!(1, texture.sample_count());
+        let extent = texture.size();
+        assert_eq!(UVec2::new(extent.width, extent.height), dmabuf.size);
+        assert_eq!(size, dmabuf.size);
+    }
+
+    #[test]
+    fn depth_follows_render_target() {
+        let Some(mut test) = SceneTest::new(Msaa::default()) else {
+            return;
+        };
+        for size in [UVec2::new(1280, 720), UVec2::new(333, 77)] {
+            let (_texture, _dmabuf, rendered) = test.render_at(size);
+            assert_eq!(Some(size), rendered.target_size);
+            assert_eq!(size, rendered.depth_size);
+            assert_eq!(rendered.main_samples, rendered.depth_samples);
+        }
+    }
+
+    /// Reports a layout of the render widget, as the GTK thread would.
+    fn lay_out(world: &mut World, entity: Entity, width: i32, height: i32, scale_factor: f32) {
+        let window = world.get::<AdwaitaWindow>(entity).unwrap();
+        window.render_target_width.store(width, Ordering::SeqCst);
+        window.render_target_height.store(height, Ordering::SeqCst);
+        window
+            .scale_factor
+            .store(scale_factor.to_bits(), Ordering::SeqCst);
+    }
+
+    #[test]
+    fn render_target_at_fractional_scale() {
+        let (mut world, _recv_window_open) = test_world();
+        let entity = open_window(&mut world, AdwaitaWindowConfig::default());
+        let now = Instant::now();
+
+        for (width, height, scale_factor, expected) in [
+            (1000, 700, 1.0, UVec2::new(1000, 700)),
+            (1000, 700, 1.25, UVec2::new(1250, 875)),
+            (1000, 700, 1.5, UVec2::new(1500, 1050)),
+            (801, 601, 1.75, UVec2::new(1402, 1052)),
+            (1000, 700, 2.0, UVec2::new(2000, 1400)),
+        ] {
+            lay_out(&mut world, entity, width, height, scale_factor);
+            let mut window = world.get_mut::<AdwaitaWindow>(entity).unwrap();
+            assert_eq!(
+                Some(expected),
+                window.render_target_size(entity, now),
+                "{width}x{height} at {scale_factor}x",
+            );
+        }
+    }
+
+    #[test]
+    fn debounce_live_resize() {
+        let (mut world, _recv_window_open) = test_world();
+        let allocations = |world: &mut World, resize_debounce: Duration| {
+            let entity = open_window(
+                world,
+                AdwaitaWindowConfig {
+                    resize_debounce,
+                    ..default()
+                },
+            );
+            let mut window = world.get_mut::<AdwaitaWindow>(entity).unwrap();
+            let start = Instant::now();
+            // dragging the window edge, with a new size every frame, then
+            // holding it still
+            let sizes = (0..60)
+                .map(|i| UVec2::new(800 + i * 10, 600))
+                .chain(std::iter::repeat(UVec2::new(1400, 600)).take(20));
+            for (i, size) in sizes.enumerate() {
+                let now = start + Duration::from_millis(10) * i as u32;
+                let settled = window.settle_resize(size, now);
+                window.reallocate_render_target(size, settled, false);
+            }
+            assert_eq!(UVec2::new(1400, 600), window.last_render_target_size);
+            window.render_target_allocations
+        };
+
+        assert_eq!(61, allocations(&mut world, Duration::ZERO));
+        // the first size, and the one it settled on
+        assert_eq!(2, allocations(&mut world, Duration::from_millis(50)));
+    }
+
+    /// Feeds `frame_time` to `adaptive` for `samples` samples' worth of
+    /// frames, returning each render scale it picked.
+    fn run_adaptive(
+        adaptive: &mut render::AdaptiveRenderScale,
+        render_scale: &mut f32,
+        now: &mut Instant,
+        samples: u32,
+        gpu_time_at_full_scale: Duration,
+    ) -> Vec<f32> {
+        let settings = AdwaitaAdaptiveRenderScale::default();
+        let mut scales = Vec::new();
+        for _ in 0..samples * 15 {
+            // GPU time grows with the number of pixels
+            let frame_time = gpu_time_at_full_scale.mul_f32(render_scale.powi(2));
+            *now += Duration::from_millis(17);
+            *render_scale = adaptive.update(&settings, *render_scale, &[frame_time], *now);
+            if scales.last() != Some(render_scale) {
+                scales.push(*render_scale);
+            }
+        }
+        scales
+    }
+
+    #[test]
+    fn adaptive_render_scale_recovers() {
+        let mut adaptive = render::AdaptiveRenderScale::default();
+        let mut render_scale = 1.0;
+        let mut now = Instant::now();
+
+        // too slow: 20ms at full scale against the 12ms default target
+        let scales = run_adaptive(
+            &mut adaptive,
+            &mut render_scale,
+            &mut now,
+            8,
+            Duration::from_millis(20),
+        );
+        assert!(render_scale < 0.8, "{scales:?}");
+        // jumps most of the way down at once, instead of one step at a time
+        assert!(scales.len() <= 3, "{scales:?}");
+
+        // the load goes away, even while vsync would keep the frame interval
+        // at 16.7ms
+        let scales = run_adaptive(
+            &mut adaptive,
+            &mut render_scale,
+            &mut now,
+            40,
+            Duration::from_millis(5),
+        );
+        assert_eq!(1.0, render_scale, "{scales:?}");
+
+        // settles rather than oscillating once the target is just met
+        run_adaptive(
+            &mut adaptive,
+            &mut render_scale,
+            &mut now,
+            20,
+            Duration::from_millis(14),
+        );
+        let scales = run_adaptive(
+            &mut adaptive,
+            &mut render_scale,
+            &mut now,
+            20,
+            Duration::from_millis(14),
+        );
+        assert_eq!(1, scales.len(), "{scales:?}");
+    }
+
+    #[test]
+    fn buffer_free_once_gdk_releases_it() {
+        let users = Arc::new(render::BufferUsers::default());
+        assert!(!users.is_busy());
+
+        // Bevy picks the buffer, then hands the frame off to GTK
+        let frame_info = users.acquire();
+        assert!(users.is_busy());
+        // GDK imports it, and `create_dmabuf_texture` gives its release func
+        // its own use of the buffer
+        let gdk_texture = frame_info.clone();
+        let release_func = move || drop(gdk_texture);
+
+        // GTK moves on to a newer frame, but GDK may still be drawing this one
+        drop(frame_info);
+        assert!(users.is_busy());
+        release_func();
+        assert!(!users.is_busy());
+    }
+
+    /// Renderer which can export render targets as dmabufs, like the one
+    /// Adwaita windows render with, along with every wgpu error it reports.
+    ///
+    /// [`None`] if this machine can't export dmabufs, e.g. without a Vulkan
+    /// GPU, in which case the test should be skipped.
+    fn test_renderer() -> Option<(RenderCreation, RenderDevice, Arc<Mutex<Vec<String>>>)> {
+        let creation = match render::create_renderer(
+            WgpuSettings::default(),
+            AdwaitaAdapterSelection::default(),
+            false,
+        ) {
+            Ok(creation) => creation,
+            Err(
+                err @ (AdwaitaRendererError::CreateInstance(_)
+                | AdwaitaRendererError::NoAdapters
+                | AdwaitaRendererError::MissingExtensions { .. }),
+            ) => {
+                eprintln!("skipping, this machine can't export dmabufs: {err}");
+                return None;
+            }
+            Err(err) => panic!("failed to create renderer: {err}"),
+        };
+        let RenderCreation::Manual(render_device, ..) = &creation else {
+            panic!("renderer should be created manually");
+        };
+        let render_device = render_device.clone();
+
+        let errors = Arc::new(Mutex::new(Vec::new()));
+        render_device.wgpu_device().on_uncaptured_error(Box::new({
+            let errors = errors.clone();
+            move |err| errors.lock().unwrap().push(err.to_string())
+        }));
+        Some((creation, render_device, errors))
+    }
+
+    /// Inode of the file which `fd` refers to, if it's open.
+    fn fd_inode(fd: i32) -> Option<libc::ino_t> {
+        // SAFETY: `stat` is only read if `fstat` filled it in
+        unsafe {
+            let mut stat = std::mem::zeroed::<libc::stat>();
+            (libc::fstat(fd, &mut stat) == 0).then_some(stat.st_ino)
+        }
+    }
+
+    #[test]
+    fn render_target_outlives_closed_window() {
+        let Some((_, render_device, errors)) = test_renderer() else {
+            return;
+        };
+        let (mut world, _recv_window_open) = test_world();
+        world.insert_resource(render_device.clone());
+        world.init_resource::<GpuLost>();
+        world.init_resource::<Events<AdwaitaWindowReady>>();
+        world.init_resource::<Events<AdwaitaWindowError>>();
+        let mut schedule = Schedule::default();
+        schedule.add_systems(poll_windows);
+
+        let entity = open_window(&mut world, AdwaitaWindowConfig::default());
+        lay_out(&mut world, entity, 64, 48, 1.0);
+        schedule.run(&mut world);
+        let window = world.get::<AdwaitaWindow>(entity).unwrap();
+        assert!(
+            world.resource::<Events<AdwaitaWindowError>>().is_empty(),
+            "render target should be shared as a dmabuf",
+        );
+        let users = window.swapchain[0].users.clone();
+        // what the render world extracts, and holds while rendering the frame
+        let next_frame_to_send = *window.next_frame_to_render.take(Ordering::SeqCst).unwrap();
+        assert_eq!(0, next_frame_to_send.buffer_index);
+        let fd = next_frame_to_send.dmabuf.fd;
+        let inode = fd_inode(fd).unwrap();
+        // GTK presents a previous frame from the same buffer, and GDK still
+        // holds a texture imported from it
+        let release_func = render::dmabuf_release_func(&next_frame_to_send);
+
+        // the window closes while the frame is being rendered
+        window.closed.store(true, Ordering::SeqCst);
+        schedule.run(&mut world);
+        assert!(world.get_entity(entity).is_none());
+        let still_alive = || {
+            render_device.wgpu_device().poll(wgpu::Maintain::Wait);
+            users.is_busy() && fd_inode(fd) == Some(inode)
+        };
+        assert!(still_alive());
+
+        // rendering finishes, and GTK moves on...
+        drop(next_frame_to_send);
+        assert!(still_alive());
+        // ...but it's only freed once GDK releases its texture
+        release_func();
+        assert!(!still_alive());
+        assert!(errors.lock().unwrap().is_empty(), "{errors:?}");
+    }
+
+    #[test]
+    fn wait_for_buffer_release() {
+        let users = Arc::new(render::BufferUsers::default());
+        let frame_info = users.acquire();
+        let gdk_texture = frame_info.clone();
+
+        // the render world waits until only its own frame info is left
+        let deadline = Instant::now() + Duration::from_millis(10);
+        assert!(!frame_info.wait_until_sole(deadline));
+        let release = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(10));
+            drop(gdk_texture);
+        });
+        let deadline = Instant::now() + Duration::from_secs(10);
+        assert!(frame_info.wait_until_sole(deadline));
+        release.join().unwrap();
+    }
+
+    #[test]
+    fn skip_busy_swapchain_buffers() {
+        let busy = |busy: &'static [usize]| move |index| busy.contains(&index);
+        assert_eq!(Some(1), next_free_buffer(0, 3, busy(&[])));
+        assert_eq!(Some(0), next_free_buffer(2, 3, busy(&[])));
+        // GTK still presents buffer 1
+        assert_eq!(Some(2), next_free_buffer(0, 3, busy(&[1])));
+        // the frame in buffer 0 is still being rendered
+        assert_eq!(Some(1), next_free_buffer(0, 2, busy(&[0])));
+        assert_eq!(None, next_free_buffer(0, 2, busy(&[0, 1])));
+    }
+
+    #[test]
+    fn transparent_windows_clear_to_transparent() {
+        let (mut world, _recv_window_open) = test_world();
+        let transparent = open_window(
+            &mut world,
+            AdwaitaWindowConfig {
+                transparent: true,
+                ..default()
+            },
+        );
+        let opaque = open_window(&mut world, AdwaitaWindowConfig::default());
+        let mut spawn_camera = |window: Entity, clear_color: ClearColorConfig| {
+            let target = world.get::<AdwaitaWindow>(window).unwrap().render_target();
+            world
+                .spawn(Camera {
+                    target,
+                    clear_color,
+                    ..default()
+                })
+                .id()
+        };
+        let cameras = [
+            spawn_camera(transparent, ClearColorConfig::Default),
+            spawn_camera(transparent, ClearColorConfig::Custom(Color::WHITE)),
+            spawn_camera(opaque, ClearColorConfig::Default),
+        ];
+        let mut schedule = Schedule::default();
+        schedule.add_systems(clear_transparent_window_cameras);
+        schedule.run(&mut world);
+
+        let clear_colors =
+            cameras.map(
+                |camera| match world.get::<Camera>(camera).unwrap().clear_color {
+                    ClearColorConfig::Default => None,
+                    ClearColorConfig::Custom(color) => Some(color),
+                    ClearColorConfig::None => unreachable!(),
+                },
+            );
+        assert_eq!([Some(Color::NONE), Some(Color::WHITE), None], clear_colors);
+    }
+
+    #[test]
+    fn viewport_restored_after_resize() {
+        let (mut world, _recv_window_open) = test_world();
+        let window = open_window(&mut world, AdwaitaWindowConfig::default());
+        let handle = world
+            .get::<AdwaitaWindow>(window)
+            .unwrap()
+            .render_target_handle;
+        let viewport = Viewport {
+            physical_position: UVec2::new(100, 100),
+            physical_size: UVec2::new(800, 600),
+            ..default()
+        };
+        let camera = world
+            .spawn(Camera {
+                target: RenderTarget::TextureView(handle),
+                viewport: Some(viewport.clone()),
+                ..default()
+            })
+            .id();
+        let mut schedule = Schedule::default();
+        schedule.add_systems(clamp_camera_viewports);
+        let mut resize = |world: &mut World, size: UVec2| {
+            world
+                .get_mut::<AdwaitaWindow>(window)
+                .unwrap()
+                .last_render_target_size = size;
+            schedule.run(world);
+            let viewport = world
+                .get::<Camera>(camera)
+                .unwrap()
+                .viewport
+                .clone()
+                .unwrap();
+            (viewport.physical_position, viewport.physical_size)
+        };
+
+        assert_eq!(
+            (UVec2::new(100, 100), UVec2::new(800, 600)),
+            resize(&mut world, UVec2::new(1280, 720)),
+        );
+        // shrunk to fit while the window is small
+        assert_eq!(
+            (UVec2::new(100, 100), UVec2::new(400, 300)),
+            resize(&mut world, UVec2::new(500, 400)),
+        );
+        assert_eq!(
+            (UVec2::new(49, 49), UVec2::new(1, 1)),
+            resize(&mut world, UVec2::new(50, 50)),
+        );
+        // and back to what the user set once it's large again
+        assert_eq!(
+            (UVec2::new(100, 100), UVec2::new(800, 600)),
+            resize(&mut world, UVec2::new(1280, 720)),
+        );
+        assert!(world.get::<ClampedViewport>(camera).is_none());
+
+        // a viewport which the user sets while it's clamped replaces theirs
+        resize(&mut world, UVec2::new(500, 400));
+        world.get_mut::<Camera>(camera).unwrap().viewport = Some(Viewport {
+            physical_size: UVec2::new(200, 200),
+            ..default()
+        });
+        assert_eq!(
+            (UVec2::ZERO, UVec2::new(200, 200)),
+            resize(&mut world, UVec2::new(1280, 720)),
+        );
+    }
+}
@@ -0,0 +1,90 @@
+use gtk::{gdk, glib, graphene, gsk, prelude::*, subclass::prelude::*};
+
+glib::wrapper! {
+    /// Paintable which presents the latest frame rendered by Bevy.
+    ///
+    /// A single instance of this is created per window, and stays the same
+    /// for the window's entire lifetime, even as the underlying render target
+    /// is reallocated. Once the window closes, the paintable is cleared and
+    /// draws nothing.
+    pub struct FramePaintable(ObjectSubclass<imp::FramePaintable>)
+        @implements gdk::Paintable;
+}
+
+impl Default for FramePaintable {
+    fn default() -> Self {
+        glib::Object::new()
+    }
+}
+
+impl FramePaintable {
+    #[must_use]
+    pub fn texture(&self) -> Option<gdk::Texture> {
+        self.imp().texture.borrow().clone()
+    }
+
+    pub fn set_texture(&self, texture: Option<gdk::Texture>) {
+        let old_size = self.imp().size();
+        self.imp().texture.replace(texture);
+
+        if self.imp().size() != old_size {
+            self.invalidate_size();
+        }
+        self.invalidate_contents();
+    }
+}
+
+mod imp {
+    use std::cell::RefCell;
+
+    use super::*;
+
+    #[derive(Debug, Default)]
+    pub struct FramePaintable {
+        pub texture: RefCell<Option<gdk::Texture>>,
+    }
+
+    impl FramePaintable {
+        pub fn size(&self) -> (i32, i32) {
+            self.texture
+                .borrow()
+                .as_ref()
+                .map_or((0, 0), |texture| (texture.width(), texture.height()))
+        }
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for FramePaintable {
+        const NAME: &'static str = "BevyModAdwaitaFramePaintable";
+        type Type = super::FramePaintable;
+        type Interfaces = (gdk::Paintable,);
+    }
+
+    impl ObjectImpl for FramePaintable {}
+
+    impl PaintableImpl for FramePaintable {
+        fn snapshot(&self, snapshot: &gdk::Snapshot, width: f64, height: f64) {
+            let Some(texture) = self.texture.borrow().clone() else {
+                return;
+            };
+            // the texture may be smaller than the widget when rendering
+            // with a render scale, so make sure we upscale it smoothly
+            match snapshot.downcast_ref::<gtk::Snapshot>() {
+                Some(snapshot) => snapshot.append_scaled_texture(
+                    &texture,
+                    gsk::ScalingFilter::Linear,
+                    &graphene::Rect::new(0.0, 0.0, width as f32, height as f32),
+                ),
+                None => texture.snapshot(snapshot, width, height),
+            }
+        }
+
+        fn intrinsic_width(&self) -> i32 {
+            self.size().0
+        }
+
+        fn intrinsic_height(&self) -> i32 {
+            self.size().1
+        }
+    }
+}
@@ -0,0 +1,428 @@
+//! Publishing the render target of a window as a PipeWire video source, so
+//! that it can be used as a virtual camera or captured by e.g. OBS.
+
+use std::{
+    cell::RefCell,
+    ffi::c_void,
+    fmt, io,
+    os::fd::AsRawFd,
+    rc::Rc,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+};
+
+use ::pipewire as pw;
+use bevy::prelude::*;
+use pw::spa::{
+    self,
+    param::{
+        format::{FormatProperties, MediaSubtype, MediaType},
+        video::VideoFormat,
+        ParamType,
+    },
+    pod::{serialize::PodSerializer, Object, Pod, Property, PropertyFlags, Value},
+    utils::{Direction, Fraction, Id, Rectangle, SpaTypes},
+};
+
+use crate::AdwaitaDmabuf;
+
+/// Error which can occur when starting a PipeWire stream with
+/// [`AdwaitaWindow::start_pipewire_stream`].
+///
+/// [`AdwaitaWindow::start_pipewire_stream`]: crate::AdwaitaWindow::start_pipewire_stream
+#[derive(Debug, thiserror::Error)]
+pub enum AdwaitaPipeWireError {
+    #[error("failed to spawn PipeWire thread")]
+    SpawnThread(#[source] io::Error),
+    #[error("failed to connect to PipeWire, is it running?")]
+    Connect(#[source] pw::Error),
+    #[error("failed to create PipeWire stream")]
+    CreateStream(#[source] pw::Error),
+    #[error("PipeWire thread exited before the stream was created")]
+    ThreadExited,
+}
+
+/// Node ID of a stream which hasn't been assigned one yet.
+const NO_NODE_ID: u32 = spa::sys::SPA_ID_INVALID;
+
+#[derive(Debug)]
+enum StreamCommand {
+    SetBuffers(Vec<AdwaitaDmabuf>),
+    /// A new frame was rendered into the swapchain buffer with this index.
+    NewFrame(usize),
+    Stop,
+}
+
+/// Handle to a PipeWire stream running on its own thread, which stops the
+/// stream when dropped.
+pub struct PipeWireStream {
+    send_command: pw::channel::Sender<StreamCommand>,
+    node_id: Arc<AtomicU32>,
+    thread: Option<JoinHandle<()>>,
+    last_frame_id: u64,
+}
+
+impl PipeWireStream {
+    pub fn start(name: String) -> Result<Self, AdwaitaPipeWireError> {
+        let (send_command, recv_command) = pw::channel::channel();
+        let (send_started, recv_started) = flume::bounded(1);
+        let node_id = Arc::new(AtomicU32::new(NO_NODE_ID));
+
+        let thread = thread::Builder::new()
+            .name(format!("pipewire-{name}"))
+            .spawn({
+                let node_id = node_id.clone();
+                move || run(&name, recv_command, &node_id, &send_started)
+            })
+            .map_err(AdwaitaPipeWireError::SpawnThread)?;
+
+        match recv_started.recv() {
+            Ok(Ok(())) => Ok(Self {
+                send_command,
+                node_id,
+                thread: Some(thread),
+                last_frame_id: 0,
+            }),
+            Ok(Err(err)) => {
+                _ = thread.join();
+                Err(err)
+            }
+            Err(_) => Err(AdwaitaPipeWireError::ThreadExited),
+        }
+    }
+
+    /// ID of the stream's node in the PipeWire graph, once it has been
+    /// assigned one.
+    pub fn node_id(&self) -> Option<u32> {
+        match self.node_id.load(Ordering::SeqCst) {
+            NO_NODE_ID => None,
+            node_id => Some(node_id),
+        }
+    }
+
+    /// Publishes frames from the swapchain made of `dmabufs` from now on.
+    pub fn set_buffers(&self, dmabufs: Vec<AdwaitaDmabuf>) {
+        _ = self.send_command.send(StreamCommand::SetBuffers(dmabufs));
+    }
+
+    /// Tells consumers about the frame with ID `frame_id`, which was rendered
+    /// into the swapchain buffer at `buffer_index`, if it's new.
+    pub fn new_frame(&mut self, frame_id: u64, buffer_index: usize) {
+        if frame_id > self.last_frame_id {
+            self.last_frame_id = frame_id;
+            _ = self
+                .send_command
+                .send(StreamCommand::NewFrame(buffer_index));
+        }
+    }
+}
+
+impl fmt::Debug for PipeWireStream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PipeWireStream")
+            .field("node_id", &self.node_id())
+            .field("last_frame_id", &self.last_frame_id)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Drop for PipeWireStream {
+    fn drop(&mut self) {
+        _ = self.send_command.send(StreamCommand::Stop);
+        if let Some(thread) = self.thread.take() {
+            _ = thread.join();
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct StreamState {
+    current: Vec<AdwaitaDmabuf>,
+    // PipeWire may still hold buffers pointing at the previous dmabufs until
+    // it has renegotiated, so keep their fds open until then
+    _previous: Vec<AdwaitaDmabuf>,
+    /// How many PipeWire buffers have been pointed at `current` so far.
+    added_buffers: usize,
+    /// PipeWire buffers which consumers are done with, and the index of the
+    /// swapchain buffer each one points at.
+    ///
+    /// These are dequeued as soon as they're available, so that we can queue
+    /// whichever one points at the latest frame.
+    dequeued: Vec<(*mut pw::sys::pw_buffer, usize)>,
+    /// Swapchain buffer of the latest frame, if it hasn't been queued yet.
+    pending_frame: Option<usize>,
+    connected: bool,
+}
+
+fn run(
+    name: &str,
+    recv_command: pw::channel::Receiver<StreamCommand>,
+    node_id: &Arc<AtomicU32>,
+    send_started: &flume::Sender<Result<(), AdwaitaPipeWireError>>,
+) {
+    pw::init();
+
+    let (main_loop, _context, _core, stream) = match connect(name) {
+        Ok(connection) => connection,
+        Err(err) => {
+            _ = send_started.send(Err(err));
+            return;
+        }
+    };
+    let stream = Rc::new(stream);
+    let state = Rc::new(RefCell::new(StreamState::default()));
+
+    let _listener = stream
+        .add_local_listener_with_user_data(())
+        .state_changed({
+            let node_id = node_id.clone();
+            move |stream, (), old, new| {
+                debug!("PipeWire stream state changed from {old:?} to {new:?}");
+                node_id.store(stream.node_id(), Ordering::SeqCst);
+            }
+        })
+        .add_buffer({
+            let state = state.clone();
+            move |_, (), buffer| {
+                let mut state = state.borrow_mut();
+                if state.current.is_empty() {
+                    return;
+                }
+                // we ask for one PipeWire buffer per swapchain buffer, which
+                // are added in order
+                let index = state.added_buffers % state.current.len();
+                state.added_buffers += 1;
+                let dmabuf = &state.current[index];
+                // SAFETY: PipeWire gives us a valid buffer with one data
+                // block, which we asked for in the buffers param
+                unsafe {
+                    // remembered for when we queue it, see `buffer_index`
+                    (*buffer).user_data = index as *mut c_void;
+                    let data = &mut *(*(*buffer).buffer).datas;
+                    data.type_ = spa::sys::SPA_DATA_DmaBuf;
+                    data.flags = spa::sys::SPA_DATA_FLAG_READABLE;
+                    data.fd = i64::from(dmabuf.fd.as_raw_fd());
+                    data.mapoffset = 0;
+                    data.maxsize = dmabuf_len(dmabuf);
+                    data.data = std::ptr::null_mut();
+                }
+            }
+        })
+        .remove_buffer({
+            let state = state.clone();
+            move |_, (), buffer| {
+                let mut state = state.borrow_mut();
+                state._previous.clear();
+                state.dequeued.retain(|&(dequeued, _)| dequeued != buffer);
+            }
+        })
+        .process({
+            let state = state.clone();
+            move |stream, ()| {
+                let mut state = state.borrow_mut();
+                loop {
+                    // SAFETY: `remove_buffer` forgets buffers before PipeWire
+                    // frees them
+                    let buffer = unsafe { stream.dequeue_raw_buffer() };
+                    if buffer.is_null() {
+                        break;
+                    }
+                    let index = unsafe { buffer_index(buffer) };
+                    state.dequeued.push((buffer, index));
+                }
+
+                let Some(frame_index) = state.pending_frame else {
+                    return;
+                };
+                // the consumer may still hold the buffer of the latest frame,
+                // in which case we try again on the next one
+                let Some(position) = state
+                    .dequeued
+                    .iter()
+                    .position(|&(_, index)| index == frame_index)
+                else {
+                    return;
+                };
+                let Some(dmabuf) = state.current.get(frame_index) else {
+                    return;
+                };
+                let (offset, size, stride) = (dmabuf.offset, dmabuf_len(dmabuf), dmabuf.stride);
+                let (buffer, _) = state.dequeued.swap_remove(position);
+                state.pending_frame = None;
+                // SAFETY: we dequeued the buffer, and it has the one data
+                // block which we asked for in the buffers param
+                unsafe {
+                    let chunk = &mut *(*(*(*buffer).buffer).datas).chunk;
+                    chunk.offset = offset;
+                    chunk.size = size;
+                    chunk.stride = i32::try_from(stride).unwrap_or(i32::MAX);
+                    stream.queue_raw_buffer(buffer);
+                }
+            }
+        })
+        .register();
+
+    let _recv_command = recv_command.attach(main_loop.loop_(), {
+        let main_loop = main_loop.clone();
+        let stream = stream.clone();
+        move |command| match command {
+            StreamCommand::SetBuffers(dmabufs) => {
+                let Some(first) = dmabufs.first() else {
+                    return;
+                };
+                let (format, buffers) = (format_param(first), buffers_param(dmabufs.len()));
+                let mut params = [pod(&format), pod(&buffers)];
+
+                let mut state = state.borrow_mut();
+                state._previous = std::mem::replace(&mut state.current, dmabufs);
+                state.added_buffers = 0;
+                state.pending_frame = None;
+                let result = if state.connected {
+                    stream.update_params(&mut params)
+                } else {
+                    state.connected = true;
+                    stream.connect(
+                        Direction::Output,
+                        None,
+                        pw::stream::StreamFlags::DRIVER | pw::stream::StreamFlags::ALLOC_BUFFERS,
+                        &mut params,
+                    )
+                };
+                if let Err(err) = result {
+                    warn!("Failed to update PipeWire stream buffers: {err}");
+                }
+            }
+            StreamCommand::NewFrame(buffer_index) => {
+                state.borrow_mut().pending_frame = Some(buffer_index);
+                if let Err(err) = stream.trigger_process() {
+                    debug!("Failed to trigger PipeWire stream process: {err}");
+                }
+            }
+            StreamCommand::Stop => main_loop.quit(),
+        }
+    });
+
+    _ = send_started.send(Ok(()));
+    main_loop.run();
+
+    _ = stream.disconnect();
+    node_id.store(NO_NODE_ID, Ordering::SeqCst);
+}
+
+fn connect(
+    name: &str,
+) -> Result<
+    (
+        pw::main_loop::MainLoop,
+        pw::context::Context,
+        pw::core::Core,
+        pw::stream::Stream,
+    ),
+    AdwaitaPipeWireError,
+> {
+    let main_loop = pw::main_loop::MainLoop::new(None).map_err(AdwaitaPipeWireError::Connect)?;
+    let context = pw::context::Context::new(&main_loop).map_err(AdwaitaPipeWireError::Connect)?;
+    let core = context
+        .connect(None)
+        .map_err(AdwaitaPipeWireError::Connect)?;
+    let stream = pw::stream::Stream::new(
+        &core,
+        name,
+        pw::properties::properties! {
+            *pw::keys::MEDIA_TYPE => "Video",
+            *pw::keys::MEDIA_CATEGORY => "Capture",
+            *pw::keys::MEDIA_ROLE => "Camera",
+            *pw::keys::MEDIA_CLASS => "Video/Source",
+        },
+    )
+    .map_err(AdwaitaPipeWireError::CreateStream)?;
+    Ok((main_loop, context, core, stream))
+}
+
+/// Index of the swapchain buffer which `buffer` points at, as stored in
+/// `add_buffer`.
+///
+/// # Safety
+///
+/// `buffer` must be a valid buffer of our stream.
+unsafe fn buffer_index(buffer: *mut pw::sys::pw_buffer) -> usize {
+    (*buffer).user_data as usize
+}
+
+fn dmabuf_len(dmabuf: &AdwaitaDmabuf) -> u32 {
+    dmabuf.stride * dmabuf.size.y
+}
+
+fn format_param(dmabuf: &AdwaitaDmabuf) -> Vec<u8> {
+    // the modifier must be included for consumers to import a dmabuf which
+    // isn't laid out linearly
+    let modifier = Property {
+        key: FormatProperties::VideoModifier.as_raw(),
+        flags: PropertyFlags::MANDATORY,
+        value: Value::Long(dmabuf.modifier as i64),
+    };
+    serialize(Object {
+        type_: SpaTypes::ObjectParamFormat.as_raw(),
+        id: ParamType::EnumFormat.as_raw(),
+        properties: vec![
+            Property::new(
+                FormatProperties::MediaType.as_raw(),
+                Value::Id(Id(MediaType::Video.as_raw())),
+            ),
+            Property::new(
+                FormatProperties::MediaSubtype.as_raw(),
+                Value::Id(Id(MediaSubtype::Raw.as_raw())),
+            ),
+            // same byte order as the dmabuf's DRM format
+            Property::new(
+                FormatProperties::VideoFormat.as_raw(),
+                Value::Id(Id(VideoFormat::RGBA.as_raw())),
+            ),
+            modifier,
+            Property::new(
+                FormatProperties::VideoSize.as_raw(),
+                Value::Rectangle(Rectangle {
+                    width: dmabuf.size.x,
+                    height: dmabuf.size.y,
+                }),
+            ),
+            // frames are sent whenever Bevy renders one
+            Property::new(
+                FormatProperties::VideoFramerate.as_raw(),
+                Value::Fraction(Fraction { num: 0, denom: 1 }),
+            ),
+        ],
+    })
+}
+
+fn buffers_param(count: usize) -> Vec<u8> {
+    serialize(Object {
+        type_: SpaTypes::ObjectParamBuffers.as_raw(),
+        id: ParamType::Buffers.as_raw(),
+        properties: vec![
+            Property::new(
+                spa::sys::SPA_PARAM_BUFFERS_buffers,
+                Value::Int(i32::try_from(count).unwrap_or(i32::MAX)),
+            ),
+            Property::new(spa::sys::SPA_PARAM_BUFFERS_blocks, Value::Int(1)),
+            Property::new(
+                spa::sys::SPA_PARAM_BUFFERS_dataType,
+                Value::Int(1 << spa::sys::SPA_DATA_DmaBuf),
+            ),
+        ],
+    })
+}
+
+fn serialize(object: Object) -> Vec<u8> {
+    PodSerializer::serialize(io::Cursor::new(Vec::new()), &Value::Object(object))
+        .expect("param should serialize")
+        .0
+        .into_inner()
+}
+
+fn pod(bytes: &[u8]) -> &Pod {
+    Pod::from_bytes(bytes).expect("serialized param should be a valid pod")
+}
@@ -1,7 +1,12 @@
 use std::{
+    ffi::CStr,
     fs::File,
     os::{fd::FromRawFd, raw::c_void},
-    sync::Arc,
+    sync::{
+        atomic::{AtomicU8, Ordering},
+        Arc, Condvar, Mutex, MutexGuard, PoisonError,
+    },
+    time::{Duration, Instant},
 };
 
 use ash::vk;
@@ -9,35 +14,291 @@ use bevy::{
     prelude::*,
     render::{
         camera::ManualTextureView,
-        render_resource::TextureView,
+        render_asset::RenderAssetUsages,
+        render_resource::{Texture, TextureView},
         renderer::{
             RenderAdapter, RenderAdapterInfo, RenderDevice, RenderInstance, RenderQueue,
             WgpuWrapper,
         },
         settings::{RenderCreation, WgpuSettings},
+        view::ViewTarget,
     },
 };
-use gtk::{gdk, prelude::Cast};
+use gtk::{gdk, glib};
 use wgpu::TextureFormat;
 use wgpu_hal::{vulkan, Instance};
 
-use crate::hal_custom;
+use crate::{
+    adwaita_app::assert_i32, hal_custom, AdwaitaAdaptiveRenderScale, AdwaitaWindowErrorKind,
+};
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub struct DmabufInfo {
     pub size: UVec2,
     pub fd: i32,
+    /// DRM format modifier which the image was laid out with.
+    pub modifier: u64,
+    /// Offset of the first pixel in the dmabuf, in bytes.
+    pub offset: u32,
+    /// Bytes per row of pixels.
+    pub stride: u32,
+    /// Whether the pixels hold linear colors instead of sRGB ones, for
+    /// [`AdwaitaWindowConfig::transparent`](crate::AdwaitaWindowConfig::transparent)
+    /// windows.
+    pub linear: bool,
+}
+
+/// Counts what uses a swapchain buffer, so that Bevy only renders into it
+/// once GTK is done with it.
+///
+/// Every [`FrameInfo`] of the buffer holds a [`BufferUse`], from when Bevy
+/// picks the buffer to render into until GTK has moved on to a newer frame.
+/// Every texture which GDK imports from the buffer also holds one, until GDK
+/// calls its release func.
+#[derive(Debug, Default)]
+pub struct BufferUsers {
+    count: Mutex<u32>,
+    released: Condvar,
+}
+
+impl BufferUsers {
+    fn lock(&self) -> MutexGuard<'_, u32> {
+        self.count.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Marks the buffer as used until the returned [`BufferUse`] is dropped.
+    #[must_use]
+    pub fn acquire(self: &Arc<Self>) -> BufferUse {
+        *self.lock() += 1;
+        BufferUse(self.clone())
+    }
+
+    /// Whether anything still uses the buffer.
+    #[must_use]
+    pub fn is_busy(&self) -> bool {
+        *self.lock() > 0
+    }
+
+    /// Blocks until at most `max` uses of the buffer are left, or until
+    /// `deadline`, and returns whether that many are left.
+    fn wait_until_at_most(&self, max: u32, deadline: Instant) -> bool {
+        let timeout = deadline.saturating_duration_since(Instant::now());
+        let (count, _) = self
+            .released
+            .wait_timeout_while(self.lock(), timeout, |count| *count > max)
+            .unwrap_or_else(PoisonError::into_inner);
+        *count <= max
+    }
+}
+
+/// One use of a swapchain buffer, see [`BufferUsers`].
+///
+/// Each clone counts as another use.
+#[derive(Debug)]
+pub struct BufferUse(Arc<BufferUsers>);
+
+impl BufferUse {
+    /// Blocks until this is the only use of the buffer left, or until
+    /// `deadline`, and returns whether it is.
+    pub fn wait_until_sole(&self, deadline: Instant) -> bool {
+        self.0.wait_until_at_most(1, deadline)
+    }
+}
+
+impl Clone for BufferUse {
+    fn clone(&self) -> Self {
+        self.0.acquire()
+    }
+}
+
+impl Drop for BufferUse {
+    fn drop(&mut self) {
+        *self.0.lock() -= 1;
+        self.0.released.notify_all();
+    }
 }
 
 #[derive(Debug)]
 pub struct FrameInfo {
+    /// ID of the frame rendered into this swapchain buffer.
+    pub frame_id: u64,
+    /// Index of the swapchain buffer in the window's swapchain.
+    pub buffer_index: usize,
+    /// Marks the swapchain buffer as in use while this is alive.
+    pub buffer_use: BufferUse,
     pub dmabuf: DmabufInfo,
-    // just keep this around so that we own the view while we're drawing it
-    // as soon as we drop this, the GPU image + texture + dmabuf is freed
-    pub _texture_view: TextureView,
+    /// When Bevy handed this frame off to GTK.
+    pub handed_off_at: Instant,
+    /// Keeps the render target alive, along with the image, memory and
+    /// dmabuf fd which it owns.
+    ///
+    /// The render target is freed once every clone of this is dropped - that
+    /// is, once Bevy has reallocated or dropped its [`ManualTextureView`],
+    /// GTK has moved on to a newer frame, *and* GDK has released every
+    /// texture imported from the dmabuf. wgpu additionally
+    /// defers destroying the image until the GPU has finished any work which
+    /// was submitted with it, so a window closing mid-frame can't free it
+    /// from under a render pass.
+    pub texture_view: TextureView,
+}
+
+/// Error which can occur when creating the renderer for Adwaita windows.
+#[derive(Debug, thiserror::Error)]
+pub enum AdwaitaRendererError {
+    #[error("failed to create Vulkan instance")]
+    CreateInstance(#[source] wgpu_hal::InstanceError),
+    #[error(
+        "renderer must be allowed to use the Vulkan backend to share render targets with GTK, \
+         but only {0:?} were requested"
+    )]
+    IncompatibleBackends(wgpu::Backends),
+    #[error("no Vulkan adapters available")]
+    NoAdapters,
+    #[error(
+        "no Vulkan adapter supports the device extensions required to share render targets \
+         with GTK via dmabuf ({})", format_missing_extensions(.missing)
+    )]
+    MissingExtensions {
+        /// For each adapter, its name and the required extensions it's missing.
+        missing: Vec<(String, Vec<&'static CStr>)>,
+    },
+    #[error(
+        "no adapter which supports sharing render targets with GTK matches {selection:?}, \
+         available adapters are {available:?}"
+    )]
+    NoMatchingAdapter {
+        selection: AdwaitaAdapterSelection,
+        /// Names of the adapters which support sharing render targets.
+        available: Vec<String>,
+    },
+    #[error("adapter {adapter:?} does not support requested features {features:?}")]
+    UnsupportedFeatures {
+        adapter: String,
+        features: wgpu::Features,
+    },
+    #[error("failed to open Vulkan device")]
+    OpenDevice(#[source] wgpu_hal::DeviceError),
+    #[error("failed to create wgpu device")]
+    CreateDevice(#[source] wgpu::RequestDeviceError),
 }
 
-pub fn create_renderer(settings: WgpuSettings) -> RenderCreation {
+fn format_missing_extensions(missing: &[(String, Vec<&'static CStr>)]) -> String {
+    missing
+        .iter()
+        .map(|(adapter, extensions)| {
+            let extensions = extensions
+                .iter()
+                .map(|ext| ext.to_string_lossy())
+                .collect::<Vec<_>>()
+                .join(", ");
+            format!("{adapter} is missing {extensions}")
+        })
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+/// Which GPU renders into Adwaita windows, out of those which support sharing
+/// render targets with GTK.
+///
+/// On a hybrid laptop, the GPU driving the display can usually import dmabufs
+/// from the other one, but only with a slow copy, or sometimes not at all. If
+/// frames fail to be presented or are slow to present, try picking the other
+/// GPU. Without any filters, the adapter is picked according to
+/// [`WgpuSettings::power_preference`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AdwaitaAdapterSelection {
+    /// PCI device ID of the adapter to use, e.g. as shown in brackets by
+    /// `lspci -nn`.
+    pub device_id: Option<u32>,
+    /// Part of the name of the adapter to use, ignoring case, e.g. `intel`.
+    ///
+    /// If [`None`], the `WGPU_ADAPTER_NAME` environment variable is used
+    /// instead if it's set, like Bevy's default renderer.
+    pub name: Option<String>,
+}
+
+impl AdwaitaAdapterSelection {
+    fn matches(&self, info: &wgpu::AdapterInfo) -> bool {
+        let name_matches = |name: &str| {
+            info.name
+                .to_lowercase()
+                .contains(name.to_lowercase().as_str())
+        };
+        self.device_id
+            .map_or(true, |device_id| info.device == device_id)
+            && self.name.as_deref().map_or(true, name_matches)
+    }
+}
+
+/// Sorts adapters so that those preferred by `power_preference` come first.
+fn adapter_rank(power_preference: wgpu::PowerPreference, device_type: wgpu::DeviceType) -> u8 {
+    use wgpu::{DeviceType, PowerPreference};
+
+    match (power_preference, device_type) {
+        (PowerPreference::HighPerformance, DeviceType::DiscreteGpu)
+        | (PowerPreference::LowPower, DeviceType::IntegratedGpu) => 0,
+        (PowerPreference::HighPerformance, DeviceType::IntegratedGpu)
+        | (PowerPreference::LowPower, DeviceType::DiscreteGpu) => 1,
+        (PowerPreference::None, _) => 0,
+        (_, DeviceType::VirtualGpu) => 2,
+        (_, DeviceType::Cpu) => 3,
+        (_, DeviceType::Other) => 4,
+    }
+}
+
+/// Device extensions which we need on top of what wgpu needs, to be able to
+/// export render targets as dmabufs.
+fn required_extensions() -> [&'static CStr; 2] {
+    [
+        ash::extensions::khr::GetMemoryRequirements2::name(),
+        ash::extensions::khr::ExternalMemoryFd::name(),
+    ]
+}
+
+/// Device extensions which let us export render targets with a tiled or
+/// compressed layout, instead of always using a linear one. These are enabled
+/// if the adapter supports them.
+fn modifier_extensions() -> [&'static CStr; 3] {
+    [
+        vk::ExtImageDrmFormatModifierFn::name(),
+        vk::ExtExternalMemoryDmaBufFn::name(),
+        vk::KhrImageFormatListFn::name(),
+    ]
+}
+
+/// Checks that `settings` can be used to create a renderer which shares its
+/// render targets with GTK.
+fn validate_settings(settings: &WgpuSettings) -> Result<(), AdwaitaRendererError> {
+    if let Some(backends) = settings.backends {
+        if !backends.contains(wgpu::Backends::VULKAN) {
+            return Err(AdwaitaRendererError::IncompatibleBackends(backends));
+        }
+        if backends != wgpu::Backends::VULKAN {
+            warn!("Requested backends {backends:?}, but only Vulkan will be used");
+        }
+    }
+    Ok(())
+}
+
+/// Creates the renderer on the adapter picked by `selection`, out of those
+/// which can export render targets as dmabufs.
+///
+/// If none can and `software_fallback` is set, all adapters are considered
+/// instead, and render targets are created with
+/// [`setup_software_render_target`].
+pub fn create_renderer(
+    settings: WgpuSettings,
+    selection: AdwaitaAdapterSelection,
+    software_fallback: bool,
+) -> Result<RenderCreation, AdwaitaRendererError> {
+    validate_settings(&settings)?;
+    let selection = AdwaitaAdapterSelection {
+        name: selection
+            .name
+            .or_else(|| std::env::var("WGPU_ADAPTER_NAME").ok()),
+        ..selection
+    };
+
     let do_async = async move {
         let instance = unsafe {
             vulkan::Instance::init(&wgpu_hal::InstanceDescriptor {
@@ -47,7 +308,7 @@ pub fn create_renderer(settings: WgpuSettings) -> RenderCreation {
                 gles_minor_version: settings.gles3_minor_version,
             })
         }
-        .expect("failed to create vulkan instance");
+        .map_err(AdwaitaRendererError::CreateInstance)?;
 
         // validation works
         // let instance = unsafe { wgpu::Instance::from_hal::<vulkan::Api>(instance) };
@@ -63,41 +324,114 @@ pub fn create_renderer(settings: WgpuSettings) -> RenderCreation {
         // .await;
 
         // validation fails
-        let adapter = unsafe { instance.enumerate_adapters() }
-            .into_iter()
-            .next()
-            .expect("no adapters");
+        let adapters = unsafe { instance.enumerate_adapters() };
+        if adapters.is_empty() {
+            return Err(AdwaitaRendererError::NoAdapters);
+        }
+
+        let mut missing = Vec::new();
+        let mut candidates = Vec::new();
+        let mut skipped = Vec::new();
+        for adapter in adapters {
+            let missing_extensions =
+                hal_custom::missing_extensions(&adapter.adapter, required_extensions());
+            if missing_extensions.is_empty() {
+                candidates.push(adapter);
+                continue;
+            }
+            warn!(
+                "Skipping adapter {:?}, which doesn't support dmabuf export",
+                adapter.info.name
+            );
+            missing.push((adapter.info.name.clone(), missing_extensions));
+            skipped.push(adapter);
+        }
+        let dmabuf_export = !candidates.is_empty();
+        if !dmabuf_export {
+            if !software_fallback {
+                return Err(AdwaitaRendererError::MissingExtensions { missing });
+            }
+            warn!(
+                "No adapter supports dmabuf export, so all Adwaita windows will use software \
+                 present (slow)"
+            );
+            candidates = skipped;
+        }
+
+        let available = candidates
+            .iter()
+            .map(|adapter| adapter.info.name.clone())
+            .collect::<Vec<_>>();
+        candidates.retain(|adapter| selection.matches(&adapter.info));
+        // stable, so equally preferred adapters keep the driver's order
+        candidates.sort_by_key(|adapter| {
+            adapter_rank(settings.power_preference, adapter.info.device_type)
+        });
+        let Some(adapter) = candidates.into_iter().next() else {
+            return Err(AdwaitaRendererError::NoMatchingAdapter {
+                selection,
+                available,
+            });
+        };
+        info!(
+            "Using adapter {:?} ({:?}, vendor {:#06x}, device {:#06x}) out of {available:?}",
+            adapter.info.name, adapter.info.device_type, adapter.info.vendor, adapter.info.device,
+        );
+
+        // user's features and limits, merged with what the adapter supports
+        let device_descriptor = hal_custom::make_device_descriptor(&settings, &adapter);
+        let unsupported_features = device_descriptor.required_features - adapter.features;
+        if !unsupported_features.is_empty() {
+            return Err(AdwaitaRendererError::UnsupportedFeatures {
+                adapter: adapter.info.name.clone(),
+                features: unsupported_features,
+            });
+        }
+
+        let modifier_extensions = if !dmabuf_export {
+            Vec::new()
+        } else if hal_custom::missing_extensions(&adapter.adapter, modifier_extensions()).is_empty()
+        {
+            modifier_extensions().to_vec()
+        } else {
+            info!(
+                "Adapter doesn't support DRM format modifiers, render targets will use a \
+                 linear layout"
+            );
+            Vec::new()
+        };
+
+        // the dmabuf export extensions are enabled on top of whatever wgpu
+        // needs for the requested features
         let device = unsafe {
             hal_custom::open_adapter(
                 &adapter.adapter,
-                settings.features.clone(),
-                [
-                    ash::extensions::khr::GetMemoryRequirements2::name(),
-                    ash::extensions::khr::ExternalMemoryFd::name(),
-                ],
+                device_descriptor.required_features,
+                required_extensions()
+                    .into_iter()
+                    .filter(|_| dmabuf_export)
+                    .chain(modifier_extensions),
             )
-            .expect("failed to open device")
-        };
+        }
+        .map_err(AdwaitaRendererError::OpenDevice)?;
         let instance = unsafe { wgpu::Instance::from_hal::<vulkan::Api>(instance) };
         let adapter = unsafe { instance.create_adapter_from_hal(adapter) };
         let adapter_info = adapter.get_info();
-        let device_descriptor =
-            hal_custom::make_device_descriptor(&settings, &adapter, &adapter_info);
         let (device, queue) =
             unsafe { adapter.create_device_from_hal(device, &device_descriptor, None) }
-                .expect("failed to create device");
+                .map_err(AdwaitaRendererError::CreateDevice)?;
         let device = RenderDevice::from(device);
         let queue = RenderQueue(Arc::new(WgpuWrapper::new(queue)));
         let adapter_info = RenderAdapterInfo(WgpuWrapper::new(adapter_info));
         let adapter = RenderAdapter(Arc::new(WgpuWrapper::new(adapter)));
 
-        RenderCreation::Manual(
+        Ok(RenderCreation::Manual(
             device,
             queue,
             adapter_info,
             adapter,
             RenderInstance(Arc::new(WgpuWrapper::new(instance))),
-        )
+        ))
     };
 
     futures_lite::future::block_on(do_async)
@@ -105,41 +439,707 @@ pub fn create_renderer(settings: WgpuSettings) -> RenderCreation {
 
 // https://github.com/dzfranklin/drm-fourcc-rs/blob/main/src/consts.rs
 // const DMABUF_MODIFIER: u64 = 0xff_ffff_ffff_ffff; // invalid
-const DMABUF_MODIFIER: u64 = 0; // DRM_FORMAT_MOD_LINEAR
+pub const DRM_FORMAT_MOD_LINEAR: u64 = 0;
 
 // https://github.com/torvalds/linux/blob/master/include/uapi/drm/drm_fourcc.h
 // Why isn't this RGBA8? I don't know! But this works!
-const DMABUF_FORMAT: u32 = u32::from_le_bytes(*b"AB24"); // ABGR8888
+pub const DMABUF_FORMAT: u32 = u32::from_le_bytes(*b"AB24"); // ABGR8888
 const VK_FORMAT: vk::Format = vk::Format::R8G8B8A8_SRGB;
 const TEXTURE_FORMAT: TextureFormat = TextureFormat::Rgba8UnormSrgb;
+/// Formats of the render targets of transparent windows.
+///
+/// Bevy blends in linear space, so an sRGB target holds colors which were
+/// premultiplied *before* being encoded, which GDK can't present correctly.
+/// A linear target holds the premultiplied colors as they are, and GDK is
+/// told to present them with a linear color state.
+const TRANSPARENT_VK_FORMAT: vk::Format = vk::Format::R8G8B8A8_UNORM;
+const TRANSPARENT_TEXTURE_FORMAT: TextureFormat = TextureFormat::Rgba8Unorm;
 
-pub fn setup_render_target(size: UVec2, render_device: &RenderDevice) -> (ManualTextureView, i32) {
-    let wgpu_device = render_device.wgpu_device();
-    let (texture, dmabuf_fd) = unsafe {
-        let r = wgpu_device.as_hal::<vulkan::Api, _, _>(|hal_device| {
-            let hal_device = hal_device.expect("`RenderDevice` is not a vulkan device");
-            create_target_from_hal(wgpu_device, hal_device, size.x, size.y)
+const fn render_target_formats(transparent: bool) -> (vk::Format, TextureFormat) {
+    if transparent {
+        (TRANSPARENT_VK_FORMAT, TRANSPARENT_TEXTURE_FORMAT)
+    } else {
+        (VK_FORMAT, TEXTURE_FORMAT)
+    }
+}
+
+/// Size of a pixel in [`TEXTURE_FORMAT`].
+const BYTES_PER_PIXEL: u32 = 4;
+
+/// Converts a size in logical pixels to device pixels, the same way that GTK
+/// does when drawing a widget of that size.
+#[must_use]
+pub fn physical_size(logical: UVec2, scale_factor: f32) -> UVec2 {
+    (logical.as_vec2() * scale_factor).round().as_uvec2()
+}
+
+/// Smallest and largest allowed [`AdwaitaWindowConfig::render_scale`].
+///
+/// [`AdwaitaWindowConfig::render_scale`]: crate::AdwaitaWindowConfig::render_scale
+pub const RENDER_SCALE_RANGE: (f32, f32) = (0.25, 2.0);
+
+/// Clamps a render scale to [`RENDER_SCALE_RANGE`], treating non-finite
+/// values as no scaling.
+#[must_use]
+pub fn clamp_render_scale(render_scale: f32) -> f32 {
+    if render_scale.is_finite() {
+        render_scale.clamp(RENDER_SCALE_RANGE.0, RENDER_SCALE_RANGE.1)
+    } else {
+        1.0
+    }
+}
+
+/// Smallest and largest allowed [`AdwaitaWindowConfig::swapchain_buffers`].
+///
+/// [`AdwaitaWindowConfig::swapchain_buffers`]: crate::AdwaitaWindowConfig::swapchain_buffers
+pub const SWAPCHAIN_BUFFERS_RANGE: (u32, u32) = (2, 3);
+
+/// Size of the render target to allocate for a widget of `physical` device
+/// pixels, rendered at `render_scale`.
+#[must_use]
+pub fn scaled_size(physical: UVec2, render_scale: f32) -> UVec2 {
+    (physical.as_vec2() * clamp_render_scale(render_scale))
+        .round()
+        .as_uvec2()
+        .max(UVec2::ONE)
+}
+
+/// How long to measure GPU frame times for before each adjustment made by
+/// [`AdaptiveRenderScale`].
+const ADAPTIVE_SAMPLE_TIME: Duration = Duration::from_millis(250);
+
+/// Smallest change which [`AdaptiveRenderScale`] makes to the render scale.
+const ADAPTIVE_STEP: f32 = 0.05;
+
+/// The render scale is lowered once the GPU frame time exceeds the target by
+/// this factor.
+const ADAPTIVE_LOWER_THRESHOLD: f32 = 1.05;
+
+/// The render scale is raised a step once the GPU frame time at the higher
+/// scale is predicted to stay below the target by this factor, for
+/// [`ADAPTIVE_RAISE_SAMPLES`] samples in a row.
+///
+/// Together with [`ADAPTIVE_LOWER_THRESHOLD`], this stops the scale from
+/// oscillating around the target, which would reallocate the render target
+/// every time.
+const ADAPTIVE_RAISE_THRESHOLD: f32 = 0.9;
+
+/// See [`ADAPTIVE_RAISE_THRESHOLD`].
+const ADAPTIVE_RAISE_SAMPLES: u32 = 4;
+
+/// Controller for [`AdwaitaWindowConfig::adaptive_render_scale`].
+///
+/// [`AdwaitaWindowConfig::adaptive_render_scale`]: crate::AdwaitaWindowConfig::adaptive_render_scale
+#[derive(Debug, Default)]
+pub struct AdaptiveRenderScale {
+    /// When the current sample started, and the total and number of GPU frame
+    /// times measured since.
+    sample: Option<(Instant, Duration, u32)>,
+    /// How many samples in a row would have allowed raising the render scale.
+    raise_streak: u32,
+}
+
+impl AdaptiveRenderScale {
+    /// Discards the current sample, e.g. because the window stopped rendering
+    /// for a while and the frame time would be meaningless.
+    pub fn reset(&mut self) {
+        self.sample = None;
+        self.raise_streak = 0;
+    }
+
+    /// Adds the GPU frame times measured since the last update to the current
+    /// sample, and returns the render scale to use from now on.
+    #[must_use]
+    pub fn update(
+        &mut self,
+        settings: &AdwaitaAdaptiveRenderScale,
+        render_scale: f32,
+        frame_times: &[Duration],
+        now: Instant,
+    ) -> f32 {
+        let (min_scale, max_scale) = settings.scale_range();
+        let render_scale = render_scale.clamp(min_scale, max_scale);
+        let (start, total, count) = self.sample.get_or_insert((now, Duration::ZERO, 0));
+        *total += frame_times.iter().sum::<Duration>();
+        *count += frame_times.len() as u32;
+        if now.duration_since(*start) < ADAPTIVE_SAMPLE_TIME {
+            return render_scale;
+        }
+        let (total, count) = (*total, *count);
+        self.sample = Some((now, Duration::ZERO, 0));
+        if count == 0 {
+            return render_scale;
+        }
+
+        let frame_time = total.as_secs_f32() / count as f32;
+        let target = settings.target_frame_time.as_secs_f32();
+        if frame_time > target * ADAPTIVE_LOWER_THRESHOLD {
+            self.raise_streak = 0;
+            // GPU time is roughly proportional to the number of pixels, so go
+            // straight to about the scale which meets the target, instead of
+            // reallocating the render target on every step down
+            let fitting = render_scale * (target / frame_time).sqrt();
+            let steps = ((render_scale - fitting) / ADAPTIVE_STEP).ceil().max(1.0);
+            return (render_scale - steps * ADAPTIVE_STEP).clamp(min_scale, max_scale);
+        }
+
+        let raised = (render_scale + ADAPTIVE_STEP).min(max_scale);
+        let predicted = frame_time * (raised / render_scale).powi(2);
+        if raised > render_scale && predicted < target * ADAPTIVE_RAISE_THRESHOLD {
+            self.raise_streak += 1;
+            if self.raise_streak >= ADAPTIVE_RAISE_SAMPLES {
+                self.raise_streak = 0;
+                return raised;
+            }
+        } else {
+            self.raise_streak = 0;
+        }
+        render_scale
+    }
+}
+
+/// [`GpuFrameTimer`] hasn't started a measurement, or has read one back.
+const TIMER_IDLE: u8 = 0;
+
+/// [`GpuFrameTimer`] is waiting for its readback buffer to be mapped.
+const TIMER_MAPPING: u8 = 1;
+
+/// [`GpuFrameTimer`]'s readback buffer is mapped and holds a measurement.
+const TIMER_MAPPED: u8 = 2;
+
+/// Size of the begin and end timestamps of [`GpuFrameTimer`].
+const TIMESTAMPS_SIZE: u64 = 2 * std::mem::size_of::<u64>() as u64;
+
+/// Measures how long the GPU spends running Bevy's render graph each frame,
+/// from timestamps written right before and after it.
+///
+/// Only one measurement is in flight at a time, so frames which render while
+/// the last one is still being read back aren't measured. This needs
+/// [`wgpu::Features::TIMESTAMP_QUERY`].
+#[derive(Debug, Resource)]
+pub struct GpuFrameTimer {
+    query_set: wgpu::QuerySet,
+    resolve_buffer: wgpu::Buffer,
+    readback_buffer: wgpu::Buffer,
+    /// Nanoseconds per timestamp tick.
+    period: f32,
+    state: Arc<AtomicU8>,
+    /// Whether the begin timestamp was written this frame.
+    started: bool,
+    send: flume::Sender<Duration>,
+}
+
+impl GpuFrameTimer {
+    pub fn new(
+        render_device: &RenderDevice,
+        render_queue: &RenderQueue,
+        send: flume::Sender<Duration>,
+    ) -> Self {
+        let device = render_device.wgpu_device();
+        let query_set = device.create_query_set(&wgpu::QuerySetDescriptor {
+            label: Some("adwaita_gpu_frame_timer"),
+            ty: wgpu::QueryType::Timestamp,
+            count: 2,
+        });
+        let resolve_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("adwaita_gpu_frame_timer_resolve"),
+            size: TIMESTAMPS_SIZE,
+            usage: wgpu::BufferUsages::QUERY_RESOLVE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let readback_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("adwaita_gpu_frame_timer_readback"),
+            size: TIMESTAMPS_SIZE,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        Self {
+            query_set,
+            resolve_buffer,
+            readback_buffer,
+            period: render_queue.get_timestamp_period(),
+            state: Arc::new(AtomicU8::new(TIMER_IDLE)),
+            started: false,
+            send,
+        }
+    }
+
+    /// Writes the begin timestamp, unless the last measurement is still being
+    /// read back.
+    pub fn begin(&mut self, render_device: &RenderDevice, render_queue: &RenderQueue) {
+        match self.state.load(Ordering::SeqCst) {
+            TIMER_MAPPING => return,
+            TIMER_MAPPED => self.read_back(),
+            _ => {}
+        }
+
+        let mut encoder = render_device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("adwaita_gpu_frame_timer_begin"),
+        });
+        self.write_timestamp(&mut encoder, 0);
+        render_queue.submit([encoder.finish()]);
+        self.started = true;
+    }
+
+    /// Writes the end timestamp, and starts reading back both, if
+    /// [`GpuFrameTimer::begin`] wrote one this frame.
+    pub fn end(&mut self, render_device: &RenderDevice, render_queue: &RenderQueue) {
+        if !std::mem::take(&mut self.started) {
+            return;
+        }
+
+        let mut encoder = render_device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("adwaita_gpu_frame_timer_end"),
+        });
+        self.write_timestamp(&mut encoder, 1);
+        encoder.resolve_query_set(&self.query_set, 0..2, &self.resolve_buffer, 0);
+        encoder.copy_buffer_to_buffer(
+            &self.resolve_buffer,
+            0,
+            &self.readback_buffer,
+            0,
+            TIMESTAMPS_SIZE,
+        );
+        render_queue.submit([encoder.finish()]);
+
+        self.state.store(TIMER_MAPPING, Ordering::SeqCst);
+        let state = self.state.clone();
+        // wgpu calls this during a later submit or poll, e.g. next frame's
+        self.readback_buffer
+            .slice(..)
+            .map_async(wgpu::MapMode::Read, move |result| {
+                let next = if result.is_ok() {
+                    TIMER_MAPPED
+                } else {
+                    TIMER_IDLE
+                };
+                state.store(next, Ordering::SeqCst);
+            });
+    }
+
+    fn write_timestamp(&self, encoder: &mut wgpu::CommandEncoder, index: u32) {
+        // without `TIMESTAMP_QUERY_INSIDE_ENCODERS`, timestamps can only be
+        // written at the boundaries of a pass
+        encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("adwaita_gpu_frame_timer"),
+            timestamp_writes: Some(wgpu::ComputePassTimestampWrites {
+                query_set: &self.query_set,
+                beginning_of_pass_write_index: Some(index),
+                end_of_pass_write_index: None,
+            }),
         });
-        r.unwrap()
+    }
+
+    fn read_back(&mut self) {
+        let (begin, end) = {
+            let data = self.readback_buffer.slice(..).get_mapped_range();
+            let (begin, end) = data.split_at(std::mem::size_of::<u64>());
+            (
+                u64::from_ne_bytes(begin.try_into().expect("should be 8 bytes")),
+                u64::from_ne_bytes(end.try_into().expect("should be 8 bytes")),
+            )
+        };
+        self.readback_buffer.unmap();
+        self.state.store(TIMER_IDLE, Ordering::SeqCst);
+
+        let nanos = end.saturating_sub(begin) as f64 * f64::from(self.period);
+        // nothing reads these if no window has an adaptive render scale
+        _ = self.send.try_send(Duration::from_nanos(nanos as u64));
+    }
+}
+
+/// Usages which every render target needs, regardless of what the user asks
+/// for.
+const REQUIRED_USAGES: wgpu::TextureUsages =
+    wgpu::TextureUsages::RENDER_ATTACHMENT.union(wgpu::TextureUsages::COPY_SRC);
+
+/// Picks the highest sample count up to `msaa` which cameras rendering into a
+/// render target can use on this adapter, with or without HDR.
+pub fn supported_msaa(msaa: Msaa, render_adapter: &RenderAdapter) -> Msaa {
+    let supported = |samples| {
+        [TEXTURE_FORMAT, ViewTarget::TEXTURE_FORMAT_HDR]
+            .into_iter()
+            .all(|format| {
+                render_adapter
+                    .get_texture_format_features(format)
+                    .flags
+                    .sample_count_supported(samples)
+            })
     };
+    [Msaa::Sample8, Msaa::Sample4, Msaa::Sample2]
+        .into_iter()
+        .find(|candidate| candidate.samples() <= msaa.samples() && supported(candidate.samples()))
+        .unwrap_or(Msaa::Off)
+}
+
+/// Merges the user's requested usages with the ones we need, dropping any
+/// which the render target's format can't support.
+#[must_use]
+pub fn render_target_usages(requested: wgpu::TextureUsages) -> wgpu::TextureUsages {
+    // sRGB formats can't be used as storage textures, so compute shaders have
+    // to write to a separate texture and copy or blit into the render target
+    let unsupported = requested
+        & !TEXTURE_FORMAT
+            .guaranteed_format_features(wgpu::Features::empty())
+            .allowed_usages;
+    if !unsupported.is_empty() {
+        warn!("Render target format {TEXTURE_FORMAT:?} does not support usages {unsupported:?}, ignoring them");
+    }
+    (requested - unsupported) | REQUIRED_USAGES
+}
 
+/// Creates a render target which is exported to GTK as a dmabuf.
+///
+/// `modifiers` are the DRM format modifiers which GTK can import the dmabuf
+/// with. If the device supports any of them besides linear, one of those is
+/// used, since a linear layout is slow to render into on most GPUs. Otherwise,
+/// or if `modifiers` is empty, the render target uses a linear layout.
+///
+/// This only creates the color target which is presented. Bevy allocates the
+/// depth, MSAA and intermediate HDR textures of each camera itself, sized to
+/// the camera's physical target size - for a [`ManualTextureView`], that's
+/// the size given here - so they're reallocated along with the render target
+/// and don't need any setup of their own. In particular, with [`Msaa`], Bevy
+/// resolves into this target, so it's always single-sampled, and it's this
+/// resolved image that's exported.
+///
+/// If `transparent`, the target stores linear colors instead of sRGB ones -
+/// see [`TRANSPARENT_TEXTURE_FORMAT`].
+///
+/// Errors if the render device isn't a Vulkan device, or if the driver fails
+/// to allocate or export the target.
+pub fn setup_render_target(
+    size: UVec2,
+    usages: wgpu::TextureUsages,
+    modifiers: &[u64],
+    transparent: bool,
+    render_device: &RenderDevice,
+) -> Result<(ManualTextureView, Texture, DmabufInfo), AdwaitaWindowErrorKind> {
+    let wgpu_device = render_device.wgpu_device();
+    let (texture, dmabuf) = unsafe {
+        wgpu_device
+            .as_hal::<vulkan::Api, _, _>(|hal_device| {
+                let hal_device = hal_device.ok_or(AdwaitaWindowErrorKind::DmabufUnsupported)?;
+                // not enabled if the renderer fell back to software present
+                if !hal_device
+                    .enabled_device_extensions()
+                    .contains(&ash::extensions::khr::ExternalMemoryFd::name())
+                {
+                    return Err(AdwaitaWindowErrorKind::DmabufUnsupported);
+                }
+                create_target_from_hal(
+                    wgpu_device,
+                    hal_device,
+                    size,
+                    usages,
+                    modifiers,
+                    transparent,
+                )
+            })
+            .ok_or(AdwaitaWindowErrorKind::DmabufUnsupported)??
+    };
+
+    let texture = Texture::from(texture);
+    let texture_view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+    let manual_texture_view = ManualTextureView {
+        texture_view,
+        size,
+        format: render_target_formats(transparent).1,
+    };
+
+    Ok((manual_texture_view, texture, dmabuf))
+}
+
+/// Creates a render target which is only used on the Bevy side, and is never
+/// exported to GTK.
+pub fn setup_secondary_render_target(
+    size: UVec2,
+    render_device: &RenderDevice,
+) -> (ManualTextureView, Texture) {
+    let texture = create_offscreen_texture(
+        size,
+        TEXTURE_FORMAT,
+        wgpu::TextureUsages::TEXTURE_BINDING,
+        render_device,
+        "adwaita_secondary_render_target",
+    );
+    let texture_view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+    let manual_texture_view = ManualTextureView {
+        texture_view,
+        size,
+        format: TEXTURE_FORMAT,
+    };
+    (manual_texture_view, texture)
+}
+
+/// Creates the [`Image`] which stands in for a secondary render target on the
+/// main world side. Its contents are never used, since its [`GpuImage`] is
+/// replaced with the render target every frame.
+///
+/// [`GpuImage`]: bevy::render::texture::GpuImage
+#[must_use]
+pub fn placeholder_image() -> Image {
+    Image::new_fill(
+        wgpu::Extent3d {
+            width: 1,
+            height: 1,
+            depth_or_array_layers: 1,
+        },
+        wgpu::TextureDimension::D2,
+        &[0, 0, 0, 0],
+        TEXTURE_FORMAT,
+        RenderAssetUsages::default(),
+    )
+}
+
+/// Creates a render target which is read back to the CPU every frame, instead
+/// of being shared with GTK as a dmabuf.
+pub fn setup_software_render_target(
+    size: UVec2,
+    usages: wgpu::TextureUsages,
+    transparent: bool,
+    render_device: &RenderDevice,
+) -> (ManualTextureView, Texture) {
+    let format = render_target_formats(transparent).1;
+    let texture = create_offscreen_texture(
+        size,
+        format,
+        usages,
+        render_device,
+        "adwaita_software_render_target",
+    );
+    let texture_view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+    let manual_texture_view = ManualTextureView {
+        texture_view,
+        size,
+        format,
+    };
+    (manual_texture_view, texture)
+}
+
+/// Creates a render target which Bevy renders into instead of the shared
+/// render target, and which is copied into it once rendered, with
+/// [`AdwaitaRenderTargetSharing::Copy`](crate::AdwaitaRenderTargetSharing::Copy).
+pub fn setup_copy_source_render_target(
+    size: UVec2,
+    usages: wgpu::TextureUsages,
+    transparent: bool,
+    render_device: &RenderDevice,
+) -> (ManualTextureView, Texture) {
+    let format = render_target_formats(transparent).1;
+    let texture = create_offscreen_texture(
+        size,
+        format,
+        usages,
+        render_device,
+        "adwaita_copy_source_render_target",
+    );
     let texture_view = texture.create_view(&wgpu::TextureViewDescriptor::default());
 
     let manual_texture_view = ManualTextureView {
-        texture_view: texture_view.into(),
+        texture_view,
+        size,
+        format,
+    };
+    (manual_texture_view, texture)
+}
+
+/// Copies the render target which Bevy rendered into to the shared render
+/// target which is handed off to GTK.
+pub fn copy_to_shared_target(
+    source: &Texture,
+    target: &Texture,
+    size: UVec2,
+    render_device: &RenderDevice,
+    render_queue: &RenderQueue,
+) {
+    let mut encoder = render_device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("adwaita_copy_to_shared_target"),
+    });
+    encoder.copy_texture_to_texture(
+        source.as_image_copy(),
+        target.as_image_copy(),
+        wgpu::Extent3d {
+            width: size.x,
+            height: size.y,
+            depth_or_array_layers: 1,
+        },
+    );
+    render_queue.submit([encoder.finish()]);
+}
+
+fn create_offscreen_texture(
+    size: UVec2,
+    format: TextureFormat,
+    usages: wgpu::TextureUsages,
+    render_device: &RenderDevice,
+    label: &str,
+) -> Texture {
+    render_device.create_texture(&wgpu::TextureDescriptor {
+        label: Some(label),
+        size: wgpu::Extent3d {
+            width: size.x,
+            height: size.y,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format,
+        usage: usages | REQUIRED_USAGES,
+        view_formats: &[],
+    })
+}
+
+/// Frame which was read back from the GPU into CPU memory.
+#[derive(Debug)]
+pub struct SoftwareFrame {
+    pub frame_id: u64,
+    /// When the frame finished being read back, and was handed off to GTK.
+    pub handed_off_at: Instant,
+    pub size: UVec2,
+    /// Number of bytes between the start of each row in `data`.
+    pub stride: usize,
+    /// See [`DmabufInfo::linear`].
+    pub linear: bool,
+    pub data: Vec<u8>,
+}
+
+/// Copies the contents of `texture` into CPU memory, blocking until the copy
+/// is done.
+///
+/// # Errors
+///
+/// Errors if the readback buffer couldn't be mapped, e.g. if the device was
+/// lost.
+pub fn read_back_texture(
+    texture: &Texture,
+    size: UVec2,
+    frame_id: u64,
+    render_device: &RenderDevice,
+    render_queue: &RenderQueue,
+) -> Result<SoftwareFrame, wgpu::BufferAsyncError> {
+    let unpadded_bytes_per_row = size.x * BYTES_PER_PIXEL;
+    let bytes_per_row = unpadded_bytes_per_row.div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT)
+        * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+    let device = render_device.wgpu_device();
+    let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("adwaita_software_readback"),
+        size: u64::from(bytes_per_row) * u64::from(size.y),
+        usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("adwaita_software_readback"),
+    });
+    encoder.copy_texture_to_buffer(
+        texture.as_image_copy(),
+        wgpu::ImageCopyBuffer {
+            buffer: &buffer,
+            layout: wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(bytes_per_row),
+                rows_per_image: None,
+            },
+        },
+        wgpu::Extent3d {
+            width: size.x,
+            height: size.y,
+            depth_or_array_layers: 1,
+        },
+    );
+    render_queue.submit([encoder.finish()]);
+
+    let slice = buffer.slice(..);
+    let (send_mapped, recv_mapped) = flume::bounded(1);
+    slice.map_async(wgpu::MapMode::Read, move |result| {
+        _ = send_mapped.send(result);
+    });
+    device.poll(wgpu::Maintain::Wait);
+    // the callback is always called by the time a waiting poll returns, but if
+    // it somehow wasn't, the buffer isn't mapped either
+    recv_mapped
+        .try_recv()
+        .unwrap_or(Err(wgpu::BufferAsyncError))?;
+    let data = slice.get_mapped_range().to_vec();
+    buffer.unmap();
+
+    Ok(SoftwareFrame {
+        frame_id,
+        handed_off_at: Instant::now(),
         size,
-        format: wgpu::TextureFormat::Rgba8UnormSrgb,
+        stride: bytes_per_row as usize,
+        linear: texture.format() == TRANSPARENT_TEXTURE_FORMAT,
+        data,
+    })
+}
+
+/// Copies the contents of `texture` into an [`Image`], blocking until the copy
+/// is done.
+///
+/// # Errors
+///
+/// See [`read_back_texture`].
+pub fn read_back_image(
+    texture: &Texture,
+    size: UVec2,
+    render_device: &RenderDevice,
+    render_queue: &RenderQueue,
+) -> Result<Image, wgpu::BufferAsyncError> {
+    let frame = read_back_texture(texture, size, 0, render_device, render_queue)?;
+    // rows are padded to `COPY_BYTES_PER_ROW_ALIGNMENT` for the copy, but
+    // images are tightly packed
+    let row_len = (size.x * BYTES_PER_PIXEL) as usize;
+    let data = if frame.stride == row_len {
+        frame.data
+    } else {
+        frame
+            .data
+            .chunks_exact(frame.stride)
+            .flat_map(|row| &row[..row_len])
+            .copied()
+            .collect()
     };
+    Ok(Image::new(
+        wgpu::Extent3d {
+            width: size.x,
+            height: size.y,
+            depth_or_array_layers: 1,
+        },
+        wgpu::TextureDimension::D2,
+        data,
+        texture.format(),
+        RenderAssetUsages::default(),
+    ))
+}
 
-    (manual_texture_view, dmabuf_fd)
+pub fn create_memory_texture(frame: SoftwareFrame) -> gdk::Texture {
+    let bytes = glib::Bytes::from_owned(frame.data);
+    let builder = gdk::MemoryTextureBuilder::new();
+    builder.set_width(assert_i32(frame.size.x, "width"));
+    builder.set_height(assert_i32(frame.size.y, "height"));
+    // matches how GDK interprets our dmabufs
+    builder.set_format(gdk::MemoryFormat::R8g8b8a8Premultiplied);
+    builder.set_bytes(Some(&bytes));
+    builder.set_stride(frame.stride);
+    if frame.linear {
+        builder.set_color_state(&gdk::ColorState::srgb_linear());
+    }
+    builder.build()
 }
 
 fn create_target_from_hal(
     wgpu_device: &wgpu::Device,
     hal_device: &vulkan::Device,
-    width: u32,
-    height: u32,
-) -> (wgpu::Texture, i32) {
+    size: UVec2,
+    usages: wgpu::TextureUsages,
+    modifiers: &[u64],
+    transparent: bool,
+) -> Result<(wgpu::Texture, DmabufInfo), AdwaitaWindowErrorKind> {
     struct DropGuard {
         device: ash::Device,
         memory: vk::DeviceMemory,
@@ -159,17 +1159,67 @@ fn create_target_from_hal(
         }
     }
 
+    let (width, height) = (size.x, size.y);
+    let (vk_format, texture_format) = render_target_formats(transparent);
     let vk_device = hal_device.raw_device();
     let instance = hal_device.shared_instance().raw_instance();
 
+    let modifiers = if hal_device
+        .enabled_device_extensions()
+        .contains(&vk::ExtImageDrmFormatModifierFn::name())
+    {
+        let supported = unsafe {
+            vk_supported_modifiers(
+                instance,
+                hal_device.raw_physical_device(),
+                vk_format,
+                vk_format_features(usages),
+            )
+        };
+        preferred_modifiers(modifiers, &supported)
+    } else {
+        Vec::new()
+    };
+    // with modifiers, the driver picks the best layout out of our list
+    let use_modifiers = !modifiers.is_empty();
+    // modifiers are only listed if they support all of `usages`, but linear
+    // tiling supports fewer usages than optimal, and drivers vary
+    let usages = if use_modifiers {
+        usages
+    } else {
+        unsafe {
+            linear_supported_usages(
+                instance,
+                hal_device.raw_physical_device(),
+                vk_format,
+                usages,
+            )?
+        }
+    };
+    let handle_type = if use_modifiers {
+        vk::ExternalMemoryHandleTypeFlags::DMA_BUF_EXT
+    } else {
+        vk::ExternalMemoryHandleTypeFlags::OPAQUE_FD
+    };
+
+    let modifier_list_create = vk::ImageDrmFormatModifierListCreateInfoEXT {
+        drm_format_modifier_count: modifiers.len() as u32,
+        p_drm_format_modifiers: modifiers.as_ptr(),
+        ..default()
+    };
     let external_memory_image_create = vk::ExternalMemoryImageCreateInfo {
-        handle_types: vk::ExternalMemoryHandleTypeFlags::OPAQUE_FD,
+        p_next: if use_modifiers {
+            &modifier_list_create as *const _ as *const c_void
+        } else {
+            std::ptr::null()
+        },
+        handle_types: handle_type,
         ..default()
     };
     let image_create = vk::ImageCreateInfo {
         p_next: &external_memory_image_create as *const _ as *const c_void,
         image_type: vk::ImageType::TYPE_2D,
-        format: VK_FORMAT,
+        format: vk_format,
         extent: vk::Extent3D {
             width,
             height,
@@ -178,14 +1228,28 @@ fn create_target_from_hal(
         mip_levels: 1,
         array_layers: 1,
         samples: vk::SampleCountFlags::TYPE_1,
-        tiling: vk::ImageTiling::LINEAR, // or OPTIMAL?
-        usage: vk::ImageUsageFlags::TRANSFER_SRC | vk::ImageUsageFlags::COLOR_ATTACHMENT,
+        tiling: if use_modifiers {
+            vk::ImageTiling::DRM_FORMAT_MODIFIER_EXT
+        } else {
+            vk::ImageTiling::LINEAR
+        },
+        usage: vk_image_usage(usages),
         sharing_mode: vk::SharingMode::EXCLUSIVE,
         initial_layout: vk::ImageLayout::UNDEFINED,
         ..default()
     };
-    let image =
-        unsafe { vk_device.create_image(&image_create, None) }.expect("failed to create image");
+    let image = unsafe { vk_device.create_image(&image_create, None) }
+        .map_err(|err| texture_creation_error("create image", err))?;
+    // frees what was created so far, if one of the later steps fails
+    let fail = |memory: Option<vk::DeviceMemory>, operation: &str, err: vk::Result| {
+        unsafe {
+            vk_device.destroy_image(image, None);
+            if let Some(memory) = memory {
+                vk_device.free_memory(memory, None);
+            }
+        }
+        texture_creation_error(operation, err)
+    };
 
     let mut memory_requirements = vk::MemoryRequirements2KHR::default();
     unsafe {
@@ -199,7 +1263,7 @@ fn create_target_from_hal(
     let dedicated_alloc_info = vk::MemoryDedicatedAllocateInfo { image, ..default() };
     let export_info = vk::ExportMemoryAllocateInfo {
         p_next: &dedicated_alloc_info as *const _ as *const c_void,
-        handle_types: vk::ExternalMemoryHandleTypeFlags::OPAQUE_FD,
+        handle_types: handle_type,
         ..default()
     };
     let alloc_info = vk::MemoryAllocateInfo {
@@ -207,8 +1271,8 @@ fn create_target_from_hal(
         allocation_size: memory_requirements.memory_requirements.size,
         ..default()
     };
-    let memory =
-        unsafe { vk_device.allocate_memory(&alloc_info, None) }.expect("failed to allocate memory");
+    let memory = unsafe { vk_device.allocate_memory(&alloc_info, None) }
+        .map_err(|err| fail(None, "allocate memory", err))?;
 
     let bind_image_memory = vk::BindImageMemoryInfo {
         image,
@@ -216,18 +1280,59 @@ fn create_target_from_hal(
         ..default()
     };
     unsafe { vk_device.bind_image_memory2(&[bind_image_memory]) }
-        .expect("failed to bind memory to image");
+        .map_err(|err| fail(Some(memory), "bind memory to image", err))?;
 
     let get_memory_info = vk::MemoryGetFdInfoKHR {
         memory,
-        handle_type: vk::ExternalMemoryHandleTypeFlags::OPAQUE_FD,
+        handle_type,
         ..default()
     };
     let dmabuf_fd = unsafe {
         ash::extensions::khr::ExternalMemoryFd::new(instance, vk_device)
             .get_memory_fd(&get_memory_info)
     }
-    .expect("failed to get fd for allocated memory");
+    .map_err(|err| fail(Some(memory), "get fd for allocated memory", err))?;
+
+    let dmabuf = if use_modifiers {
+        let mut modifier_properties = vk::ImageDrmFormatModifierPropertiesEXT::default();
+        unsafe {
+            ash::extensions::ext::ImageDrmFormatModifier::new(instance, vk_device)
+                .get_image_drm_format_modifier_properties(image, &mut modifier_properties)
+        }
+        .map_err(|err| {
+            drop(unsafe { File::from_raw_fd(dmabuf_fd) });
+            fail(Some(memory), "get modifier of image", err)
+        })?;
+        let layout = unsafe {
+            vk_device.get_image_subresource_layout(
+                image,
+                vk::ImageSubresource {
+                    aspect_mask: vk::ImageAspectFlags::MEMORY_PLANE_0_EXT,
+                    mip_level: 0,
+                    array_layer: 0,
+                },
+            )
+        };
+        DmabufInfo {
+            size,
+            fd: dmabuf_fd,
+            modifier: modifier_properties.drm_format_modifier,
+            offset: u32::try_from(layout.offset).expect("plane offset should fit in a u32"),
+            stride: u32::try_from(layout.row_pitch).expect("row pitch should fit in a u32"),
+            linear: transparent,
+        }
+    } else {
+        // what the fuck?
+        const VAL: u32 = 64;
+        DmabufInfo {
+            size,
+            fd: dmabuf_fd,
+            modifier: DRM_FORMAT_MOD_LINEAR,
+            offset: 0,
+            stride: (width / VAL) * VAL * 4, // bytes per row
+            linear: transparent,
+        }
+    };
 
     let texture_desc = wgpu_hal::TextureDescriptor {
         label: Some("adwaita_render_target"),
@@ -239,8 +1344,8 @@ fn create_target_from_hal(
         mip_level_count: 1,
         sample_count: 1,
         dimension: wgpu::TextureDimension::D2,
-        format: TEXTURE_FORMAT,
-        usage: wgpu_hal::TextureUses::COPY_SRC | wgpu_hal::TextureUses::COLOR_TARGET,
+        format: texture_format,
+        usage: hal_texture_uses(usages),
         memory_flags: wgpu_hal::MemoryFlags::empty(),
         view_formats: Vec::new(),
     };
@@ -267,18 +1372,231 @@ fn create_target_from_hal(
                 mip_level_count: 1,
                 sample_count: 1,
                 dimension: wgpu::TextureDimension::D2,
-                format: TEXTURE_FORMAT,
-                usage: wgpu::TextureUsages::COPY_SRC | wgpu::TextureUsages::RENDER_ATTACHMENT,
+                format: texture_format,
+                usage: usages,
                 view_formats: &[],
             },
         )
     };
 
-    (texture, dmabuf_fd)
+    Ok((texture, dmabuf))
+}
+
+fn texture_creation_error(operation: &str, err: vk::Result) -> AdwaitaWindowErrorKind {
+    AdwaitaWindowErrorKind::TextureCreation(format!("failed to {operation}: {err}"))
+}
+
+/// Drops the usages out of `usages` which a linear image of our format can't
+/// have on this device, keeping the ones which every render target needs.
+///
+/// Errors if even those aren't supported.
+unsafe fn linear_supported_usages(
+    instance: &ash::Instance,
+    physical_device: vk::PhysicalDevice,
+    vk_format: vk::Format,
+    usages: wgpu::TextureUsages,
+) -> Result<wgpu::TextureUsages, AdwaitaWindowErrorKind> {
+    let supports = |usages: wgpu::TextureUsages| {
+        instance
+            .get_physical_device_image_format_properties(
+                physical_device,
+                vk_format,
+                vk::ImageType::TYPE_2D,
+                vk::ImageTiling::LINEAR,
+                vk_image_usage(usages),
+                vk::ImageCreateFlags::empty(),
+            )
+            .is_ok()
+    };
+    if supports(usages) {
+        return Ok(usages);
+    }
+    if !supports(REQUIRED_USAGES) {
+        return Err(texture_creation_error(
+            "create linear image for rendering",
+            vk::Result::ERROR_FORMAT_NOT_SUPPORTED,
+        ));
+    }
+
+    let supported = (usages - REQUIRED_USAGES)
+        .iter()
+        .filter(|&usage| supports(REQUIRED_USAGES | usage))
+        .fold(REQUIRED_USAGES, |supported, usage| supported | usage);
+    warn!(
+        "Linear render targets of format {vk_format:?} do not support usages {:?} on \
+         this device, ignoring them",
+        usages - supported
+    );
+    Ok(supported)
+}
+
+/// Picks which of the modifiers that GTK can import, out of `importable`, we
+/// let the driver choose from, given the modifiers it supports.
+///
+/// Linear is only kept if nothing else is supported by both sides, since
+/// tiled and compressed layouts are much faster to render into.
+fn preferred_modifiers(importable: &[u64], supported: &[u64]) -> Vec<u64> {
+    let common = importable
+        .iter()
+        .copied()
+        .filter(|modifier| supported.contains(modifier))
+        .collect::<Vec<_>>();
+    if common
+        .iter()
+        .any(|&modifier| modifier != DRM_FORMAT_MOD_LINEAR)
+    {
+        common
+            .into_iter()
+            .filter(|&modifier| modifier != DRM_FORMAT_MOD_LINEAR)
+            .collect()
+    } else {
+        common
+    }
+}
+
+/// Gets the single-plane DRM format modifiers which the device supports for
+/// render targets of `vk_format` with `features`.
+unsafe fn vk_supported_modifiers(
+    instance: &ash::Instance,
+    physical_device: vk::PhysicalDevice,
+    vk_format: vk::Format,
+    features: vk::FormatFeatureFlags,
+) -> Vec<u64> {
+    // first get the number of modifiers, then fill them in
+    let mut modifier_list = vk::DrmFormatModifierPropertiesListEXT::default();
+    let mut format_properties = vk::FormatProperties2 {
+        p_next: &mut modifier_list as *mut _ as *mut c_void,
+        ..default()
+    };
+    instance.get_physical_device_format_properties2(
+        physical_device,
+        vk_format,
+        &mut format_properties,
+    );
+
+    let mut modifiers = vec![
+        vk::DrmFormatModifierPropertiesEXT::default();
+        modifier_list.drm_format_modifier_count as usize
+    ];
+    modifier_list.p_drm_format_modifier_properties = modifiers.as_mut_ptr();
+    let mut format_properties = vk::FormatProperties2 {
+        p_next: &mut modifier_list as *mut _ as *mut c_void,
+        ..default()
+    };
+    instance.get_physical_device_format_properties2(
+        physical_device,
+        vk_format,
+        &mut format_properties,
+    );
+
+    modifiers
+        .into_iter()
+        .take(modifier_list.drm_format_modifier_count as usize)
+        // extra planes hold e.g. compression metadata, which we can't export
+        // through a single fd
+        .filter(|properties| {
+            properties.drm_format_modifier_plane_count == 1
+                && properties
+                    .drm_format_modifier_tiling_features
+                    .contains(features)
+        })
+        .map(|properties| properties.drm_format_modifier)
+        .collect()
+}
+
+fn vk_format_features(usages: wgpu::TextureUsages) -> vk::FormatFeatureFlags {
+    let mut features = vk::FormatFeatureFlags::empty();
+    if usages.contains(wgpu::TextureUsages::COPY_SRC) {
+        features |= vk::FormatFeatureFlags::TRANSFER_SRC;
+    }
+    if usages.contains(wgpu::TextureUsages::COPY_DST) {
+        features |= vk::FormatFeatureFlags::TRANSFER_DST;
+    }
+    if usages.contains(wgpu::TextureUsages::TEXTURE_BINDING) {
+        features |= vk::FormatFeatureFlags::SAMPLED_IMAGE;
+    }
+    if usages.contains(wgpu::TextureUsages::STORAGE_BINDING) {
+        features |= vk::FormatFeatureFlags::STORAGE_IMAGE;
+    }
+    if usages.contains(wgpu::TextureUsages::RENDER_ATTACHMENT) {
+        features |= vk::FormatFeatureFlags::COLOR_ATTACHMENT;
+    }
+    features
+}
+
+fn vk_image_usage(usages: wgpu::TextureUsages) -> vk::ImageUsageFlags {
+    let mut flags = vk::ImageUsageFlags::empty();
+    if usages.contains(wgpu::TextureUsages::COPY_SRC) {
+        flags |= vk::ImageUsageFlags::TRANSFER_SRC;
+    }
+    if usages.contains(wgpu::TextureUsages::COPY_DST) {
+        flags |= vk::ImageUsageFlags::TRANSFER_DST;
+    }
+    if usages.contains(wgpu::TextureUsages::TEXTURE_BINDING) {
+        flags |= vk::ImageUsageFlags::SAMPLED;
+    }
+    if usages.contains(wgpu::TextureUsages::STORAGE_BINDING) {
+        flags |= vk::ImageUsageFlags::STORAGE;
+    }
+    if usages.contains(wgpu::TextureUsages::RENDER_ATTACHMENT) {
+        flags |= vk::ImageUsageFlags::COLOR_ATTACHMENT;
+    }
+    flags
+}
+
+fn hal_texture_uses(usages: wgpu::TextureUsages) -> wgpu_hal::TextureUses {
+    let mut uses = wgpu_hal::TextureUses::empty();
+    if usages.contains(wgpu::TextureUsages::COPY_SRC) {
+        uses |= wgpu_hal::TextureUses::COPY_SRC;
+    }
+    if usages.contains(wgpu::TextureUsages::COPY_DST) {
+        uses |= wgpu_hal::TextureUses::COPY_DST;
+    }
+    if usages.contains(wgpu::TextureUsages::TEXTURE_BINDING) {
+        uses |= wgpu_hal::TextureUses::RESOURCE;
+    }
+    if usages.contains(wgpu::TextureUsages::STORAGE_BINDING) {
+        uses |= wgpu_hal::TextureUses::STORAGE_READ | wgpu_hal::TextureUses::STORAGE_READ_WRITE;
+    }
+    if usages.contains(wgpu::TextureUsages::RENDER_ATTACHMENT) {
+        uses |= wgpu_hal::TextureUses::COLOR_TARGET;
+    }
+    uses
+}
+
+/// Whether GDK can import the dmabufs we export for rendering, at least with
+/// a linear layout.
+pub fn is_dmabuf_format_supported(formats: &gdk::DmabufFormats) -> bool {
+    formats.contains(DMABUF_FORMAT, DRM_FORMAT_MOD_LINEAR)
 }
 
-pub fn create_dmabuf_texture(info: &DmabufInfo) -> gdk::Paintable {
-    let &DmabufInfo { size, fd } = info;
+/// Gets the DRM format modifiers which GDK can import the dmabufs we export
+/// for rendering with.
+pub fn dmabuf_modifiers(formats: &gdk::DmabufFormats) -> Vec<u64> {
+    (0..formats.n_formats())
+        .map(|index| formats.format(index))
+        .filter(|&(fourcc, _)| fourcc == DMABUF_FORMAT)
+        .map(|(_, modifier)| modifier)
+        .collect()
+}
+
+/// Imports the render target of `frame` as a texture.
+///
+/// GDK doesn't take ownership of the dmabuf fd, so the texture holds its own
+/// reference to the render target, which is dropped when GDK releases the
+/// texture. Otherwise, GTK could still be drawing the texture after the fd
+/// was closed and the memory behind it freed. Until then, the texture also
+/// keeps the swapchain buffer marked as in use, so that Bevy doesn't render
+/// into it while GTK may still draw it.
+pub fn create_dmabuf_texture(frame: &FrameInfo) -> Result<gdk::Texture, glib::Error> {
+    let DmabufInfo {
+        size,
+        fd,
+        modifier,
+        offset,
+        stride,
+        linear,
+    } = frame.dmabuf;
 
     // https://docs.gtk.org/gdk4/class.DmabufTextureBuilder.html
 
@@ -286,17 +1604,31 @@ pub fn create_dmabuf_texture(info: &DmabufInfo) -> gdk::Paintable {
     builder.set_width(size.x);
     builder.set_height(size.y);
     builder.set_fourcc(DMABUF_FORMAT);
-    builder.set_modifier(DMABUF_MODIFIER);
+    builder.set_modifier(modifier);
+    // blending over a transparent clear color leaves premultiplied colors,
+    // the same as software frames, but only linear ones present correctly
+    builder.set_premultiplied(true);
+    if linear {
+        builder.set_color_state(Some(&gdk::ColorState::srgb_linear()));
+    }
 
     builder.set_n_planes(1);
     builder.set_fd(0, fd);
-    builder.set_offset(0, 0);
+    builder.set_offset(0, offset);
+    builder.set_stride(0, stride);
 
-    // what the fuck?
-    const VAL: u32 = 64;
-    builder.set_stride(0, (size.x / VAL) * VAL * 4); // bytes per row
+    // SAFETY: the fd stays open until the release func drops its texture view
+    unsafe { builder.build_with_release_func(dmabuf_release_func(frame)) }
+}
 
-    unsafe { builder.build() }
-        .expect("should be a valid dmabuf texture")
-        .upcast()
+/// Creates the release func which a texture imported from `frame` is built
+/// with, which holds its own reference to the render target and its own use
+/// of the swapchain buffer until GDK calls it.
+pub fn dmabuf_release_func(frame: &FrameInfo) -> impl FnOnce() + Send + 'static {
+    let texture_view = frame.texture_view.clone();
+    let buffer_use = frame.buffer_use.clone();
+    move || {
+        drop(texture_view);
+        drop(buffer_use);
+    }
 }